@@ -122,6 +122,22 @@ fn main(){
             prepare_pileup(m, mode);
 
         },
+        Some("cluster") => {
+            let m = matches.subcommand_matches("cluster").unwrap();
+            if m.is_present("full-help") {
+                println!("{}", cluster_full_help());
+                process::exit(1);
+            }
+            set_log_level(m, true);
+            let threads = m.value_of("threads").unwrap().parse().unwrap();
+            rayon::ThreadPoolBuilder::new().num_threads(threads).build_global().unwrap();
+            let output_prefix = m.value_of("output-directory").unwrap();
+            match std::fs::create_dir_all(output_prefix) {
+                Ok(_) => {},
+                Err(err) => panic!(format!("Unable to create output directory {:?}", err))
+            };
+            contig::cluster_checkpoint(m, output_prefix, threads);
+        },
         Some("evolve") => {
             let m = matches.subcommand_matches("evolve").unwrap();
             let mode = "evolve";
@@ -1,1886 +1,2508 @@
-use clap::*;
-
-
-const MAPPING_SOFTWARE_LIST: &[&str] = &["bwa-mem", "minimap2-sr", "minimap2-ont", "minimap2-pb","minimap2-no-preset", "ngmlr"];
-const DEFAULT_MAPPING_SOFTWARE: &str = "minimap2-sr";
-
-const LONGREAD_MAPPING_SOFTWARE_LIST: &[&str] = &["minimap2-ont", "minimap2-pb","ngmlr-ont", "ngmlr-pb"];
-const DEFAULT_LONGREAD_MAPPING_SOFTWARE: &str = "ngmlr-ont";
-
-
-const MAPPER_HELP: &'static str =
-    "    -p, --mapper <NAME>             Underlying mapping software used
-                                         (\"minimap2-sr\", \"bwa-mem\",
-                                         \"ngmlr-ont\", \"ngmlr-pb\", \"minimap2-ont\",
-                                         \"minimap2-pb\", or \"minimap2-no-preset\").
-                                         minimap2 -sr, -ont, -pb, -no-preset specify
-                                         '-x' preset of minimap2 to be used
-                                         (with map-ont, map-pb for -ont, -pb).
-                                         [default: \"minimap2-sr\"] \n
-         --minimap2-params PARAMS        Extra parameters to provide to minimap2,
-                                         both indexing command (if used) and for
-                                         mapping. Note that usage of this parameter
-                                         has security implications if untrusted input
-                                         is specified. '-a' is always specified.
-                                         [default \"\"] \n
-         --minimap2-reference-is-index   Treat reference as a minimap2 database, not
-                                         as a FASTA file.\n
-         --bwa-params PARAMS             Extra parameters to provide to BWA. Note
-                                         that usage of this parameter has security
-                                         implications if untrusted input is specified.
-                                         [default \"\"]\n
-         --ngmlr-params PARAMS           Extra parameters to provide to NGMLR.
-                                         --bam-fix, -x ont, -t are already set. Note
-                                         that usage of this parameter has security
-                                         implications if untrusted input is specified.\n";
-
-const ALIGNMENT_OPTIONS: &'static str =
-"Define mapping(s) (required):
-  Either define BAM:
-   -b, --bam-files <PATH> ..             Path to BAM file(s). These must be
-                                         reference sorted (e.g. with samtools sort)
-                                         unless --sharded is specified, in which
-                                         case they must be read name sorted (e.g.
-                                         with samtools sort -n).
-  -l, --longread-bam-files <PATH> ..     Path to BAM files(s) generated from longreads.
-                                         Must be reference sorted.
-
-  Or do mapping:
-   -r, --reference <PATH> ..             FASTA file of contigs or BWA index stem
-                                         e.g. concatenated genomes or assembly.
-                                         If multiple reference FASTA files are
-                                         provided and --sharded is specified,
-                                         then reads will be mapped to reference
-                                         separately as sharded BAMs
-   -d, --genome-fasta-directory <PATH>   Directory containing FASTA files to be analyzed
-   -x, --genome-fasta-extension <STR>    FASTA file extension in --genome-fasta-directory
-                                         [default \"fna\"]
-   -t, --threads <INT>                   Number of threads for mapping / sorting
-   -1 <PATH> ..                          Forward FASTA/Q file(s) for mapping
-   -2 <PATH> ..                          Reverse FASTA/Q file(s) for mapping
-   -c, --coupled <PATH> <PATH> ..        One or more pairs of forward and reverse
-                                         FASTA/Q files for mapping in order
-                                         <sample1_R1.fq.gz> <sample1_R2.fq.gz>
-                                         <sample2_R1.fq.gz> <sample2_R2.fq.gz> ..
-   --interleaved <PATH> ..               Interleaved FASTA/Q files(s) for mapping.
-   --single <PATH> ..                    Unpaired FASTA/Q files(s) for mapping.
-   --longreads <PATH> ..                 pacbio or oxford nanopore long reads FASTA/Q files(s).
-   --bam-file-cache-directory            Directory to store cached BAM files. BAM files are stored
-                                         in /tmp by default.
-   -d, --output-directory                Output directory";
-
-pub fn filter_full_help() -> &'static str {
-    "lorikeet filter: Remove alignments with insufficient identity.
-
-Only primary, non-supplementary alignments are considered, and output files
-are grouped by reference, but not sorted by position.
-
-Files (both required):
-   -b, --bam-files <PATH> ..             Path to reference-sorted BAM file(s)
-   -o, --output-bam-files <PATH> ..      Path to corresponding output file(s)
-
-Thresholds:
-   --min-read-aligned-length <INT>            Exclude reads with smaller numbers of
-                                              aligned bases [default: 0]
-   --min-read-percent-identity <FLOAT>        Exclude reads by overall percent
-                                              identity e.g. 0.95 for 95%. [default 0.0]
-   --min-read-aligned-percent <FLOAT>         Exclude reads by percent aligned
-                                              bases e.g. 0.95 means 95% of the read's
-                                              bases must be aligned. [default 0.97]
-   --min-read-aligned-length-pair <INT>       Exclude pairs with smaller numbers of
-                                              aligned bases.
-                                              Implies --proper-pairs-only. [default: 0]
-   --min-read-percent-identity-pair <FLOAT>   Exclude pairs by overall percent
-                                              identity e.g. 0.95 for 95%.
-                                              Implies --proper-pairs-only. [default 0.0]
-   --min-read-aligned-percent-pair <FLOAT>    Exclude reads by percent aligned
-                                              bases e.g. 0.95 means 95% of the read's
-                                              bases must be aligned.
-                                              Implies --proper-pairs-only. [default 0.0]
-   --proper-pairs-only                        Require reads to be mapped as proper pairs
-
-Other:
-   -t, --threads <INT>                   Number of threads for output compression
-                                         [default 1]
-   --inverse                             Only keep reads which are unmapped or
-                                         align below thresholds. Note that output
-                                         records may still be marked as mapped
-                                         if they do not meet the thresholds.
-                                         [default false]
-   --verbose                             Print extra debugging information
-   -q, --quiet                           Unless there is an error, do not print
-                                         log messages
-
-Example usage:
-
-  lorikeet filter -b in.bam -o out.bam --min-read-aligned-length 75
-
-Rhys J.P. Newell <r.newell near uq.edu.au>"
-}
-
-pub fn polymorph_full_help() -> &'static str {
-    lazy_static! {
-        static ref POLYMORPH_HELP: String = format!(
-    "lorikeet polymorph: Print variant sites along a contig
-
-{}
-{}
-
-Sharding i.e. multiple reference sets (optional):
-   --sharded                             If -b/--bam-files was used:
-                                           Input BAM files are read-sorted alignments
-                                           of a set of reads mapped to multiple
-                                           reference contig sets. Choose the best
-                                           hit for each read pair.
-
-                                         Otherwise if mapping was carried out:
-                                           Map reads to each reference, choosing the
-                                           best hit for each pair.
-
-Alignment filtering (optional):
-   --min-read-aligned-length <INT>            Exclude reads with smaller numbers of
-                                              aligned bases [default: 0]
-   --min-read-percent-identity <FLOAT>        Exclude reads by overall percent
-                                              identity e.g. 0.95 for 95%. [default 0.0]
-   --min-read-aligned-percent <FLOAT>         Exclude reads by percent aligned
-                                              bases e.g. 0.95 means 95% of the read's
-                                              bases must be aligned. [default 0.97]
-   --min-read-aligned-length-pair <INT>       Exclude pairs with smaller numbers of
-                                              aligned bases.
-                                              Conflicts --proper-pairs-only. [default 0.0]
-   --min-read-percent-identity-pair <FLOAT>   Exclude pairs by overall percent
-                                              identity e.g. 0.95 for 95%.
-                                              Conflicts --proper-pairs-only. [default 0.0]
-   --min-read-aligned-percent-pair <FLOAT>    Exclude reads by percent aligned
-                                              bases e.g. 0.95 means 95% of the read's
-                                              bases must be aligned.
-                                              Conflicts --proper-pairs-only. [default 0.0]
-   --proper-pairs-only                     Allows reads to be mapped as improper pairs
-   --include-supplementary                    Includes read alignments flagged as supplementary
-   --include-secondary                        Includes read alignments flagged as secondary
-
-Other arguments (optional):
-   -m, --method <METHOD>                 Method for calculating coverage.
-                                         One or more (space separated) of:
-                                           trimmed_mean
-                                           mean
-                                           metabat (\"MetaBAT adjusted coverage\")
-                                         A more thorough description of the different
-                                         method is available at
-                                         https://github.com/rhysnewell/lorikeet
-   -q, mapq-threshold <INT>              Mapping quality threshold used to verify
-                                         a variant. [default: 10]
-   -o, --output-prefix <STRING>          Output prefix for files. [default: output]
-   -f, --min-variant-depth <INT>         Minimum depth threshold value a variant must occur at
-                                         for it to be considered. [default: 10]
-   --min-variant-quality <INT>           Minimum QUAL value required for a variant to be included in
-                                         analysis. [default: 10]
-   --output-format FORMAT                Shape of output: 'sparse' for long format,
-                                         'dense' for species-by-site.
-                                         [default: dense]
-   --min-covered-fraction FRACTION       Contigs with less coverage than this
-                                         reported as having zero coverage.
-                                         [default: 0.0]
-   --include-indels                      Flag indicating whether to attempt to calculate INDEL sites
-                                         Not recommended if using nanopore long read data.
-   --coverage-fold                       Percentage value of coverage to look above and below
-                                         when calculating variant locations. e.g. if coverage-fold
-                                         is equal to 0.1, only areas of coverage * (1.0 - 0.1) and
-                                         coverage * (1.0 + 0.1) will be considered.
-                                         [default: 0.5]
-   --contig-end-exclusion                Exclude bases at the ends of reference
-                                         sequences from calculation [default: 75]
-   --trim-min FRACTION                   Remove this smallest fraction of positions
-                                         when calculating trimmed_mean
-                                         [default: 0.05]
-   --trim-max FRACTION                   Maximum fraction for trimmed_mean
-                                         calculations [default: 0.95]
-   -t, --threads                         Number of threads used. [default: 1]
-   --no-zeros                            Omit printing of genomes that have zero
-                                         coverage
-   --discard-unmapped                    Exclude unmapped reads from cached BAM files.
-   -v, --verbose                         Print extra debugging information
-   -q, --quiet                           Unless there is an error, do not print
-                                         log messages
-
-Rhys J. P. Newell <r.newell near uq.edu.au>", ALIGNMENT_OPTIONS, MAPPER_HELP);
-    }
-    &POLYMORPH_HELP
-}
-
-pub fn evolve_full_help() -> &'static str {
-    lazy_static! {
-        static ref EVOLVE_HELP: String = format!(
-    "lorikeet evolve: Calculate dN/dS values in coding regions based on variants found in read mappings
-
-{}
-{}
-
-Sharding i.e. multiple reference sets (optional):
-   --sharded                             If -b/--bam-files was used:
-                                           Input BAM files are read-sorted alignments
-                                           of a set of reads mapped to multiple
-                                           reference contig sets. Choose the best
-                                           hit for each read pair.
-
-                                         Otherwise if mapping was carried out:
-                                           Map reads to each reference, choosing the
-                                           best hit for each pair.
-
-Alignment filtering (optional):
-   --min-read-aligned-length <INT>            Exclude reads with smaller numbers of
-                                              aligned bases [default: 0]
-   --min-read-percent-identity <FLOAT>        Exclude reads by overall percent
-                                              identity e.g. 0.95 for 95%. [default 0.0]
-   --min-read-aligned-percent <FLOAT>         Exclude reads by percent aligned
-                                              bases e.g. 0.95 means 95% of the read's
-                                              bases must be aligned. [default 0.97]
-   --min-read-aligned-length-pair <INT>       Exclude pairs with smaller numbers of
-                                              aligned bases.
-                                              Conflicts --proper-pairs-only. [default 0.0]
-   --min-read-percent-identity-pair <FLOAT>   Exclude pairs by overall percent
-                                              identity e.g. 0.95 for 95%.
-                                              Conflicts --proper-pairs-only. [default 0.0]
-   --min-read-aligned-percent-pair <FLOAT>    Exclude reads by percent aligned
-                                              bases e.g. 0.95 means 95% of the read's
-                                              bases must be aligned.
-                                              Conflicts --proper-pairs-only. [default 0.0]
-   --proper-pairs-only                     Allows reads to be mapped as improper pairs
-   --include-supplementary                    Includes read alignments flagged as supplementary
-   --include-secondary                        Includes read alignments flagged as secondary
-
-Other arguments (optional):
-   -m, --method <METHOD>                 Method for calculating coverage.
-                                         One or more (space separated) of:
-                                           trimmed_mean
-                                           mean
-                                           metabat (\"MetaBAT adjusted coverage\")
-                                         A more thorough description of the different
-                                         method is available at
-                                         https://github.com/rhysnewell/lorikeet
-   --include-indels                      Flag indicating whether to attempt to calculate INDEL sites
-                                         Not recommended if using nanopore long read data.
-   -q, mapq-threshold <INT>              Mapping quality threshold used to verify
-                                         a variant. [default: 10]
-   --base-quality-threshold <INT>        The minimum PHRED score for base in a read for it to be
-                                         considered in the variant calling process.
-   --min-repeat-entropy <FLOAT>          To detect interrupted repeats, build across sequence until it has
-                                         entropy > N bits per bp. Set to 0 to turn off. (default: 1.5)
-   -o, --output-prefix <STRING>          Output prefix for files. [default: output]
-   -f, --min-variant-depth <INT>         Minimum depth threshold value a variant must occur at
-                                         for it to be considered. [default: 10]
-   --min-variant-quality <INT>           Minimum QUAL value required for a variant to be included in
-                                         analysis. [default: 10]
-   --min-covered-fraction FRACTION       Contigs with less coverage than this
-                                         reported as having zero coverage.
-                                         [default: 0.0]
-   --coverage-fold                       Percentage value of coverage to look above and below
-                                         when calculating variant locations. e.g. if coverage-fold
-                                         is equal to 0.1, only areas of coverage * (1.0 - 0.1) and
-                                         coverage * (1.0 + 0.1) will be considered.
-                                         [default: 0.5]
-   --contig-end-exclusion                Exclude bases at the ends of reference
-                                         sequences from calculation [default: 75]
-   --trim-min FRACTION                   Remove this smallest fraction of positions
-                                         when calculating trimmed_mean
-                                         [default: 0.05]
-   --trim-max FRACTION                   Maximum fraction for trimmed_mean
-                                         calculations [default: 0.95]
-   -t, --threads                         Number of threads used. [default: 1]
-   --no-zeros                            Omit printing of genomes that have zero
-                                         coverage
-   --discard-unmapped                    Exclude unmapped reads from cached BAM files.
-   -v, --verbose                         Print extra debugging information
-   -q, --quiet                           Unless there is an error, do not print
-                                         log messages
-
-Rhys J. P. Newell <r.newell near uq.edu.au>", ALIGNMENT_OPTIONS, MAPPER_HELP);
-    }
-    &EVOLVE_HELP
-}
-
-
-pub fn summarize_full_help() -> &'static str {
-    lazy_static! {
-        static ref SUMMARIZE_HELP: String = format!(
-    "lorikeet summarize: Provides per contig variant statistics for a metagenome
-
-{}
-{}
-
-Alignment filtering (optional):
-   --min-read-aligned-length <INT>            Exclude reads with smaller numbers of
-                                         aligned bases [default: 0]
-   --min-read-percent-identity <FLOAT>        Exclude reads by overall percent
-                                         identity e.g. 0.95 for 95%. [default 0.0]
-   --min-read-aligned-percent <FLOAT>         Exclude reads by percent aligned
-                                         bases e.g. 0.95 means 95% of the read's
-                                         bases must be aligned. [default 0.97]
-   --min-read-aligned-length-pair <INT>       Exclude pairs with smaller numbers of
-                                         aligned bases.
-                                         Conflicts --proper-pairs-only. [default 0.0]
-   --min-read-percent-identity-pair <FLOAT>   Exclude pairs by overall percent
-                                         identity e.g. 0.95 for 95%.
-                                         Conflicts --proper-pairs-only. [default 0.0]
-   --min-read-aligned-percent-pair <FLOAT>    Exclude reads by percent aligned
-                                         bases e.g. 0.95 means 95% of the read's
-                                         bases must be aligned.
-                                         Conflicts --proper-pairs-only. [default 0.0]
-   --proper-pairs-only                Allows reads to be mapped as improper pairs
-   --include-supplementary               Includes read alignments flagged as supplementary
-   --include-secondary                   Includes read alignments flagged as secondary
-
-Other arguments (optional):
-   -m, --method <METHOD>                 Method for calculating coverage.
-                                         One or more (space separated) of:
-                                           trimmed_mean
-                                           mean
-                                           metabat (\"MetaBAT adjusted coverage\")
-                                         A more thorough description of the different
-                                         methods is available at
-                                         https://github.com/rhysnewell/lorikeet
-   -w, --window-size <FLOAT>             Window size in kilobase pairs at which to calculate SNP and
-                                         SV density.
-   --include-longread-svs                Include structural variants produced by SVIM in genotyping
-                                         analysis. Can often overestimate number of variants present.
-   -q, mapq-threshold <INT>              Mapping quality threshold used to verify
-                                         a variant. [default: 10]
-   --base-quality-threshold <INT>        The minimum PHRED score for base in a read for it to be
-                                         considered in the variant calling process.
-   --min-repeat-entropy <FLOAT>          To detect interrupted repeats, build across sequence until it has
-                                         entropy > N bits per bp. Set to 0 to turn off. (default: 1.5)
-   -o, --output-prefix <STRING>          Output prefix for files. [default: output]
-   -f, --min-variant-depth <INT>         Minimum depth threshold value a variant must occur at
-                                         for it to be considered. [default: 10]
-   --min-variant-quality <INT>           Minimum QUAL value required for a variant to be included in
-                                         analysis. [default: 10]
-   --output-format FORMAT                Shape of output: 'sparse' for long format,
-                                         'dense' for species-by-site.
-                                         [default: dense]
-   --min-covered-fraction FRACTION       Contigs with less coverage than this
-                                         reported as having zero coverage.
-                                         [default: 0.0]
-   --include-longread-svs                Flag indicating whether to use SVIM to calculate structural
-                                         variants.
-   --contig-end-exclusion                Exclude bases at the ends of reference
-                                         sequences from calculation [default: 75]
-   --trim-min FRACTION                   Remove this smallest fraction of positions
-                                         when calculating trimmed_mean
-                                         [default: 0.05]
-   --trim-max FRACTION                   Maximum fraction for trimmed_mean
-                                         calculations [default: 0.95]
-   -t, --threads                         Number of threads used. [default: 1]
-   --no-zeros                            Omit printing of genomes that have zero
-                                         coverage
-   --discard-unmapped                    Exclude unmapped reads from cached BAM files.
-   -v, --verbose                         Print extra debugging information
-   -q, --quiet                           Unless there is an error, do not print
-                                         log messages
-
-Rhys J. P. Newell <r.newell near uq.edu.au>", ALIGNMENT_OPTIONS, MAPPER_HELP);
-    }
-    &SUMMARIZE_HELP
-}
-
-pub fn genotype_full_help() -> &'static str {
-    lazy_static! {
-        static ref GENOTYPE_HELP: String = format!(
-    "lorikeet genotype: Resolves strain-level genotypes and abundance from metagenomes
-
-{}
-{}
-
-Sharding i.e. multiple reference sets (optional):
-   --sharded                             If -b/--bam-files was used:
-                                           Input BAM files are read-sorted alignments
-                                           of a set of reads mapped to multiple
-                                           reference contig sets. Choose the best
-                                           hit for each read pair.
-
-                                         Otherwise if mapping was carried out:
-                                           Map reads to each reference, choosing the
-                                           best hit for each pair.
-
-Alignment filtering (optional):
-   --min-read-aligned-length <INT>            Exclude reads with smaller numbers of
-                                         aligned bases [default: 0]
-   --min-read-percent-identity <FLOAT>        Exclude reads by overall percent
-                                         identity e.g. 0.95 for 95%. [default 0.0]
-   --min-read-aligned-percent <FLOAT>         Exclude reads by percent aligned
-                                         bases e.g. 0.95 means 95% of the read's
-                                         bases must be aligned. [default 0.97]
-   --min-read-aligned-length-pair <INT>       Exclude pairs with smaller numbers of
-                                         aligned bases.
-                                         Conflicts --proper-pairs-only. [default 0.0]
-   --min-read-percent-identity-pair <FLOAT>   Exclude pairs by overall percent
-                                         identity e.g. 0.95 for 95%.
-                                         Conflicts --proper-pairs-only. [default 0.0]
-   --min-read-aligned-percent-pair <FLOAT>    Exclude reads by percent aligned
-                                         bases e.g. 0.95 means 95% of the read's
-                                         bases must be aligned.
-                                         Conflicts --proper-pairs-only. [default 0.0]
-   --proper-pairs-only                Allows reads to be mapped as improper pairs
-   --include-supplementary               Includes read alignments flagged as supplementary
-   --include-secondary                   Includes read alignments flagged as secondary
-
-
-Other arguments (optional):
-   -m, --method <METHOD>                 Method for calculating coverage.
-                                         One or more (space separated) of:
-                                           trimmed_mean
-                                           mean
-                                           metabat (\"MetaBAT adjusted coverage\")
-                                         A more thorough description of the different
-                                         methods is available at
-                                         https://github.com/rhysnewell/lorikeet
-   -k, --kmer-size <INT>                 K-mer size used to generate k-mer frequency
-                                         table. [default: 4]
-   -q, mapq-threshold <INT>              Mapping quality threshold used to verify
-                                         a variant. [default: 10]
-   --base-quality-threshold <INT>        The minimum PHRED score for base in a read for it to be
-                                         considered in the variant calling process.
-   --min-repeat-entropy <FLOAT>          To detect interrupted repeats, build across sequence until it has
-                                         entropy > N bits per bp. Set to 0 to turn off. (default: 1.5)
-   -o, --output-prefix <STRING>          Output prefix for files. [default: output]
-   -f, --min-variant-depth <INT>         Minimum depth threshold value a variant must occur at
-                                         for it to be considered. [default: 10]
-   --min-variant-quality <INT>           Minimum QUAL value required for a variant to be included in
-                                         analysis. [default: 10]
-   --e-min                               Minimum epsilon value used in fuzzyDBSCAN algorithm.
-                                         The minimum distance between two points required for clustering.
-   --e-max                               Maximum epsilon value used in fuzzyDBSCAN algorithm.
-                                         The maximum distance between two points for border clustering.
-   --pts-min                             Minimum points as percentage in fuzzyDBSCAN algorithm.
-                                         The fraction of points needed to be within e-max
-                                         to begin core clustering.
-   --pts-max                             Maximum points as percentage in fuzzyDBSCAN algorithm.
-                                         The fraction of points needed to be within e-max
-                                         to begin border clustering.
-   -n, --minimum-seed-size <INT>         Minimum seed size for initiating fuzzy DBSCAN with. Corresponds
-                                         to the number of variants within the seed. [default: 20]
-   -s, --maximum-seed-similarity <FLOAT> The maximum Jaccard's similarity value allowed between two
-                                         seeds before they get concatenated into one seed. Prevents
-                                         overclustering. [default: 0.97]
-   --minimum-reads-in-link <INT>         Minimum amount of reads required to be shared between two
-                                         variants before they are counted as 'linked'. [default: 5]
-   --include-longread-svs                Include structural variants produced by SVIM in genotyping
-                                         analysis. Can often overestimate number of variants present.
-   --min-covered-fraction FRACTION       Contigs with less coverage than this
-                                         reported as having zero coverage.
-                                         [default: 0.0]
-   --contig-end-exclusion                Exclude bases at the ends of reference
-                                         sequences from calculation [default: 75]
-   --trim-min FRACTION                   Remove this smallest fraction of positions
-                                         when calculating trimmed_mean
-                                         [default: 0.05]
-   --trim-max FRACTION                   Maximum fraction for trimmed_mean
-                                         calculations [default: 0.95]
-   --plot                                Produce SNP density plots
-   -w, --window-size <FLOAT>             Window size in kilobase pairs at which to calculate SNP and
-                                         SV density.
-   -t, --threads                         Number of threads used. [default: 1]
-   --no-zeros                            Omit printing of genomes that have zero
-                                         coverage
-
-   --discard-unmapped                    Exclude unmapped reads from cached BAM files.
-   -v, --verbose                         Print extra debugging information
-   -q, --quiet                           Unless there is an error, do not print
-                                         log messages
-
-Rhys J. P. Newell <r.newell near uq.edu.au>", ALIGNMENT_OPTIONS, MAPPER_HELP);
-    }
-    &GENOTYPE_HELP
-}
-
-pub fn build_cli() -> App<'static, 'static> {
-    // specify _2 lazily because need to define it at runtime.
-    lazy_static! {
-        static ref POLYMORPH_HELP: String = format!(
-            "
-                            {}
-              {}
-
-{}
-
-  lorikeet polymorph --coupled read1.fastq.gz read2.fastq.gz --reference assembly.fna --threads 10
-
-{}
-
-  lorikeet polymorph --bam-files my.bam --reference assembly.fna
-    --bam-file-cache-directory saved_bam_files --threads 10
-
-See lorikeet polymorph --full-help for further options and further detail.
-",
-            ansi_term::Colour::Green.paint(
-                "lorikeet polymorph"),
-            ansi_term::Colour::Green.paint(
-                "Generate variant positions along contigs with a sample"),
-            ansi_term::Colour::Purple.paint(
-                "Example: Calculate variant positions from reads and assembly:"),
-            ansi_term::Colour::Purple.paint(
-                "Example: Calculate variant positions using MetaBAT adjusted coverage from a sorted BAM file, saving
-the unfiltered BAM files in the saved_bam_files folder:")
-        ).to_string();
-
-        static ref EVOLVE_HELP: String = format!(
-            "
-                            {}
-              {}
-
-{}
-
-  lorikeet evolve --coupled read1.fastq.gz read2.fastq.gz --reference assembly.fna --threads 10
-
-{}
-
-  lorikeet evolve --bam-files my.bam --longread-bam-files my-longread.bam --genome-fasta-directory genomes/ -x fna
-    --bam-file-cache-directory saved_bam_files --output-directory lorikeet_out/ --threads 10
-
-See lorikeet evolve --full-help for further options and further detail.
-",
-            ansi_term::Colour::Green.paint(
-                "lorikeet evolve"),
-            ansi_term::Colour::Green.paint(
-                "Calculate dN/dS values of genes based on read mappings"),
-            ansi_term::Colour::Purple.paint(
-                "Example: Calculate gene dN/dS values from reads and assembly:"),
-            ansi_term::Colour::Purple.paint(
-                "Example: Calculate gene dN/dS values from reads against many genomes in a directory and cache bams and save output to directory:")
-        ).to_string();
-
-
-        static ref SUMMARIZE_HELP: String = format!(
-            "
-                            {}
-              {}
-
-{}
-
-  lorikeet summarize --coupled read1.fastq.gz read2.fastq.gz --reference assembly.fna --threads 10 --window-size 1
-
-{}
-
-  lorikeet summarize --bam-files my.bam --longread-bam-files my-longread.bam --genome-fasta-directory genomes/ -x fna
-    --bam-file-cache-directory saved_bam_files --output-directory lorikeet_out/ --threads 10
-
-See lorikeet summarize --full-help for further options and further detail.
-",
-            ansi_term::Colour::Green.paint(
-                "lorikeet summarize"),
-            ansi_term::Colour::Green.paint(
-                "Summarizes contigs stats across given window size"),
-            ansi_term::Colour::Purple.paint(
-                "Example: Map paired reads to a reference and generate contig stats across samples using a 1kb window size"),
-            ansi_term::Colour::Purple.paint(
-                "Example: Summarizes genomic variation across contigs in genomes in directory using long and short reads:"),
-        ).to_string();
-
-        static ref GENOTYPE_HELP: String = format!(
-            "
-                            {}
-              {}
-
-{}
-
-  lorikeet genotype --coupled read1.fastq.gz read2.fastq.gz --reference assembly.fna --threads 10
-
-{}
-
-  lorikeet genotype --bam-files my.bam --longread-bam-files my-longread.bam --genome-fasta-directory genomes/ -x fna
-    --bam-file-cache-directory saved_bam_files --output-directory lorikeet_out/ --threads 10 --plot
-
-See lorikeet genotype --full-help for further options and further detail.
-",
-            ansi_term::Colour::Green.paint(
-                "lorikeet genotype"),
-            ansi_term::Colour::Green.paint(
-                "*EXPERIMENTAL* Report strain-level genotypes and abundances based on variant read mappings"),
-            ansi_term::Colour::Purple.paint(
-                "Example: Map paired reads to a reference and generate genotypes"),
-            ansi_term::Colour::Purple.paint(
-                "Example: Generate strain-level genotypes from read mappings compared to reference from a sorted BAM file and plots the results:"),
-        ).to_string();
-
-        static ref FILTER_HELP: String = format!(
-            "
-                            {}
-                     {}
-
-{}
-
-  lorikeet filter --bam-files input.bam --output-bam filtered.bam
-    --min-read-aligned-length 50
-
-{}
-
-  lorikeet filter -b input.bam -o inverse_filtered.bam --inverse
-    --min-read-percent-identity 0.95 --threads 16
-
-See lorikeet filter --full-help for further options and further detail.
-",
-            ansi_term::Colour::Green.paint(
-                "lorikeet filter"),
-            ansi_term::Colour::Green.paint(
-                "Filter BAM file alignments"),
-            ansi_term::Colour::Purple.paint(
-                "Example: Filter a BAM file by removing alignments shorter than 50bp:"),
-            ansi_term::Colour::Purple.paint(
-                "Example: Filter inverse: Keep alignments that have <95% alignment identity\n\
-                 and those which do map at all. Note that the output BAM file will likely\n\
-                 records that are still mapped, but align with < 95% identity. Use 16\n\
-                 threads for output compression:"),
-        ).to_string();
-    }
-
-    return App::new("lorikeet")
-        .version(crate_version!())
-        .author("Rhys J.P. Newell <r.newell near uq.edu.au>")
-        .about("Variant analysis of metagenomic datasets")
-        .args_from_usage("-v, --verbose       'Print extra debug logging information'
-             -q, --quiet         'Unless there is an error, do not print logging information'")
-        .help("
-Strain genotyping analysis for metagenomics
-
-Usage: lorikeet <subcommand> ...
-
-Main subcommands:
-\tgenotype \tReport strain-level genotypes and abundances from metagenomes (*experimental*)
-\tsummarize\tSummarizes contig stats from one or multiple samples
-\tevolve   \tCalculate dN/dS values for genes from read mappings
-
-Less used utility subcommands:
-\tkmer     \tCalculate kmer frequencies within contigs
-\tfilter   \tRemove (or only keep) alignments with insufficient identity
-
-Other options:
-\t-V, --version\tPrint version information
-
-Rhys J. P. Newell <r.newell near uq.edu.au>
-")
-        .global_setting(AppSettings::ArgRequiredElseHelp)
-        .subcommand(
-            SubCommand::with_name("polymorph")
-                .about("Perform variant calling analysis")
-                .help(POLYMORPH_HELP.as_str())
-                .arg(Arg::with_name("full-help")
-                    .long("full-help"))
-                .arg(Arg::with_name("bam-files")
-                    .short("b")
-                    .long("bam-files")
-                    .multiple(true)
-                    .takes_value(true)
-                    .required_unless_one(
-                        &["read1","read2","coupled","interleaved","single","full-help"]))
-                .arg(Arg::with_name("sharded")
-                    .long("sharded")
-                    .required(false))
-                .arg(Arg::with_name("read1")
-                    .short("-1")
-                    .multiple(true)
-                    .takes_value(true)
-                    .requires("read2")
-                    .required_unless_one(
-                        &["bam-files","coupled","interleaved","single","full-help"])
-                    .conflicts_with("bam-files"))
-                .arg(Arg::with_name("read2")
-                    .short("-2")
-                    .multiple(true)
-                    .takes_value(true)
-                    .requires("read1")
-                    .required_unless_one(
-                        &["bam-files","coupled","interleaved","single","full-help"])
-                    .conflicts_with("bam-files"))
-                .arg(Arg::with_name("coupled")
-                    .short("-c")
-                    .long("coupled")
-                    .multiple(true)
-                    .takes_value(true)
-                    .required_unless_one(
-                        &["bam-files","read1","interleaved","single","full-help"])
-                    .conflicts_with("bam-files"))
-                .arg(Arg::with_name("interleaved")
-                    .long("interleaved")
-                    .multiple(true)
-                    .takes_value(true)
-                    .required_unless_one(
-                        &["bam-files","read1","coupled","single","full-help"])
-                    .conflicts_with("bam-files"))
-                .arg(Arg::with_name("single")
-                    .long("single")
-                    .multiple(true)
-                    .takes_value(true)
-                    .required_unless_one(
-                        &["bam-files","read1","coupled","interleaved","full-help"])
-                    .conflicts_with("bam-files"))
-                .arg(Arg::with_name("longreads")
-                    .long("longreads")
-                    .multiple(true)
-                    .takes_value(true)
-                    .required(false)
-                    .conflicts_with_all(&["longread-bam-files"]))
-                .arg(Arg::with_name("longread-bam-files")
-                    .short("l")
-                    .multiple(true)
-                    .takes_value(true)
-                    .required(false)
-                    .conflicts_with_all(&["longreads"]))
-                .arg(Arg::with_name("reference")
-                    .short("r")
-                    .long("reference")
-                    .alias("genome-fasta-files")
-                    .takes_value(true)
-                    .multiple(true)
-                    .required_unless_one(&["genome-fasta-directory", "full-help"]))
-                .arg(Arg::with_name("genome-fasta-directory")
-                    .long("genome-fasta-directory")
-                    .short("d")
-                    .takes_value(true)
-                    .required_unless_one(&["reference", "genome-fasta-files", "full-help"]))
-                .arg(Arg::with_name("genome-fasta-extension")
-                    .long("genome-fasta-extension")
-                    .short("x")
-                    .takes_value(true)
-                    .default_value("fna"))
-                .arg(Arg::with_name("bam-file-cache-directory")
-                    .long("bam-file-cache-directory")
-                    .takes_value(true))
-                .arg(Arg::with_name("output-directory")
-                    .long("output-directory")
-                    .short("o")
-                    .default_value("./"))
-                .arg(Arg::with_name("threads")
-                    .short("-t")
-                    .long("threads")
-                    .default_value("1")
-                    .takes_value(true))
-                .arg(
-                    Arg::with_name("mapper")
-                        .short("p")
-                        .long("mapper")
-                        .possible_values(MAPPING_SOFTWARE_LIST)
-                        .default_value(DEFAULT_MAPPING_SOFTWARE),
-                )
-                .arg(
-                    Arg::with_name("longread-mapper")
-                        .long("longread-mapper")
-                        .possible_values(LONGREAD_MAPPING_SOFTWARE_LIST)
-                        .default_value(DEFAULT_LONGREAD_MAPPING_SOFTWARE),
-                )
-                .arg(
-                    Arg::with_name("minimap2-params")
-                        .long("minimap2-params")
-                        .long("minimap2-parameters")
-                        .takes_value(true)
-                        .allow_hyphen_values(true),
-                )
-                .arg(
-                    Arg::with_name("minimap2-reference-is-index")
-                        .long("minimap2-reference-is-index")
-                        .requires("reference"),
-                )
-                .arg(
-                    Arg::with_name("bwa-params")
-                        .long("bwa-params")
-                        .long("bwa-parameters")
-                        .takes_value(true)
-                        .allow_hyphen_values(true)
-                        .requires("reference"),
-                )
-                .arg(Arg::with_name("discard-unmapped")
-                    .long("discard-unmapped")
-                    .requires("bam-file-cache-directory"))
-
-                .arg(Arg::with_name("min-read-aligned-length")
-                    .long("min-read-aligned-length")
-                    .takes_value(true))
-                .arg(Arg::with_name("min-read-percent-identity")
-                    .long("min-read-percent-identity")
-                    .takes_value(true))
-                .arg(Arg::with_name("min-read-aligned-percent")
-                    .long("min-read-aligned-percent")
-                    .takes_value(true)
-                    .default_value("0.0"))
-                .arg(Arg::with_name("min-read-aligned-length-pair")
-                    .long("min-read-aligned-length-pair")
-                    .takes_value(true)
-                    .conflicts_with("proper-pairs-only"))
-                .arg(Arg::with_name("min-read-percent-identity-pair")
-                    .long("min-read-percent-identity-pair")
-                    .takes_value(true)
-                    .conflicts_with("proper-pairs-only"))
-                .arg(Arg::with_name("min-read-aligned-percent-pair")
-                    .long("min-read-aligned-percent-pair")
-                    .takes_value(true)
-                    .conflicts_with("proper-pairs-only"))
-                .arg(Arg::with_name("method")
-                    .short("m")
-                    .long("method")
-                    .takes_value(true)
-                    .multiple(false)
-                    .possible_values(&[
-                        "trimmed_mean",
-                        "mean",
-                        "metabat"])
-                    .default_value("trimmed_mean"))
-                .arg(Arg::with_name("epsilon")
-                    .long("epsilon")
-                    .short("e")
-                    .default_value("0.05"))
-                .arg(Arg::with_name("min-cluster-size")
-                    .long("min-cluster-size")
-                    .short("s")
-                    .default_value("10"))
-                .arg(Arg::with_name("min-covered-fraction")
-                    .long("min-covered-fraction")
-                    .default_value("0.0"))
-                .arg(Arg::with_name("coverage-fold")
-                    .long("coverage-fold")
-                    .default_value("0.5"))
-                .arg(Arg::with_name("min-variant-depth")
-                    .long("min-variant-depth")
-                    .short("f")
-                    .default_value("10"))
-                .arg(Arg::with_name("min-variant-quality")
-                    .long("min-variant-quality")
-                    .default_value("10"))
-                .arg(Arg::with_name("mapq-threshold")
-                    .long("mapq-threshold")
-                    .short("q")
-                    .default_value("0"))
-                .arg(Arg::with_name("contig-end-exclusion")
-                    .long("contig-end-exclusion")
-                    .default_value("75"))
-                .arg(Arg::with_name("trim-min")
-                    .long("trim-min")
-                    .default_value("0.05"))
-                .arg(Arg::with_name("trim-max")
-                    .long("trim-max")
-                    .default_value("0.95"))
-                .arg(Arg::with_name("no-zeros")
-                    .long("no-zeros"))
-                .arg(Arg::with_name("proper-pairs-only")
-                    .long("proper-pairs-only"))
-                .arg(Arg::with_name("nanopore")
-                    .long("nanopore"))
-                .arg(Arg::with_name("include-secondary")
-                    .long("include-secondary"))
-                .arg(Arg::with_name("include-supplementary")
-                    .long("include-supplementary"))
-                .arg(Arg::with_name("include-indels")
-                    .long("include-indels"))
-                .arg(Arg::with_name("verbose")
-                    .short("v")
-                    .long("verbose"))
-                .arg(Arg::with_name("quiet")
-                    .long("quiet")))
-        .subcommand(
-            SubCommand::with_name("evolve")
-                .about("Pinpoint sites of synonymous and non-synonymous mutations")
-                .help(EVOLVE_HELP.as_str())
-                .arg(Arg::with_name("full-help")
-                    .long("full-help"))
-
-                .arg(Arg::with_name("bam-files")
-                    .short("b")
-                    .long("bam-files")
-                    .multiple(true)
-                    .takes_value(true)
-                    .required_unless_one(
-                        &["read1","read2","coupled","interleaved","single","full-help"]))
-                .arg(Arg::with_name("gff")
-                    .long("gff")
-                    .takes_value(true))
-                .arg(Arg::with_name("prokka-params")
-                    .long("prokka-params")
-                    .takes_value(true)
-                    .conflicts_with("gff"))
-                .arg(Arg::with_name("sharded")
-                    .long("sharded")
-                    .required(false))
-                .arg(Arg::with_name("read1")
-                    .short("-1")
-                    .multiple(true)
-                    .takes_value(true)
-                    .requires("read2")
-                    .required_unless_one(
-                        &["bam-files","coupled","interleaved","single","full-help"])
-                    .conflicts_with("bam-files"))
-                .arg(Arg::with_name("read2")
-                    .short("-2")
-                    .multiple(true)
-                    .takes_value(true)
-                    .requires("read1")
-                    .required_unless_one(
-                        &["bam-files","coupled","interleaved","single","full-help"])
-                    .conflicts_with("bam-files"))
-                .arg(Arg::with_name("coupled")
-                    .short("-c")
-                    .long("coupled")
-                    .multiple(true)
-                    .takes_value(true)
-                    .required_unless_one(
-                        &["bam-files","read1","interleaved","single","full-help"])
-                    .conflicts_with("bam-files"))
-                .arg(Arg::with_name("interleaved")
-                    .long("interleaved")
-                    .multiple(true)
-                    .takes_value(true)
-                    .required_unless_one(
-                        &["bam-files","read1","coupled","single","full-help"])
-                    .conflicts_with("bam-files"))
-                .arg(Arg::with_name("single")
-                    .long("single")
-                    .multiple(true)
-                    .takes_value(true)
-                    .required_unless_one(
-                        &["bam-files","read1","coupled","interleaved","full-help"])
-                    .conflicts_with("bam-files"))
-                .arg(Arg::with_name("reference")
-                    .short("r")
-                    .long("reference")
-                    .alias("genome-fasta-files")
-                    .takes_value(true)
-                    .multiple(true)
-                    .required_unless_one(&["genome-fasta-directory", "full-help"]))
-                .arg(Arg::with_name("genome-fasta-directory")
-                    .long("genome-fasta-directory")
-                    .short("d")
-                    .takes_value(true)
-                    .required_unless_one(&["reference", "genome-fasta-files", "full-help"]))
-                .arg(Arg::with_name("genome-fasta-extension")
-                    .long("genome-fasta-extension")
-                    .short("x")
-                    .takes_value(true)
-                    .default_value("fna"))
-                .arg(Arg::with_name("bam-file-cache-directory")
-                    .long("bam-file-cache-directory")
-                    .takes_value(true))
-                .arg(Arg::with_name("output-directory")
-                    .long("output-directory")
-                    .short("o")
-                    .default_value("./"))
-                .arg(Arg::with_name("longreads")
-                    .long("longreads")
-                    .multiple(true)
-                    .takes_value(true)
-                    .required(false)
-                    .conflicts_with_all(&["longread-bam-files"]))
-                .arg(Arg::with_name("longread-bam-files")
-                    .short("l")
-                    .multiple(true)
-                    .takes_value(true)
-                    .required(false)
-                    .conflicts_with_all(&["longreads"]))
-                .arg(Arg::with_name("threads")
-                    .short("t")
-                    .long("threads")
-                    .default_value("1")
-                    .takes_value(true))
-                .arg(
-                    Arg::with_name("mapper")
-                        .short("p")
-                        .long("mapper")
-                        .possible_values(MAPPING_SOFTWARE_LIST)
-                        .default_value(DEFAULT_MAPPING_SOFTWARE),
-                )
-                .arg(
-                    Arg::with_name("longread-mapper")
-                        .long("longread-mapper")
-                        .possible_values(LONGREAD_MAPPING_SOFTWARE_LIST)
-                        .default_value(DEFAULT_LONGREAD_MAPPING_SOFTWARE),
-                )
-                .arg(
-                    Arg::with_name("minimap2-params")
-                        .long("minimap2-params")
-                        .long("minimap2-parameters")
-                        .takes_value(true)
-                        .allow_hyphen_values(true),
-                )
-                .arg(
-                    Arg::with_name("minimap2-reference-is-index")
-                        .long("minimap2-reference-is-index")
-                        .requires("reference"),
-                )
-                .arg(
-                    Arg::with_name("bwa-params")
-                        .long("bwa-params")
-                        .long("bwa-parameters")
-                        .takes_value(true)
-                        .allow_hyphen_values(true)
-                        .requires("reference"),
-                )
-                .arg(Arg::with_name("discard-unmapped")
-                    .long("discard-unmapped")
-                    .requires("bam-file-cache-directory"))
-
-                .arg(Arg::with_name("min-read-aligned-length")
-                    .long("min-read-aligned-length")
-                    .takes_value(true))
-                .arg(Arg::with_name("min-read-percent-identity")
-                    .long("min-read-percent-identity")
-                    .takes_value(true))
-                .arg(Arg::with_name("min-read-aligned-percent")
-                    .long("min-read-aligned-percent")
-                    .default_value("0.0")
-                    .takes_value(true))
-                .arg(Arg::with_name("min-read-aligned-length-pair")
-                    .long("min-read-aligned-length-pair")
-                    .takes_value(true)
-                    .conflicts_with("proper-pairs-only"))
-                .arg(Arg::with_name("min-read-percent-identity-pair")
-                    .long("min-read-percent-identity-pair")
-                    .takes_value(true)
-                    .conflicts_with("proper-pairs-only"))
-                .arg(Arg::with_name("min-read-aligned-percent-pair")
-                    .long("min-read-aligned-percent-pair")
-                    .takes_value(true)
-                    .conflicts_with("proper-pairs-only"))
-                .arg(Arg::with_name("method")
-                    .short("m")
-                    .long("method")
-                    .takes_value(true)
-                    .possible_values(&[
-                        "trimmed_mean",
-                        "mean",
-                        "metabat"])
-                    .default_value("trimmed_mean"))
-                .arg(Arg::with_name("min-covered-fraction")
-                    .long("min-covered-fraction")
-                    .default_value("0.0"))
-                .arg(Arg::with_name("coverage-fold")
-                    .long("coverage-fold")
-                    .default_value("0.5"))
-                .arg(Arg::with_name("min-variant-depth")
-                    .long("min-variant-depth")
-                    .short("f")
-                    .default_value("10"))
-                .arg(Arg::with_name("min-variant-quality")
-                    .long("min-variant-quality")
-                    .default_value("10"))
-                .arg(Arg::with_name("mapq-threshold")
-                    .long("mapq-threshold")
-                    .default_value("0"))
-                .arg(Arg::with_name("base-quality-threshold")
-                    .long("base-quality-threshold")
-                    .short("q")
-                    .default_value("13"))
-                .arg(Arg::with_name("min-repeat-entropy")
-                    .long("min-repeat-entropy")
-                    .default_value("1.5"))
-                .arg(Arg::with_name("kmer-size")
-                    .long("kmer-size")
-                    .short("k")
-                    .default_value("4"))
-                .arg(Arg::with_name("contig-end-exclusion")
-                    .long("contig-end-exclusion")
-                    .default_value("75"))
-                .arg(Arg::with_name("trim-min")
-                    .long("trim-min")
-                    .default_value("0.05"))
-                .arg(Arg::with_name("trim-max")
-                    .long("trim-max")
-                    .default_value("0.95"))
-                .arg(Arg::with_name("no-zeros")
-                    .long("no-zeros"))
-                .arg(Arg::with_name("proper-pairs-only")
-                    .long("proper-pairs-only"))
-                .arg(Arg::with_name("window-size")
-                    .long("window-size")
-                    .short("w")
-                    .default_value("1"))
-                .arg(Arg::with_name("plot")
-                    .long("plot"))
-                .arg(Arg::with_name("nanopore")
-                    .long("nanopore"))
-                .arg(Arg::with_name("include-longread-svs")
-                    .long("include-longread-svs"))
-                .arg(Arg::with_name("include-secondary")
-                    .long("include-secondary"))
-                .arg(Arg::with_name("include-soft-clipping")
-                    .long("include-soft-clipping"))
-                .arg(Arg::with_name("include-supplementary")
-                    .long("include-supplementary"))
-                .arg(Arg::with_name("include-indels")
-                    .long("include-indels"))
-                .arg(Arg::with_name("ploidy")
-                    .long("ploidy")
-                    .default_value("2")
-                    .required(false))
-                .arg(Arg::with_name("verbose")
-                    .short("v")
-                    .long("verbose"))
-                .arg(Arg::with_name("quiet")
-                    .long("quiet")))
-        .subcommand(
-            SubCommand::with_name("summarize")
-                .about("Perform variant calling analysis and then binning")
-                .help(SUMMARIZE_HELP.as_str())
-                .arg(Arg::with_name("full-help")
-                    .long("full-help"))
-
-                .arg(Arg::with_name("bam-files")
-                    .short("b")
-                    .long("bam-files")
-                    .multiple(true)
-                    .takes_value(true)
-                    .required_unless_one(
-                        &["read1","read2","coupled","interleaved","single","full-help"]))
-                .arg(Arg::with_name("sharded")
-                    .long("sharded")
-                    .required(false))
-                .arg(Arg::with_name("read1")
-                    .short("-1")
-                    .multiple(true)
-                    .takes_value(true)
-                    .requires("read2")
-                    .required_unless_one(
-                        &["bam-files","coupled","interleaved","single","full-help"])
-                    .conflicts_with("bam-files"))
-                .arg(Arg::with_name("read2")
-                    .short("-2")
-                    .multiple(true)
-                    .takes_value(true)
-                    .requires("read1")
-                    .required_unless_one(
-                        &["bam-files","coupled","interleaved","single","full-help"])
-                    .conflicts_with("bam-files"))
-                .arg(Arg::with_name("coupled")
-                    .short("-c")
-                    .long("coupled")
-                    .multiple(true)
-                    .takes_value(true)
-                    .required_unless_one(
-                        &["bam-files","read1","interleaved","single","full-help"])
-                    .conflicts_with("bam-files"))
-                .arg(Arg::with_name("interleaved")
-                    .long("interleaved")
-                    .multiple(true)
-                    .takes_value(true)
-                    .required_unless_one(
-                        &["bam-files","read1","coupled","single","full-help"])
-                    .conflicts_with("bam-files"))
-                .arg(Arg::with_name("single")
-                    .long("single")
-                    .multiple(true)
-                    .takes_value(true)
-                    .required_unless_one(
-                        &["bam-files","read1","coupled","interleaved","full-help"])
-                    .conflicts_with("bam-files"))
-                .arg(Arg::with_name("longreads")
-                    .long("longreads")
-                    .multiple(true)
-                    .takes_value(true)
-                    .required(false)
-                    .conflicts_with_all(&["longread-bam-files"]))
-                .arg(Arg::with_name("longread-bam-files")
-                    .short("l")
-                    .multiple(true)
-                    .takes_value(true)
-                    .required(false)
-                    .conflicts_with_all(&["longreads"]))
-                .arg(Arg::with_name("reference")
-                    .short("r")
-                    .long("reference")
-                    .alias("genome-fasta-files")
-                    .takes_value(true)
-                    .multiple(true)
-                    .required_unless_one(&["genome-fasta-directory", "full-help"]))
-                .arg(Arg::with_name("genome-fasta-directory")
-                    .long("genome-fasta-directory")
-                    .short("d")
-                    .takes_value(true)
-                    .required_unless_one(&["reference", "genome-fasta-files", "full-help"]))
-                .arg(Arg::with_name("genome-fasta-extension")
-                    .long("genome-fasta-extension")
-                    .short("x")
-                    .takes_value(true)
-                    .default_value("fna"))
-                .arg(Arg::with_name("bam-file-cache-directory")
-                    .long("bam-file-cache-directory")
-                    .takes_value(true))
-                .arg(Arg::with_name("output-directory")
-                    .long("output-directory")
-                    .short("o")
-                    .default_value("./"))
-                .arg(Arg::with_name("threads")
-                    .short("-t")
-                    .long("threads")
-                    .default_value("1")
-                    .takes_value(true))
-                .arg(
-                    Arg::with_name("mapper")
-                        .short("p")
-                        .long("mapper")
-                        .possible_values(MAPPING_SOFTWARE_LIST)
-                        .default_value(DEFAULT_MAPPING_SOFTWARE),
-                )
-                .arg(
-                    Arg::with_name("longread-mapper")
-                        .long("longread-mapper")
-                        .possible_values(LONGREAD_MAPPING_SOFTWARE_LIST)
-                        .default_value(DEFAULT_LONGREAD_MAPPING_SOFTWARE),
-                )
-                .arg(
-                    Arg::with_name("minimap2-params")
-                        .long("minimap2-params")
-                        .long("minimap2-parameters")
-                        .takes_value(true)
-                        .allow_hyphen_values(true),
-                )
-                .arg(
-                    Arg::with_name("minimap2-reference-is-index")
-                        .long("minimap2-reference-is-index")
-                        .requires("reference"),
-                )
-                .arg(
-                    Arg::with_name("bwa-params")
-                        .long("bwa-params")
-                        .long("bwa-parameters")
-                        .takes_value(true)
-                        .allow_hyphen_values(true)
-                        .requires("reference"),
-                )
-                .arg(Arg::with_name("discard-unmapped")
-                    .long("discard-unmapped")
-                    .requires("bam-file-cache-directory"))
-
-                .arg(Arg::with_name("min-read-aligned-length")
-                    .long("min-read-aligned-length")
-                    .takes_value(true))
-                .arg(Arg::with_name("min-read-percent-identity")
-                    .long("min-read-percent-identity")
-                    .takes_value(true))
-                .arg(Arg::with_name("min-read-aligned-percent")
-                    .long("min-read-aligned-percent")
-                    .default_value("0.0")
-                    .takes_value(true))
-                .arg(Arg::with_name("min-read-aligned-length-pair")
-                    .long("min-read-aligned-length-pair")
-                    .takes_value(true)
-                    .conflicts_with("proper-pairs-only"))
-                .arg(Arg::with_name("min-read-percent-identity-pair")
-                    .long("min-read-percent-identity-pair")
-                    .takes_value(true)
-                    .conflicts_with("proper-pairs-only"))
-                .arg(Arg::with_name("min-read-aligned-percent-pair")
-                    .long("min-read-aligned-percent-pair")
-                    .takes_value(true)
-                    .conflicts_with("proper-pairs-only"))
-                .arg(Arg::with_name("method")
-                    .short("m")
-                    .long("method")
-                    .takes_value(true)
-                    .possible_values(&[
-                        "trimmed_mean",
-                        "mean",
-                        "metabat"])
-                    .default_value("trimmed_mean"))
-                .arg(Arg::with_name("window-size")
-                    .long("window-size")
-                    .short("w")
-                    .default_value("1"))
-                .arg(Arg::with_name("epsilon")
-                    .long("epsilon")
-                    .short("e")
-                    .default_value("0.05"))
-                .arg(Arg::with_name("min-cluster-size")
-                    .long("min-cluster-size")
-                    .short("s")
-                    .default_value("10"))
-                .arg(Arg::with_name("min-covered-fraction")
-                    .long("min-covered-fraction")
-                    .default_value("0.0"))
-                .arg(Arg::with_name("coverage-fold")
-                    .long("coverage-fold")
-                    .default_value("0.5"))
-                .arg(Arg::with_name("min-variant-depth")
-                    .long("min-variant-depth")
-                    .short("f")
-                    .default_value("10"))
-                .arg(Arg::with_name("min-variant-quality")
-                    .long("min-variant-quality")
-                    .default_value("10"))
-                .arg(Arg::with_name("strain-ani")
-                    .long("strain-ani")
-                    .short("a"))
-                .arg(Arg::with_name("mapq-threshold")
-                    .long("mapq-threshold")
-                    .default_value("10"))
-                .arg(Arg::with_name("base-quality-threshold")
-                    .long("base-quality-threshold")
-                    .short("q")
-                    .default_value("13"))
-                .arg(Arg::with_name("min-repeat-entropy")
-                    .long("min-repeat-entropy")
-                    .default_value("1.5"))
-                .arg(Arg::with_name("kmer-size")
-                    .long("kmer-size")
-                    .short("k")
-                    .default_value("4"))
-                .arg(Arg::with_name("contig-end-exclusion")
-                    .long("contig-end-exclusion")
-                    .default_value("75"))
-                .arg(Arg::with_name("trim-min")
-                    .long("trim-min")
-                    .default_value("0.05"))
-                .arg(Arg::with_name("trim-max")
-                    .long("trim-max")
-                    .default_value("0.95"))
-                .arg(Arg::with_name("no-zeros")
-                    .long("no-zeros"))
-                .arg(Arg::with_name("proper-pairs-only")
-                    .long("proper-pairs-only"))
-                .arg(Arg::with_name("nanopore")
-                    .long("nanopore"))
-                .arg(Arg::with_name("include-secondary")
-                    .long("include-secondary"))
-                .arg(Arg::with_name("include-supplementary")
-                    .long("include-supplementary"))
-                .arg(Arg::with_name("include-longread-svs")
-                    .long("include-longread-svs"))
-                .arg(Arg::with_name("verbose")
-                    .short("v")
-                    .long("verbose"))
-                .arg(Arg::with_name("quiet")
-                    .long("quiet")))
-        .subcommand(
-            SubCommand::with_name("genotype")
-                .about("Perform variant calling analysis and then binning")
-                .help(GENOTYPE_HELP.as_str())
-                .arg(Arg::with_name("full-help")
-                    .long("full-help"))
-
-                .arg(Arg::with_name("bam-files")
-                    .short("b")
-                    .long("bam-files")
-                    .multiple(true)
-                    .takes_value(true)
-                    .required_unless_one(
-                        &["read1","read2","coupled","interleaved","single","full-help"]))
-                .arg(Arg::with_name("sharded")
-                    .long("sharded")
-                    .required(false))
-                .arg(Arg::with_name("read1")
-                    .short("-1")
-                    .multiple(true)
-                    .takes_value(true)
-                    .requires("read2")
-                    .required_unless_one(
-                        &["bam-files","coupled","interleaved","single","full-help"])
-                    .conflicts_with("bam-files"))
-                .arg(Arg::with_name("read2")
-                    .short("-2")
-                    .multiple(true)
-                    .takes_value(true)
-                    .requires("read1")
-                    .required_unless_one(
-                        &["bam-files","coupled","interleaved","single","full-help"])
-                    .conflicts_with("bam-files"))
-                .arg(Arg::with_name("coupled")
-                    .short("-c")
-                    .long("coupled")
-                    .multiple(true)
-                    .takes_value(true)
-                    .required_unless_one(
-                        &["bam-files","read1","interleaved","single","full-help"])
-                    .conflicts_with("bam-files"))
-                .arg(Arg::with_name("interleaved")
-                    .long("interleaved")
-                    .multiple(true)
-                    .takes_value(true)
-                    .required_unless_one(
-                        &["bam-files","read1","coupled","single","full-help"])
-                    .conflicts_with("bam-files"))
-                .arg(Arg::with_name("single")
-                    .long("single")
-                    .multiple(true)
-                    .takes_value(true)
-                    .required_unless_one(
-                        &["bam-files","read1","coupled","interleaved","full-help"])
-                    .conflicts_with("bam-files"))
-                .arg(Arg::with_name("longreads")
-                    .long("longreads")
-                    .multiple(true)
-                    .takes_value(true)
-                    .required(false)
-                    .conflicts_with_all(&["longread-bam-files"]))
-                .arg(Arg::with_name("longread-bam-files")
-                    .short("l")
-                    .long("longread-bam-files")
-                    .multiple(true)
-                    .takes_value(true)
-                    .required(false)
-                    .conflicts_with_all(&["longreads"]))
-                .arg(Arg::with_name("reference")
-                    .short("r")
-                    .long("reference")
-                    .alias("genome-fasta-files")
-                    .takes_value(true)
-                    .multiple(true)
-                    .required_unless_one(&["genome-fasta-directory", "full-help"]))
-                .arg(Arg::with_name("genome-fasta-directory")
-                    .long("genome-fasta-directory")
-                    .short("d")
-                    .takes_value(true)
-                    .required_unless_one(&["reference", "genome-fasta-files", "full-help"]))
-                .arg(Arg::with_name("genome-fasta-extension")
-                    .long("genome-fasta-extension")
-                    .short("x")
-                    .takes_value(true)
-                    .default_value("fna"))
-                .arg(Arg::with_name("bam-file-cache-directory")
-                    .long("bam-file-cache-directory")
-                    .takes_value(true))
-                .arg(Arg::with_name("output-directory")
-                    .long("output-directory")
-                    .short("o")
-                    .default_value("./"))
-                .arg(Arg::with_name("vcfs")
-                    .long("vcfs")
-                    .multiple(true)
-                    .required(false))
-                .arg(Arg::with_name("threads")
-                    .short("-t")
-                    .long("threads")
-                    .default_value("1")
-                    .takes_value(true))
-                .arg(
-                    Arg::with_name("mapper")
-                        .short("p")
-                        .long("mapper")
-                        .possible_values(MAPPING_SOFTWARE_LIST)
-                        .default_value(DEFAULT_MAPPING_SOFTWARE),
-                )
-                .arg(
-                    Arg::with_name("longread-mapper")
-                        .long("longread-mapper")
-                        .possible_values(LONGREAD_MAPPING_SOFTWARE_LIST)
-                        .default_value(DEFAULT_LONGREAD_MAPPING_SOFTWARE),
-                )
-                .arg(
-                    Arg::with_name("minimap2-params")
-                        .long("minimap2-params")
-                        .long("minimap2-parameters")
-                        .takes_value(true)
-                        .allow_hyphen_values(true),
-                )
-                .arg(
-                    Arg::with_name("minimap2-reference-is-index")
-                        .long("minimap2-reference-is-index")
-                        .requires("reference"),
-                )
-                .arg(
-                    Arg::with_name("bwa-params")
-                        .long("bwa-params")
-                        .long("bwa-parameters")
-                        .takes_value(true)
-                        .allow_hyphen_values(true)
-                        .requires("reference"),
-                )
-                .arg(Arg::with_name("discard-unmapped")
-                    .long("discard-unmapped")
-                    .requires("bam-file-cache-directory"))
-
-                .arg(Arg::with_name("min-read-aligned-length")
-                    .long("min-read-aligned-length")
-                    .takes_value(true))
-                .arg(Arg::with_name("min-read-percent-identity")
-                    .long("min-read-percent-identity")
-                    .takes_value(true))
-                .arg(Arg::with_name("min-read-aligned-percent")
-                    .long("min-read-aligned-percent")
-                    .default_value("0.0")
-                    .takes_value(true))
-                .arg(Arg::with_name("min-read-aligned-length-pair")
-                    .long("min-read-aligned-length-pair")
-                    .takes_value(true)
-                    .conflicts_with("proper-pairs-only"))
-                .arg(Arg::with_name("min-read-percent-identity-pair")
-                    .long("min-read-percent-identity-pair")
-                    .takes_value(true)
-                    .conflicts_with("proper-pairs-only"))
-                .arg(Arg::with_name("min-read-aligned-percent-pair")
-                    .long("min-read-aligned-percent-pair")
-                    .takes_value(true)
-                    .conflicts_with("proper-pairs-only"))
-                .arg(Arg::with_name("method")
-                    .short("m")
-                    .long("method")
-                    .takes_value(true)
-                    .possible_values(&[
-                        "trimmed_mean",
-                        "mean",
-                        "metabat"])
-                    .default_value("trimmed_mean"))
-                .arg(Arg::with_name("e-min")
-                    .long("e-min")
-                    .default_value("0.1"))
-                .arg(Arg::with_name("e-max")
-                    .long("e-max")
-                    .default_value("0.5"))
-                .arg(Arg::with_name("pts-min")
-                    .long("pts-min")
-                    .default_value("0.05"))
-                .arg(Arg::with_name("pts-max")
-                    .long("pts-max")
-                    .default_value("0.1"))
-                .arg(Arg::with_name("phi")
-                    .long("phi")
-                    .default_value("0.0"))
-                .arg(Arg::with_name("min-covered-fraction")
-                    .long("min-covered-fraction")
-                    .default_value("0.0"))
-                .arg(Arg::with_name("coverage-fold")
-                    .long("coverage-fold")
-                    .default_value("1.0"))
-                .arg(Arg::with_name("min-variant-depth")
-                    .long("min-variant-depth")
-                    .short("f")
-                    .default_value("10"))
-                .arg(Arg::with_name("min-variant-quality")
-                    .long("min-variant-quality")
-                    .default_value("10"))
-                .arg(Arg::with_name("strain-ani")
-                    .long("strain-ani")
-                    .short("a")
-                    .takes_value(true))
-                .arg(Arg::with_name("mapq-threshold")
-                    .long("mapq-threshold")
-                    .default_value("10"))
-                .arg(Arg::with_name("minimum-seed-size")
-                    .long("minimum-seed-size")
-                    .short("n")
-                    .default_value("20"))
-                .arg(Arg::with_name("minimum-reads-in-link")
-                    .long("minimum-reads-in-link")
-                    .default_value("5"))
-                .arg(Arg::with_name("maximum-seed-similarity")
-                    .long("maximum-seed-similarity")
-                    .short("s")
-                    .default_value("0.97"))
-                .arg(Arg::with_name("base-quality-threshold")
-                    .long("base-quality-threshold")
-                    .short("q")
-                    .default_value("13"))
-                .arg(Arg::with_name("min-repeat-entropy")
-                    .long("min-repeat-entropy")
-                    .default_value("1.5"))
-                .arg(Arg::with_name("kmer-size")
-                    .long("kmer-size")
-                    .short("k")
-                    .default_value("4"))
-                .arg(Arg::with_name("contig-end-exclusion")
-                    .long("contig-end-exclusion")
-                    .default_value("75"))
-                .arg(Arg::with_name("trim-min")
-                    .long("trim-min")
-                    .default_value("0.05"))
-                .arg(Arg::with_name("trim-max")
-                    .long("trim-max")
-                    .default_value("0.95"))
-                .arg(Arg::with_name("no-zeros")
-                    .long("no-zeros"))
-                .arg(Arg::with_name("proper-pairs-only")
-                    .long("proper-pairs-only"))
-                .arg(Arg::with_name("window-size")
-                    .long("window-size")
-                    .short("w")
-                    .default_value("1"))
-                .arg(Arg::with_name("plot")
-                    .long("plot"))
-                .arg(Arg::with_name("nanopore")
-                    .long("nanopore"))
-                .arg(Arg::with_name("include-longread-svs")
-                    .long("include-longread-svs"))
-                .arg(Arg::with_name("include-secondary")
-                    .long("include-secondary"))
-                .arg(Arg::with_name("include-soft-clipping")
-                    .long("include-soft-clipping"))
-                .arg(Arg::with_name("include-supplementary")
-                    .long("include-supplementary"))
-                .arg(Arg::with_name("include-indels")
-                    .long("include-indels"))
-                .arg(Arg::with_name("ploidy")
-                    .long("ploidy")
-                    .default_value("2")
-                    .required(false))
-                .arg(Arg::with_name("verbose")
-                    .short("v")
-                    .long("verbose"))
-                .arg(Arg::with_name("quiet")
-                    .long("quiet")))
-        .subcommand(
-            SubCommand::with_name("kmer")
-                .about("Generate kmer count matrix for contigs")
-//                .help(CONTIG_HELP.as_str())
-                .arg(Arg::with_name("full-help")
-                    .long("full-help"))
-                .arg(Arg::with_name("reference")
-                    .short("-r")
-                    .long("reference")
-                    .takes_value(true)
-                    .required(true))
-                .arg(Arg::with_name("verbose")
-                    .short("v")
-                    .long("verbose")))
-        .subcommand(
-            SubCommand::with_name("filter")
-                .about("Remove alignments with insufficient identity")
-                .help(FILTER_HELP.as_str())
-
-                .arg(Arg::with_name("full-help")
-                    .long("full-help"))
-
-                .arg(Arg::with_name("bam-files")
-                    .short("b")
-                    .long("bam-files")
-                    .multiple(true)
-                    .takes_value(true)
-                    .required_unless_one(&["full-help"]))
-                .arg(Arg::with_name("output-bam-files")
-                    .short("o")
-                    .long("output-bam-files")
-                    .multiple(true)
-                    .takes_value(true)
-                    .required_unless_one(&["full-help"]))
-                .arg(Arg::with_name("inverse")
-                    .long("inverse"))
-
-                .arg(Arg::with_name("min-read-aligned-length")
-                    .long("min-read-aligned-length")
-                    .takes_value(true))
-                .arg(Arg::with_name("min-read-percent-identity")
-                    .long("min-read-percent-identity")
-                    .takes_value(true))
-                .arg(Arg::with_name("min-read-aligned-percent")
-                    .long("min-read-aligned-percent")
-                    .default_value("0.0")
-                    .takes_value(true))
-                .arg(Arg::with_name("min-read-aligned-length-pair")
-                    .long("min-read-aligned-length-pair")
-                    .takes_value(true)
-                    .conflicts_with("proper-pairs-only"))
-                .arg(Arg::with_name("min-read-percent-identity-pair")
-                    .long("min-read-percent-identity-pair")
-                    .takes_value(true)
-                    .conflicts_with("proper-pairs-only"))
-                .arg(Arg::with_name("min-read-aligned-percent-pair")
-                    .long("min-read-aligned-percent-pair")
-                    .takes_value(true)
-                    .conflicts_with("proper-pairs-only"))
-                .arg(Arg::with_name("proper-pairs-only")
-                    .long("proper-pairs-only"))
-                .arg(Arg::with_name("threads")
-                    .long("threads")
-                    .short("t")
-                    .default_value("1"))
-
-                .arg(Arg::with_name("verbose")
-                    // .short("v") // Do not use since could be confused with
-                    // inverse (a la grep -v)
-                    .long("verbose"))
-                .arg(Arg::with_name("quiet")
-                    .short("q")
-                    .long("quiet")))
-        .subcommand(
-            SubCommand::with_name("polish")
-                .about("Polish an assembly using highly abundant variant calls")
-                .help(POLYMORPH_HELP.as_str())
-                .arg(Arg::with_name("full-help")
-                    .long("full-help"))
-                .arg(Arg::with_name("bam-file")
-                    .short("b")
-                    .long("bam-file")
-                    .multiple(true)
-                    .takes_value(true)
-                    .required_unless_one(
-                        &["read1","read2","coupled","interleaved","single","full-help"]))
-                .arg(Arg::with_name("sharded")
-                    .long("sharded")
-                    .required(false))
-                .arg(Arg::with_name("read1")
-                    .short("-1")
-                    .multiple(true)
-                    .takes_value(true)
-                    .requires("read2")
-                    .required_unless_one(
-                        &["bam-file","coupled","interleaved","single","full-help"])
-                    .conflicts_with("bam-file"))
-                .arg(Arg::with_name("read2")
-                    .short("-2")
-                    .multiple(true)
-                    .takes_value(true)
-                    .requires("read1")
-                    .required_unless_one(
-                        &["bam-file","coupled","interleaved","single","full-help"])
-                    .conflicts_with("bam-file"))
-                .arg(Arg::with_name("coupled")
-                    .short("c")
-                    .long("coupled")
-                    .multiple(true)
-                    .takes_value(true)
-                    .required_unless_one(
-                        &["bam-file","read1","interleaved","single","full-help"])
-                    .conflicts_with("bam-file"))
-                .arg(Arg::with_name("longreads")
-                    .long("longreads")
-                    .multiple(true)
-                    .takes_value(true)
-                    .required(false)
-                    .conflicts_with_all(&["longread-bam-files"]))
-                .arg(Arg::with_name("longread-bam-files")
-                    .short("l")
-                    .multiple(true)
-                    .takes_value(true)
-                    .required(false)
-                    .conflicts_with_all(&["longreads"]))
-                .arg(Arg::with_name("interleaved")
-                    .long("interleaved")
-                    .multiple(true)
-                    .takes_value(true)
-                    .required_unless_one(
-                        &["bam-file","read1","coupled","single","full-help"])
-                    .conflicts_with("bam-file"))
-                .arg(Arg::with_name("single")
-                    .long("single")
-                    .multiple(true)
-                    .takes_value(true)
-                    .required_unless_one(
-                        &["bam-file","read1","coupled","interleaved","full-help"])
-                    .conflicts_with("bam-file"))
-                .arg(Arg::with_name("reference")
-                    .short("r")
-                    .long("reference")
-                    .alias("reference")
-                    .takes_value(true)
-                    .multiple(true)
-                    .required_unless_one(&["genome-fasta-directory", "full-help"]))
-                .arg(Arg::with_name("genome-fasta-directory")
-                    .long("genome-fasta-directory")
-                    .short("d")
-                    .takes_value(true)
-                    .required_unless_one(&["reference", "genome-fasta-files", "full-help"]))
-                .arg(Arg::with_name("genome-fasta-extension")
-                    .long("genome-fasta-extension")
-                    .short("x")
-                    .takes_value(true)
-                    .default_value("fna"))
-                .arg(Arg::with_name("bam-file-cache-directory")
-                    .long("bam-file-cache-directory")
-                    .takes_value(true))
-                .arg(Arg::with_name("output-directory")
-                    .long("output-directory")
-                    .short("o")
-                    .default_value("./"))
-                .arg(Arg::with_name("threads")
-                    .short("-t")
-                    .long("threads")
-                    .default_value("1")
-                    .takes_value(true))
-                .arg(
-                    Arg::with_name("mapper")
-                        .short("p")
-                        .long("mapper")
-                        .possible_values(MAPPING_SOFTWARE_LIST)
-                        .default_value(DEFAULT_MAPPING_SOFTWARE),
-                )
-                .arg(
-                    Arg::with_name("longread-mapper")
-                        .long("longread-mapper")
-                        .possible_values(LONGREAD_MAPPING_SOFTWARE_LIST)
-                        .default_value(DEFAULT_LONGREAD_MAPPING_SOFTWARE),
-                )
-                .arg(
-                    Arg::with_name("minimap2-params")
-                        .long("minimap2-params")
-                        .long("minimap2-parameters")
-                        .takes_value(true)
-                        .allow_hyphen_values(true),
-                )
-                .arg(
-                    Arg::with_name("minimap2-reference-is-index")
-                        .long("minimap2-reference-is-index")
-                        .requires("reference"),
-                )
-                .arg(
-                    Arg::with_name("bwa-params")
-                        .long("bwa-params")
-                        .long("bwa-parameters")
-                        .takes_value(true)
-                        .allow_hyphen_values(true)
-                        .requires("reference"),
-                )
-                .arg(Arg::with_name("discard-unmapped")
-                    .long("discard-unmapped")
-                    .requires("bam-file-cache-directory"))
-
-                .arg(Arg::with_name("min-read-aligned-length")
-                    .long("min-read-aligned-length")
-                    .takes_value(true))
-                .arg(Arg::with_name("min-read-percent-identity")
-                    .long("min-read-percent-identity")
-                    .takes_value(true))
-                .arg(Arg::with_name("min-read-aligned-percent")
-                    .long("min-read-aligned-percent")
-                    .takes_value(true)
-                    .default_value("0.0"))
-                .arg(Arg::with_name("min-read-aligned-length-pair")
-                    .long("min-read-aligned-length-pair")
-                    .takes_value(true)
-                    .conflicts_with("proper-pairs-only"))
-                .arg(Arg::with_name("min-read-percent-identity-pair")
-                    .long("min-read-percent-identity-pair")
-                    .takes_value(true)
-                    .conflicts_with("proper-pairs-only"))
-                .arg(Arg::with_name("min-read-aligned-percent-pair")
-                    .long("min-read-aligned-percent-pair")
-                    .takes_value(true)
-                    .conflicts_with("proper-pairs-only"))
-                .arg(Arg::with_name("method")
-                    .short("m")
-                    .long("method")
-                    .takes_value(true)
-                    .multiple(false)
-                    .possible_values(&[
-                        "trimmed_mean",
-                        "mean",
-                        "metabat"])
-                    .default_value("trimmed_mean"))
-                .arg(Arg::with_name("min-covered-fraction")
-                    .long("min-covered-fraction")
-                    .default_value("0.0"))
-                .arg(Arg::with_name("coverage-fold")
-                    .long("coverage-fold")
-                    .default_value("0.5"))
-                .arg(Arg::with_name("min-variant-depth")
-                    .long("min-variant-depth")
-                    .short("f")
-                    .default_value("10"))
-                .arg(Arg::with_name("min-variant-quality")
-                    .long("min-variant-quality")
-                    .default_value("10"))
-                .arg(Arg::with_name("mapq-threshold")
-                    .long("mapq-threshold")
-                    .short("q")
-                    .default_value("0"))
-                .arg(Arg::with_name("include-indels")
-                    .long("include-indels"))
-                .arg(Arg::with_name("contig-end-exclusion")
-                    .long("contig-end-exclusion")
-                    .default_value("75"))
-                .arg(Arg::with_name("trim-min")
-                    .long("trim-min")
-                    .default_value("0.05"))
-                .arg(Arg::with_name("trim-max")
-                    .long("trim-max")
-                    .default_value("0.95"))
-                .arg(Arg::with_name("no-zeros")
-                    .long("no-zeros"))
-                .arg(Arg::with_name("proper-pairs-only")
-                    .long("proper-pairs-only"))
-                .arg(Arg::with_name("verbose")
-                    .short("v")
-                    .long("verbose"))
-                .arg(Arg::with_name("quiet")
-                    .long("quiet")));
-}
+use clap::*;
+
+
+const MAPPING_SOFTWARE_LIST: &[&str] = &["bwa-mem", "minimap2-sr", "minimap2-ont", "minimap2-pb","minimap2-no-preset", "ngmlr"];
+const DEFAULT_MAPPING_SOFTWARE: &str = "minimap2-sr";
+
+const LONGREAD_MAPPING_SOFTWARE_LIST: &[&str] = &["minimap2-ont", "minimap2-pb","ngmlr-ont", "ngmlr-pb"];
+const DEFAULT_LONGREAD_MAPPING_SOFTWARE: &str = "ngmlr-ont";
+
+// Strain counts that can be searched exhaustively, matching MAX_DECONVOLUTION_STRAINS
+const STRAIN_COUNT_LIST: &[&str] = &["1", "2", "3", "4", "5", "6", "7", "8", "9", "10"];
+
+
+const MAPPER_HELP: &'static str =
+    "    -p, --mapper <NAME>             Underlying mapping software used
+                                         (\"minimap2-sr\", \"bwa-mem\",
+                                         \"ngmlr-ont\", \"ngmlr-pb\", \"minimap2-ont\",
+                                         \"minimap2-pb\", or \"minimap2-no-preset\").
+                                         minimap2 -sr, -ont, -pb, -no-preset specify
+                                         '-x' preset of minimap2 to be used
+                                         (with map-ont, map-pb for -ont, -pb).
+                                         [default: \"minimap2-sr\"] \n
+         --minimap2-params PARAMS        Extra parameters to provide to minimap2,
+                                         both indexing command (if used) and for
+                                         mapping. Note that usage of this parameter
+                                         has security implications if untrusted input
+                                         is specified. '-a' is always specified.
+                                         [default \"\"] \n
+         --minimap2-reference-is-index   Treat reference as a minimap2 database, not
+                                         as a FASTA file.\n
+         --bwa-params PARAMS             Extra parameters to provide to BWA. Note
+                                         that usage of this parameter has security
+                                         implications if untrusted input is specified.
+                                         [default \"\"]\n
+         --ngmlr-params PARAMS           Extra parameters to provide to NGMLR.
+                                         --bam-fix, -x ont, -t are already set. Note
+                                         that usage of this parameter has security
+                                         implications if untrusted input is specified.\n";
+
+const ALIGNMENT_OPTIONS: &'static str =
+"Define mapping(s) (required):
+  Either define BAM:
+   -b, --bam-files <PATH> ..             Path to BAM file(s). These must be
+                                         reference sorted (e.g. with samtools sort)
+                                         unless --sharded is specified, in which
+                                         case they must be read name sorted (e.g.
+                                         with samtools sort -n).
+  -l, --longread-bam-files <PATH> ..     Path to BAM files(s) generated from longreads.
+                                         Must be reference sorted.
+
+  Or do mapping:
+   -r, --reference <PATH> ..             FASTA file of contigs or BWA index stem
+                                         e.g. concatenated genomes or assembly.
+                                         If multiple reference FASTA files are
+                                         provided and --sharded is specified,
+                                         then reads will be mapped to reference
+                                         separately as sharded BAMs
+   -d, --genome-fasta-directory <PATH>   Directory containing FASTA files to be analyzed
+   -x, --genome-fasta-extension <STR>    FASTA file extension in --genome-fasta-directory
+                                         [default \"fna\"]
+   -t, --threads <INT>                   Number of threads for mapping / sorting
+   -1 <PATH> ..                          Forward FASTA/Q file(s) for mapping
+   -2 <PATH> ..                          Reverse FASTA/Q file(s) for mapping
+   -c, --coupled <PATH> <PATH> ..        One or more pairs of forward and reverse
+                                         FASTA/Q files for mapping in order
+                                         <sample1_R1.fq.gz> <sample1_R2.fq.gz>
+                                         <sample2_R1.fq.gz> <sample2_R2.fq.gz> ..
+   --interleaved <PATH> ..               Interleaved FASTA/Q files(s) for mapping.
+   --single <PATH> ..                    Unpaired FASTA/Q files(s) for mapping.
+   --longreads <PATH> ..                 pacbio or oxford nanopore long reads FASTA/Q files(s).
+   --bam-file-cache-directory            Directory to store cached BAM files. BAM files are stored
+                                         in /tmp by default.
+   -d, --output-directory                Output directory";
+
+pub fn filter_full_help() -> &'static str {
+    "lorikeet filter: Remove alignments with insufficient identity.
+
+Only primary, non-supplementary alignments are considered, and output files
+are grouped by reference, but not sorted by position.
+
+Files (both required):
+   -b, --bam-files <PATH> ..             Path to reference-sorted BAM file(s)
+   -o, --output-bam-files <PATH> ..      Path to corresponding output file(s)
+
+Thresholds:
+   --min-read-aligned-length <INT>            Exclude reads with smaller numbers of
+                                              aligned bases [default: 0]
+   --min-read-percent-identity <FLOAT>        Exclude reads by overall percent
+                                              identity e.g. 0.95 for 95%. [default 0.0]
+   --min-read-aligned-percent <FLOAT>         Exclude reads by percent aligned
+                                              bases e.g. 0.95 means 95% of the read's
+                                              bases must be aligned. [default 0.97]
+   --min-read-aligned-length-pair <INT>       Exclude pairs with smaller numbers of
+                                              aligned bases.
+                                              Implies --proper-pairs-only. [default: 0]
+   --min-read-percent-identity-pair <FLOAT>   Exclude pairs by overall percent
+                                              identity e.g. 0.95 for 95%.
+                                              Implies --proper-pairs-only. [default 0.0]
+   --min-read-aligned-percent-pair <FLOAT>    Exclude reads by percent aligned
+                                              bases e.g. 0.95 means 95% of the read's
+                                              bases must be aligned.
+                                              Implies --proper-pairs-only. [default 0.0]
+   --proper-pairs-only                        Require reads to be mapped as proper pairs
+
+Other:
+   -t, --threads <INT>                   Number of threads for output compression
+                                         [default 1]
+   --inverse                             Only keep reads which are unmapped or
+                                         align below thresholds. Note that output
+                                         records may still be marked as mapped
+                                         if they do not meet the thresholds.
+                                         [default false]
+   --verbose                             Print extra debugging information
+   -q, --quiet                           Unless there is an error, do not print
+                                         log messages
+
+Example usage:
+
+  lorikeet filter -b in.bam -o out.bam --min-read-aligned-length 75
+
+Rhys J.P. Newell <r.newell near uq.edu.au>"
+}
+
+pub fn polymorph_full_help() -> &'static str {
+    lazy_static! {
+        static ref POLYMORPH_HELP: String = format!(
+    "lorikeet polymorph: Print variant sites along a contig
+
+{}
+{}
+
+Sharding i.e. multiple reference sets (optional):
+   --sharded                             If -b/--bam-files was used:
+                                           Input BAM files are read-sorted alignments
+                                           of a set of reads mapped to multiple
+                                           reference contig sets. Choose the best
+                                           hit for each read pair.
+
+                                         Otherwise if mapping was carried out:
+                                           Map reads to each reference, choosing the
+                                           best hit for each pair.
+
+Alignment filtering (optional):
+   --min-read-aligned-length <INT>            Exclude reads with smaller numbers of
+                                              aligned bases [default: 0]
+   --min-read-percent-identity <FLOAT>        Exclude reads by overall percent
+                                              identity e.g. 0.95 for 95%. [default 0.0]
+   --min-read-aligned-percent <FLOAT>         Exclude reads by percent aligned
+                                              bases e.g. 0.95 means 95% of the read's
+                                              bases must be aligned. [default 0.97]
+   --min-read-aligned-length-pair <INT>       Exclude pairs with smaller numbers of
+                                              aligned bases.
+                                              Conflicts --proper-pairs-only. [default 0.0]
+   --min-read-percent-identity-pair <FLOAT>   Exclude pairs by overall percent
+                                              identity e.g. 0.95 for 95%.
+                                              Conflicts --proper-pairs-only. [default 0.0]
+   --min-read-aligned-percent-pair <FLOAT>    Exclude reads by percent aligned
+                                              bases e.g. 0.95 means 95% of the read's
+                                              bases must be aligned.
+                                              Conflicts --proper-pairs-only. [default 0.0]
+   --proper-pairs-only                     Allows reads to be mapped as improper pairs
+   --include-supplementary                    Includes read alignments flagged as supplementary
+   --include-secondary                        Includes read alignments flagged as secondary
+
+Other arguments (optional):
+   -m, --method <METHOD>                 Method for calculating coverage.
+                                         One or more (space separated) of:
+                                           trimmed_mean
+                                           mean
+                                           metabat (\"MetaBAT adjusted coverage\")
+                                         A more thorough description of the different
+                                         method is available at
+                                         https://github.com/rhysnewell/lorikeet
+   -q, mapq-threshold <INT>              Mapping quality threshold used to verify
+                                         a variant. [default: 10]
+   -o, --output-prefix <STRING>          Output prefix for files. [default: output]
+   -f, --min-variant-depth <INT>         Minimum depth threshold value a variant must occur at
+                                         for it to be considered. [default: 10]
+   --min-variant-quality <INT>           Minimum QUAL value required for a variant to be included in
+                                         analysis. [default: 10]
+   --variant-caller <NAME>               Short read variant caller to use, either \"native\" for the
+                                         built-in pileup caller or \"freebayes\" for the
+                                         freebayes-parallel pipeline. [default: freebayes]
+   --min-allele-fraction <FLOAT>         Minimum fraction of the reads at a site that must support an
+                                         alternate allele for the native variant caller to call it.
+                                         [default: 0.05]
+   --base-quality-threshold <INT>        The minimum PHRED score for base in a read for it to be
+                                         considered by the native variant caller. [default: 13]
+   --sv-caller <NAME>                    Long read structural variant caller to use, either
+                                         \"native\" for the built-in split and gapped alignment
+                                         caller or \"svim\". [default: native]
+   --min-sv-size <INT>                   Minimum length of a structural variant detected in long
+                                         reads. [default: 40]
+   --min-sv-support <INT>                Minimum number of long reads supporting a structural
+                                         variant. [default: 3]
+   --sv-cluster-distance <INT>           Maximum distance between breakpoints of long reads
+                                         supporting the same structural variant. [default: 500]
+   --output-format FORMAT                Shape of output: 'sparse' for long format,
+                                         'dense' for species-by-site.
+                                         [default: dense]
+   --min-covered-fraction FRACTION       Contigs with less coverage than this
+                                         reported as having zero coverage.
+                                         [default: 0.0]
+   --include-indels                      Flag indicating whether to attempt to calculate INDEL sites
+                                         Not recommended if using nanopore long read data.
+   --coverage-fold                       Percentage value of coverage to look above and below
+                                         when calculating variant locations. e.g. if coverage-fold
+                                         is equal to 0.1, only areas of coverage * (1.0 - 0.1) and
+                                         coverage * (1.0 + 0.1) will be considered.
+                                         [default: 0.5]
+   --contig-end-exclusion                Exclude bases at the ends of reference
+                                         sequences from calculation [default: 75]
+   --trim-min FRACTION                   Remove this smallest fraction of positions
+                                         when calculating trimmed_mean
+                                         [default: 0.05]
+   --trim-max FRACTION                   Maximum fraction for trimmed_mean
+                                         calculations [default: 0.95]
+   -t, --threads                         Number of threads used. [default: 1]
+   --no-zeros                            Omit printing of genomes that have zero
+                                         coverage
+   --discard-unmapped                    Exclude unmapped reads from cached BAM files.
+   -v, --verbose                         Print extra debugging information
+   -q, --quiet                           Unless there is an error, do not print
+                                         log messages
+
+Rhys J. P. Newell <r.newell near uq.edu.au>", ALIGNMENT_OPTIONS, MAPPER_HELP);
+    }
+    &POLYMORPH_HELP
+}
+
+pub fn evolve_full_help() -> &'static str {
+    lazy_static! {
+        static ref EVOLVE_HELP: String = format!(
+    "lorikeet evolve: Calculate dN/dS values in coding regions based on variants found in read mappings
+The allele frequency weighted pN/pS of each gene in each sample is written to <genome>_pnps.tsv
+
+{}
+{}
+
+Sharding i.e. multiple reference sets (optional):
+   --sharded                             If -b/--bam-files was used:
+                                           Input BAM files are read-sorted alignments
+                                           of a set of reads mapped to multiple
+                                           reference contig sets. Choose the best
+                                           hit for each read pair.
+
+                                         Otherwise if mapping was carried out:
+                                           Map reads to each reference, choosing the
+                                           best hit for each pair.
+
+Alignment filtering (optional):
+   --min-read-aligned-length <INT>            Exclude reads with smaller numbers of
+                                              aligned bases [default: 0]
+   --min-read-percent-identity <FLOAT>        Exclude reads by overall percent
+                                              identity e.g. 0.95 for 95%. [default 0.0]
+   --min-read-aligned-percent <FLOAT>         Exclude reads by percent aligned
+                                              bases e.g. 0.95 means 95% of the read's
+                                              bases must be aligned. [default 0.97]
+   --min-read-aligned-length-pair <INT>       Exclude pairs with smaller numbers of
+                                              aligned bases.
+                                              Conflicts --proper-pairs-only. [default 0.0]
+   --min-read-percent-identity-pair <FLOAT>   Exclude pairs by overall percent
+                                              identity e.g. 0.95 for 95%.
+                                              Conflicts --proper-pairs-only. [default 0.0]
+   --min-read-aligned-percent-pair <FLOAT>    Exclude reads by percent aligned
+                                              bases e.g. 0.95 means 95% of the read's
+                                              bases must be aligned.
+                                              Conflicts --proper-pairs-only. [default 0.0]
+   --proper-pairs-only                     Allows reads to be mapped as improper pairs
+   --include-supplementary                    Includes read alignments flagged as supplementary
+   --include-secondary                        Includes read alignments flagged as secondary
+
+Other arguments (optional):
+   -m, --method <METHOD>                 Method for calculating coverage.
+                                         One or more (space separated) of:
+                                           trimmed_mean
+                                           mean
+                                           metabat (\"MetaBAT adjusted coverage\")
+                                         A more thorough description of the different
+                                         method is available at
+                                         https://github.com/rhysnewell/lorikeet
+   --include-indels                      Flag indicating whether to attempt to calculate INDEL sites
+                                         Not recommended if using nanopore long read data.
+   --gff <PATH> ..                       GFF3 or GenBank files annotating the coding sequences of the
+                                         references. Sequence names must match the reference contig
+                                         names. Genes of genomes without annotations are predicted
+                                         with --gene-caller
+   --gene-caller <NAME>                  Gene caller used for genomes without annotations. Either
+                                         \"native\" for the built-in prodigal-style gene caller,
+                                         trained on each genome, or \"prokka\". [default: native]
+   --prokka-params <STRING>              Extra parameters passed to prokka
+   --translation-table <INT>             NCBI translation table used to translate genes, e.g. 4 for
+                                         Mycoplasma or 2 for vertebrate mitochondria. [default: 11]
+   --translation-table-file <PATH>       Tab separated file of genome names, i.e. reference file
+                                         names without extension, and the translation table each
+                                         uses. Overrides --translation-table for those genomes
+   -q, mapq-threshold <INT>              Mapping quality threshold used to verify
+                                         a variant. [default: 10]
+   --base-quality-threshold <INT>        The minimum PHRED score for base in a read for it to be
+                                         considered in the variant calling process.
+   --min-repeat-entropy <FLOAT>          To detect interrupted repeats, build across sequence until it has
+                                         entropy > N bits per bp. Set to 0 to turn off. (default: 1.5)
+   -o, --output-prefix <STRING>          Output prefix for files. [default: output]
+   -f, --min-variant-depth <INT>         Minimum depth threshold value a variant must occur at
+                                         for it to be considered. [default: 10]
+   --min-variant-quality <INT>           Minimum QUAL value required for a variant to be included in
+                                         analysis. [default: 10]
+   --variant-caller <NAME>               Short read variant caller to use, either \"native\" for the
+                                         built-in pileup caller or \"freebayes\" for the
+                                         freebayes-parallel pipeline. [default: freebayes]
+   --min-allele-fraction <FLOAT>         Minimum fraction of the reads at a site that must support an
+                                         alternate allele for the native variant caller to call it.
+                                         [default: 0.05]
+   --sv-caller <NAME>                    Long read structural variant caller to use, either
+                                         \"native\" for the built-in split and gapped alignment
+                                         caller or \"svim\". [default: native]
+   --min-sv-size <INT>                   Minimum length of a structural variant detected in long
+                                         reads. [default: 40]
+   --min-sv-support <INT>                Minimum number of long reads supporting a structural
+                                         variant. [default: 3]
+   --sv-cluster-distance <INT>           Maximum distance between breakpoints of long reads
+                                         supporting the same structural variant. [default: 500]
+   --min-covered-fraction FRACTION       Contigs with less coverage than this
+                                         reported as having zero coverage.
+                                         [default: 0.0]
+   --coverage-fold                       Percentage value of coverage to look above and below
+                                         when calculating variant locations. e.g. if coverage-fold
+                                         is equal to 0.1, only areas of coverage * (1.0 - 0.1) and
+                                         coverage * (1.0 + 0.1) will be considered.
+                                         [default: 0.5]
+   --contig-end-exclusion                Exclude bases at the ends of reference
+                                         sequences from calculation [default: 75]
+   --trim-min FRACTION                   Remove this smallest fraction of positions
+                                         when calculating trimmed_mean
+                                         [default: 0.05]
+   --trim-max FRACTION                   Maximum fraction for trimmed_mean
+                                         calculations [default: 0.95]
+   -t, --threads                         Number of threads used. [default: 1]
+   --no-zeros                            Omit printing of genomes that have zero
+                                         coverage
+   --discard-unmapped                    Exclude unmapped reads from cached BAM files.
+   -v, --verbose                         Print extra debugging information
+   -q, --quiet                           Unless there is an error, do not print
+                                         log messages
+
+Rhys J. P. Newell <r.newell near uq.edu.au>", ALIGNMENT_OPTIONS, MAPPER_HELP);
+    }
+    &EVOLVE_HELP
+}
+
+
+pub fn summarize_full_help() -> &'static str {
+    lazy_static! {
+        static ref SUMMARIZE_HELP: String = format!(
+    "lorikeet summarize: Provides per contig variant statistics for a metagenome
+
+{}
+{}
+
+Alignment filtering (optional):
+   --min-read-aligned-length <INT>            Exclude reads with smaller numbers of
+                                         aligned bases [default: 0]
+   --min-read-percent-identity <FLOAT>        Exclude reads by overall percent
+                                         identity e.g. 0.95 for 95%. [default 0.0]
+   --min-read-aligned-percent <FLOAT>         Exclude reads by percent aligned
+                                         bases e.g. 0.95 means 95% of the read's
+                                         bases must be aligned. [default 0.97]
+   --min-read-aligned-length-pair <INT>       Exclude pairs with smaller numbers of
+                                         aligned bases.
+                                         Conflicts --proper-pairs-only. [default 0.0]
+   --min-read-percent-identity-pair <FLOAT>   Exclude pairs by overall percent
+                                         identity e.g. 0.95 for 95%.
+                                         Conflicts --proper-pairs-only. [default 0.0]
+   --min-read-aligned-percent-pair <FLOAT>    Exclude reads by percent aligned
+                                         bases e.g. 0.95 means 95% of the read's
+                                         bases must be aligned.
+                                         Conflicts --proper-pairs-only. [default 0.0]
+   --proper-pairs-only                Allows reads to be mapped as improper pairs
+   --include-supplementary               Includes read alignments flagged as supplementary
+   --include-secondary                   Includes read alignments flagged as secondary
+
+Other arguments (optional):
+   -m, --method <METHOD>                 Method for calculating coverage.
+                                         One or more (space separated) of:
+                                           trimmed_mean
+                                           mean
+                                           metabat (\"MetaBAT adjusted coverage\")
+                                         A more thorough description of the different
+                                         methods is available at
+                                         https://github.com/rhysnewell/lorikeet
+   -w, --window-size <FLOAT>             Window size in kilobase pairs at which to calculate SNP and
+                                         SV density.
+   --include-longread-svs                Include structural variants detected in long reads in genotyping
+                                         analysis. Can often overestimate number of variants present.
+   --min-sv-size <INT>                   Minimum length of a structural variant detected in long
+                                         reads. [default: 40]
+   --min-sv-support <INT>                Minimum number of long reads supporting a structural
+                                         variant. [default: 3]
+   --sv-cluster-distance <INT>           Maximum distance between breakpoints of long reads
+                                         supporting the same structural variant. [default: 500]
+   -q, mapq-threshold <INT>              Mapping quality threshold used to verify
+                                         a variant. [default: 10]
+   --base-quality-threshold <INT>        The minimum PHRED score for base in a read for it to be
+                                         considered in the variant calling process.
+   --min-repeat-entropy <FLOAT>          To detect interrupted repeats, build across sequence until it has
+                                         entropy > N bits per bp. Set to 0 to turn off. (default: 1.5)
+   -o, --output-prefix <STRING>          Output prefix for files. [default: output]
+   -f, --min-variant-depth <INT>         Minimum depth threshold value a variant must occur at
+                                         for it to be considered. [default: 10]
+   --min-variant-quality <INT>           Minimum QUAL value required for a variant to be included in
+                                         analysis. [default: 10]
+   --variant-caller <NAME>               Short read variant caller to use, either \"native\" for the
+                                         built-in pileup caller or \"freebayes\" for the
+                                         freebayes-parallel pipeline. [default: freebayes]
+   --min-allele-fraction <FLOAT>         Minimum fraction of the reads at a site that must support an
+                                         alternate allele for the native variant caller to call it.
+                                         [default: 0.05]
+   --sv-caller <NAME>                    Long read structural variant caller to use, either
+                                         \"native\" for the built-in split and gapped alignment
+                                         caller or \"svim\". [default: native]
+   --output-format FORMAT                Shape of output: 'sparse' for long format,
+                                         'dense' for species-by-site.
+                                         [default: dense]
+   --min-covered-fraction FRACTION       Contigs with less coverage than this
+                                         reported as having zero coverage.
+                                         [default: 0.0]
+   --include-longread-svs                Include structural variants detected in long reads in genotyping
+                                         analysis. Can often overestimate number of variants present.
+   --variant-effects                     Annotate the effect of each variant on the genes of the
+                                         reference, e.g. missense or frameshift, in a snpEff style
+                                         ANN INFO field of the output VCF
+   --gff <PATH> ..                       GFF3 or GenBank files annotating the coding sequences of the
+                                         references for --variant-effects. Genes of genomes without
+                                         annotations are predicted with --gene-caller
+   --gene-caller <NAME>                  Either \"native\" or \"prokka\". [default: native]
+   --prokka-params <STRING>              Extra parameters passed to prokka
+   --translation-table <INT>             NCBI translation table used by --variant-effects. [default: 11]
+   --translation-table-file <PATH>       Tab separated file of genome names and the translation table
+                                         each uses, overriding --translation-table
+   --contig-end-exclusion                Exclude bases at the ends of reference
+                                         sequences from calculation [default: 75]
+   --trim-min FRACTION                   Remove this smallest fraction of positions
+                                         when calculating trimmed_mean
+                                         [default: 0.05]
+   --trim-max FRACTION                   Maximum fraction for trimmed_mean
+                                         calculations [default: 0.95]
+   -t, --threads                         Number of threads used. [default: 1]
+   --no-zeros                            Omit printing of genomes that have zero
+                                         coverage
+   --discard-unmapped                    Exclude unmapped reads from cached BAM files.
+   -v, --verbose                         Print extra debugging information
+   -q, --quiet                           Unless there is an error, do not print
+                                         log messages
+
+Rhys J. P. Newell <r.newell near uq.edu.au>", ALIGNMENT_OPTIONS, MAPPER_HELP);
+    }
+    &SUMMARIZE_HELP
+}
+
+pub fn genotype_full_help() -> &'static str {
+    lazy_static! {
+        static ref GENOTYPE_HELP: String = format!(
+    "lorikeet genotype: Resolves strain-level genotypes and abundance from metagenomes
+
+{}
+{}
+
+Sharding i.e. multiple reference sets (optional):
+   --sharded                             If -b/--bam-files was used:
+                                           Input BAM files are read-sorted alignments
+                                           of a set of reads mapped to multiple
+                                           reference contig sets. Choose the best
+                                           hit for each read pair.
+
+                                         Otherwise if mapping was carried out:
+                                           Map reads to each reference, choosing the
+                                           best hit for each pair.
+
+Alignment filtering (optional):
+   --min-read-aligned-length <INT>            Exclude reads with smaller numbers of
+                                         aligned bases [default: 0]
+   --min-read-percent-identity <FLOAT>        Exclude reads by overall percent
+                                         identity e.g. 0.95 for 95%. [default 0.0]
+   --min-read-aligned-percent <FLOAT>         Exclude reads by percent aligned
+                                         bases e.g. 0.95 means 95% of the read's
+                                         bases must be aligned. [default 0.97]
+   --min-read-aligned-length-pair <INT>       Exclude pairs with smaller numbers of
+                                         aligned bases.
+                                         Conflicts --proper-pairs-only. [default 0.0]
+   --min-read-percent-identity-pair <FLOAT>   Exclude pairs by overall percent
+                                         identity e.g. 0.95 for 95%.
+                                         Conflicts --proper-pairs-only. [default 0.0]
+   --min-read-aligned-percent-pair <FLOAT>    Exclude reads by percent aligned
+                                         bases e.g. 0.95 means 95% of the read's
+                                         bases must be aligned.
+                                         Conflicts --proper-pairs-only. [default 0.0]
+   --proper-pairs-only                Allows reads to be mapped as improper pairs
+   --include-supplementary               Includes read alignments flagged as supplementary
+   --include-secondary                   Includes read alignments flagged as secondary
+
+
+Other arguments (optional):
+   -m, --method <METHOD>                 Method for calculating coverage.
+                                         One or more (space separated) of:
+                                           trimmed_mean
+                                           mean
+                                           metabat (\"MetaBAT adjusted coverage\")
+                                         A more thorough description of the different
+                                         methods is available at
+                                         https://github.com/rhysnewell/lorikeet
+   -k, --kmer-size <INT>                 K-mer size used to generate k-mer frequency
+                                         table. [default: 4]
+   -q, mapq-threshold <INT>              Mapping quality threshold used to verify
+                                         a variant. [default: 10]
+   --base-quality-threshold <INT>        The minimum PHRED score for base in a read for it to be
+                                         considered in the variant calling process.
+   --min-repeat-entropy <FLOAT>          To detect interrupted repeats, build across sequence until it has
+                                         entropy > N bits per bp. Set to 0 to turn off. (default: 1.5)
+   -o, --output-prefix <STRING>          Output prefix for files. [default: output]
+   -f, --min-variant-depth <INT>         Minimum depth threshold value a variant must occur at
+                                         for it to be considered. [default: 10]
+   --min-variant-quality <INT>           Minimum QUAL value required for a variant to be included in
+                                         analysis. [default: 10]
+   --variant-caller <NAME>               Short read variant caller to use, either \"native\" for the
+                                         built-in pileup caller or \"freebayes\" for the
+                                         freebayes-parallel pipeline. [default: freebayes]
+   --min-allele-fraction <FLOAT>         Minimum fraction of the reads at a site that must support an
+                                         alternate allele for the native variant caller to call it.
+                                         [default: 0.05]
+   --sv-caller <NAME>                    Long read structural variant caller to use, either
+                                         \"native\" for the built-in split and gapped alignment
+                                         caller or \"svim\". [default: native]
+   --e-min                               Minimum epsilon value used in fuzzyDBSCAN algorithm.
+                                         The minimum distance between two points required for clustering.
+   --e-max                               Maximum epsilon value used in fuzzyDBSCAN algorithm.
+                                         The maximum distance between two points for border clustering.
+   --pts-min                             Minimum points as percentage in fuzzyDBSCAN algorithm.
+                                         The fraction of points needed to be within e-max
+                                         to begin core clustering.
+   --pts-max                             Maximum points as percentage in fuzzyDBSCAN algorithm.
+                                         The fraction of points needed to be within e-max
+                                         to begin border clustering.
+   -n, --minimum-seed-size <INT>         Minimum seed size for initiating fuzzy DBSCAN with. Corresponds
+                                         to the number of variants within the seed. [default: 20]
+   -s, --maximum-seed-similarity <FLOAT> The maximum Jaccard's similarity value allowed between two
+                                         seeds before they get concatenated into one seed. Prevents
+                                         overclustering. [default: 0.97]
+   --minimum-reads-in-link <INT>         Minimum amount of reads required to be shared between two
+                                         variants before they are counted as 'linked'. [default: 5]
+   --matrix <PATH> ..                    Variant matrices saved by previous genotype runs. The samples
+                                         of this run are appended to the samples already in the matrix
+                                         and all samples are clustered together.
+   --write-checkpoint                    Save the variant matrix of each genome to
+                                         <genome>_matrix.bin, so samples can be added to it with
+                                         --matrix or it can be reclustered with lorikeet cluster.
+   --distance <NAME>                     Distance between variants used for clustering. One of \"rho\",
+                                         \"phi\", \"phi-dist\", \"concordance\" or \"aitchison\" on
+                                         CLR transformed depths, or \"jaccard-reads\" on supporting
+                                         reads. With a single sample the depth based distances
+                                         are all aitchison. [default: phi-dist]
+   --read-distance-weight <FLOAT>        Weight between 0 and 1 given to the Jaccard distance of
+                                         supporting reads when blending it with the depth based
+                                         distance. [default: 0.0]
+   --export-matrices                     Write the variant depth matrix, CLR transformed depths and
+                                         geometric means as TSV files, and the distances within
+                                         e-max between variants as a Matrix Market file
+   --clustering-method <NAME>            Method used to cluster variants into strains. One of
+                                         \"fuzzy-dbscan\", \"hdbscan\" for HDBSCAN style density
+                                         clustering using pts-min as the minimum cluster size, or
+                                         \"hierarchical\" for average linkage clustering cut at
+                                         e-max. HDBSCAN and hierarchical clustering calculate the
+                                         distance between every pair of variants, so a warning is
+                                         given above 20000 variants in a genome where fuzzy-dbscan
+                                         should be used instead. [default: fuzzy-dbscan]
+   --cluster-refinement                  Refine clusters by read linkage. Clusters with conflicting
+                                         alleles at a site are split, clusters sharing most of their
+                                         reads are merged and unclustered variants join the cluster
+                                         they share the most reads with
+   --cluster-merge-similarity <FLOAT>    Minimum Jaccard's similarity of reads, scaled by the smaller
+                                         cluster, for two clusters to be merged by
+                                         --cluster-refinement. [default: 0.8]
+   --max-strains <INT>                   Largest number of strains considered when estimating the strain
+                                         count of each genome from its allele frequency spectrum, at
+                                         most 10. The error rate of each sample is the slope of variant
+                                         depth on read depth across called variant sites, so it includes
+                                         real variant signal and is an upper bound on the sequencing
+                                         error rate. The estimates are written to
+                                         <genome>_strain_count.tsv [default: 4]
+   --constrain-strain-count              Keep only as many of the largest clusters as the estimated
+                                         number of strains
+   --strain-inference <NAME>             How strains are inferred from the variants. Either
+                                         \"clustering\" to cluster variants with --clustering-method,
+                                         \"deconvolution\" to factorise the allele counts of all
+                                         samples into strain haplotypes and per-sample proportions,
+                                         which are written to <genome>_strain_proportions.tsv, or
+                                         \"phasing\" to use each haplotype of each block phased with
+                                         long reads as a strain, or \"read-graph\" to follow haplotype paths through
+                                         variants linked by reads and mates. Deconvolution works best
+                                         with many samples, e.g. time series, while read-graph suits
+                                         single sample projects. [default: clustering]
+   --deconvolution-strains <INT>         Number of strains used by deconvolution, at most 10. Every
+                                         strain carries one allele of each site. Defaults to the
+                                         estimated strain count
+   --deconvolution-restarts <INT>        Number of random starts for deconvolution. [default: 5]
+   --phase-long-reads                    Phase variants with the reads of --longread-bam-files by
+                                         minimising the number of read alleles that disagree with
+                                         their haplotype (MEC) within each block of read connected
+                                         sites, solving for --ploidy haplotypes. Blocks are written to
+                                         <genome>_phased_blocks.tsv and used as clustering seeds
+   --min-phased-block-sites <INT>        Minimum number of variant sites in a phased block. [default: 2]
+   --ploidy <INT>                        Number of haplotypes solved for in each phased block. [default: 2]
+   --strain-dnds                         Calculate the dN/dS of each strain's genes against the reference
+                                         and the pN/pS of each gene in each sample, written to
+                                         <genome>_strain_dnds.tsv and <genome>_pnps.tsv
+   --variant-effects                     Annotate the effect of each variant on the genes of the
+                                         reference, e.g. missense or frameshift, in a snpEff style
+                                         ANN INFO field of the output VCF
+   --gff <PATH> ..                       GFF3 or GenBank files annotating the coding sequences of the
+                                         references for --strain-dnds and --variant-effects. Genes of
+                                         genomes without annotations are predicted with --gene-caller
+   --gene-caller <NAME>                  Either \"native\" or \"prokka\". [default: native]
+   --translation-table <INT>             NCBI translation table used by --strain-dnds and
+                                         --variant-effects. [default: 11]
+   --translation-table-file <PATH>       Tab separated file of genome names and the translation table
+                                         each uses, overriding --translation-table
+   --auto-parameters                     Choose e-min, e-max, pts-min, pts-max and phi automatically by
+                                         scanning a grid of half, equal and double the given e-min,
+                                         e-max and pts-min and scoring each clustering on up to 2000
+                                         variants. All candidates are written to
+                                         <genome>_parameter_search.tsv
+   --auto-parameters-criterion <NAME>    Score used by --auto-parameters, either \"silhouette\" for the
+                                         mean silhouette width weighted by the fraction of clustered
+                                         variants, or \"read-consistency\" for the fraction of read
+                                         linked variant pairs placed in the same cluster, corrected
+                                         for the fraction expected by chance from the cluster sizes.
+                                         [default: silhouette]
+   --mask-mode <MODE>                    How strain sequences mark low confidence sites whose allele
+                                         could not be resolved by read linkage. One of \"iupac\" for
+                                         ambiguity codes, \"lowercase\" or \"none\". [default: none]
+   --assign-reads                        Assign each read to the strain it most likely originated from
+                                         and write the posteriors to <genome>_read_assignments.tsv
+   --strain-bams                         Also split each BAM file into one BAM file per strain.
+                                         Informative reads that could not be assigned are written
+                                         to <genome>_<sample>_unassigned.bam. Requires --assign-reads.
+   --min-read-posterior <FLOAT>          Minimum posterior probability needed to assign a read to a
+                                         strain. [default: 0.95]
+   --include-longread-svs                Include structural variants detected in long reads in genotyping
+                                         analysis. Can often overestimate number of variants present.
+   --min-sv-size <INT>                   Minimum length of a structural variant detected in long
+                                         reads. [default: 40]
+   --min-sv-support <INT>                Minimum number of long reads supporting a structural
+                                         variant. [default: 3]
+   --sv-cluster-distance <INT>           Maximum distance between breakpoints of long reads
+                                         supporting the same structural variant. [default: 500]
+   --min-covered-fraction FRACTION       Contigs with less coverage than this
+                                         reported as having zero coverage.
+                                         [default: 0.0]
+   --contig-end-exclusion                Exclude bases at the ends of reference
+                                         sequences from calculation [default: 75]
+   --trim-min FRACTION                   Remove this smallest fraction of positions
+                                         when calculating trimmed_mean
+                                         [default: 0.05]
+   --trim-max FRACTION                   Maximum fraction for trimmed_mean
+                                         calculations [default: 0.95]
+   --plot                                Produce SNP density plots
+   -w, --window-size <FLOAT>             Window size in kilobase pairs at which to calculate SNP and
+                                         SV density.
+   -t, --threads                         Number of threads used. [default: 1]
+   --no-zeros                            Omit printing of genomes that have zero
+                                         coverage
+
+   --discard-unmapped                    Exclude unmapped reads from cached BAM files.
+   -v, --verbose                         Print extra debugging information
+   -q, --quiet                           Unless there is an error, do not print
+                                         log messages
+
+Rhys J. P. Newell <r.newell near uq.edu.au>", ALIGNMENT_OPTIONS, MAPPER_HELP);
+    }
+    &GENOTYPE_HELP
+}
+
+pub fn cluster_full_help() -> &'static str {
+    "lorikeet cluster: Rerun strain clustering from a saved variant matrix
+
+Genotype runs given --write-checkpoint save their variant matrix to
+<output-directory>/<genome>_matrix.bin after variant calling and read assignment. This subcommand reloads that matrix and
+reruns only clustering, genotyping and VCF output, so the clustering parameters can
+be tuned without recalling variants.
+
+Input (both required):
+   --matrix <PATH>                       Variant matrix written by lorikeet genotype --write-checkpoint
+   -r, --reference <PATH>                The reference genome the matrix was built from
+
+Clustering parameters (optional):
+   --e-min                               Minimum epsilon value used in fuzzyDBSCAN algorithm.
+                                         The minimum distance between two points required for clustering.
+                                         [default: 0.1]
+   --e-max                               Maximum epsilon value used in fuzzyDBSCAN algorithm.
+                                         The maximum distance between two points for border clustering.
+                                         [default: 0.5]
+   --pts-min                             Minimum points as percentage in fuzzyDBSCAN algorithm.
+                                         [default: 0.05]
+   --pts-max                             Maximum points as percentage in fuzzyDBSCAN algorithm.
+                                         [default: 0.1]
+   --phi                                 Phi value used in fuzzyDBSCAN algorithm. [default: 0.0]
+   -n, --minimum-seed-size <INT>         Minimum seed size for initiating fuzzy DBSCAN with. Corresponds
+                                         to the number of variants within the seed. [default: 20]
+   -s, --maximum-seed-similarity <FLOAT> The maximum Jaccard's similarity value allowed between two
+                                         seeds before they get concatenated into one seed. [default: 0.97]
+   --minimum-reads-in-link <INT>         Minimum amount of reads required to be shared between two
+                                         variants before they are counted as 'linked'. [default: 5]
+   --distance <NAME>                     Distance between variants used for clustering. One of \"rho\",
+                                         \"phi\", \"phi-dist\", \"concordance\" or \"aitchison\" on
+                                         CLR transformed depths, or \"jaccard-reads\" on supporting
+                                         reads. With a single sample the depth based distances
+                                         are all aitchison. [default: phi-dist]
+   --read-distance-weight <FLOAT>        Weight between 0 and 1 given to the Jaccard distance of
+                                         supporting reads when blending it with the depth based
+                                         distance. [default: 0.0]
+   --export-matrices                     Write the variant depth matrix, CLR transformed depths and
+                                         geometric means as TSV files, and the distances within
+                                         e-max between variants as a Matrix Market file
+   --clustering-method <NAME>            Method used to cluster variants into strains. One of
+                                         \"fuzzy-dbscan\", \"hdbscan\" for HDBSCAN style density
+                                         clustering using pts-min as the minimum cluster size, or
+                                         \"hierarchical\" for average linkage clustering cut at
+                                         e-max. HDBSCAN and hierarchical clustering calculate the
+                                         distance between every pair of variants, so a warning is
+                                         given above 20000 variants in a genome where fuzzy-dbscan
+                                         should be used instead. [default: fuzzy-dbscan]
+   --cluster-refinement                  Refine clusters by read linkage. Clusters with conflicting
+                                         alleles at a site are split, clusters sharing most of their
+                                         reads are merged and unclustered variants join the cluster
+                                         they share the most reads with
+   --cluster-merge-similarity <FLOAT>    Minimum Jaccard's similarity of reads, scaled by the smaller
+                                         cluster, for two clusters to be merged by
+                                         --cluster-refinement. [default: 0.8]
+   --max-strains <INT>                   Largest number of strains considered when estimating the strain
+                                         count of each genome from its allele frequency spectrum, at
+                                         most 10. The error rate of each sample is the slope of variant
+                                         depth on read depth across called variant sites, so it includes
+                                         real variant signal and is an upper bound on the sequencing
+                                         error rate. The estimates are written to
+                                         <genome>_strain_count.tsv [default: 4]
+   --constrain-strain-count              Keep only as many of the largest clusters as the estimated
+                                         number of strains
+   --strain-inference <NAME>             How strains are inferred from the variants. Either
+                                         \"clustering\" to cluster variants with --clustering-method,
+                                         \"deconvolution\" to factorise the allele counts of all
+                                         samples into strain haplotypes and per-sample proportions,
+                                         which are written to <genome>_strain_proportions.tsv, or
+                                         \"read-graph\" to follow haplotype paths through variants
+                                         linked by reads and mates. Deconvolution works best with
+                                         many samples, e.g. time series, while read-graph suits
+                                         single sample projects. [default: clustering]
+   --deconvolution-strains <INT>         Number of strains used by deconvolution, at most 10. Every
+                                         strain carries one allele of each site. Defaults to the
+                                         estimated strain count
+   --deconvolution-restarts <INT>        Number of random starts for deconvolution. [default: 5]
+   --auto-parameters                     Choose e-min, e-max, pts-min, pts-max and phi automatically by
+                                         scanning a grid of half, equal and double the given e-min,
+                                         e-max and pts-min and scoring each clustering on up to 2000
+                                         variants. All candidates are written to
+                                         <genome>_parameter_search.tsv
+   --auto-parameters-criterion <NAME>    Score used by --auto-parameters, either \"silhouette\" for the
+                                         mean silhouette width weighted by the fraction of clustered
+                                         variants, or \"read-consistency\" for the fraction of read
+                                         linked variant pairs placed in the same cluster, corrected
+                                         for the fraction expected by chance from the cluster sizes.
+                                         [default: silhouette]
+   --mask-mode <MODE>                    How strain sequences mark low confidence sites whose allele
+                                         could not be resolved by read linkage. One of \"iupac\" for
+                                         ambiguity codes, \"lowercase\" or \"none\". [default: none]
+
+Other arguments (optional):
+   -o, --output-directory <PATH>         Output directory. [default: ./]
+   --plot                                Produce SNP density plots
+   -w, --window-size <FLOAT>             Window size in kilobase pairs at which to calculate SNP and
+                                         SV density. [default: 1]
+   -t, --threads                         Number of threads used. [default: 1]
+   -v, --verbose                         Print extra debugging information
+   -q, --quiet                           Unless there is an error, do not print
+                                         log messages
+
+Example usage:
+
+  lorikeet cluster --matrix lorikeet_out/assembly_matrix.bin -r assembly.fna --e-min 0.05
+
+Rhys J.P. Newell <r.newell near uq.edu.au>"
+}
+
+pub fn build_cli() -> App<'static, 'static> {
+    // specify _2 lazily because need to define it at runtime.
+    lazy_static! {
+        static ref POLYMORPH_HELP: String = format!(
+            "
+                            {}
+              {}
+
+{}
+
+  lorikeet polymorph --coupled read1.fastq.gz read2.fastq.gz --reference assembly.fna --threads 10
+
+{}
+
+  lorikeet polymorph --bam-files my.bam --reference assembly.fna
+    --bam-file-cache-directory saved_bam_files --threads 10
+
+See lorikeet polymorph --full-help for further options and further detail.
+",
+            ansi_term::Colour::Green.paint(
+                "lorikeet polymorph"),
+            ansi_term::Colour::Green.paint(
+                "Generate variant positions along contigs with a sample"),
+            ansi_term::Colour::Purple.paint(
+                "Example: Calculate variant positions from reads and assembly:"),
+            ansi_term::Colour::Purple.paint(
+                "Example: Calculate variant positions using MetaBAT adjusted coverage from a sorted BAM file, saving
+the unfiltered BAM files in the saved_bam_files folder:")
+        ).to_string();
+
+        static ref EVOLVE_HELP: String = format!(
+            "
+                            {}
+              {}
+
+{}
+
+  lorikeet evolve --coupled read1.fastq.gz read2.fastq.gz --reference assembly.fna --threads 10
+
+{}
+
+  lorikeet evolve --bam-files my.bam --longread-bam-files my-longread.bam --genome-fasta-directory genomes/ -x fna
+    --bam-file-cache-directory saved_bam_files --output-directory lorikeet_out/ --threads 10
+
+See lorikeet evolve --full-help for further options and further detail.
+",
+            ansi_term::Colour::Green.paint(
+                "lorikeet evolve"),
+            ansi_term::Colour::Green.paint(
+                "Calculate dN/dS values of genes based on read mappings"),
+            ansi_term::Colour::Purple.paint(
+                "Example: Calculate gene dN/dS values from reads and assembly:"),
+            ansi_term::Colour::Purple.paint(
+                "Example: Calculate gene dN/dS values from reads against many genomes in a directory and cache bams and save output to directory:")
+        ).to_string();
+
+
+        static ref SUMMARIZE_HELP: String = format!(
+            "
+                            {}
+              {}
+
+{}
+
+  lorikeet summarize --coupled read1.fastq.gz read2.fastq.gz --reference assembly.fna --threads 10 --window-size 1
+
+{}
+
+  lorikeet summarize --bam-files my.bam --longread-bam-files my-longread.bam --genome-fasta-directory genomes/ -x fna
+    --bam-file-cache-directory saved_bam_files --output-directory lorikeet_out/ --threads 10
+
+See lorikeet summarize --full-help for further options and further detail.
+",
+            ansi_term::Colour::Green.paint(
+                "lorikeet summarize"),
+            ansi_term::Colour::Green.paint(
+                "Summarizes contigs stats across given window size"),
+            ansi_term::Colour::Purple.paint(
+                "Example: Map paired reads to a reference and generate contig stats across samples using a 1kb window size"),
+            ansi_term::Colour::Purple.paint(
+                "Example: Summarizes genomic variation across contigs in genomes in directory using long and short reads:"),
+        ).to_string();
+
+        static ref GENOTYPE_HELP: String = format!(
+            "
+                            {}
+              {}
+
+{}
+
+  lorikeet genotype --coupled read1.fastq.gz read2.fastq.gz --reference assembly.fna --threads 10
+
+{}
+
+  lorikeet genotype --bam-files my.bam --longread-bam-files my-longread.bam --genome-fasta-directory genomes/ -x fna
+    --bam-file-cache-directory saved_bam_files --output-directory lorikeet_out/ --threads 10 --plot
+
+See lorikeet genotype --full-help for further options and further detail.
+",
+            ansi_term::Colour::Green.paint(
+                "lorikeet genotype"),
+            ansi_term::Colour::Green.paint(
+                "*EXPERIMENTAL* Report strain-level genotypes and abundances based on variant read mappings"),
+            ansi_term::Colour::Purple.paint(
+                "Example: Map paired reads to a reference and generate genotypes"),
+            ansi_term::Colour::Purple.paint(
+                "Example: Generate strain-level genotypes from read mappings compared to reference from a sorted BAM file and plots the results:"),
+        ).to_string();
+
+        static ref FILTER_HELP: String = format!(
+            "
+                            {}
+                     {}
+
+{}
+
+  lorikeet filter --bam-files input.bam --output-bam filtered.bam
+    --min-read-aligned-length 50
+
+{}
+
+  lorikeet filter -b input.bam -o inverse_filtered.bam --inverse
+    --min-read-percent-identity 0.95 --threads 16
+
+See lorikeet filter --full-help for further options and further detail.
+",
+            ansi_term::Colour::Green.paint(
+                "lorikeet filter"),
+            ansi_term::Colour::Green.paint(
+                "Filter BAM file alignments"),
+            ansi_term::Colour::Purple.paint(
+                "Example: Filter a BAM file by removing alignments shorter than 50bp:"),
+            ansi_term::Colour::Purple.paint(
+                "Example: Filter inverse: Keep alignments that have <95% alignment identity\n\
+                 and those which do map at all. Note that the output BAM file will likely\n\
+                 records that are still mapped, but align with < 95% identity. Use 16\n\
+                 threads for output compression:"),
+        ).to_string();
+    }
+
+    return App::new("lorikeet")
+        .version(crate_version!())
+        .author("Rhys J.P. Newell <r.newell near uq.edu.au>")
+        .about("Variant analysis of metagenomic datasets")
+        .args_from_usage("-v, --verbose       'Print extra debug logging information'
+             -q, --quiet         'Unless there is an error, do not print logging information'")
+        .help("
+Strain genotyping analysis for metagenomics
+
+Usage: lorikeet <subcommand> ...
+
+Main subcommands:
+\tgenotype \tReport strain-level genotypes and abundances from metagenomes (*experimental*)
+\tsummarize\tSummarizes contig stats from one or multiple samples
+\tevolve   \tCalculate dN/dS values for genes from read mappings
+
+Less used utility subcommands:
+\tkmer     \tCalculate kmer frequencies within contigs
+\tfilter   \tRemove (or only keep) alignments with insufficient identity
+
+Other options:
+\t-V, --version\tPrint version information
+
+Rhys J. P. Newell <r.newell near uq.edu.au>
+")
+        .global_setting(AppSettings::ArgRequiredElseHelp)
+        .subcommand(
+            SubCommand::with_name("polymorph")
+                .about("Perform variant calling analysis")
+                .help(POLYMORPH_HELP.as_str())
+                .arg(Arg::with_name("full-help")
+                    .long("full-help"))
+                .arg(Arg::with_name("bam-files")
+                    .short("b")
+                    .long("bam-files")
+                    .multiple(true)
+                    .takes_value(true)
+                    .required_unless_one(
+                        &["read1","read2","coupled","interleaved","single","full-help"]))
+                .arg(Arg::with_name("sharded")
+                    .long("sharded")
+                    .required(false))
+                .arg(Arg::with_name("read1")
+                    .short("-1")
+                    .multiple(true)
+                    .takes_value(true)
+                    .requires("read2")
+                    .required_unless_one(
+                        &["bam-files","coupled","interleaved","single","full-help"])
+                    .conflicts_with("bam-files"))
+                .arg(Arg::with_name("read2")
+                    .short("-2")
+                    .multiple(true)
+                    .takes_value(true)
+                    .requires("read1")
+                    .required_unless_one(
+                        &["bam-files","coupled","interleaved","single","full-help"])
+                    .conflicts_with("bam-files"))
+                .arg(Arg::with_name("coupled")
+                    .short("-c")
+                    .long("coupled")
+                    .multiple(true)
+                    .takes_value(true)
+                    .required_unless_one(
+                        &["bam-files","read1","interleaved","single","full-help"])
+                    .conflicts_with("bam-files"))
+                .arg(Arg::with_name("interleaved")
+                    .long("interleaved")
+                    .multiple(true)
+                    .takes_value(true)
+                    .required_unless_one(
+                        &["bam-files","read1","coupled","single","full-help"])
+                    .conflicts_with("bam-files"))
+                .arg(Arg::with_name("single")
+                    .long("single")
+                    .multiple(true)
+                    .takes_value(true)
+                    .required_unless_one(
+                        &["bam-files","read1","coupled","interleaved","full-help"])
+                    .conflicts_with("bam-files"))
+                .arg(Arg::with_name("longreads")
+                    .long("longreads")
+                    .multiple(true)
+                    .takes_value(true)
+                    .required(false)
+                    .conflicts_with_all(&["longread-bam-files"]))
+                .arg(Arg::with_name("longread-bam-files")
+                    .short("l")
+                    .multiple(true)
+                    .takes_value(true)
+                    .required(false)
+                    .conflicts_with_all(&["longreads"]))
+                .arg(Arg::with_name("reference")
+                    .short("r")
+                    .long("reference")
+                    .alias("genome-fasta-files")
+                    .takes_value(true)
+                    .multiple(true)
+                    .required_unless_one(&["genome-fasta-directory", "full-help"]))
+                .arg(Arg::with_name("genome-fasta-directory")
+                    .long("genome-fasta-directory")
+                    .short("d")
+                    .takes_value(true)
+                    .required_unless_one(&["reference", "genome-fasta-files", "full-help"]))
+                .arg(Arg::with_name("genome-fasta-extension")
+                    .long("genome-fasta-extension")
+                    .short("x")
+                    .takes_value(true)
+                    .default_value("fna"))
+                .arg(Arg::with_name("bam-file-cache-directory")
+                    .long("bam-file-cache-directory")
+                    .takes_value(true))
+                .arg(Arg::with_name("output-directory")
+                    .long("output-directory")
+                    .short("o")
+                    .default_value("./"))
+                .arg(Arg::with_name("threads")
+                    .short("-t")
+                    .long("threads")
+                    .default_value("1")
+                    .takes_value(true))
+                .arg(
+                    Arg::with_name("mapper")
+                        .short("p")
+                        .long("mapper")
+                        .possible_values(MAPPING_SOFTWARE_LIST)
+                        .default_value(DEFAULT_MAPPING_SOFTWARE),
+                )
+                .arg(
+                    Arg::with_name("longread-mapper")
+                        .long("longread-mapper")
+                        .possible_values(LONGREAD_MAPPING_SOFTWARE_LIST)
+                        .default_value(DEFAULT_LONGREAD_MAPPING_SOFTWARE),
+                )
+                .arg(
+                    Arg::with_name("minimap2-params")
+                        .long("minimap2-params")
+                        .long("minimap2-parameters")
+                        .takes_value(true)
+                        .allow_hyphen_values(true),
+                )
+                .arg(
+                    Arg::with_name("minimap2-reference-is-index")
+                        .long("minimap2-reference-is-index")
+                        .requires("reference"),
+                )
+                .arg(
+                    Arg::with_name("bwa-params")
+                        .long("bwa-params")
+                        .long("bwa-parameters")
+                        .takes_value(true)
+                        .allow_hyphen_values(true)
+                        .requires("reference"),
+                )
+                .arg(Arg::with_name("discard-unmapped")
+                    .long("discard-unmapped")
+                    .requires("bam-file-cache-directory"))
+
+                .arg(Arg::with_name("min-read-aligned-length")
+                    .long("min-read-aligned-length")
+                    .takes_value(true))
+                .arg(Arg::with_name("min-read-percent-identity")
+                    .long("min-read-percent-identity")
+                    .takes_value(true))
+                .arg(Arg::with_name("min-read-aligned-percent")
+                    .long("min-read-aligned-percent")
+                    .takes_value(true)
+                    .default_value("0.0"))
+                .arg(Arg::with_name("min-read-aligned-length-pair")
+                    .long("min-read-aligned-length-pair")
+                    .takes_value(true)
+                    .conflicts_with("proper-pairs-only"))
+                .arg(Arg::with_name("min-read-percent-identity-pair")
+                    .long("min-read-percent-identity-pair")
+                    .takes_value(true)
+                    .conflicts_with("proper-pairs-only"))
+                .arg(Arg::with_name("min-read-aligned-percent-pair")
+                    .long("min-read-aligned-percent-pair")
+                    .takes_value(true)
+                    .conflicts_with("proper-pairs-only"))
+                .arg(Arg::with_name("method")
+                    .short("m")
+                    .long("method")
+                    .takes_value(true)
+                    .multiple(false)
+                    .possible_values(&[
+                        "trimmed_mean",
+                        "mean",
+                        "metabat"])
+                    .default_value("trimmed_mean"))
+                .arg(Arg::with_name("epsilon")
+                    .long("epsilon")
+                    .short("e")
+                    .default_value("0.05"))
+                .arg(Arg::with_name("min-cluster-size")
+                    .long("min-cluster-size")
+                    .short("s")
+                    .default_value("10"))
+                .arg(Arg::with_name("min-covered-fraction")
+                    .long("min-covered-fraction")
+                    .default_value("0.0"))
+                .arg(Arg::with_name("coverage-fold")
+                    .long("coverage-fold")
+                    .default_value("0.5"))
+                .arg(Arg::with_name("min-variant-depth")
+                    .long("min-variant-depth")
+                    .short("f")
+                    .default_value("10"))
+                .arg(Arg::with_name("min-variant-quality")
+                    .long("min-variant-quality")
+                    .default_value("10"))
+                .arg(Arg::with_name("variant-caller")
+                    .long("variant-caller")
+                    .possible_values(&["native", "freebayes"])
+                    .default_value("freebayes"))
+                .arg(Arg::with_name("min-allele-fraction")
+                    .long("min-allele-fraction")
+                    .default_value("0.05"))
+                .arg(Arg::with_name("base-quality-threshold")
+                    .long("base-quality-threshold")
+                    .default_value("13"))
+                .arg(Arg::with_name("sv-caller")
+                    .long("sv-caller")
+                    .possible_values(&["native", "svim"])
+                    .default_value("native"))
+                .arg(Arg::with_name("min-sv-size")
+                    .long("min-sv-size")
+                    .default_value("40"))
+                .arg(Arg::with_name("min-sv-support")
+                    .long("min-sv-support")
+                    .default_value("3"))
+                .arg(Arg::with_name("sv-cluster-distance")
+                    .long("sv-cluster-distance")
+                    .default_value("500"))
+                .arg(Arg::with_name("mapq-threshold")
+                    .long("mapq-threshold")
+                    .short("q")
+                    .default_value("0"))
+                .arg(Arg::with_name("contig-end-exclusion")
+                    .long("contig-end-exclusion")
+                    .default_value("75"))
+                .arg(Arg::with_name("trim-min")
+                    .long("trim-min")
+                    .default_value("0.05"))
+                .arg(Arg::with_name("trim-max")
+                    .long("trim-max")
+                    .default_value("0.95"))
+                .arg(Arg::with_name("no-zeros")
+                    .long("no-zeros"))
+                .arg(Arg::with_name("proper-pairs-only")
+                    .long("proper-pairs-only"))
+                .arg(Arg::with_name("nanopore")
+                    .long("nanopore"))
+                .arg(Arg::with_name("include-secondary")
+                    .long("include-secondary"))
+                .arg(Arg::with_name("include-supplementary")
+                    .long("include-supplementary"))
+                .arg(Arg::with_name("include-indels")
+                    .long("include-indels"))
+                .arg(Arg::with_name("verbose")
+                    .short("v")
+                    .long("verbose"))
+                .arg(Arg::with_name("quiet")
+                    .long("quiet")))
+        .subcommand(
+            SubCommand::with_name("evolve")
+                .about("Pinpoint sites of synonymous and non-synonymous mutations")
+                .help(EVOLVE_HELP.as_str())
+                .arg(Arg::with_name("full-help")
+                    .long("full-help"))
+
+                .arg(Arg::with_name("bam-files")
+                    .short("b")
+                    .long("bam-files")
+                    .multiple(true)
+                    .takes_value(true)
+                    .required_unless_one(
+                        &["read1","read2","coupled","interleaved","single","full-help"]))
+                .arg(Arg::with_name("gff")
+                    .long("gff")
+                    .multiple(true)
+                    .takes_value(true))
+                .arg(Arg::with_name("gene-caller")
+                    .long("gene-caller")
+                    .possible_values(&["native", "prokka"])
+                    .default_value("native"))
+                .arg(Arg::with_name("prokka-params")
+                    .long("prokka-params")
+                    .takes_value(true))
+                .arg(Arg::with_name("translation-table")
+                    .long("translation-table")
+                    .default_value("11"))
+                .arg(Arg::with_name("translation-table-file")
+                    .long("translation-table-file")
+                    .takes_value(true))
+                .arg(Arg::with_name("sharded")
+                    .long("sharded")
+                    .required(false))
+                .arg(Arg::with_name("read1")
+                    .short("-1")
+                    .multiple(true)
+                    .takes_value(true)
+                    .requires("read2")
+                    .required_unless_one(
+                        &["bam-files","coupled","interleaved","single","full-help"])
+                    .conflicts_with("bam-files"))
+                .arg(Arg::with_name("read2")
+                    .short("-2")
+                    .multiple(true)
+                    .takes_value(true)
+                    .requires("read1")
+                    .required_unless_one(
+                        &["bam-files","coupled","interleaved","single","full-help"])
+                    .conflicts_with("bam-files"))
+                .arg(Arg::with_name("coupled")
+                    .short("-c")
+                    .long("coupled")
+                    .multiple(true)
+                    .takes_value(true)
+                    .required_unless_one(
+                        &["bam-files","read1","interleaved","single","full-help"])
+                    .conflicts_with("bam-files"))
+                .arg(Arg::with_name("interleaved")
+                    .long("interleaved")
+                    .multiple(true)
+                    .takes_value(true)
+                    .required_unless_one(
+                        &["bam-files","read1","coupled","single","full-help"])
+                    .conflicts_with("bam-files"))
+                .arg(Arg::with_name("single")
+                    .long("single")
+                    .multiple(true)
+                    .takes_value(true)
+                    .required_unless_one(
+                        &["bam-files","read1","coupled","interleaved","full-help"])
+                    .conflicts_with("bam-files"))
+                .arg(Arg::with_name("reference")
+                    .short("r")
+                    .long("reference")
+                    .alias("genome-fasta-files")
+                    .takes_value(true)
+                    .multiple(true)
+                    .required_unless_one(&["genome-fasta-directory", "full-help"]))
+                .arg(Arg::with_name("genome-fasta-directory")
+                    .long("genome-fasta-directory")
+                    .short("d")
+                    .takes_value(true)
+                    .required_unless_one(&["reference", "genome-fasta-files", "full-help"]))
+                .arg(Arg::with_name("genome-fasta-extension")
+                    .long("genome-fasta-extension")
+                    .short("x")
+                    .takes_value(true)
+                    .default_value("fna"))
+                .arg(Arg::with_name("bam-file-cache-directory")
+                    .long("bam-file-cache-directory")
+                    .takes_value(true))
+                .arg(Arg::with_name("output-directory")
+                    .long("output-directory")
+                    .short("o")
+                    .default_value("./"))
+                .arg(Arg::with_name("longreads")
+                    .long("longreads")
+                    .multiple(true)
+                    .takes_value(true)
+                    .required(false)
+                    .conflicts_with_all(&["longread-bam-files"]))
+                .arg(Arg::with_name("longread-bam-files")
+                    .short("l")
+                    .multiple(true)
+                    .takes_value(true)
+                    .required(false)
+                    .conflicts_with_all(&["longreads"]))
+                .arg(Arg::with_name("threads")
+                    .short("t")
+                    .long("threads")
+                    .default_value("1")
+                    .takes_value(true))
+                .arg(
+                    Arg::with_name("mapper")
+                        .short("p")
+                        .long("mapper")
+                        .possible_values(MAPPING_SOFTWARE_LIST)
+                        .default_value(DEFAULT_MAPPING_SOFTWARE),
+                )
+                .arg(
+                    Arg::with_name("longread-mapper")
+                        .long("longread-mapper")
+                        .possible_values(LONGREAD_MAPPING_SOFTWARE_LIST)
+                        .default_value(DEFAULT_LONGREAD_MAPPING_SOFTWARE),
+                )
+                .arg(
+                    Arg::with_name("minimap2-params")
+                        .long("minimap2-params")
+                        .long("minimap2-parameters")
+                        .takes_value(true)
+                        .allow_hyphen_values(true),
+                )
+                .arg(
+                    Arg::with_name("minimap2-reference-is-index")
+                        .long("minimap2-reference-is-index")
+                        .requires("reference"),
+                )
+                .arg(
+                    Arg::with_name("bwa-params")
+                        .long("bwa-params")
+                        .long("bwa-parameters")
+                        .takes_value(true)
+                        .allow_hyphen_values(true)
+                        .requires("reference"),
+                )
+                .arg(Arg::with_name("discard-unmapped")
+                    .long("discard-unmapped")
+                    .requires("bam-file-cache-directory"))
+
+                .arg(Arg::with_name("min-read-aligned-length")
+                    .long("min-read-aligned-length")
+                    .takes_value(true))
+                .arg(Arg::with_name("min-read-percent-identity")
+                    .long("min-read-percent-identity")
+                    .takes_value(true))
+                .arg(Arg::with_name("min-read-aligned-percent")
+                    .long("min-read-aligned-percent")
+                    .default_value("0.0")
+                    .takes_value(true))
+                .arg(Arg::with_name("min-read-aligned-length-pair")
+                    .long("min-read-aligned-length-pair")
+                    .takes_value(true)
+                    .conflicts_with("proper-pairs-only"))
+                .arg(Arg::with_name("min-read-percent-identity-pair")
+                    .long("min-read-percent-identity-pair")
+                    .takes_value(true)
+                    .conflicts_with("proper-pairs-only"))
+                .arg(Arg::with_name("min-read-aligned-percent-pair")
+                    .long("min-read-aligned-percent-pair")
+                    .takes_value(true)
+                    .conflicts_with("proper-pairs-only"))
+                .arg(Arg::with_name("method")
+                    .short("m")
+                    .long("method")
+                    .takes_value(true)
+                    .possible_values(&[
+                        "trimmed_mean",
+                        "mean",
+                        "metabat"])
+                    .default_value("trimmed_mean"))
+                .arg(Arg::with_name("min-covered-fraction")
+                    .long("min-covered-fraction")
+                    .default_value("0.0"))
+                .arg(Arg::with_name("coverage-fold")
+                    .long("coverage-fold")
+                    .default_value("0.5"))
+                .arg(Arg::with_name("min-variant-depth")
+                    .long("min-variant-depth")
+                    .short("f")
+                    .default_value("10"))
+                .arg(Arg::with_name("min-variant-quality")
+                    .long("min-variant-quality")
+                    .default_value("10"))
+                .arg(Arg::with_name("variant-caller")
+                    .long("variant-caller")
+                    .possible_values(&["native", "freebayes"])
+                    .default_value("freebayes"))
+                .arg(Arg::with_name("min-allele-fraction")
+                    .long("min-allele-fraction")
+                    .default_value("0.05"))
+                .arg(Arg::with_name("sv-caller")
+                    .long("sv-caller")
+                    .possible_values(&["native", "svim"])
+                    .default_value("native"))
+                .arg(Arg::with_name("mapq-threshold")
+                    .long("mapq-threshold")
+                    .default_value("0"))
+                .arg(Arg::with_name("base-quality-threshold")
+                    .long("base-quality-threshold")
+                    .short("q")
+                    .default_value("13"))
+                .arg(Arg::with_name("min-repeat-entropy")
+                    .long("min-repeat-entropy")
+                    .default_value("1.5"))
+                .arg(Arg::with_name("kmer-size")
+                    .long("kmer-size")
+                    .short("k")
+                    .default_value("4"))
+                .arg(Arg::with_name("contig-end-exclusion")
+                    .long("contig-end-exclusion")
+                    .default_value("75"))
+                .arg(Arg::with_name("trim-min")
+                    .long("trim-min")
+                    .default_value("0.05"))
+                .arg(Arg::with_name("trim-max")
+                    .long("trim-max")
+                    .default_value("0.95"))
+                .arg(Arg::with_name("no-zeros")
+                    .long("no-zeros"))
+                .arg(Arg::with_name("proper-pairs-only")
+                    .long("proper-pairs-only"))
+                .arg(Arg::with_name("window-size")
+                    .long("window-size")
+                    .short("w")
+                    .default_value("1"))
+                .arg(Arg::with_name("plot")
+                    .long("plot"))
+                .arg(Arg::with_name("nanopore")
+                    .long("nanopore"))
+                .arg(Arg::with_name("include-longread-svs")
+                    .long("include-longread-svs"))
+                .arg(Arg::with_name("min-sv-size")
+                    .long("min-sv-size")
+                    .default_value("40"))
+                .arg(Arg::with_name("min-sv-support")
+                    .long("min-sv-support")
+                    .default_value("3"))
+                .arg(Arg::with_name("sv-cluster-distance")
+                    .long("sv-cluster-distance")
+                    .default_value("500"))
+                .arg(Arg::with_name("include-secondary")
+                    .long("include-secondary"))
+                .arg(Arg::with_name("include-soft-clipping")
+                    .long("include-soft-clipping"))
+                .arg(Arg::with_name("include-supplementary")
+                    .long("include-supplementary"))
+                .arg(Arg::with_name("include-indels")
+                    .long("include-indels"))
+                .arg(Arg::with_name("ploidy")
+                    .long("ploidy")
+                    .default_value("2")
+                    .required(false))
+                .arg(Arg::with_name("verbose")
+                    .short("v")
+                    .long("verbose"))
+                .arg(Arg::with_name("quiet")
+                    .long("quiet")))
+        .subcommand(
+            SubCommand::with_name("summarize")
+                .about("Perform variant calling analysis and then binning")
+                .help(SUMMARIZE_HELP.as_str())
+                .arg(Arg::with_name("full-help")
+                    .long("full-help"))
+
+                .arg(Arg::with_name("bam-files")
+                    .short("b")
+                    .long("bam-files")
+                    .multiple(true)
+                    .takes_value(true)
+                    .required_unless_one(
+                        &["read1","read2","coupled","interleaved","single","full-help"]))
+                .arg(Arg::with_name("sharded")
+                    .long("sharded")
+                    .required(false))
+                .arg(Arg::with_name("read1")
+                    .short("-1")
+                    .multiple(true)
+                    .takes_value(true)
+                    .requires("read2")
+                    .required_unless_one(
+                        &["bam-files","coupled","interleaved","single","full-help"])
+                    .conflicts_with("bam-files"))
+                .arg(Arg::with_name("read2")
+                    .short("-2")
+                    .multiple(true)
+                    .takes_value(true)
+                    .requires("read1")
+                    .required_unless_one(
+                        &["bam-files","coupled","interleaved","single","full-help"])
+                    .conflicts_with("bam-files"))
+                .arg(Arg::with_name("coupled")
+                    .short("-c")
+                    .long("coupled")
+                    .multiple(true)
+                    .takes_value(true)
+                    .required_unless_one(
+                        &["bam-files","read1","interleaved","single","full-help"])
+                    .conflicts_with("bam-files"))
+                .arg(Arg::with_name("interleaved")
+                    .long("interleaved")
+                    .multiple(true)
+                    .takes_value(true)
+                    .required_unless_one(
+                        &["bam-files","read1","coupled","single","full-help"])
+                    .conflicts_with("bam-files"))
+                .arg(Arg::with_name("single")
+                    .long("single")
+                    .multiple(true)
+                    .takes_value(true)
+                    .required_unless_one(
+                        &["bam-files","read1","coupled","interleaved","full-help"])
+                    .conflicts_with("bam-files"))
+                .arg(Arg::with_name("longreads")
+                    .long("longreads")
+                    .multiple(true)
+                    .takes_value(true)
+                    .required(false)
+                    .conflicts_with_all(&["longread-bam-files"]))
+                .arg(Arg::with_name("longread-bam-files")
+                    .short("l")
+                    .multiple(true)
+                    .takes_value(true)
+                    .required(false)
+                    .conflicts_with_all(&["longreads"]))
+                .arg(Arg::with_name("reference")
+                    .short("r")
+                    .long("reference")
+                    .alias("genome-fasta-files")
+                    .takes_value(true)
+                    .multiple(true)
+                    .required_unless_one(&["genome-fasta-directory", "full-help"]))
+                .arg(Arg::with_name("genome-fasta-directory")
+                    .long("genome-fasta-directory")
+                    .short("d")
+                    .takes_value(true)
+                    .required_unless_one(&["reference", "genome-fasta-files", "full-help"]))
+                .arg(Arg::with_name("genome-fasta-extension")
+                    .long("genome-fasta-extension")
+                    .short("x")
+                    .takes_value(true)
+                    .default_value("fna"))
+                .arg(Arg::with_name("bam-file-cache-directory")
+                    .long("bam-file-cache-directory")
+                    .takes_value(true))
+                .arg(Arg::with_name("output-directory")
+                    .long("output-directory")
+                    .short("o")
+                    .default_value("./"))
+                .arg(Arg::with_name("threads")
+                    .short("-t")
+                    .long("threads")
+                    .default_value("1")
+                    .takes_value(true))
+                .arg(
+                    Arg::with_name("mapper")
+                        .short("p")
+                        .long("mapper")
+                        .possible_values(MAPPING_SOFTWARE_LIST)
+                        .default_value(DEFAULT_MAPPING_SOFTWARE),
+                )
+                .arg(
+                    Arg::with_name("longread-mapper")
+                        .long("longread-mapper")
+                        .possible_values(LONGREAD_MAPPING_SOFTWARE_LIST)
+                        .default_value(DEFAULT_LONGREAD_MAPPING_SOFTWARE),
+                )
+                .arg(
+                    Arg::with_name("minimap2-params")
+                        .long("minimap2-params")
+                        .long("minimap2-parameters")
+                        .takes_value(true)
+                        .allow_hyphen_values(true),
+                )
+                .arg(
+                    Arg::with_name("minimap2-reference-is-index")
+                        .long("minimap2-reference-is-index")
+                        .requires("reference"),
+                )
+                .arg(
+                    Arg::with_name("bwa-params")
+                        .long("bwa-params")
+                        .long("bwa-parameters")
+                        .takes_value(true)
+                        .allow_hyphen_values(true)
+                        .requires("reference"),
+                )
+                .arg(Arg::with_name("discard-unmapped")
+                    .long("discard-unmapped")
+                    .requires("bam-file-cache-directory"))
+
+                .arg(Arg::with_name("min-read-aligned-length")
+                    .long("min-read-aligned-length")
+                    .takes_value(true))
+                .arg(Arg::with_name("min-read-percent-identity")
+                    .long("min-read-percent-identity")
+                    .takes_value(true))
+                .arg(Arg::with_name("min-read-aligned-percent")
+                    .long("min-read-aligned-percent")
+                    .default_value("0.0")
+                    .takes_value(true))
+                .arg(Arg::with_name("min-read-aligned-length-pair")
+                    .long("min-read-aligned-length-pair")
+                    .takes_value(true)
+                    .conflicts_with("proper-pairs-only"))
+                .arg(Arg::with_name("min-read-percent-identity-pair")
+                    .long("min-read-percent-identity-pair")
+                    .takes_value(true)
+                    .conflicts_with("proper-pairs-only"))
+                .arg(Arg::with_name("min-read-aligned-percent-pair")
+                    .long("min-read-aligned-percent-pair")
+                    .takes_value(true)
+                    .conflicts_with("proper-pairs-only"))
+                .arg(Arg::with_name("method")
+                    .short("m")
+                    .long("method")
+                    .takes_value(true)
+                    .possible_values(&[
+                        "trimmed_mean",
+                        "mean",
+                        "metabat"])
+                    .default_value("trimmed_mean"))
+                .arg(Arg::with_name("window-size")
+                    .long("window-size")
+                    .short("w")
+                    .default_value("1"))
+                .arg(Arg::with_name("epsilon")
+                    .long("epsilon")
+                    .short("e")
+                    .default_value("0.05"))
+                .arg(Arg::with_name("min-cluster-size")
+                    .long("min-cluster-size")
+                    .short("s")
+                    .default_value("10"))
+                .arg(Arg::with_name("min-covered-fraction")
+                    .long("min-covered-fraction")
+                    .default_value("0.0"))
+                .arg(Arg::with_name("coverage-fold")
+                    .long("coverage-fold")
+                    .default_value("0.5"))
+                .arg(Arg::with_name("min-variant-depth")
+                    .long("min-variant-depth")
+                    .short("f")
+                    .default_value("10"))
+                .arg(Arg::with_name("min-variant-quality")
+                    .long("min-variant-quality")
+                    .default_value("10"))
+                .arg(Arg::with_name("variant-caller")
+                    .long("variant-caller")
+                    .possible_values(&["native", "freebayes"])
+                    .default_value("freebayes"))
+                .arg(Arg::with_name("min-allele-fraction")
+                    .long("min-allele-fraction")
+                    .default_value("0.05"))
+                .arg(Arg::with_name("sv-caller")
+                    .long("sv-caller")
+                    .possible_values(&["native", "svim"])
+                    .default_value("native"))
+                .arg(Arg::with_name("strain-ani")
+                    .long("strain-ani")
+                    .short("a"))
+                .arg(Arg::with_name("mapq-threshold")
+                    .long("mapq-threshold")
+                    .default_value("10"))
+                .arg(Arg::with_name("base-quality-threshold")
+                    .long("base-quality-threshold")
+                    .short("q")
+                    .default_value("13"))
+                .arg(Arg::with_name("min-repeat-entropy")
+                    .long("min-repeat-entropy")
+                    .default_value("1.5"))
+                .arg(Arg::with_name("kmer-size")
+                    .long("kmer-size")
+                    .short("k")
+                    .default_value("4"))
+                .arg(Arg::with_name("contig-end-exclusion")
+                    .long("contig-end-exclusion")
+                    .default_value("75"))
+                .arg(Arg::with_name("trim-min")
+                    .long("trim-min")
+                    .default_value("0.05"))
+                .arg(Arg::with_name("trim-max")
+                    .long("trim-max")
+                    .default_value("0.95"))
+                .arg(Arg::with_name("no-zeros")
+                    .long("no-zeros"))
+                .arg(Arg::with_name("proper-pairs-only")
+                    .long("proper-pairs-only"))
+                .arg(Arg::with_name("nanopore")
+                    .long("nanopore"))
+                .arg(Arg::with_name("include-secondary")
+                    .long("include-secondary"))
+                .arg(Arg::with_name("include-supplementary")
+                    .long("include-supplementary"))
+                .arg(Arg::with_name("include-longread-svs")
+                    .long("include-longread-svs"))
+                .arg(Arg::with_name("min-sv-size")
+                    .long("min-sv-size")
+                    .default_value("40"))
+                .arg(Arg::with_name("min-sv-support")
+                    .long("min-sv-support")
+                    .default_value("3"))
+                .arg(Arg::with_name("sv-cluster-distance")
+                    .long("sv-cluster-distance")
+                    .default_value("500"))
+                .arg(Arg::with_name("variant-effects")
+                    .long("variant-effects"))
+                .arg(Arg::with_name("gff")
+                    .long("gff")
+                    .multiple(true)
+                    .takes_value(true))
+                .arg(Arg::with_name("gene-caller")
+                    .long("gene-caller")
+                    .possible_values(&["native", "prokka"])
+                    .default_value("native"))
+                .arg(Arg::with_name("prokka-params")
+                    .long("prokka-params")
+                    .takes_value(true))
+                .arg(Arg::with_name("translation-table")
+                    .long("translation-table")
+                    .default_value("11"))
+                .arg(Arg::with_name("translation-table-file")
+                    .long("translation-table-file")
+                    .takes_value(true))
+                .arg(Arg::with_name("verbose")
+                    .short("v")
+                    .long("verbose"))
+                .arg(Arg::with_name("quiet")
+                    .long("quiet")))
+        .subcommand(
+            SubCommand::with_name("genotype")
+                .about("Perform variant calling analysis and then binning")
+                .help(GENOTYPE_HELP.as_str())
+                .arg(Arg::with_name("full-help")
+                    .long("full-help"))
+
+                .arg(Arg::with_name("bam-files")
+                    .short("b")
+                    .long("bam-files")
+                    .multiple(true)
+                    .takes_value(true)
+                    .required_unless_one(
+                        &["read1","read2","coupled","interleaved","single","full-help"]))
+                .arg(Arg::with_name("sharded")
+                    .long("sharded")
+                    .required(false))
+                .arg(Arg::with_name("read1")
+                    .short("-1")
+                    .multiple(true)
+                    .takes_value(true)
+                    .requires("read2")
+                    .required_unless_one(
+                        &["bam-files","coupled","interleaved","single","full-help"])
+                    .conflicts_with("bam-files"))
+                .arg(Arg::with_name("read2")
+                    .short("-2")
+                    .multiple(true)
+                    .takes_value(true)
+                    .requires("read1")
+                    .required_unless_one(
+                        &["bam-files","coupled","interleaved","single","full-help"])
+                    .conflicts_with("bam-files"))
+                .arg(Arg::with_name("coupled")
+                    .short("-c")
+                    .long("coupled")
+                    .multiple(true)
+                    .takes_value(true)
+                    .required_unless_one(
+                        &["bam-files","read1","interleaved","single","full-help"])
+                    .conflicts_with("bam-files"))
+                .arg(Arg::with_name("interleaved")
+                    .long("interleaved")
+                    .multiple(true)
+                    .takes_value(true)
+                    .required_unless_one(
+                        &["bam-files","read1","coupled","single","full-help"])
+                    .conflicts_with("bam-files"))
+                .arg(Arg::with_name("single")
+                    .long("single")
+                    .multiple(true)
+                    .takes_value(true)
+                    .required_unless_one(
+                        &["bam-files","read1","coupled","interleaved","full-help"])
+                    .conflicts_with("bam-files"))
+                .arg(Arg::with_name("longreads")
+                    .long("longreads")
+                    .multiple(true)
+                    .takes_value(true)
+                    .required(false)
+                    .conflicts_with_all(&["longread-bam-files"]))
+                .arg(Arg::with_name("longread-bam-files")
+                    .short("l")
+                    .long("longread-bam-files")
+                    .multiple(true)
+                    .takes_value(true)
+                    .required(false)
+                    .conflicts_with_all(&["longreads"]))
+                .arg(Arg::with_name("reference")
+                    .short("r")
+                    .long("reference")
+                    .alias("genome-fasta-files")
+                    .takes_value(true)
+                    .multiple(true)
+                    .required_unless_one(&["genome-fasta-directory", "full-help"]))
+                .arg(Arg::with_name("genome-fasta-directory")
+                    .long("genome-fasta-directory")
+                    .short("d")
+                    .takes_value(true)
+                    .required_unless_one(&["reference", "genome-fasta-files", "full-help"]))
+                .arg(Arg::with_name("genome-fasta-extension")
+                    .long("genome-fasta-extension")
+                    .short("x")
+                    .takes_value(true)
+                    .default_value("fna"))
+                .arg(Arg::with_name("bam-file-cache-directory")
+                    .long("bam-file-cache-directory")
+                    .takes_value(true))
+                .arg(Arg::with_name("output-directory")
+                    .long("output-directory")
+                    .short("o")
+                    .default_value("./"))
+                .arg(Arg::with_name("vcfs")
+                    .long("vcfs")
+                    .multiple(true)
+                    .required(false))
+                .arg(Arg::with_name("threads")
+                    .short("-t")
+                    .long("threads")
+                    .default_value("1")
+                    .takes_value(true))
+                .arg(
+                    Arg::with_name("mapper")
+                        .short("p")
+                        .long("mapper")
+                        .possible_values(MAPPING_SOFTWARE_LIST)
+                        .default_value(DEFAULT_MAPPING_SOFTWARE),
+                )
+                .arg(
+                    Arg::with_name("longread-mapper")
+                        .long("longread-mapper")
+                        .possible_values(LONGREAD_MAPPING_SOFTWARE_LIST)
+                        .default_value(DEFAULT_LONGREAD_MAPPING_SOFTWARE),
+                )
+                .arg(
+                    Arg::with_name("minimap2-params")
+                        .long("minimap2-params")
+                        .long("minimap2-parameters")
+                        .takes_value(true)
+                        .allow_hyphen_values(true),
+                )
+                .arg(
+                    Arg::with_name("minimap2-reference-is-index")
+                        .long("minimap2-reference-is-index")
+                        .requires("reference"),
+                )
+                .arg(
+                    Arg::with_name("bwa-params")
+                        .long("bwa-params")
+                        .long("bwa-parameters")
+                        .takes_value(true)
+                        .allow_hyphen_values(true)
+                        .requires("reference"),
+                )
+                .arg(Arg::with_name("discard-unmapped")
+                    .long("discard-unmapped")
+                    .requires("bam-file-cache-directory"))
+
+                .arg(Arg::with_name("min-read-aligned-length")
+                    .long("min-read-aligned-length")
+                    .takes_value(true))
+                .arg(Arg::with_name("min-read-percent-identity")
+                    .long("min-read-percent-identity")
+                    .takes_value(true))
+                .arg(Arg::with_name("min-read-aligned-percent")
+                    .long("min-read-aligned-percent")
+                    .default_value("0.0")
+                    .takes_value(true))
+                .arg(Arg::with_name("min-read-aligned-length-pair")
+                    .long("min-read-aligned-length-pair")
+                    .takes_value(true)
+                    .conflicts_with("proper-pairs-only"))
+                .arg(Arg::with_name("min-read-percent-identity-pair")
+                    .long("min-read-percent-identity-pair")
+                    .takes_value(true)
+                    .conflicts_with("proper-pairs-only"))
+                .arg(Arg::with_name("min-read-aligned-percent-pair")
+                    .long("min-read-aligned-percent-pair")
+                    .takes_value(true)
+                    .conflicts_with("proper-pairs-only"))
+                .arg(Arg::with_name("method")
+                    .short("m")
+                    .long("method")
+                    .takes_value(true)
+                    .possible_values(&[
+                        "trimmed_mean",
+                        "mean",
+                        "metabat"])
+                    .default_value("trimmed_mean"))
+                .arg(Arg::with_name("e-min")
+                    .long("e-min")
+                    .default_value("0.1"))
+                .arg(Arg::with_name("e-max")
+                    .long("e-max")
+                    .default_value("0.5"))
+                .arg(Arg::with_name("pts-min")
+                    .long("pts-min")
+                    .default_value("0.05"))
+                .arg(Arg::with_name("pts-max")
+                    .long("pts-max")
+                    .default_value("0.1"))
+                .arg(Arg::with_name("phi")
+                    .long("phi")
+                    .default_value("0.0"))
+                .arg(Arg::with_name("min-covered-fraction")
+                    .long("min-covered-fraction")
+                    .default_value("0.0"))
+                .arg(Arg::with_name("coverage-fold")
+                    .long("coverage-fold")
+                    .default_value("1.0"))
+                .arg(Arg::with_name("min-variant-depth")
+                    .long("min-variant-depth")
+                    .short("f")
+                    .default_value("10"))
+                .arg(Arg::with_name("min-variant-quality")
+                    .long("min-variant-quality")
+                    .default_value("10"))
+                .arg(Arg::with_name("variant-caller")
+                    .long("variant-caller")
+                    .possible_values(&["native", "freebayes"])
+                    .default_value("freebayes"))
+                .arg(Arg::with_name("min-allele-fraction")
+                    .long("min-allele-fraction")
+                    .default_value("0.05"))
+                .arg(Arg::with_name("sv-caller")
+                    .long("sv-caller")
+                    .possible_values(&["native", "svim"])
+                    .default_value("native"))
+                .arg(Arg::with_name("strain-ani")
+                    .long("strain-ani")
+                    .short("a")
+                    .takes_value(true))
+                .arg(Arg::with_name("mapq-threshold")
+                    .long("mapq-threshold")
+                    .default_value("10"))
+                .arg(Arg::with_name("minimum-seed-size")
+                    .long("minimum-seed-size")
+                    .short("n")
+                    .default_value("20"))
+                .arg(Arg::with_name("minimum-reads-in-link")
+                    .long("minimum-reads-in-link")
+                    .default_value("5"))
+                .arg(Arg::with_name("maximum-seed-similarity")
+                    .long("maximum-seed-similarity")
+                    .short("s")
+                    .default_value("0.97"))
+                .arg(Arg::with_name("base-quality-threshold")
+                    .long("base-quality-threshold")
+                    .short("q")
+                    .default_value("13"))
+                .arg(Arg::with_name("min-repeat-entropy")
+                    .long("min-repeat-entropy")
+                    .default_value("1.5"))
+                .arg(Arg::with_name("kmer-size")
+                    .long("kmer-size")
+                    .short("k")
+                    .default_value("4"))
+                .arg(Arg::with_name("contig-end-exclusion")
+                    .long("contig-end-exclusion")
+                    .default_value("75"))
+                .arg(Arg::with_name("trim-min")
+                    .long("trim-min")
+                    .default_value("0.05"))
+                .arg(Arg::with_name("trim-max")
+                    .long("trim-max")
+                    .default_value("0.95"))
+                .arg(Arg::with_name("no-zeros")
+                    .long("no-zeros"))
+                .arg(Arg::with_name("proper-pairs-only")
+                    .long("proper-pairs-only"))
+                .arg(Arg::with_name("window-size")
+                    .long("window-size")
+                    .short("w")
+                    .default_value("1"))
+                .arg(Arg::with_name("plot")
+                    .long("plot"))
+                .arg(Arg::with_name("nanopore")
+                    .long("nanopore"))
+                .arg(Arg::with_name("include-longread-svs")
+                    .long("include-longread-svs"))
+                .arg(Arg::with_name("min-sv-size")
+                    .long("min-sv-size")
+                    .default_value("40"))
+                .arg(Arg::with_name("min-sv-support")
+                    .long("min-sv-support")
+                    .default_value("3"))
+                .arg(Arg::with_name("sv-cluster-distance")
+                    .long("sv-cluster-distance")
+                    .default_value("500"))
+                .arg(Arg::with_name("include-secondary")
+                    .long("include-secondary"))
+                .arg(Arg::with_name("include-soft-clipping")
+                    .long("include-soft-clipping"))
+                .arg(Arg::with_name("include-supplementary")
+                    .long("include-supplementary"))
+                .arg(Arg::with_name("include-indels")
+                    .long("include-indels"))
+                .arg(Arg::with_name("distance")
+                    .long("distance")
+                    .possible_values(&["rho", "phi", "phi-dist", "concordance", "aitchison", "jaccard-reads"])
+                    .default_value("phi-dist"))
+                .arg(Arg::with_name("read-distance-weight")
+                    .long("read-distance-weight")
+                    .default_value("0.0"))
+                .arg(Arg::with_name("export-matrices")
+                    .long("export-matrices"))
+                .arg(Arg::with_name("clustering-method")
+                    .long("clustering-method")
+                    .possible_values(&["fuzzy-dbscan", "hdbscan", "hierarchical"])
+                    .default_value("fuzzy-dbscan"))
+                .arg(Arg::with_name("cluster-refinement")
+                    .long("cluster-refinement"))
+                .arg(Arg::with_name("cluster-merge-similarity")
+                    .long("cluster-merge-similarity")
+                    .default_value("0.8"))
+                .arg(Arg::with_name("max-strains")
+                    .long("max-strains")
+                    .possible_values(STRAIN_COUNT_LIST)
+                    .default_value("4"))
+                .arg(Arg::with_name("constrain-strain-count")
+                    .long("constrain-strain-count"))
+                .arg(Arg::with_name("strain-inference")
+                    .long("strain-inference")
+                    .possible_values(&["clustering", "deconvolution", "phasing", "read-graph"])
+                    .default_value("clustering"))
+                .arg(Arg::with_name("deconvolution-strains")
+                    .long("deconvolution-strains")
+                    .possible_values(STRAIN_COUNT_LIST)
+                    .takes_value(true))
+                .arg(Arg::with_name("deconvolution-restarts")
+                    .long("deconvolution-restarts")
+                    .default_value("5"))
+                .arg(Arg::with_name("phase-long-reads")
+                    .long("phase-long-reads"))
+                .arg(Arg::with_name("min-phased-block-sites")
+                    .long("min-phased-block-sites")
+                    .default_value("2"))
+                .arg(Arg::with_name("strain-dnds")
+                    .long("strain-dnds"))
+                .arg(Arg::with_name("variant-effects")
+                    .long("variant-effects"))
+                .arg(Arg::with_name("gff")
+                    .long("gff")
+                    .multiple(true)
+                    .takes_value(true))
+                .arg(Arg::with_name("gene-caller")
+                    .long("gene-caller")
+                    .possible_values(&["native", "prokka"])
+                    .default_value("native"))
+                .arg(Arg::with_name("prokka-params")
+                    .long("prokka-params")
+                    .takes_value(true))
+                .arg(Arg::with_name("translation-table")
+                    .long("translation-table")
+                    .default_value("11"))
+                .arg(Arg::with_name("translation-table-file")
+                    .long("translation-table-file")
+                    .takes_value(true))
+                .arg(Arg::with_name("auto-parameters")
+                    .long("auto-parameters"))
+                .arg(Arg::with_name("auto-parameters-criterion")
+                    .long("auto-parameters-criterion")
+                    .possible_values(&["silhouette", "read-consistency"])
+                    .default_value("silhouette"))
+                .arg(Arg::with_name("mask-mode")
+                    .long("mask-mode")
+                    .possible_values(&["iupac", "lowercase", "none"])
+                    .default_value("none"))
+                .arg(Arg::with_name("assign-reads")
+                    .long("assign-reads"))
+                .arg(Arg::with_name("strain-bams")
+                    .long("strain-bams")
+                    .requires("assign-reads"))
+                .arg(Arg::with_name("min-read-posterior")
+                    .long("min-read-posterior")
+                    .default_value("0.95"))
+                .arg(Arg::with_name("matrix")
+                    .long("matrix")
+                    .multiple(true)
+                    .takes_value(true))
+                .arg(Arg::with_name("write-checkpoint")
+                    .long("write-checkpoint"))
+                .arg(Arg::with_name("ploidy")
+                    .long("ploidy")
+                    .default_value("2")
+                    .required(false))
+                .arg(Arg::with_name("verbose")
+                    .short("v")
+                    .long("verbose"))
+                .arg(Arg::with_name("quiet")
+                    .long("quiet")))
+        .subcommand(
+            SubCommand::with_name("cluster")
+                .about("Rerun strain clustering from a saved variant matrix")
+                .arg(Arg::with_name("full-help")
+                    .long("full-help"))
+                .arg(Arg::with_name("matrix")
+                    .long("matrix")
+                    .takes_value(true)
+                    .required_unless("full-help"))
+                .arg(Arg::with_name("reference")
+                    .short("-r")
+                    .long("reference")
+                    .takes_value(true)
+                    .required_unless("full-help"))
+                .arg(Arg::with_name("output-directory")
+                    .long("output-directory")
+                    .short("o")
+                    .default_value("./"))
+                .arg(Arg::with_name("threads")
+                    .long("threads")
+                    .short("t")
+                    .default_value("1"))
+                .arg(Arg::with_name("e-min")
+                    .long("e-min")
+                    .default_value("0.1"))
+                .arg(Arg::with_name("e-max")
+                    .long("e-max")
+                    .default_value("0.5"))
+                .arg(Arg::with_name("pts-min")
+                    .long("pts-min")
+                    .default_value("0.05"))
+                .arg(Arg::with_name("pts-max")
+                    .long("pts-max")
+                    .default_value("0.1"))
+                .arg(Arg::with_name("phi")
+                    .long("phi")
+                    .default_value("0.0"))
+                .arg(Arg::with_name("minimum-seed-size")
+                    .long("minimum-seed-size")
+                    .short("n")
+                    .default_value("20"))
+                .arg(Arg::with_name("minimum-reads-in-link")
+                    .long("minimum-reads-in-link")
+                    .default_value("5"))
+                .arg(Arg::with_name("maximum-seed-similarity")
+                    .long("maximum-seed-similarity")
+                    .short("s")
+                    .default_value("0.97"))
+                .arg(Arg::with_name("distance")
+                    .long("distance")
+                    .possible_values(&["rho", "phi", "phi-dist", "concordance", "aitchison", "jaccard-reads"])
+                    .default_value("phi-dist"))
+                .arg(Arg::with_name("read-distance-weight")
+                    .long("read-distance-weight")
+                    .default_value("0.0"))
+                .arg(Arg::with_name("export-matrices")
+                    .long("export-matrices"))
+                .arg(Arg::with_name("clustering-method")
+                    .long("clustering-method")
+                    .possible_values(&["fuzzy-dbscan", "hdbscan", "hierarchical"])
+                    .default_value("fuzzy-dbscan"))
+                .arg(Arg::with_name("cluster-refinement")
+                    .long("cluster-refinement"))
+                .arg(Arg::with_name("cluster-merge-similarity")
+                    .long("cluster-merge-similarity")
+                    .default_value("0.8"))
+                .arg(Arg::with_name("max-strains")
+                    .long("max-strains")
+                    .possible_values(STRAIN_COUNT_LIST)
+                    .default_value("4"))
+                .arg(Arg::with_name("constrain-strain-count")
+                    .long("constrain-strain-count"))
+                .arg(Arg::with_name("strain-inference")
+                    .long("strain-inference")
+                    .possible_values(&["clustering", "deconvolution", "read-graph"])
+                    .default_value("clustering"))
+                .arg(Arg::with_name("deconvolution-strains")
+                    .long("deconvolution-strains")
+                    .possible_values(STRAIN_COUNT_LIST)
+                    .takes_value(true))
+                .arg(Arg::with_name("deconvolution-restarts")
+                    .long("deconvolution-restarts")
+                    .default_value("5"))
+                .arg(Arg::with_name("auto-parameters")
+                    .long("auto-parameters"))
+                .arg(Arg::with_name("auto-parameters-criterion")
+                    .long("auto-parameters-criterion")
+                    .possible_values(&["silhouette", "read-consistency"])
+                    .default_value("silhouette"))
+                .arg(Arg::with_name("mask-mode")
+                    .long("mask-mode")
+                    .possible_values(&["iupac", "lowercase", "none"])
+                    .default_value("none"))
+                .arg(Arg::with_name("window-size")
+                    .long("window-size")
+                    .short("w")
+                    .default_value("1"))
+                .arg(Arg::with_name("plot")
+                    .long("plot"))
+                .arg(Arg::with_name("verbose")
+                    .short("v")
+                    .long("verbose"))
+                .arg(Arg::with_name("quiet")
+                    .short("q")
+                    .long("quiet")))
+        .subcommand(
+            SubCommand::with_name("kmer")
+                .about("Generate kmer count matrix for contigs")
+//                .help(CONTIG_HELP.as_str())
+                .arg(Arg::with_name("full-help")
+                    .long("full-help"))
+                .arg(Arg::with_name("reference")
+                    .short("-r")
+                    .long("reference")
+                    .takes_value(true)
+                    .required(true))
+                .arg(Arg::with_name("verbose")
+                    .short("v")
+                    .long("verbose")))
+        .subcommand(
+            SubCommand::with_name("filter")
+                .about("Remove alignments with insufficient identity")
+                .help(FILTER_HELP.as_str())
+
+                .arg(Arg::with_name("full-help")
+                    .long("full-help"))
+
+                .arg(Arg::with_name("bam-files")
+                    .short("b")
+                    .long("bam-files")
+                    .multiple(true)
+                    .takes_value(true)
+                    .required_unless_one(&["full-help"]))
+                .arg(Arg::with_name("output-bam-files")
+                    .short("o")
+                    .long("output-bam-files")
+                    .multiple(true)
+                    .takes_value(true)
+                    .required_unless_one(&["full-help"]))
+                .arg(Arg::with_name("inverse")
+                    .long("inverse"))
+
+                .arg(Arg::with_name("min-read-aligned-length")
+                    .long("min-read-aligned-length")
+                    .takes_value(true))
+                .arg(Arg::with_name("min-read-percent-identity")
+                    .long("min-read-percent-identity")
+                    .takes_value(true))
+                .arg(Arg::with_name("min-read-aligned-percent")
+                    .long("min-read-aligned-percent")
+                    .default_value("0.0")
+                    .takes_value(true))
+                .arg(Arg::with_name("min-read-aligned-length-pair")
+                    .long("min-read-aligned-length-pair")
+                    .takes_value(true)
+                    .conflicts_with("proper-pairs-only"))
+                .arg(Arg::with_name("min-read-percent-identity-pair")
+                    .long("min-read-percent-identity-pair")
+                    .takes_value(true)
+                    .conflicts_with("proper-pairs-only"))
+                .arg(Arg::with_name("min-read-aligned-percent-pair")
+                    .long("min-read-aligned-percent-pair")
+                    .takes_value(true)
+                    .conflicts_with("proper-pairs-only"))
+                .arg(Arg::with_name("proper-pairs-only")
+                    .long("proper-pairs-only"))
+                .arg(Arg::with_name("threads")
+                    .long("threads")
+                    .short("t")
+                    .default_value("1"))
+
+                .arg(Arg::with_name("verbose")
+                    // .short("v") // Do not use since could be confused with
+                    // inverse (a la grep -v)
+                    .long("verbose"))
+                .arg(Arg::with_name("quiet")
+                    .short("q")
+                    .long("quiet")))
+        .subcommand(
+            SubCommand::with_name("polish")
+                .about("Polish an assembly using highly abundant variant calls")
+                .help(POLYMORPH_HELP.as_str())
+                .arg(Arg::with_name("full-help")
+                    .long("full-help"))
+                .arg(Arg::with_name("bam-file")
+                    .short("b")
+                    .long("bam-file")
+                    .multiple(true)
+                    .takes_value(true)
+                    .required_unless_one(
+                        &["read1","read2","coupled","interleaved","single","full-help"]))
+                .arg(Arg::with_name("sharded")
+                    .long("sharded")
+                    .required(false))
+                .arg(Arg::with_name("read1")
+                    .short("-1")
+                    .multiple(true)
+                    .takes_value(true)
+                    .requires("read2")
+                    .required_unless_one(
+                        &["bam-file","coupled","interleaved","single","full-help"])
+                    .conflicts_with("bam-file"))
+                .arg(Arg::with_name("read2")
+                    .short("-2")
+                    .multiple(true)
+                    .takes_value(true)
+                    .requires("read1")
+                    .required_unless_one(
+                        &["bam-file","coupled","interleaved","single","full-help"])
+                    .conflicts_with("bam-file"))
+                .arg(Arg::with_name("coupled")
+                    .short("c")
+                    .long("coupled")
+                    .multiple(true)
+                    .takes_value(true)
+                    .required_unless_one(
+                        &["bam-file","read1","interleaved","single","full-help"])
+                    .conflicts_with("bam-file"))
+                .arg(Arg::with_name("longreads")
+                    .long("longreads")
+                    .multiple(true)
+                    .takes_value(true)
+                    .required(false)
+                    .conflicts_with_all(&["longread-bam-files"]))
+                .arg(Arg::with_name("longread-bam-files")
+                    .short("l")
+                    .multiple(true)
+                    .takes_value(true)
+                    .required(false)
+                    .conflicts_with_all(&["longreads"]))
+                .arg(Arg::with_name("interleaved")
+                    .long("interleaved")
+                    .multiple(true)
+                    .takes_value(true)
+                    .required_unless_one(
+                        &["bam-file","read1","coupled","single","full-help"])
+                    .conflicts_with("bam-file"))
+                .arg(Arg::with_name("single")
+                    .long("single")
+                    .multiple(true)
+                    .takes_value(true)
+                    .required_unless_one(
+                        &["bam-file","read1","coupled","interleaved","full-help"])
+                    .conflicts_with("bam-file"))
+                .arg(Arg::with_name("reference")
+                    .short("r")
+                    .long("reference")
+                    .alias("reference")
+                    .takes_value(true)
+                    .multiple(true)
+                    .required_unless_one(&["genome-fasta-directory", "full-help"]))
+                .arg(Arg::with_name("genome-fasta-directory")
+                    .long("genome-fasta-directory")
+                    .short("d")
+                    .takes_value(true)
+                    .required_unless_one(&["reference", "genome-fasta-files", "full-help"]))
+                .arg(Arg::with_name("genome-fasta-extension")
+                    .long("genome-fasta-extension")
+                    .short("x")
+                    .takes_value(true)
+                    .default_value("fna"))
+                .arg(Arg::with_name("bam-file-cache-directory")
+                    .long("bam-file-cache-directory")
+                    .takes_value(true))
+                .arg(Arg::with_name("output-directory")
+                    .long("output-directory")
+                    .short("o")
+                    .default_value("./"))
+                .arg(Arg::with_name("threads")
+                    .short("-t")
+                    .long("threads")
+                    .default_value("1")
+                    .takes_value(true))
+                .arg(
+                    Arg::with_name("mapper")
+                        .short("p")
+                        .long("mapper")
+                        .possible_values(MAPPING_SOFTWARE_LIST)
+                        .default_value(DEFAULT_MAPPING_SOFTWARE),
+                )
+                .arg(
+                    Arg::with_name("longread-mapper")
+                        .long("longread-mapper")
+                        .possible_values(LONGREAD_MAPPING_SOFTWARE_LIST)
+                        .default_value(DEFAULT_LONGREAD_MAPPING_SOFTWARE),
+                )
+                .arg(
+                    Arg::with_name("minimap2-params")
+                        .long("minimap2-params")
+                        .long("minimap2-parameters")
+                        .takes_value(true)
+                        .allow_hyphen_values(true),
+                )
+                .arg(
+                    Arg::with_name("minimap2-reference-is-index")
+                        .long("minimap2-reference-is-index")
+                        .requires("reference"),
+                )
+                .arg(
+                    Arg::with_name("bwa-params")
+                        .long("bwa-params")
+                        .long("bwa-parameters")
+                        .takes_value(true)
+                        .allow_hyphen_values(true)
+                        .requires("reference"),
+                )
+                .arg(Arg::with_name("discard-unmapped")
+                    .long("discard-unmapped")
+                    .requires("bam-file-cache-directory"))
+
+                .arg(Arg::with_name("min-read-aligned-length")
+                    .long("min-read-aligned-length")
+                    .takes_value(true))
+                .arg(Arg::with_name("min-read-percent-identity")
+                    .long("min-read-percent-identity")
+                    .takes_value(true))
+                .arg(Arg::with_name("min-read-aligned-percent")
+                    .long("min-read-aligned-percent")
+                    .takes_value(true)
+                    .default_value("0.0"))
+                .arg(Arg::with_name("min-read-aligned-length-pair")
+                    .long("min-read-aligned-length-pair")
+                    .takes_value(true)
+                    .conflicts_with("proper-pairs-only"))
+                .arg(Arg::with_name("min-read-percent-identity-pair")
+                    .long("min-read-percent-identity-pair")
+                    .takes_value(true)
+                    .conflicts_with("proper-pairs-only"))
+                .arg(Arg::with_name("min-read-aligned-percent-pair")
+                    .long("min-read-aligned-percent-pair")
+                    .takes_value(true)
+                    .conflicts_with("proper-pairs-only"))
+                .arg(Arg::with_name("method")
+                    .short("m")
+                    .long("method")
+                    .takes_value(true)
+                    .multiple(false)
+                    .possible_values(&[
+                        "trimmed_mean",
+                        "mean",
+                        "metabat"])
+                    .default_value("trimmed_mean"))
+                .arg(Arg::with_name("min-covered-fraction")
+                    .long("min-covered-fraction")
+                    .default_value("0.0"))
+                .arg(Arg::with_name("coverage-fold")
+                    .long("coverage-fold")
+                    .default_value("0.5"))
+                .arg(Arg::with_name("min-variant-depth")
+                    .long("min-variant-depth")
+                    .short("f")
+                    .default_value("10"))
+                .arg(Arg::with_name("min-variant-quality")
+                    .long("min-variant-quality")
+                    .default_value("10"))
+                .arg(Arg::with_name("variant-caller")
+                    .long("variant-caller")
+                    .possible_values(&["native", "freebayes"])
+                    .default_value("freebayes"))
+                .arg(Arg::with_name("min-allele-fraction")
+                    .long("min-allele-fraction")
+                    .default_value("0.05"))
+                .arg(Arg::with_name("base-quality-threshold")
+                    .long("base-quality-threshold")
+                    .default_value("13"))
+                .arg(Arg::with_name("sv-caller")
+                    .long("sv-caller")
+                    .possible_values(&["native", "svim"])
+                    .default_value("native"))
+                .arg(Arg::with_name("min-sv-size")
+                    .long("min-sv-size")
+                    .default_value("40"))
+                .arg(Arg::with_name("min-sv-support")
+                    .long("min-sv-support")
+                    .default_value("3"))
+                .arg(Arg::with_name("sv-cluster-distance")
+                    .long("sv-cluster-distance")
+                    .default_value("500"))
+                .arg(Arg::with_name("mapq-threshold")
+                    .long("mapq-threshold")
+                    .short("q")
+                    .default_value("0"))
+                .arg(Arg::with_name("include-indels")
+                    .long("include-indels"))
+                .arg(Arg::with_name("contig-end-exclusion")
+                    .long("contig-end-exclusion")
+                    .default_value("75"))
+                .arg(Arg::with_name("trim-min")
+                    .long("trim-min")
+                    .default_value("0.05"))
+                .arg(Arg::with_name("trim-max")
+                    .long("trim-max")
+                    .default_value("0.95"))
+                .arg(Arg::with_name("no-zeros")
+                    .long("no-zeros"))
+                .arg(Arg::with_name("proper-pairs-only")
+                    .long("proper-pairs-only"))
+                .arg(Arg::with_name("verbose")
+                    .short("v")
+                    .long("verbose"))
+                .arg(Arg::with_name("quiet")
+                    .long("quiet")));
+}
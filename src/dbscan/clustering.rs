@@ -0,0 +1,374 @@
+use std::collections::HashMap;
+use std::f64;
+use kodama::{Method, linkage};
+use dbscan::fuzzy::{self, Assignment, Category, Cluster, MetricSpace};
+use dbscan::tuning::DistanceMatrix;
+
+/// HDBSCAN and hierarchical clustering hold the distance between every pair of variants, which
+/// takes about 1.6 GB and a long time to calculate at this many variants. Fuzzy DBSCAN only
+/// stores the distances within eps_max and should be used for larger genomes
+pub const DENSE_VARIANT_LIMIT: usize = 20000;
+
+/// A method of grouping variants into strains. Clusters are returned as fuzzy DBSCAN
+/// [assignments](Assignment) so that every method feeds the same genotyping code
+pub trait VariantClusterer {
+    /// Clusters `points`, seeded by clusters of read linked variants
+    fn cluster<P: MetricSpace>(&self, points: &[P], initial_clusters: Vec<Cluster>) -> Vec<Cluster>;
+}
+
+impl VariantClusterer for fuzzy::FuzzyDBSCAN {
+    fn cluster<P: MetricSpace>(&self, points: &[P], initial_clusters: Vec<Cluster>) -> Vec<Cluster> {
+        fuzzy::FuzzyDBSCAN::cluster(self, points, initial_clusters)
+    }
+}
+
+/// HDBSCAN style density clustering. Clusters are the most stable branches of the single
+/// linkage tree over mutual reachability distances.
+pub struct HDBSCAN {
+    /// The smallest group of variants considered a cluster
+    pub min_cluster_size: usize,
+    /// The neighbour used to define the core distance of each variant
+    pub min_samples: usize,
+    /// The minimum membership required for a variant to be a Core point.
+    pub phi: f64,
+    pub geom_var: Vec<f64>,
+    pub geom_dep: Vec<f64>,
+    pub geom_frq: Vec<f64>,
+}
+
+impl VariantClusterer for HDBSCAN {
+    fn cluster<P: MetricSpace>(&self, points: &[P], initial_clusters: Vec<Cluster>) -> Vec<Cluster> {
+        let n = points.len();
+        if n < 2 {
+            return Vec::new()
+        }
+        let mut distances = seeded_distances(points, &initial_clusters,
+                                             &self.geom_var, &self.geom_dep, &self.geom_frq);
+
+        // Core distance is the distance to the min_samples nearest neighbour
+        let min_samples = std::cmp::min(std::cmp::max(self.min_samples, 1), n - 1);
+        let core_distances = (0..n).map(|i| {
+            let mut row = (0..n).filter(|j| *j != i)
+                .map(|j| distances.get(i, j)).collect::<Vec<f64>>();
+            row.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            row[min_samples - 1]
+        }).collect::<Vec<f64>>();
+
+        // Mutual reachability replaces the distances in place, so only one matrix is held
+        let mut index = 0;
+        for i in 0..n {
+            for j in (i + 1)..n {
+                distances.distances[index] = distances.distances[index]
+                    .max(core_distances[i]).max(core_distances[j]);
+                index += 1;
+            }
+        }
+        let dend = linkage(&mut distances.distances, n, Method::Single);
+
+        // Condense the tree, keeping only splits where both sides are large enough to be clusters
+        let min_cluster_size = std::cmp::max(self.min_cluster_size, 2);
+        let lambda = |distance: f64| 1. / distance.max(1e-10);
+        let size = |node: usize| if node < n { 1 } else { dend.steps()[node - n].size };
+        let leaves = |node: usize| -> Vec<usize> {
+            let mut leaves = Vec::new();
+            let mut stack = vec![node];
+            while let Some(node) = stack.pop() {
+                if node < n {
+                    leaves.push(node);
+                } else {
+                    stack.push(dend.steps()[node - n].cluster1);
+                    stack.push(dend.steps()[node - n].cluster2);
+                }
+            }
+            leaves
+        };
+
+        // Cluster 0 is the root. For each condensed cluster keep its parent, birth lambda and
+        // the points that fall out of it with their lambda
+        let mut parents: Vec<Option<usize>> = vec![None];
+        let mut births = vec![0.];
+        let mut fallen: Vec<Vec<(usize, f64)>> = vec![Vec::new()];
+        let mut stack = vec![(n + dend.steps().len() - 1, 0)];
+        while let Some((node, label)) = stack.pop() {
+            let step = &dend.steps()[node - n];
+            let split_lambda = lambda(step.dissimilarity);
+            let children = [step.cluster1, step.cluster2];
+            let large = children.iter().map(|child| size(*child) >= min_cluster_size)
+                .collect::<Vec<bool>>();
+            for (child, is_large) in children.iter().zip(large.iter()) {
+                if *is_large && large[0] == large[1] {
+                    // A true split into two new clusters
+                    parents.push(Some(label));
+                    births.push(split_lambda);
+                    fallen.push(Vec::new());
+                    stack.push((*child, parents.len() - 1));
+                } else if *is_large {
+                    // The cluster continues through its large child
+                    stack.push((*child, label));
+                } else {
+                    for leaf in leaves(*child) {
+                        fallen[label].push((leaf, split_lambda));
+                    }
+                }
+            }
+        }
+
+        // Stability of each cluster, then select clusters bottom up. Children always have higher
+        // labels than their parents
+        let cluster_count = parents.len();
+        let mut children: Vec<Vec<usize>> = vec![Vec::new(); cluster_count];
+        for (label, parent) in parents.iter().enumerate() {
+            if let Some(parent) = parent {
+                children[*parent].push(label);
+            }
+        }
+        // Number of points falling out of each cluster or any of its descendants
+        let mut subtree_sizes = vec![0; cluster_count];
+        for label in (0..cluster_count).rev() {
+            subtree_sizes[label] = fallen[label].len()
+                + children[label].iter().map(|child| subtree_sizes[*child]).sum::<usize>();
+        }
+        let mut stability = (0..cluster_count).map(|label| {
+            let mut stability = fallen[label].iter()
+                .map(|(_, point_lambda)| point_lambda - births[label]).sum::<f64>();
+            for child in children[label].iter() {
+                stability += (births[*child] - births[label]) * subtree_sizes[*child] as f64;
+            }
+            stability
+        }).collect::<Vec<f64>>();
+        let mut selected = vec![false; cluster_count];
+        for label in (1..cluster_count).rev() {
+            let child_stability = children[label].iter().map(|child| stability[*child]).sum::<f64>();
+            if children[label].len() == 0 || stability[label] >= child_stability {
+                selected[label] = true;
+                let mut descendants = children[label].clone();
+                while let Some(descendant) = descendants.pop() {
+                    selected[descendant] = false;
+                    descendants.extend(children[descendant].iter());
+                }
+            } else {
+                stability[label] = child_stability;
+            }
+        }
+
+        // Each point belongs to the selected cluster it or its descendants fell out of, if any
+        let mut members: Vec<Vec<(usize, f64)>> = vec![Vec::new(); cluster_count];
+        let mut owners: Vec<Option<usize>> = vec![None; cluster_count];
+        for label in 1..cluster_count {
+            owners[label] = if selected[label] {
+                Some(label)
+            } else {
+                parents[label].and_then(|parent| owners[parent])
+            };
+            if let Some(owner) = owners[label] {
+                members[owner].extend(fallen[label].iter());
+            }
+        }
+
+        // Membership is the lambda at which a point left the cluster relative to the most
+        // persistent point of the cluster
+        (1..cluster_count).filter(|label| selected[*label]).map(|label| {
+            let points = std::mem::replace(&mut members[label], Vec::new());
+            let max_lambda = points.iter().map(|(_, point_lambda)| *point_lambda)
+                .fold(births[label], f64::max);
+            points.into_iter().map(|(index, point_lambda)| {
+                let membership = if max_lambda > births[label] {
+                    (point_lambda - births[label]) / (max_lambda - births[label])
+                } else {
+                    1.
+                };
+                Assignment {
+                    index,
+                    label: membership,
+                    category: if membership >= self.phi { Category::Core } else { Category::Border },
+                }
+            }).collect::<Cluster>()
+        }).collect()
+    }
+}
+
+/// Hierarchical agglomerative clustering of the variants using average linkage, with the
+/// dendrogram cut at a fixed distance
+pub struct HierarchicalClustering {
+    /// Groups closer than this distance are merged
+    pub threshold: f64,
+    /// The smallest group of variants considered a cluster. Smaller groups are noise.
+    pub min_cluster_size: usize,
+    pub geom_var: Vec<f64>,
+    pub geom_dep: Vec<f64>,
+    pub geom_frq: Vec<f64>,
+}
+
+impl VariantClusterer for HierarchicalClustering {
+    fn cluster<P: MetricSpace>(&self, points: &[P], initial_clusters: Vec<Cluster>) -> Vec<Cluster> {
+        let n = points.len();
+        if n < 2 {
+            return Vec::new()
+        }
+        let mut distances = seeded_distances(points, &initial_clusters,
+                                             &self.geom_var, &self.geom_dep, &self.geom_frq).distances;
+        let dend = linkage(&mut distances, n, Method::Average);
+
+        // Each step creates cluster n + step index, so members can be tracked as we go
+        let mut members: Vec<Vec<usize>> = (0..n).map(|index| vec![index]).collect();
+        let mut merged = vec![false; n];
+        for step in dend.steps().iter() {
+            if step.dissimilarity <= self.threshold {
+                let mut combined = members[step.cluster1].clone();
+                combined.extend(members[step.cluster2].iter());
+                merged[step.cluster1] = true;
+                merged[step.cluster2] = true;
+                members.push(combined);
+            } else {
+                members.push(Vec::new());
+            }
+            merged.push(false);
+        }
+
+        members.into_iter().zip(merged.into_iter())
+            .filter(|(cluster, merged)| !merged && cluster.len() >= std::cmp::max(self.min_cluster_size, 2))
+            .map(|(cluster, _)| {
+                cluster.into_iter().map(|index| Assignment {
+                    index,
+                    label: 1.,
+                    category: Category::Core,
+                }).collect::<Cluster>()
+            }).collect()
+    }
+}
+
+/// The clustering methods available to the genotyping pipeline
+pub enum ClusteringMethod {
+    FuzzyDBSCAN(fuzzy::FuzzyDBSCAN),
+    HDBSCAN(HDBSCAN),
+    Hierarchical(HierarchicalClustering),
+}
+
+impl ClusteringMethod {
+    /// Builds the named method from the fuzzy DBSCAN parameters. HDBSCAN uses pts_min as its
+    /// minimum cluster size and phi as its core membership threshold, while hierarchical
+    /// clustering cuts the tree at eps_max and discards groups smaller than pts_min
+    pub fn new(name: &str, parameters: fuzzy::FuzzyDBSCAN) -> ClusteringMethod {
+        let min_cluster_size = parameters.pts_min.round() as usize;
+        match name {
+            "hdbscan" => ClusteringMethod::HDBSCAN(HDBSCAN {
+                min_cluster_size,
+                min_samples: min_cluster_size,
+                phi: parameters.phi,
+                geom_var: parameters.geom_var,
+                geom_dep: parameters.geom_dep,
+                geom_frq: parameters.geom_frq,
+            }),
+            "hierarchical" => ClusteringMethod::Hierarchical(HierarchicalClustering {
+                threshold: parameters.eps_max,
+                min_cluster_size,
+                geom_var: parameters.geom_var,
+                geom_dep: parameters.geom_dep,
+                geom_frq: parameters.geom_frq,
+            }),
+            "fuzzy-dbscan" => ClusteringMethod::FuzzyDBSCAN(parameters),
+            _ => panic!("Unknown clustering method {}", name),
+        }
+    }
+}
+
+impl VariantClusterer for ClusteringMethod {
+    fn cluster<P: MetricSpace>(&self, points: &[P], initial_clusters: Vec<Cluster>) -> Vec<Cluster> {
+        match self {
+            ClusteringMethod::FuzzyDBSCAN(clusterer) =>
+                VariantClusterer::cluster(clusterer, points, initial_clusters),
+            ClusteringMethod::HDBSCAN(clusterer) => clusterer.cluster(points, initial_clusters),
+            ClusteringMethod::Hierarchical(clusterer) => clusterer.cluster(points, initial_clusters),
+        }
+    }
+}
+
+/// Pairwise distances between points in which variants of the same read linked seed are placed
+/// at distance zero. Undefined distances are replaced by the largest defined distance. The
+/// matrix grows with the square of the number of points, see [DENSE_VARIANT_LIMIT](DENSE_VARIANT_LIMIT)
+fn seeded_distances<P: MetricSpace>(points: &[P], initial_clusters: &Vec<Cluster>,
+                                    geom_var: &Vec<f64>, geom_dep: &Vec<f64>,
+                                    geom_frq: &Vec<f64>) -> DistanceMatrix {
+    if points.len() > DENSE_VARIANT_LIMIT {
+        let pairs = points.len() * (points.len() - 1) / 2;
+        warn!("Calculating all {} pairwise distances between {} variants, which needs about {:.1} GB. \
+               Consider --clustering-method fuzzy-dbscan above {} variants",
+              pairs, points.len(), (pairs * 8) as f64 / 1e9, DENSE_VARIANT_LIMIT);
+    }
+    let mut matrix = DistanceMatrix::from_points(points, geom_var, geom_dep, geom_frq);
+    let max_distance = matrix.distances.iter().cloned()
+        .filter(|distance| distance.is_finite()).fold(0., f64::max);
+    for distance in matrix.distances.iter_mut() {
+        if !distance.is_finite() {
+            *distance = max_distance;
+        }
+    }
+
+    let n = matrix.n;
+    let mut seeded: HashMap<usize, f64> = HashMap::new();
+    for cluster in initial_clusters.iter() {
+        for (x, a) in cluster.iter().enumerate() {
+            for b in cluster[x + 1..].iter() {
+                if a.index != b.index {
+                    let (i, j) = (std::cmp::min(a.index, b.index), std::cmp::max(a.index, b.index));
+                    seeded.insert(n * i - i * (i + 1) / 2 + j - 1 - i, 0.);
+                }
+            }
+        }
+    }
+    for (index, distance) in seeded {
+        matrix.distances[index] = distance;
+    }
+    matrix
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dbscan::tuning::PrecomputedPoint;
+
+    fn two_groups() -> DistanceMatrix {
+        let positions = vec![0., 0.1, 0.2, 0.3, 10., 10.1, 10.2, 10.3];
+        let n = positions.len();
+        let mut distances = Vec::new();
+        for i in 0..n {
+            for j in (i + 1)..n {
+                distances.push((positions[i] - positions[j] as f64).abs());
+            }
+        }
+        DistanceMatrix { n, distances }
+    }
+
+    fn sorted_clusters(clusters: &Vec<Cluster>) -> Vec<Vec<usize>> {
+        let mut clusters = clusters.iter().map(|cluster| {
+            let mut indices = cluster.iter().map(|assignment| assignment.index).collect::<Vec<usize>>();
+            indices.sort();
+            indices
+        }).collect::<Vec<Vec<usize>>>();
+        clusters.sort();
+        clusters
+    }
+
+    #[test]
+    fn test_clustering_methods() {
+        let matrix = two_groups();
+        let points = (0..matrix.n).map(|index| PrecomputedPoint { index, matrix: &matrix })
+            .collect::<Vec<PrecomputedPoint>>();
+        let expected = vec![vec![0, 1, 2, 3], vec![4, 5, 6, 7]];
+
+        for method in ["fuzzy-dbscan", "hdbscan", "hierarchical"].iter() {
+            let parameters = fuzzy::FuzzyDBSCAN {
+                eps_min: 0.2,
+                eps_max: 1.,
+                pts_min: 2.,
+                pts_max: 3.,
+                phi: 0.,
+                geom_var: Vec::new(),
+                geom_dep: Vec::new(),
+                geom_frq: Vec::new(),
+            };
+            let clusters = ClusteringMethod::new(method, parameters).cluster(&points[..], Vec::new());
+            assert_eq!(sorted_clusters(&clusters), expected, "{}", method);
+        }
+    }
+}
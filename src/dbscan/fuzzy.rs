@@ -1,486 +1,851 @@
-use std::collections::HashSet;
-use std::hash::Hash;
-use std::f64;
-use rayon::prelude::*;
-use model::variants::*;
-use itertools::Itertools;
-
-#[allow(unused)]
-fn take_arbitrary<T: Hash + Eq + Copy>(set: &mut HashSet<T>) -> Option<T> {
-    let key_copy = if let Some(key_ref) = set.iter().next() {
-        Some(*key_ref)
-    } else {
-        None
-    };
-    if let Some(key) = key_copy {
-        set.take(&key)
-    } else {
-        None
-    }
-}
-
-/// A trait to compute distances between points.
-pub trait MetricSpace: Sized + Send + Sync {
-    /// Returns the distance between `self` and `other`.
-    fn distance(&self, other: &Self, geom_var: &Vec<f64>,
-                geom_dep: &Vec<f64>, geom_frq: &Vec<f64>) -> f64;
-}
-
-#[derive(Debug, Clone, PartialEq)]
-pub struct Var {
-    pub pos: i64,
-    pub var: Variant,
-    pub deps: Vec<i32>,
-    pub vars: Vec<i32>,
-//    pub rel_abunds: Vec<f64>,
-    pub tid: i32,
-    pub reads: HashSet<Vec<u8>>,
-}
-
-#[allow(unused)]
-impl MetricSpace for Var<> {
-    fn distance(&self, other: &Self, geom_var: &Vec<f64>,
-                geom_dep: &Vec<f64>, geom_frq: &Vec<f64>) -> f64 {
-
-        if self.vars.len() > 1 {
-            if self.pos == other.pos && self.tid == other.tid {
-                return 40.
-            } else {
-                let clr = |input: &Vec<f64>, geom_means: &Vec<f64>| -> Vec<f64> {
-                    let output = input.par_iter().enumerate().map(|(i, v)| {
-                        ((v + 1.) / geom_means[i]).ln()
-                    }).collect();
-                    return output
-                };
-
-//                debug!("row values {:?} geom_var {:?} geom_frq {:?}", &self.vars, &geom_var, &geom_frq);
-                let row_vals: Vec<f64> = clr(&self.vars.iter().map(|v| *v as f64).collect(), geom_var);
-
-                let col_vals: Vec<f64> = clr(&other.vars.iter().map(|v| *v as f64).collect(), geom_var);
-//                debug!("row values {:?} col values {:?}", &row_vals, &col_vals);
-
-                let mean_row = get_mean(&row_vals);
-
-                let mean_col = get_mean(&col_vals);
-
-                // lovell et al. Phi and Phi distance: https://journals.plos.org/ploscompbiol/article?id=10.1371/journal.pcbi.1004075
-                // Rho: https://www.ncbi.nlm.nih.gov/pmc/articles/PMC4870310/
-                // propr log ratios to vlr and lr2rho: https://github.com/tpq/propr/blob/master/src/lr2propr.cpp
-
-                let mut row_var = 0.;
-                let mut col_var = 0.;
-                let mut covar = 0.;
-
-                row_vals.iter()
-                    .zip(col_vals.iter()).for_each(|(r_freq, c_freq)| {
-                    row_var += (r_freq - mean_row).powf(2.);
-                    col_var += (c_freq - mean_col).powf(2.);
-                    covar += (r_freq - mean_row) * (c_freq - mean_col)
-                });
-
-                row_var = row_var / (row_vals.len() as f64 - 1.);
-                col_var = col_var / (col_vals.len() as f64 - 1.);
-                covar = covar / (row_vals.len() as f64 - 1.);
-
-                let vlr = -2. * covar + row_var + col_var;
-                let rho = 1. - vlr / (row_var + col_var);
-
-                let phi = 1. + row_var / col_var -
-                    2. * (row_var / col_var).sqrt()
-                        * covar / (col_var * row_var).sqrt();
-
-                let phi_dist = ((row_var / col_var).ln()).abs() + 2.0_f64.ln()
-                    - (covar / (col_var * row_var).sqrt() + 1.).ln();
-
-                // reciprocal concordance correlation coeffecient between 0 and 2
-                let concordance = -2. * covar / (row_var + col_var) + 1.;
-
-//                // Read ids of first variant
-//                let set1 = &self.reads;
-//
-//                // Read ids of second variant
-//                let set2 = &other.reads;
-//
-//                // Add the jaccard's similarity to the hashmap for the two clusters
-//                let intersection: HashSet<_> = set1
-//                    .intersection(&set2).collect();
-//                let jaccard = intersection.len() as f64 /
-//                    (set1.len() + set2.len() + 1) as f64;
-
-                // debug!("Phi {}, Phi-D {}, concordance {}, Rho {}, Rho-M {}, row_var {} col_var {} covar {}",
-                //        phi, phi_dist, concordance, rho, 1.-rho, row_var, col_var, covar);
-
-                return phi_dist
-            }
-        } else {
-            return if self.pos == other.pos && self.tid == other.tid {
-                // arbitrarily high distance
-                20.
-            } else {
-                let row_freq = self.vars[0] as f64;
-                let col_freq = other.vars[0] as f64;
-
-                let row_depth = self.deps[0] as f64;
-                let col_depth = other.deps[0] as f64;
-
-                // Calculate Aitchinson Distance
-                let distance = (((row_freq / geom_var[0] as f64).ln() - (col_freq / geom_var[0] as f64).ln()).powf(2.)
-                    + ((row_depth / geom_dep[0] as f64).ln() - (col_depth / geom_dep[0] as f64).ln()).powf(2.)).powf(1. / 2.);
-                debug!("Distance {}", distance);
-                distance
-            }
-        }
-    }
-}
-
-
-/// A high-level classification, as defined by the FuzzyDBSCAN algorithm.
-#[derive(Debug, PartialEq, Serialize, Clone, Copy, Hash, Eq)]
-pub enum Category {
-    Core,
-    Border,
-    Noise,
-}
-
-/// An element of a [cluster](Cluster).
-#[derive(Debug, Serialize, Clone, PartialEq)]
-pub struct Assignment {
-    /// The point index.
-    pub index: usize,
-    /// A (soft) label between `0.0` and `1.0`.
-    pub label: f64,
-    /// A high-level category.
-    pub category: Category,
-}
-
-impl Assignment {
-    pub fn new() -> Assignment {
-        Assignment {
-            index: 0,
-            label: 0.,
-            category: Category::Noise,
-        }
-    }
-}
-
-/// A group of [assigned](Assignment) points.
-pub type Cluster = Vec<Assignment>;
-
-trait Dedup<T: PartialEq + Clone> {
-    fn clear_duplicates(&mut self);
-}
-
-impl<T: PartialEq + Clone> Dedup<T> for Vec<Cluster> {
-    fn clear_duplicates(&mut self) {
-        let mut already_seen = vec![];
-        self.retain(|item| match already_seen.contains(item) {
-            true => false,
-            _ => {
-                already_seen.push(item.clone());
-                true
-            }
-        })
-    }
-}
-
-/// An instance of the FuzzyDBSCAN algorithm.
-///
-/// Note that when setting `eps_min = eps_max` and `pts_min = pts_max` the algorithm will reduce to classic DBSCAN.
-pub struct FuzzyDBSCAN {
-    /// The minimum fuzzy local neighbourhood radius.
-    pub eps_min: f64,
-    /// The maximum fuzzy local neighbourhood radius.
-    pub eps_max: f64,
-    /// The minimum fuzzy neighbourhood density (number of points).
-    pub pts_min: f64,
-    /// The maximum fuzzy neighbourhood density (number of points).
-    pub pts_max: f64,
-    /// The minimum threshold required for a label to become a Core point.
-    pub phi: f64,
-    /// The geometric mean of the depth of the variants across samples (as a vector).
-    pub geom_var: Vec<f64>,
-    /// The geometric mean of the total depth at each base across samples (as a vector).
-    pub geom_dep: Vec<f64>,
-    /// The geometric mean of the relative abundances of the variants across samples (as a vector).
-    pub geom_frq: Vec<f64>,
-}
-
-// impl FuzzyDBSCAN {
-//     /// Creates a new instance of the algorithm.
-//     pub fn new() -> Self {
-//         FuzzyDBSCAN {
-//             eps_min: f64::NAN,
-//             eps_max: f64::NAN,
-//             pts_min: f64::NAN,
-//             pts_max: f64::NAN,
-//             phi: f64::NAN,
-//             geom_var: Vec::new(),
-//             geom_dep: Vec::new(),
-//             geom_frq: Vec::new(),
-//         }
-//     }
-// }
-
-impl FuzzyDBSCAN {
-    /// Clusters a list of `points`.
-    pub fn cluster<P: MetricSpace>(&self, points: &[P], initial_clusters: Vec<Cluster>) -> Vec<Cluster> {
-        self.fuzzy_dbscan(points, initial_clusters)
-    }
-}
-
-impl FuzzyDBSCAN {
-    fn fuzzy_dbscan<P: MetricSpace>(&self, points: &[P], initial_clusters: Vec<Cluster>) -> Vec<Cluster> {
-        let mut clusters = Vec::new();
-        let mut noise_cluster = Vec::new();
-        let mut visited = vec![false; points.len()];
-
-        for initial in initial_clusters.iter() {
-            // Work out which point in our initial clusters has the highest density to other
-            // points
-            let mut point_label_max = 0.;
-            let mut point_index= 0;
-            let mut point_neighbours = HashSet::new();
-
-            for core_point in initial.iter() {
-                if visited[core_point.index] {
-                    continue;
-                }
-                visited[core_point.index] = true;
-                let neighbour_indices = self.region_query(points, core_point.index);
-                let point_label = self.mu_min_p(
-                    self.density(core_point.index, &neighbour_indices, points));
-                // check if new label is better than max
-                if point_label > point_label_max {
-                    point_label_max = point_label;
-                    point_index = core_point.index;
-                    point_neighbours = neighbour_indices;
-                }
-            }
-            // Extend neighbour indices with known connections
-            let mut initial = initial.par_iter().map(|link| link.index)
-                .collect::<HashSet<usize>>();
-            initial.remove(&point_index);
-            point_neighbours.par_extend(initial.par_iter());
-
-            // Cluster based on that core point
-            clusters.push(self.expand_cluster_fuzzy(
-                        point_label_max,
-                        point_index,
-                        point_neighbours,
-                        points,
-                        &mut visited,
-            ));
-        }
-
-        // Cluster any unvisited points
-        if initial_clusters.len() == 0 {
-            for point_index in 0..points.len() {
-                if visited[point_index] {
-                    continue;
-                }
-                visited[point_index] = true;
-                let neighbour_indices = self.region_query(points, point_index);
-                let point_label = self.mu_min_p(
-                    self.density(point_index, &neighbour_indices, points));
-                if point_label == 0.0 {
-                    noise_cluster.push(Assignment {
-                        index: point_index,
-                        category: Category::Noise,
-                        label: 1.0,
-                    });
-                } else {
-                    clusters.push(self.expand_cluster_fuzzy(
-                        point_label,
-                        point_index,
-                        neighbour_indices,
-                        points,
-                        &mut visited,
-                    ));
-                }
-            }
-        }
-        if !noise_cluster.is_empty() {
-            info!("{} Variants Clustered as noise during Fuzzy DBSCAN", noise_cluster.len());
-            // clusters.push(noise_cluster);
-        }
-
-        // Sort the clusters by smallest to largest
-        clusters.par_sort_by(
-            |a, b| a.len().cmp(&b.len())
-        );
-
-        debug!("Clusters {:?}", clusters.len());
-
-        // Deduplicate clusters by sorted indices
-        clusters.dedup_by_key(|cluster| {
-            let mut dedup_key = cluster.par_iter().map(|var|
-                                var.index).collect::<Vec<usize>>();
-            dedup_key.par_sort();
-            dedup_key.dedup();
-            debug!("dedup_key {:?}", dedup_key);
-            dedup_key
-        });
-
-        debug!("Dedup Clusters {:?}", clusters.len());
-
-        clusters
-    }
-
-    fn expand_cluster_fuzzy<P: MetricSpace>(
-        &self,
-        point_label: f64,
-        point_index: usize,
-        mut neighbour_indices: HashSet<usize>,
-        points: &[P],
-        visited: &mut [bool],
-    ) -> Vec<Assignment> {
-        let mut cluster = vec![Assignment {
-            index: point_index,
-            category: Category::Core,
-            label: point_label,
-        }];
-        let mut border_points = Vec::new();
-        let mut neighbour_visited = vec![false; points.len()];
-        while let Some(neighbour_index) = take_arbitrary(&mut neighbour_indices) {
-            neighbour_visited[neighbour_index] = true;
-            visited[neighbour_index] = true;
-            let neighbour_neighbour_indices = self.region_query(points, neighbour_index);
-            let neighbour_label =
-                self.mu_min_p(self.density(neighbour_index, &neighbour_neighbour_indices, points));
-            if neighbour_label >= self.phi {
-                for neighbour_neighbour_index in neighbour_neighbour_indices {
-                    if !neighbour_visited[neighbour_neighbour_index] {
-                        neighbour_indices.insert(neighbour_neighbour_index);
-                    }
-                }
-                cluster.push(Assignment {
-                    index: neighbour_index,
-                    category: Category::Core,
-                    label: neighbour_label,
-                });
-            } else {
-                border_points.push(Assignment {
-                    index: neighbour_index,
-                    category: Category::Border,
-                    label: f64::MAX,
-                });
-            }
-        }
-        border_points.par_iter_mut().for_each(|border_point|{
-            for cluster_point in &cluster {
-                let mu_distance =
-                    self.mu_distance(&points[border_point.index], &points[cluster_point.index]);
-                if mu_distance > 0.0 {
-                    border_point.label =
-                        cluster_point.label.min(mu_distance).min(border_point.label);
-                }
-            }
-        });
-        cluster.append(&mut border_points);
-        cluster
-    }
-
-    fn region_query<P: MetricSpace>(&self, points: &[P], point_index: usize) -> HashSet<usize> {
-        points
-            .into_par_iter()
-            .enumerate()
-            .filter(|(neighbour_index, neighbour_point)| {
-                *neighbour_index != point_index
-                    && neighbour_point.distance(&points[point_index],
-                                               &self.geom_var,
-                                               &self.geom_dep,
-                                               &self.geom_frq) <= self.eps_max
-            }).map(|(neighbour_index, _)| neighbour_index)
-            .collect() //TODO: would be neat to prevent this allocation.
-    }
-
-    fn density<P: MetricSpace>(
-        &self,
-        point_index: usize,
-        neighbour_indices: &HashSet<usize>,
-        points: &[P],
-    ) -> f64 {
-        let density: f64 = neighbour_indices.par_iter().fold(|| 0.0, |sum, &neighbour_index| {
-            sum + self.mu_distance(&points[point_index], &points[neighbour_index])
-        }).sum();
-        density + 1.0
-    }
-
-    fn mu_min_p(&self, n: f64) -> f64 {
-        if n >= self.pts_max {
-            1.0
-        } else if n < self.pts_min {
-            0.0
-        } else {
-            (n - self.pts_min) / (self.pts_max - self.pts_min)
-        }
-    }
-
-    fn mu_distance<P: MetricSpace>(&self, a: &P, b: &P) -> f64 {
-        let distance = a.distance(b, &self.geom_var,
-                                  &self.geom_dep, &self.geom_frq);
-        if distance <= self.eps_min {
-            1.0
-        } else if distance > self.eps_max {
-            0.0
-        } else {
-            (self.eps_max - distance) / (self.eps_max - self.eps_min)
-        }
-    }
-}
-
-fn get_mean(input: &Vec<f64>) -> f64 {
-    let sum = input.par_iter().sum::<f64>();
-    sum / input.len() as f64
-}
-
-#[allow(unused)]
-fn propd(row_vals: Vec<f64>, col_vals: Vec<f64>) {
-
-    let mean_row = get_mean(&row_vals);
-
-    let mean_col = get_mean(&col_vals);
-
-    // lovell et al. Phi and Phi distance: https://journals.plos.org/ploscompbiol/article?id=10.1371/journal.pcbi.1004075
-    // Rho: https://www.ncbi.nlm.nih.gov/pmc/articles/PMC4870310/
-    // propr log ratios to vlr and lr2rho: https://github.com/tpq/propr/blob/master/src/lr2propr.cpp
-
-    let mut row_var = 0.;
-    let mut col_var = 0.;
-    let mut covar = 0.;
-
-    // Differential proportionality requires the calculation of VLR for different groupings of
-    // our measured variables: https://mran.microsoft.com/snapshot/2018-03-29/web/packages/propr/vignettes/e_differential.html
-
-    for combos in (0..row_vals.len()).into_iter().combinations(2) {
-        let ind_1 = combos[0];
-        let ind_2 = combos[1];
-
-    }
-    row_vals.iter()
-        .zip(col_vals.iter()).for_each(|(r_freq, c_freq)| {
-        row_var += (r_freq - mean_row).powf(2.);
-        col_var += (c_freq - mean_col).powf(2.);
-        covar += (r_freq - mean_row) * (c_freq - mean_col)
-    });
-
-    row_var = row_var / (row_vals.len() as f64 - 1.);
-    col_var = col_var / (col_vals.len() as f64 - 1.);
-    covar = covar / (row_vals.len() as f64 - 1.);
-
-    let vlr = -2. * covar + row_var + col_var;
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-//    #[test]
-//    fn test_clustering() {
-//        let mut points =  vec![Var {
-//
-//        }];
-//    }
-
-
-}
\ No newline at end of file
+use std::collections::HashSet;
+use std::hash::Hash;
+use std::f64;
+use rayon::prelude::*;
+use model::variants::*;
+use model::read_names::ReadSet;
+use itertools::Itertools;
+
+#[allow(unused)]
+fn take_arbitrary<T: Hash + Eq + Copy>(set: &mut HashSet<T>) -> Option<T> {
+    let key_copy = if let Some(key_ref) = set.iter().next() {
+        Some(*key_ref)
+    } else {
+        None
+    };
+    if let Some(key) = key_copy {
+        set.take(&key)
+    } else {
+        None
+    }
+}
+
+/// A trait to compute distances between points.
+pub trait MetricSpace: Sized + Send + Sync {
+    /// Returns the distance between `self` and `other`.
+    fn distance(&self, other: &Self, geom_var: &Vec<f64>,
+                geom_dep: &Vec<f64>, geom_frq: &Vec<f64>) -> f64;
+
+    /// A value whose difference between two points bounds their distance from below, used by
+    /// [NeighbourGraph](NeighbourGraph) to skip pairs that cannot be neighbours. Points without a
+    /// key are compared against every other point.
+    fn index_key(&self) -> Option<f64> {
+        None
+    }
+
+    /// The largest difference in [index_key](MetricSpace::index_key) two points within `radius`
+    /// of each other can have
+    fn key_gap(&self, _radius: f64) -> f64 {
+        f64::INFINITY
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Var {
+    pub pos: i64,
+    pub var: Variant,
+    pub deps: Vec<i32>,
+    pub vars: Vec<i32>,
+//    pub rel_abunds: Vec<f64>,
+    pub tid: i32,
+    pub reads: ReadSet,
+}
+
+#[allow(unused)]
+impl MetricSpace for Var<> {
+    fn distance(&self, other: &Self, geom_var: &Vec<f64>,
+                geom_dep: &Vec<f64>, geom_frq: &Vec<f64>) -> f64 {
+        let metric = DistanceMetric::default();
+        VarFeatures::new(self, geom_var, geom_dep, metric)
+            .distance(&VarFeatures::new(other, geom_var, geom_dep, metric), geom_var, geom_dep, geom_frq)
+    }
+}
+
+/// The measure of dissimilarity between the abundances or reads of two variants
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Distance {
+    /// One minus the proportionality coefficient rho
+    Rho,
+    /// The proportionality measure phi
+    Phi,
+    /// The symmetric phi distance of Lovell et al.
+    PhiDist,
+    /// One minus Lin's concordance correlation coefficient of the CLR values, which unlike rho
+    /// also penalises a difference in the mean CLR values
+    Concordance,
+    /// Euclidean distance between the CLR values
+    Aitchison,
+    /// One minus the Jaccard similarity of the supporting reads
+    JaccardReads,
+}
+
+impl Distance {
+    pub fn from_name(name: &str) -> Distance {
+        match name {
+            "rho" => Distance::Rho,
+            "phi" => Distance::Phi,
+            "phi-dist" => Distance::PhiDist,
+            "concordance" => Distance::Concordance,
+            "aitchison" => Distance::Aitchison,
+            "jaccard-reads" => Distance::JaccardReads,
+            _ => panic!("Unknown distance {}", name),
+        }
+    }
+}
+
+/// The distance used to compare variants. Abundance based distances can be blended with the
+/// read co-occurrence distance so that variants seen on the same reads are drawn together
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DistanceMetric {
+    pub distance: Distance,
+    /// Weight between 0 and 1 given to the Jaccard distance of the supporting reads
+    pub read_weight: f64,
+}
+
+impl Default for DistanceMetric {
+    fn default() -> DistanceMetric {
+        DistanceMetric {
+            distance: Distance::PhiDist,
+            read_weight: 0.,
+        }
+    }
+}
+
+impl DistanceMetric {
+    pub fn generate_from_clap(m: &clap::ArgMatches) -> DistanceMetric {
+        let read_weight: f64 = m.value_of("read-distance-weight").unwrap().parse().unwrap();
+        if read_weight < 0. || read_weight > 1. {
+            panic!("read-distance-weight must be between 0 and 1, found {}", read_weight);
+        }
+        DistanceMetric {
+            distance: Distance::from_name(m.value_of("distance").unwrap()),
+            read_weight,
+        }
+    }
+
+    fn uses_reads(&self) -> bool {
+        self.distance == Distance::JaccardReads || self.read_weight > 0.
+    }
+}
+
+/// The transformed values of a [Var](Var) used by the distance calculation. Computing these once
+/// per variant avoids repeating the CLR transform for every pair of variants.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VarFeatures {
+    pub tid: i32,
+    pub pos: i64,
+    /// Number of samples the variant was observed across
+    pub samples: usize,
+    /// CLR transformed variant depths. With a single sample, the log ratios of the variant depth
+    /// and total depth to their geometric means
+    pub clr: Vec<f64>,
+    /// Mean of the CLR values
+    pub mean: f64,
+    /// Sample variance of the CLR values
+    pub variance: f64,
+    /// Supporting reads, only kept when the metric compares reads
+    pub reads: ReadSet,
+    pub metric: DistanceMetric,
+}
+
+impl VarFeatures {
+    pub fn new(var: &Var, geom_var: &Vec<f64>, geom_dep: &Vec<f64>,
+               metric: DistanceMetric) -> VarFeatures {
+        let reads = if metric.uses_reads() {
+            var.reads.clone()
+        } else {
+            ReadSet::new()
+        };
+        if var.vars.len() > 1 {
+            let clr = var.vars.iter().enumerate().map(|(i, v)| {
+                ((*v as f64 + 1.) / geom_var[i]).ln()
+            }).collect::<Vec<f64>>();
+            let mean = get_mean(&clr);
+            let mut variance = 0.;
+            clr.iter().for_each(|v| variance += (v - mean).powf(2.));
+            variance = variance / (clr.len() as f64 - 1.);
+
+            VarFeatures {
+                tid: var.tid,
+                pos: var.pos,
+                samples: var.vars.len(),
+                clr,
+                mean,
+                variance,
+                reads,
+                metric,
+            }
+        } else {
+            VarFeatures {
+                tid: var.tid,
+                pos: var.pos,
+                samples: 1,
+                clr: vec![(var.vars[0] as f64 / geom_var[0]).ln(),
+                          (var.deps[0] as f64 / geom_dep[0]).ln()],
+                mean: 0.,
+                variance: 0.,
+                reads,
+                metric,
+            }
+        }
+    }
+
+    /// Computes the features of every variant in parallel
+    pub fn from_vars(vars: &[Var], geom_var: &Vec<f64>, geom_dep: &Vec<f64>,
+                     metric: DistanceMetric) -> Vec<VarFeatures> {
+        vars.par_iter().map(|var| VarFeatures::new(var, geom_var, geom_dep, metric)).collect()
+    }
+
+    /// Distance between the abundances of two variants. With a single sample only the
+    /// Aitchison distance is defined, so every abundance based distance falls back to it
+    fn abundance_distance(&self, other: &VarFeatures) -> f64 {
+        if self.samples > 1 {
+            // lovell et al. Phi and Phi distance: https://journals.plos.org/ploscompbiol/article?id=10.1371/journal.pcbi.1004075
+            // Rho: https://www.ncbi.nlm.nih.gov/pmc/articles/PMC4870310/
+            // propr log ratios to vlr and lr2rho: https://github.com/tpq/propr/blob/master/src/lr2propr.cpp
+            let row_var = self.variance;
+            let col_var = other.variance;
+            let mut covar = 0.;
+            self.clr.iter()
+                .zip(other.clr.iter()).for_each(|(r_freq, c_freq)| {
+                covar += (r_freq - self.mean) * (c_freq - other.mean)
+            });
+            covar = covar / (self.clr.len() as f64 - 1.);
+
+            match self.metric.distance {
+                Distance::Rho => {
+                    let vlr = -2. * covar + row_var + col_var;
+                    let rho = 1. - vlr / (row_var + col_var);
+                    1. - rho
+                },
+                Distance::Phi => {
+                    1. + row_var / col_var -
+                        2. * (row_var / col_var).sqrt()
+                            * covar / (col_var * row_var).sqrt()
+                },
+                // reciprocal of Lin's concordance correlation coeffecient between 0 and 2. The
+                // squared difference of means is scaled to match the sample (co)variances
+                Distance::Concordance => {
+                    let n = self.clr.len() as f64;
+                    let mean_diff = (self.mean - other.mean).powf(2.) * n / (n - 1.);
+                    1. - 2. * covar / (row_var + col_var + mean_diff)
+                },
+                Distance::Aitchison => {
+                    self.clr.iter().zip(other.clr.iter()).map(|(r_freq, c_freq)| {
+                        ((r_freq - self.mean) - (c_freq - other.mean)).powf(2.)
+                    }).sum::<f64>().powf(1. / 2.)
+                },
+                _ => {
+                    ((row_var / col_var).ln()).abs() + 2.0_f64.ln()
+                        - (covar / (col_var * row_var).sqrt() + 1.).ln()
+                },
+            }
+        } else {
+            // Calculate Aitchinson Distance
+            ((self.clr[0] - other.clr[0]).powf(2.)
+                + (self.clr[1] - other.clr[1]).powf(2.)).powf(1. / 2.)
+        }
+    }
+
+    /// One minus the Jaccard similarity of the supporting reads
+    fn read_distance(&self, other: &VarFeatures) -> f64 {
+        let intersection = self.reads.intersection_count(&other.reads);
+        let union = self.reads.len() + other.reads.len() - intersection;
+        if union == 0 {
+            1.
+        } else {
+            1. - intersection as f64 / union as f64
+        }
+    }
+}
+
+impl MetricSpace for VarFeatures {
+    fn distance(&self, other: &Self, _geom_var: &Vec<f64>,
+                _geom_dep: &Vec<f64>, _geom_frq: &Vec<f64>) -> f64 {
+        if self.pos == other.pos && self.tid == other.tid {
+            return self.same_position_distance()
+        }
+        match self.metric.distance {
+            Distance::JaccardReads => self.read_distance(other),
+            _ if self.metric.read_weight > 0. => {
+                (1. - self.metric.read_weight) * self.abundance_distance(other)
+                    + self.metric.read_weight * self.read_distance(other)
+            },
+            _ => self.abundance_distance(other),
+        }
+    }
+
+    /// The first CLR value with a single sample, the norm of the centred CLR values for the
+    /// Aitchison distance and the log variance of the CLR values otherwise. The Jaccard read
+    /// distance has no such bound, so those variants are compared exhaustively
+    fn index_key(&self) -> Option<f64> {
+        if self.metric.distance == Distance::JaccardReads {
+            None
+        } else if self.samples == 1 {
+            Some(self.clr[0])
+        } else if self.metric.distance == Distance::Aitchison {
+            Some((self.variance * (self.samples as f64 - 1.)).sqrt())
+        } else {
+            Some(self.variance.ln())
+        }
+    }
+
+    fn key_gap(&self, radius: f64) -> f64 {
+        if radius >= self.same_position_distance() || self.metric.read_weight >= 1. {
+            return f64::INFINITY
+        }
+        // The read distance is at most one, so the abundance distance of neighbours is bounded
+        let radius = (radius / (1. - self.metric.read_weight)).max(0.);
+        if self.samples == 1 {
+            return radius
+        }
+        match self.metric.distance {
+            Distance::Aitchison | Distance::PhiDist => radius,
+            // Both are at least (sqrt(t) - 1)^2 / (t + 1) where t is the ratio of variances, as
+            // the difference of means only adds to the denominator of concordance when the
+            // covariance is positive
+            Distance::Rho | Distance::Concordance if radius < 1. => {
+                let root = (1. + (1. - (1. - radius).powf(2.)).sqrt()) / (1. - radius);
+                2. * root.ln()
+            },
+            // Phi is at least (1 - sqrt(t))^2 where t is the ratio of variances
+            Distance::Phi if radius < 1. => -2. * (1. - radius.sqrt()).ln(),
+            _ => f64::INFINITY,
+        }
+    }
+}
+
+impl VarFeatures {
+    /// The arbitrarily high distance given to variants at the same position
+    fn same_position_distance(&self) -> f64 {
+        if self.samples > 1 { 40. } else { 20. }
+    }
+}
+
+/// The neighbours of each point within a radius and their distances. Built once per clustering
+/// so that region queries and densities look distances up rather than recalculating them.
+#[derive(Debug, Clone)]
+pub struct NeighbourGraph {
+    /// Neighbours of each point sorted by index
+    neighbours: Vec<Vec<(usize, f64)>>,
+}
+
+impl NeighbourGraph {
+    /// Finds the neighbours of every point within `radius`. Points are sorted by their
+    /// [index_key](MetricSpace::index_key) so that distances are only calculated between points
+    /// whose keys are close enough for them to be neighbours
+    pub fn new<P: MetricSpace>(points: &[P], radius: f64, geom_var: &Vec<f64>,
+                               geom_dep: &Vec<f64>, geom_frq: &Vec<f64>) -> NeighbourGraph {
+        let n = points.len();
+        let gap = points.first().map_or(f64::INFINITY, |point| point.key_gap(radius));
+        // Allow for rounding error in the distance calculations
+        let gap = gap + 1e-9 * (1. + gap);
+        let keys = points.iter()
+            .map(|point| point.index_key().filter(|key| key.is_finite() && gap.is_finite()))
+            .collect::<Vec<Option<f64>>>();
+
+        // Points with a key are sorted by it and each is only compared with the points after it
+        // whose key is within the gap
+        let mut indexed = (0..n).filter(|i| keys[*i].is_some()).collect::<Vec<usize>>();
+        indexed.sort_by(|a, b| keys[*a].partial_cmp(&keys[*b]).unwrap());
+        let unindexed = (0..n).filter(|i| keys[*i].is_none()).collect::<Vec<usize>>();
+
+        // Distances are symmetric so each pair is calculated once, with the lower index first
+        let pair = |a: usize, b: usize| -> Option<(usize, usize, f64)> {
+            let (i, j) = (std::cmp::min(a, b), std::cmp::max(a, b));
+            let distance = points[i].distance(&points[j], geom_var, geom_dep, geom_frq);
+            if distance <= radius {
+                Some((i, j, distance))
+            } else {
+                None
+            }
+        };
+        let mut pairs = (0..indexed.len()).into_par_iter().flat_map(|x| {
+            let key = keys[indexed[x]].unwrap();
+            indexed[x + 1..].iter()
+                .take_while(|other| keys[**other].unwrap() - key <= gap)
+                .filter_map(|other| pair(indexed[x], *other))
+                .collect::<Vec<(usize, usize, f64)>>()
+        }).collect::<Vec<(usize, usize, f64)>>();
+        // Points without a key are compared with every other point
+        pairs.par_extend(unindexed.par_iter().flat_map(|i| {
+            (0..n).filter(|j| keys[*j].is_some() || j > i)
+                .filter_map(|j| pair(*i, j))
+                .collect::<Vec<(usize, usize, f64)>>()
+        }));
+
+        let mut neighbours = vec![Vec::new(); n];
+        for (i, j, distance) in pairs {
+            neighbours[i].push((j, distance));
+            neighbours[j].push((i, distance));
+        }
+        neighbours.par_iter_mut().for_each(|row| row.sort_by_key(|(index, _)| *index));
+        NeighbourGraph {
+            neighbours,
+        }
+    }
+
+    pub fn neighbours(&self, index: usize) -> &[(usize, f64)] {
+        &self.neighbours[index]
+    }
+
+    /// The distance between two points if they are neighbours
+    pub fn distance(&self, a: usize, b: usize) -> Option<f64> {
+        let row = &self.neighbours[a];
+        row.binary_search_by_key(&b, |(index, _)| *index).ok().map(|idx| row[idx].1)
+    }
+}
+
+/// A high-level classification, as defined by the FuzzyDBSCAN algorithm.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Copy, Hash, Eq)]
+pub enum Category {
+    Core,
+    Border,
+    Noise,
+}
+
+/// An element of a [cluster](Cluster).
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Assignment {
+    /// The point index.
+    pub index: usize,
+    /// A (soft) label between `0.0` and `1.0`.
+    pub label: f64,
+    /// A high-level category.
+    pub category: Category,
+}
+
+impl Assignment {
+    pub fn new() -> Assignment {
+        Assignment {
+            index: 0,
+            label: 0.,
+            category: Category::Noise,
+        }
+    }
+}
+
+/// A group of [assigned](Assignment) points.
+pub type Cluster = Vec<Assignment>;
+
+trait Dedup<T: PartialEq + Clone> {
+    fn clear_duplicates(&mut self);
+}
+
+impl<T: PartialEq + Clone> Dedup<T> for Vec<Cluster> {
+    fn clear_duplicates(&mut self) {
+        let mut already_seen = vec![];
+        self.retain(|item| match already_seen.contains(item) {
+            true => false,
+            _ => {
+                already_seen.push(item.clone());
+                true
+            }
+        })
+    }
+}
+
+/// An instance of the FuzzyDBSCAN algorithm.
+///
+/// Note that when setting `eps_min = eps_max` and `pts_min = pts_max` the algorithm will reduce to classic DBSCAN.
+pub struct FuzzyDBSCAN {
+    /// The minimum fuzzy local neighbourhood radius.
+    pub eps_min: f64,
+    /// The maximum fuzzy local neighbourhood radius.
+    pub eps_max: f64,
+    /// The minimum fuzzy neighbourhood density (number of points).
+    pub pts_min: f64,
+    /// The maximum fuzzy neighbourhood density (number of points).
+    pub pts_max: f64,
+    /// The minimum threshold required for a label to become a Core point.
+    pub phi: f64,
+    /// The geometric mean of the depth of the variants across samples (as a vector).
+    pub geom_var: Vec<f64>,
+    /// The geometric mean of the total depth at each base across samples (as a vector).
+    pub geom_dep: Vec<f64>,
+    /// The geometric mean of the relative abundances of the variants across samples (as a vector).
+    pub geom_frq: Vec<f64>,
+}
+
+// impl FuzzyDBSCAN {
+//     /// Creates a new instance of the algorithm.
+//     pub fn new() -> Self {
+//         FuzzyDBSCAN {
+//             eps_min: f64::NAN,
+//             eps_max: f64::NAN,
+//             pts_min: f64::NAN,
+//             pts_max: f64::NAN,
+//             phi: f64::NAN,
+//             geom_var: Vec::new(),
+//             geom_dep: Vec::new(),
+//             geom_frq: Vec::new(),
+//         }
+//     }
+// }
+
+impl FuzzyDBSCAN {
+    /// Clusters a list of `points`.
+    pub fn cluster<P: MetricSpace>(&self, points: &[P], initial_clusters: Vec<Cluster>) -> Vec<Cluster> {
+        let graph = NeighbourGraph::new(points, self.eps_max,
+                                        &self.geom_var, &self.geom_dep, &self.geom_frq);
+        self.fuzzy_dbscan(&graph, initial_clusters)
+    }
+
+    /// Clusters points whose neighbours within `eps_max` have already been found
+    pub fn cluster_graph(&self, graph: &NeighbourGraph, initial_clusters: Vec<Cluster>) -> Vec<Cluster> {
+        self.fuzzy_dbscan(graph, initial_clusters)
+    }
+}
+
+impl FuzzyDBSCAN {
+    fn fuzzy_dbscan(&self, graph: &NeighbourGraph, initial_clusters: Vec<Cluster>) -> Vec<Cluster> {
+        let n = graph.neighbours.len();
+        let mut clusters = Vec::new();
+        let mut noise_cluster = Vec::new();
+        let mut visited = vec![false; n];
+
+        for initial in initial_clusters.iter() {
+            // Work out which point in our initial clusters has the highest density to other
+            // points
+            let mut point_label_max = 0.;
+            let mut point_index= 0;
+            let mut point_neighbours = HashSet::new();
+
+            for core_point in initial.iter() {
+                if visited[core_point.index] {
+                    continue;
+                }
+                visited[core_point.index] = true;
+                let neighbour_indices = self.region_query(graph, core_point.index);
+                let point_label = self.mu_min_p(self.density(core_point.index, graph));
+                // check if new label is better than max
+                if point_label > point_label_max {
+                    point_label_max = point_label;
+                    point_index = core_point.index;
+                    point_neighbours = neighbour_indices;
+                }
+            }
+            // Extend neighbour indices with known connections
+            let mut initial = initial.par_iter().map(|link| link.index)
+                .collect::<HashSet<usize>>();
+            initial.remove(&point_index);
+            point_neighbours.par_extend(initial.par_iter());
+
+            // Cluster based on that core point
+            clusters.push(self.expand_cluster_fuzzy(
+                        point_label_max,
+                        point_index,
+                        point_neighbours,
+                        graph,
+                        &mut visited,
+            ));
+        }
+
+        // Cluster any unvisited points
+        if initial_clusters.len() == 0 {
+            for point_index in 0..n {
+                if visited[point_index] {
+                    continue;
+                }
+                visited[point_index] = true;
+                let neighbour_indices = self.region_query(graph, point_index);
+                let point_label = self.mu_min_p(self.density(point_index, graph));
+                if point_label == 0.0 {
+                    noise_cluster.push(Assignment {
+                        index: point_index,
+                        category: Category::Noise,
+                        label: 1.0,
+                    });
+                } else {
+                    clusters.push(self.expand_cluster_fuzzy(
+                        point_label,
+                        point_index,
+                        neighbour_indices,
+                        graph,
+                        &mut visited,
+                    ));
+                }
+            }
+        }
+        if !noise_cluster.is_empty() {
+            info!("{} Variants Clustered as noise during Fuzzy DBSCAN", noise_cluster.len());
+            // clusters.push(noise_cluster);
+        }
+
+        // Sort the clusters by smallest to largest
+        clusters.par_sort_by(
+            |a, b| a.len().cmp(&b.len())
+        );
+
+        debug!("Clusters {:?}", clusters.len());
+
+        // Deduplicate clusters by sorted indices
+        clusters.dedup_by_key(|cluster| {
+            let mut dedup_key = cluster.par_iter().map(|var|
+                                var.index).collect::<Vec<usize>>();
+            dedup_key.par_sort();
+            dedup_key.dedup();
+            debug!("dedup_key {:?}", dedup_key);
+            dedup_key
+        });
+
+        debug!("Dedup Clusters {:?}", clusters.len());
+
+        clusters
+    }
+
+    fn expand_cluster_fuzzy(
+        &self,
+        point_label: f64,
+        point_index: usize,
+        mut neighbour_indices: HashSet<usize>,
+        graph: &NeighbourGraph,
+        visited: &mut [bool],
+    ) -> Vec<Assignment> {
+        let mut cluster = vec![Assignment {
+            index: point_index,
+            category: Category::Core,
+            label: point_label,
+        }];
+        let mut border_points = Vec::new();
+        // The core point is already in the cluster, so it must not be added again as its
+        // neighbours' neighbour
+        let mut neighbour_visited = vec![false; visited.len()];
+        neighbour_visited[point_index] = true;
+        while let Some(neighbour_index) = take_arbitrary(&mut neighbour_indices) {
+            neighbour_visited[neighbour_index] = true;
+            visited[neighbour_index] = true;
+            let neighbour_neighbour_indices = self.region_query(graph, neighbour_index);
+            let neighbour_label = self.mu_min_p(self.density(neighbour_index, graph));
+            if neighbour_label >= self.phi {
+                for neighbour_neighbour_index in neighbour_neighbour_indices {
+                    if !neighbour_visited[neighbour_neighbour_index] {
+                        neighbour_indices.insert(neighbour_neighbour_index);
+                    }
+                }
+                cluster.push(Assignment {
+                    index: neighbour_index,
+                    category: Category::Core,
+                    label: neighbour_label,
+                });
+            } else {
+                border_points.push(Assignment {
+                    index: neighbour_index,
+                    category: Category::Border,
+                    label: f64::MAX,
+                });
+            }
+        }
+        border_points.par_iter_mut().for_each(|border_point|{
+            for cluster_point in &cluster {
+                let mu_distance =
+                    self.mu_distance(graph, border_point.index, cluster_point.index);
+                if mu_distance > 0.0 {
+                    border_point.label =
+                        cluster_point.label.min(mu_distance).min(border_point.label);
+                }
+            }
+        });
+        cluster.append(&mut border_points);
+        cluster
+    }
+
+    fn region_query(&self, graph: &NeighbourGraph, point_index: usize) -> HashSet<usize> {
+        graph.neighbours(point_index).iter().map(|(neighbour_index, _)| *neighbour_index).collect()
+    }
+
+    fn density(&self, point_index: usize, graph: &NeighbourGraph) -> f64 {
+        let density: f64 = graph.neighbours(point_index).iter()
+            .map(|(_, distance)| self.mu(*distance)).sum();
+        density + 1.0
+    }
+
+    fn mu_min_p(&self, n: f64) -> f64 {
+        if n >= self.pts_max {
+            1.0
+        } else if n < self.pts_min {
+            0.0
+        } else {
+            (n - self.pts_min) / (self.pts_max - self.pts_min)
+        }
+    }
+
+    /// Points further apart than `eps_max` are not neighbours and have a membership of zero
+    fn mu_distance(&self, graph: &NeighbourGraph, a: usize, b: usize) -> f64 {
+        match graph.distance(a, b) {
+            Some(distance) => self.mu(distance),
+            None => 0.0,
+        }
+    }
+
+    fn mu(&self, distance: f64) -> f64 {
+        if distance <= self.eps_min {
+            1.0
+        } else if distance > self.eps_max {
+            0.0
+        } else {
+            (self.eps_max - distance) / (self.eps_max - self.eps_min)
+        }
+    }
+}
+
+fn get_mean(input: &Vec<f64>) -> f64 {
+    let sum = input.par_iter().sum::<f64>();
+    sum / input.len() as f64
+}
+
+#[allow(unused)]
+fn propd(row_vals: Vec<f64>, col_vals: Vec<f64>) {
+
+    let mean_row = get_mean(&row_vals);
+
+    let mean_col = get_mean(&col_vals);
+
+    // lovell et al. Phi and Phi distance: https://journals.plos.org/ploscompbiol/article?id=10.1371/journal.pcbi.1004075
+    // Rho: https://www.ncbi.nlm.nih.gov/pmc/articles/PMC4870310/
+    // propr log ratios to vlr and lr2rho: https://github.com/tpq/propr/blob/master/src/lr2propr.cpp
+
+    let mut row_var = 0.;
+    let mut col_var = 0.;
+    let mut covar = 0.;
+
+    // Differential proportionality requires the calculation of VLR for different groupings of
+    // our measured variables: https://mran.microsoft.com/snapshot/2018-03-29/web/packages/propr/vignettes/e_differential.html
+
+    for combos in (0..row_vals.len()).into_iter().combinations(2) {
+        let ind_1 = combos[0];
+        let ind_2 = combos[1];
+
+    }
+    row_vals.iter()
+        .zip(col_vals.iter()).for_each(|(r_freq, c_freq)| {
+        row_var += (r_freq - mean_row).powf(2.);
+        col_var += (c_freq - mean_col).powf(2.);
+        covar += (r_freq - mean_row) * (c_freq - mean_col)
+    });
+
+    row_var = row_var / (row_vals.len() as f64 - 1.);
+    col_var = col_var / (col_vals.len() as f64 - 1.);
+    covar = covar / (row_vals.len() as f64 - 1.);
+
+    let vlr = -2. * covar + row_var + col_var;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use model::read_names::{ReadInterner, ReadSet};
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+//    #[test]
+//    fn test_clustering() {
+//        let mut points =  vec![Var {
+//
+//        }];
+//    }
+
+    fn create_var(pos: i64, vars: Vec<i32>) -> Var {
+        Var {
+            pos,
+            var: Variant::SNV(b'A'),
+            deps: vec![50; vars.len()],
+            vars,
+            tid: 0,
+            reads: ReadSet::new(),
+        }
+    }
+
+    #[test]
+    fn test_neighbour_graph() {
+        let vars = vec![
+            create_var(1, vec![10, 20, 30]),
+            create_var(2, vec![11, 19, 31]),
+            create_var(3, vec![30, 20, 10]),
+            create_var(3, vec![5, 25, 30]),
+            create_var(4, vec![2, 40, 3]),
+        ];
+        let geom_var = vec![10., 20., 15.];
+        let geom_dep = vec![50., 50., 50.];
+        let features = VarFeatures::from_vars(&vars, &geom_var, &geom_dep,
+                                              DistanceMetric::default());
+        let radius = 1.;
+        let graph = NeighbourGraph::new(&features, radius, &geom_var, &geom_dep, &geom_var);
+
+        for i in 0..vars.len() {
+            for j in 0..vars.len() {
+                if i == j {
+                    continue
+                }
+                let distance = vars[i].distance(&vars[j], &geom_var, &geom_dep, &geom_var);
+                if distance <= radius {
+                    assert_eq!(graph.distance(i, j), Some(distance));
+                } else {
+                    assert_eq!(graph.distance(i, j), None);
+                }
+            }
+        }
+        // Variants at the same position are never neighbours
+        assert_eq!(graph.distance(2, 3), None);
+        assert!(graph.distance(0, 1).is_some());
+    }
+
+    #[test]
+    fn test_neighbour_graph_index() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let geom_var = vec![10., 20., 15., 12.];
+        let geom_dep = vec![50., 50., 50., 50.];
+        let multi_sample = (0..200).map(|pos| {
+            create_var(pos / 2, (0..4).map(|_| rng.gen_range(0, 60)).collect())
+        }).collect::<Vec<Var>>();
+        let single_sample = (0..200).map(|pos| {
+            create_var(pos / 2, vec![rng.gen_range(1, 60)])
+        }).collect::<Vec<Var>>();
+
+        for name in ["rho", "phi", "phi-dist", "concordance", "aitchison"].iter() {
+            for read_weight in [0., 0.5].iter() {
+                let metric = DistanceMetric { distance: Distance::from_name(name), read_weight: *read_weight };
+                for vars in [&multi_sample, &single_sample].iter() {
+                    let features = VarFeatures::from_vars(vars, &geom_var, &geom_dep, metric);
+                    for radius in [0.05, 0.3, 1.5].iter() {
+                        let graph = NeighbourGraph::new(&features, *radius, &geom_var, &geom_dep, &geom_var);
+                        for i in 0..features.len() {
+                            let expected = (0..features.len()).filter(|j| *j != i).filter_map(|j| {
+                                let distance = features[std::cmp::min(i, j)]
+                                    .distance(&features[std::cmp::max(i, j)], &geom_var, &geom_dep, &geom_var);
+                                if distance <= *radius { Some((j, distance)) } else { None }
+                            }).collect::<Vec<(usize, f64)>>();
+                            assert_eq!(graph.neighbours(i), &expected[..], "{} {} {}", name, read_weight, radius);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_distance_metrics() {
+        // Variants whose depths are exactly proportional across samples
+        let mut read_names = ReadInterner::new();
+        let mut var_1 = create_var(1, vec![1, 3, 7]);
+        var_1.reads = ReadSet::from_names(0, vec![b"metric_a".to_vec(), b"metric_b".to_vec()], &mut read_names);
+        let mut var_2 = create_var(2, vec![3, 7, 15]);
+        var_2.reads = ReadSet::from_names(0, vec![b"metric_b".to_vec(), b"metric_c".to_vec()], &mut read_names);
+        let geom_var = vec![1., 1., 1.];
+        let geom_dep = vec![50., 50., 50.];
+
+        let distance = |name: &str, read_weight: f64| -> f64 {
+            let metric = DistanceMetric { distance: Distance::from_name(name), read_weight };
+            VarFeatures::new(&var_1, &geom_var, &geom_dep, metric)
+                .distance(&VarFeatures::new(&var_2, &geom_var, &geom_dep, metric),
+                          &geom_var, &geom_dep, &geom_var)
+        };
+        for name in ["rho", "phi", "phi-dist", "aitchison"].iter() {
+            assert!(distance(name, 0.).abs() < 1e-10, "{}", name);
+        }
+        // The CLR values of the second variant are those of the first offset by ln 2, which
+        // rho ignores but concordance does not
+        assert!((distance("concordance", 0.) - 3. / 7.).abs() < 1e-10);
+        assert!((distance("jaccard-reads", 0.) - 2. / 3.).abs() < 1e-10);
+        assert!((distance("phi-dist", 0.5) - 1. / 3.).abs() < 1e-10);
+    }
+}
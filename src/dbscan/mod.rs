@@ -1 +1,3 @@
-pub mod fuzzy;
\ No newline at end of file
+pub mod fuzzy;
+pub mod tuning;
+pub mod clustering;
@@ -0,0 +1,242 @@
+use std::collections::HashMap;
+use rayon::prelude::*;
+use rand::{SeedableRng, rngs::StdRng};
+use dbscan::fuzzy::{self, MetricSpace};
+use model::read_names::ReadId;
+
+/// Pairwise distances between points stored as a condensed upper triangle, so that repeated
+/// clusterings of the same points do not recalculate them
+#[derive(Debug, Clone)]
+pub struct DistanceMatrix {
+    pub n: usize,
+    pub distances: Vec<f64>,
+}
+
+impl DistanceMatrix {
+    pub fn from_points<P: MetricSpace>(points: &[P], geom_var: &Vec<f64>,
+                                       geom_dep: &Vec<f64>, geom_frq: &Vec<f64>) -> DistanceMatrix {
+        let n = points.len();
+        let distances = (0..n).into_par_iter().flat_map(|i| {
+            ((i + 1)..n).map(|j| points[i].distance(&points[j], geom_var, geom_dep, geom_frq))
+                .collect::<Vec<f64>>()
+        }).collect::<Vec<f64>>();
+        DistanceMatrix {
+            n,
+            distances,
+        }
+    }
+
+    pub fn get(&self, i: usize, j: usize) -> f64 {
+        if i == j {
+            0.
+        } else if i < j {
+            self.distances[self.n * i - i * (i + 1) / 2 + j - 1 - i]
+        } else {
+            self.get(j, i)
+        }
+    }
+}
+
+/// A point whose distances are looked up in a [DistanceMatrix](DistanceMatrix)
+pub struct PrecomputedPoint<'a> {
+    pub index: usize,
+    pub matrix: &'a DistanceMatrix,
+}
+
+impl<'a> MetricSpace for PrecomputedPoint<'a> {
+    fn distance(&self, other: &Self, _geom_var: &Vec<f64>,
+                _geom_dep: &Vec<f64>, _geom_frq: &Vec<f64>) -> f64 {
+        self.matrix.get(self.index, other.index)
+    }
+}
+
+/// The cluster each point is assigned to, taking the cluster with the highest label when a
+/// point belongs to several. Noise points are left unassigned.
+pub fn hard_assignments(clusters: &Vec<fuzzy::Cluster>, n: usize) -> Vec<Option<usize>> {
+    let mut best: Vec<Option<(usize, f64)>> = vec![None; n];
+    for (cluster_idx, cluster) in clusters.iter().enumerate() {
+        for assignment in cluster.iter() {
+            if assignment.category == fuzzy::Category::Noise {
+                continue
+            }
+            let replace = match best[assignment.index] {
+                Some((_, label)) => assignment.label > label,
+                None => true,
+            };
+            if replace {
+                best[assignment.index] = Some((cluster_idx, assignment.label));
+            }
+        }
+    }
+    best.into_iter().map(|b| b.map(|(cluster_idx, _)| cluster_idx)).collect()
+}
+
+/// Mean silhouette width of the assigned points. Returns 0 when fewer than two clusters have
+/// points assigned, as the silhouette is undefined.
+pub fn silhouette_score(matrix: &DistanceMatrix, assignments: &Vec<Option<usize>>) -> f64 {
+    let mut cluster_sizes: HashMap<usize, usize> = HashMap::new();
+    for assignment in assignments.iter() {
+        if let Some(cluster_idx) = assignment {
+            *cluster_sizes.entry(*cluster_idx).or_insert(0) += 1;
+        }
+    }
+    if cluster_sizes.len() < 2 {
+        return 0.
+    }
+
+    let widths = (0..assignments.len()).into_par_iter().filter_map(|i| {
+        let own = assignments[i]?;
+        if cluster_sizes[&own] < 2 {
+            // Singletons have a silhouette of zero by convention
+            return Some(0.)
+        }
+        let mut sums: HashMap<usize, f64> = HashMap::new();
+        for (j, assignment) in assignments.iter().enumerate() {
+            if i == j {
+                continue
+            }
+            if let Some(cluster_idx) = assignment {
+                *sums.entry(*cluster_idx).or_insert(0.) += matrix.get(i, j);
+            }
+        }
+        let a = sums.get(&own).cloned().unwrap_or(0.) / (cluster_sizes[&own] - 1) as f64;
+        let b = sums.iter().filter(|(cluster_idx, _)| **cluster_idx != own)
+            .map(|(cluster_idx, sum)| sum / cluster_sizes[cluster_idx] as f64)
+            .fold(std::f64::INFINITY, f64::min);
+        let denominator = a.max(b);
+        if denominator > 0. {
+            Some((b - a) / denominator)
+        } else {
+            Some(0.)
+        }
+    }).collect::<Vec<f64>>();
+
+    if widths.len() == 0 {
+        0.
+    } else {
+        widths.iter().sum::<f64>() / widths.len() as f64
+    }
+}
+
+/// Variants scored by parameter tuning are capped at this many, as the pairwise distances and
+/// silhouette widths grow quadratically with the number of variants
+pub const MAX_TUNING_VARIANTS: usize = 2000;
+
+/// Indices of the points used to score parameters: every point when there are at most
+/// max_points, otherwise a reproducible random subsample of max_points, in ascending order
+pub fn tuning_subsample(n: usize, max_points: usize) -> Vec<usize> {
+    if n <= max_points {
+        return (0..n).collect()
+    }
+    let mut rng = StdRng::seed_from_u64(n as u64);
+    let mut indices = rand::seq::index::sample(&mut rng, n, max_points).into_vec();
+    indices.sort();
+    indices
+}
+
+/// Read consistency of a clustering corrected for chance, in the manner of Cohen's kappa: the
+/// fraction of read linked variant pairs placed in the same cluster, less the fraction of all
+/// variant pairs expected to share a cluster given the cluster sizes. Without the correction
+/// putting every variant into one cluster would be perfectly consistent. Pairs where either
+/// variant is unassigned count as inconsistent. Returns 0 when there are no linked pairs or
+/// every pair is expected to share a cluster.
+pub fn read_consistency_score(points: &[fuzzy::Var],
+                              assignments: &Vec<Option<usize>>,
+                              minimum_reads_in_link: usize) -> f64 {
+    let mut read_index: HashMap<ReadId, Vec<usize>> = HashMap::new();
+    for (idx, point) in points.iter().enumerate() {
+        for read in point.reads.iter() {
+            read_index.entry(*read).or_insert(Vec::new()).push(idx);
+        }
+    }
+    let mut shared_reads: HashMap<(usize, usize), usize> = HashMap::new();
+    for variants in read_index.values() {
+        for (x, i) in variants.iter().enumerate() {
+            for j in variants[x + 1..].iter() {
+                *shared_reads.entry((*i, *j)).or_insert(0) += 1;
+            }
+        }
+    }
+
+    let linked = shared_reads.iter()
+        .filter(|(_, count)| **count >= std::cmp::max(minimum_reads_in_link, 1))
+        .map(|(pair, _)| *pair).collect::<Vec<(usize, usize)>>();
+    chance_corrected_consistency(&linked, assignments)
+}
+
+fn chance_corrected_consistency(linked: &Vec<(usize, usize)>,
+                                assignments: &Vec<Option<usize>>) -> f64 {
+    if linked.len() == 0 {
+        return 0.
+    }
+    let consistent = linked.iter().filter(|(i, j)| {
+        match (assignments[*i], assignments[*j]) {
+            (Some(a), Some(b)) => a == b,
+            _ => false,
+        }
+    }).count() as f64 / linked.len() as f64;
+
+    let mut cluster_sizes: HashMap<usize, usize> = HashMap::new();
+    for assignment in assignments.iter() {
+        if let Some(cluster_idx) = assignment {
+            *cluster_sizes.entry(*cluster_idx).or_insert(0) += 1;
+        }
+    }
+    let n = assignments.len() as f64;
+    let expected = cluster_sizes.values()
+        .map(|size| (*size as f64 / n).powi(2))
+        .sum::<f64>();
+    if expected >= 1. {
+        0.
+    } else {
+        (consistent - expected) / (1. - expected)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_silhouette_score() {
+        // Two tight groups far apart
+        let positions = vec![0., 0.1, 0.2, 10., 10.1, 10.2];
+        let n = positions.len();
+        let mut distances = Vec::new();
+        for i in 0..n {
+            for j in (i + 1)..n {
+                distances.push((positions[i] - positions[j] as f64).abs());
+            }
+        }
+        let matrix = DistanceMatrix { n, distances };
+        assert_eq!(matrix.get(4, 1), matrix.get(1, 4));
+
+        let good = vec![Some(0), Some(0), Some(0), Some(1), Some(1), Some(1)];
+        let bad = vec![Some(0), Some(1), Some(0), Some(1), Some(0), Some(1)];
+        assert!(silhouette_score(&matrix, &good) > 0.9);
+        assert!(silhouette_score(&matrix, &bad) < 0.);
+        assert_eq!(silhouette_score(&matrix, &vec![Some(0); n]), 0.);
+    }
+
+    #[test]
+    fn test_read_consistency_score() {
+        // Reads link the first three and the last three variants
+        let linked = vec![(0, 1), (1, 2), (3, 4), (4, 5)];
+        let split = vec![Some(0), Some(0), Some(0), Some(1), Some(1), Some(1)];
+        let merged = vec![Some(0); 6];
+        let mixed = vec![Some(0), Some(1), Some(0), Some(1), Some(0), Some(1)];
+        assert_eq!(chance_corrected_consistency(&linked, &split), 1.);
+        assert_eq!(chance_corrected_consistency(&linked, &merged), 0.);
+        assert!(chance_corrected_consistency(&linked, &mixed) < 0.);
+        assert_eq!(chance_corrected_consistency(&vec![], &split), 0.);
+    }
+
+    #[test]
+    fn test_tuning_subsample() {
+        assert_eq!(tuning_subsample(5, 10), vec![0, 1, 2, 3, 4]);
+        let subsample = tuning_subsample(100, 10);
+        assert_eq!(subsample.len(), 10);
+        assert!(subsample.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(subsample, tuning_subsample(100, 10));
+    }
+}
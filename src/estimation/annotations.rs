@@ -0,0 +1,275 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use bio::io::gff;
+use coverm::genomes_and_contigs::GenomesAndContigs;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AnnotationFormat {
+    GFF3,
+    GenBank,
+}
+
+/// Guesses the format of an annotation file from its extension, falling back to checking
+/// whether the file starts with a GenBank LOCUS line
+pub fn annotation_format(file_path: &str) -> AnnotationFormat {
+    let extension = Path::new(file_path).extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_lowercase());
+    match extension.as_ref().map(|extension| extension.as_str()) {
+        Some("gff") | Some("gff3") => AnnotationFormat::GFF3,
+        Some("gb") | Some("gbk") | Some("gbff") | Some("genbank") => AnnotationFormat::GenBank,
+        _ => {
+            let file = File::open(file_path)
+                .expect(&format!("Unable to open annotation file {}", file_path));
+            let first_line = BufReader::new(file).lines()
+                .filter_map(|line| line.ok())
+                .find(|line| !line.trim().is_empty())
+                .unwrap_or(String::new());
+            if first_line.starts_with("LOCUS") {
+                AnnotationFormat::GenBank
+            } else {
+                AnnotationFormat::GFF3
+            }
+        }
+    }
+}
+
+/// Reads the coding sequences of a GFF3 or GenBank file as GFF records
+pub fn read_annotations(file_path: &str) -> Vec<gff::Record> {
+    match annotation_format(file_path) {
+        AnnotationFormat::GFF3 => {
+            // Prokka and other annotators append the sequences after a ##FASTA directive,
+            // which the GFF parser can't read, so only the lines before it are parsed
+            let file = File::open(file_path)
+                .expect(&format!("Unable to read GFF file {}", file_path));
+            let mut annotation_lines = String::new();
+            for line in BufReader::new(file).lines() {
+                let line = line.expect(&format!("Unable to read GFF file {}", file_path));
+                if line.starts_with("##FASTA") || line.starts_with('>') {
+                    break
+                }
+                annotation_lines.push_str(&line);
+                annotation_lines.push('\n');
+            }
+            let mut reader = gff::Reader::new(annotation_lines.as_bytes(), gff::GffType::GFF3);
+            reader.records()
+                .map(|record| record.expect(&format!("Unable to parse record in GFF file {}", file_path)))
+                .filter(|record| record.feature_type() == "CDS")
+                .collect()
+        },
+        AnnotationFormat::GenBank => read_genbank(file_path),
+    }
+}
+
+/// A feature from the feature table of a GenBank record
+struct GenBankFeature {
+    key: String,
+    location: String,
+    qualifiers: Vec<String>,
+}
+
+/// Parses a simple GenBank location, e.g. 10..300, complement(<10..300) or 42, into its 1-based
+/// inclusive start and end and whether it is on the reverse strand. Joined, ordered and remote
+/// locations can't be represented by a single GFF record and give None
+fn parse_genbank_location(location: &str) -> Option<(u64, u64, bool)> {
+    let location = location.trim();
+    let (location, reverse) = if location.starts_with("complement(") && location.ends_with(')') {
+        (&location["complement(".len()..location.len() - 1], true)
+    } else {
+        (location, false)
+    };
+    if location.contains(|c: char| c == ',' || c == '(' || c == ':') {
+        return None
+    }
+    let location = location.replace(|c: char| c == '<' || c == '>', "");
+    let mut bounds = location.split("..");
+    let start = bounds.next()?.trim().parse::<u64>().ok()?;
+    let end = match bounds.next() {
+        Some(end) => end.trim().parse::<u64>().ok()?,
+        None => start,
+    };
+    Some((start, end, reverse))
+}
+
+/// Converts a GenBank CDS feature into a GFF record on the given sequence
+fn genbank_feature_to_record(seqname: &str, feature: &GenBankFeature) -> Option<gff::Record> {
+    let (start, end, reverse) = parse_genbank_location(&feature.location)?;
+    let mut record = gff::Record::new();
+    *record.seqname_mut() = seqname.to_string();
+    *record.source_mut() = "GenBank".to_string();
+    *record.feature_type_mut() = feature.key.clone();
+    *record.start_mut() = start;
+    *record.end_mut() = end;
+    *record.score_mut() = ".".to_string();
+    *record.strand_mut() = if reverse { "-".to_string() } else { "+".to_string() };
+    *record.frame_mut() = "0".to_string();
+    for qualifier in feature.qualifiers.iter() {
+        let mut fields = qualifier.trim_start_matches('/').splitn(2, '=');
+        let key = fields.next().unwrap_or("");
+        let value = fields.next().unwrap_or("").trim_matches('"').to_string();
+        match key {
+            // GenBank counts the codon start from 1, GFF counts the bases to skip
+            "codon_start" => {
+                let codon_start = value.parse::<usize>().unwrap_or(1);
+                *record.frame_mut() = format!("{}", codon_start.max(1) - 1);
+            },
+            "locus_tag" => {
+                record.attributes_mut().insert("ID".to_string(), value);
+            },
+            "gene" | "product" | "protein_id" => {
+                record.attributes_mut().insert(key.to_string(), value);
+            },
+            _ => {},
+        }
+    }
+    Some(record)
+}
+
+/// Reads the CDS features of every record in a GenBank file. Each record's LOCUS name is used
+/// as the sequence name
+fn read_genbank(file_path: &str) -> Vec<gff::Record> {
+    let file = File::open(file_path)
+        .expect(&format!("Unable to open GenBank file {}", file_path));
+    let mut records = Vec::new();
+    let mut seqname = String::new();
+    let mut in_features = false;
+    let mut current: Option<GenBankFeature> = None;
+    let mut skipped = 0;
+
+    let mut finish_feature = |seqname: &str, feature: Option<GenBankFeature>,
+                              records: &mut Vec<gff::Record>| {
+        if let Some(feature) = feature {
+            if feature.key == "CDS" {
+                match genbank_feature_to_record(seqname, &feature) {
+                    Some(record) => records.push(record),
+                    None => skipped += 1,
+                }
+            }
+        }
+    };
+
+    for line in BufReader::new(file).lines() {
+        let line = line.expect(&format!("Unable to read GenBank file {}", file_path));
+        if line.starts_with("LOCUS") {
+            seqname = line.split_whitespace().nth(1)
+                .expect(&format!("LOCUS line without a name in GenBank file {}", file_path))
+                .to_string();
+            in_features = false;
+        } else if line.starts_with("FEATURES") {
+            in_features = true;
+        } else if line.starts_with("ORIGIN") || line.starts_with("CONTIG") || line.starts_with("//") {
+            finish_feature(&seqname, current.take(), &mut records);
+            in_features = false;
+        } else if in_features {
+            let trimmed = line.trim();
+            if line.starts_with("     ") && !line[5..].starts_with(' ') {
+                // A new feature key followed by its location
+                finish_feature(&seqname, current.take(), &mut records);
+                let mut fields = trimmed.splitn(2, char::is_whitespace);
+                current = Some(GenBankFeature {
+                    key: fields.next().unwrap_or("").to_string(),
+                    location: fields.next().unwrap_or("").trim().to_string(),
+                    qualifiers: Vec::new(),
+                });
+            } else if let Some(feature) = current.as_mut() {
+                if trimmed.starts_with('/') {
+                    feature.qualifiers.push(trimmed.to_string());
+                } else if let Some(qualifier) = feature.qualifiers.last_mut() {
+                    qualifier.push(' ');
+                    qualifier.push_str(trimmed);
+                } else {
+                    // Locations spanning several lines are split at commas
+                    feature.location.push_str(trimmed);
+                }
+            }
+        }
+    }
+    finish_feature(&seqname, current.take(), &mut records);
+
+    if skipped > 0 {
+        warn!("Skipped {} CDS features with joined or remote locations in {}", skipped, file_path);
+    }
+    records
+}
+
+/// Reads each annotation file and assigns its coding sequences to the genome containing their
+/// sequence, keyed by genome index then contig name. Annotation files must describe the
+/// reference contigs: a file whose sequence names match no reference contig is an error, while
+/// sequence names missing from the references are reported and their features ignored
+pub fn read_genome_annotations(file_paths: &Vec<&str>, genomes_and_contigs: &GenomesAndContigs)
+    -> HashMap<usize, HashMap<String, Vec<gff::Record>>> {
+    let mut genome_annotations = HashMap::new();
+    for file_path in file_paths.iter() {
+        let records = read_annotations(file_path);
+        info!("Read {} coding sequences from {}", records.len(), file_path);
+
+        let mut matched = 0;
+        let mut unmatched_seqids = HashSet::new();
+        for record in records.into_iter() {
+            let seqname = record.seqname().to_string();
+            match genomes_and_contigs.genome_of_contig(&seqname)
+                .and_then(|genome| genomes_and_contigs.genome_index(&genome)) {
+                Some(ref_idx) => {
+                    genome_annotations.entry(ref_idx).or_insert(HashMap::new())
+                        .entry(seqname).or_insert(Vec::new())
+                        .push(record);
+                    matched += 1;
+                },
+                None => {
+                    unmatched_seqids.insert(seqname);
+                },
+            }
+        }
+
+        let mut unmatched_seqids = unmatched_seqids.into_iter().collect::<Vec<String>>();
+        unmatched_seqids.sort();
+        if matched == 0 && unmatched_seqids.len() > 0 {
+            panic!("None of the sequence names in annotation file {} match a reference contig, \
+                    e.g. {:?}. Sequence names must match the contig names of the reference FASTA headers",
+                   file_path, &unmatched_seqids[..std::cmp::min(5, unmatched_seqids.len())]);
+        } else if unmatched_seqids.len() > 0 {
+            warn!("{} sequence names in annotation file {} do not match any reference contig and \
+                   their features will be ignored: {:?}",
+                  unmatched_seqids.len(), file_path, unmatched_seqids);
+        }
+    }
+    genome_annotations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bio_types::strand::Strand;
+
+    #[test]
+    fn test_read_genbank() {
+        assert_eq!(parse_genbank_location("1..18"), Some((1, 18, false)));
+        assert_eq!(parse_genbank_location("complement(<4..>90)"), Some((4, 90, true)));
+        assert_eq!(parse_genbank_location("join(1..10,20..30)"), None);
+
+        assert_eq!(annotation_format("tests/data/dnds.gbk"), AnnotationFormat::GenBank);
+        let records = read_annotations("tests/data/dnds.gbk");
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].seqname(), "stoit");
+        assert_eq!((records[0].start(), records[0].end()), (&1, &18));
+        assert_eq!(records[0].strand(), Some(Strand::Forward));
+        assert_eq!(records[0].frame(), "0");
+        assert_eq!(records[1].strand(), Some(Strand::Reverse));
+        assert_eq!(records[1].frame(), "1");
+
+        let records = read_annotations("tests/data/dnds.gff");
+        assert_eq!(records.len(), 1);
+    }
+
+    #[test]
+    fn test_read_gff_with_fasta() {
+        assert_eq!(annotation_format("tests/data/prokka.gff"), AnnotationFormat::GFF3);
+        let records = read_annotations("tests/data/prokka.gff");
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].seqname(), "genome5~seq2");
+        assert_eq!((records[0].start(), records[0].end()), (&870, &998));
+        assert_eq!(records[0].attributes().get("locus_tag"), Some(&"GENOME5_00001".to_string()));
+    }
+}
@@ -1 +1,3 @@
-pub mod process_bam;
\ No newline at end of file
+pub mod process_bam;
+pub mod pileup_caller;
+pub mod sv_caller;
@@ -0,0 +1,437 @@
+use std;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::str;
+use rust_htslib::bam::{self, Read, pileup::Indel};
+
+use model::variants::*;
+use utils::*;
+
+/// Parameters used by the native pileup based variant caller
+#[derive(Debug, Clone)]
+pub struct PileupCallerParameters {
+    /// Minimum number of reads supporting an alternate allele
+    pub min_variant_depth: usize,
+    /// Minimum PHRED base quality for a base to be counted
+    pub base_quality_threshold: u8,
+    /// Minimum mapping quality for a read to be counted
+    pub mapq_threshold: u8,
+    /// Minimum fraction of the locus depth an alternate allele must reach
+    pub min_allele_fraction: f64,
+}
+
+impl PileupCallerParameters {
+    pub fn generate_from_clap(m: &clap::ArgMatches) -> PileupCallerParameters {
+        PileupCallerParameters {
+            min_variant_depth: m.value_of("min-variant-depth").unwrap().parse().unwrap(),
+            base_quality_threshold: m.value_of("base-quality-threshold").unwrap().parse().unwrap(),
+            mapq_threshold: m.value_of("mapq-threshold").unwrap().parse().unwrap(),
+            min_allele_fraction: m.value_of("min-allele-fraction").unwrap().parse().unwrap(),
+        }
+    }
+}
+
+/// Allele observations collected from a single pileup column
+#[derive(Debug, Clone, Default)]
+struct AlleleCounts {
+    // Number of reads with a base observed at the locus, counting reads that also carry an
+    // indel once
+    observations: i32,
+    // Number of reads supporting the reference base and their summed base quality
+    ref_count: i32,
+    ref_qual: i32,
+    // Error probabilities of every base observation, kept for the quality model
+    ref_errors: Vec<f64>,
+    // Alternate alleles mapped to read support, base quality sum and error probabilities
+    alts: HashMap<Variant, (HashSet<Vec<u8>>, i32, Vec<f64>)>,
+}
+
+impl AlleleCounts {
+    fn depth(&self) -> i32 {
+        self.observations
+    }
+}
+
+/// Pure Rust pileup based SNV, MNV and indel caller. Produces the same `Base` records that
+/// `Base::from_vcf_record` produces from freebayes output, i.e. one reference `Base` at each
+/// variant locus followed by one `Base` per alternate allele.
+#[allow(unused)]
+pub fn call_variants_from_bam(bam_path: &str,
+                              reference: &str,
+                              sample_count: usize,
+                              sample_idx: usize,
+                              min_qual: f32,
+                              threads: usize,
+                              params: &PileupCallerParameters)
+    -> HashMap<i32, HashMap<i64, HashMap<Variant, Base>>> {
+
+    let mut bam_reader = bam::Reader::from_path(&Path::new(bam_path))
+        .expect(&format!("Unable to open BAM file {}", bam_path));
+    bam_reader.set_threads(threads).expect("Unable to set threads on BAM reader");
+    let header = bam_reader.header().clone();
+    let target_names = header.target_names();
+
+    let mut reference_reader = match bio::io::fasta::IndexedReader::from_file(&Path::new(reference)) {
+        Ok(reader) => reader,
+        Err(_e) => generate_faidx(reference),
+    };
+
+    let mut variant_map = HashMap::new();
+    let mut ref_seq: Vec<u8> = Vec::new();
+    let mut last_tid: i32 = -1;
+
+    // Called SNVs on the current contig, kept so that adjacent SNVs sharing reads can be
+    // merged into MNVs once the contig is finished
+    let mut contig_snvs: Vec<(i64, u8, HashSet<Vec<u8>>)> = Vec::new();
+
+    let mut pileups = bam_reader.pileup();
+    pileups.set_max_depth(std::i32::MAX as u32);
+    for pileup in pileups {
+        let pileup = pileup.expect("Unable to read pileup");
+        let tid = pileup.tid() as i32;
+        let pos = pileup.pos() as i64;
+
+        if tid != last_tid {
+            if last_tid != -1 {
+                merge_mnvs(&mut variant_map, last_tid, &ref_seq, &contig_snvs,
+                           sample_count, sample_idx, params);
+            }
+            contig_snvs = Vec::new();
+            ref_seq = Vec::new();
+            reference_reader.fetch_all(str::from_utf8(target_names[tid as usize]).unwrap())
+                .expect("Cannot read sequence from reference");
+            reference_reader.read(&mut ref_seq)
+                .expect("Cannot read sequence from reference");
+            last_tid = tid;
+        }
+
+        if pos as usize >= ref_seq.len() {
+            continue
+        }
+        let ref_base = ref_seq[pos as usize].to_ascii_uppercase();
+
+        let mut counts = AlleleCounts::default();
+        for alignment in pileup.alignments() {
+            let record = alignment.record();
+            if record.mapq() < params.mapq_threshold
+                || record.is_duplicate()
+                || record.is_secondary()
+                || record.is_quality_check_failed() {
+                continue
+            }
+            let qname = record.qname().to_vec();
+
+            // Base level observation at this column
+            match alignment.qpos() {
+                Some(qpos) => {
+                    let base_qual = record.qual()[qpos];
+                    if base_qual < params.base_quality_threshold {
+                        continue
+                    }
+                    let error = phred_to_error(base_qual);
+                    counts.observations += 1;
+                    let read_base = record.seq()[qpos].to_ascii_uppercase();
+                    if read_base == ref_base {
+                        counts.ref_count += 1;
+                        counts.ref_qual += base_qual as i32;
+                        counts.ref_errors.push(error);
+                    } else if read_base != b'N' {
+                        let entry = counts.alts.entry(Variant::SNV(read_base))
+                            .or_insert((HashSet::new(), 0, Vec::new()));
+                        entry.0.insert(qname.clone());
+                        entry.1 += base_qual as i32;
+                        entry.2.push(error);
+                    }
+
+                    // Indels are anchored on the base preceding them, as in VCF
+                    match alignment.indel() {
+                        Indel::Ins(len) => {
+                            let start = qpos + 1;
+                            let end = std::cmp::min(start + len as usize, record.seq().len());
+                            let inserted = record.seq().as_bytes()[start..end]
+                                .iter().map(|b| b.to_ascii_uppercase()).collect::<Vec<u8>>();
+                            let entry = counts.alts.entry(Variant::Insertion(inserted))
+                                .or_insert((HashSet::new(), 0, Vec::new()));
+                            entry.0.insert(qname);
+                            entry.1 += base_qual as i32;
+                            entry.2.push(error);
+                        },
+                        Indel::Del(len) => {
+                            let entry = counts.alts.entry(Variant::Deletion(len))
+                                .or_insert((HashSet::new(), 0, Vec::new()));
+                            entry.0.insert(qname);
+                            entry.1 += base_qual as i32;
+                            entry.2.push(error);
+                        },
+                        Indel::None => {},
+                    }
+                },
+                None => {},
+            }
+        }
+
+        let depth = counts.depth();
+        if depth == 0 {
+            continue
+        }
+
+        let mut bases = Vec::new();
+        for (variant, (reads, qual_sum, errors)) in counts.alts.iter() {
+            let alt_count = reads.len();
+            if alt_count < params.min_variant_depth
+                || (alt_count as f64 / depth as f64) < params.min_allele_fraction {
+                continue
+            }
+
+            let qual = variant_quality(errors, &counts.ref_errors);
+            if qual <= min_qual {
+                continue
+            }
+
+            let refr = match variant {
+                Variant::Deletion(len) => {
+                    let end = std::cmp::min(pos as usize + *len as usize + 1, ref_seq.len());
+                    ref_seq[pos as usize..end].to_vec()
+                },
+                _ => vec![ref_base],
+            };
+
+            let mut base = Base::new(tid as u32, pos, sample_count, refr);
+            base.variant = variant.clone();
+            base.quals[sample_idx] = qual;
+            base.filters[sample_idx].insert(Filter::PASS);
+            base.baseq[sample_idx] = *qual_sum;
+            // As for freebayes calls, the allele depth is counted from the reads by process_bam
+            base.depth[sample_idx] = alt_count as i32;
+            bases.push(base);
+
+            if let Variant::SNV(alt) = variant {
+                contig_snvs.push((pos, *alt, reads.clone()));
+            }
+        }
+
+        if bases.len() > 0 {
+            let mut refr_base = Base::new(tid as u32, pos, sample_count, vec![ref_base]);
+            refr_base.baseq[sample_idx] = counts.ref_qual;
+            refr_base.depth[sample_idx] = counts.ref_count;
+
+            let variant_con = variant_map.entry(tid).or_insert(HashMap::new());
+            let variant_pos = variant_con.entry(pos).or_insert(HashMap::new());
+            variant_pos.entry(Variant::None).or_insert(refr_base);
+            for base in bases {
+                variant_pos.entry(base.variant.to_owned()).or_insert(base);
+            }
+        }
+    }
+
+    if last_tid != -1 {
+        merge_mnvs(&mut variant_map, last_tid, &ref_seq, &contig_snvs,
+                   sample_count, sample_idx, params);
+    }
+
+    variant_map
+}
+
+/// Merges runs of adjacent SNVs that are supported by the same reads into a single MNV
+fn merge_mnvs(variant_map: &mut HashMap<i32, HashMap<i64, HashMap<Variant, Base>>>,
+              tid: i32,
+              ref_seq: &Vec<u8>,
+              snvs: &Vec<(i64, u8, HashSet<Vec<u8>>)>,
+              sample_count: usize,
+              sample_idx: usize,
+              params: &PileupCallerParameters) {
+    let mut idx = 0;
+    while idx < snvs.len() {
+        let (start, first_alt, first_reads) = &snvs[idx];
+        let mut alt = vec![*first_alt];
+        let mut shared_reads = first_reads.clone();
+        let mut next = idx + 1;
+
+        while next < snvs.len() && snvs[next].0 == start + alt.len() as i64 {
+            let intersection: HashSet<Vec<u8>> = shared_reads
+                .intersection(&snvs[next].2).cloned().collect();
+            if intersection.len() < params.min_variant_depth {
+                break
+            }
+            alt.push(snvs[next].1);
+            shared_reads = intersection;
+            next += 1;
+        }
+
+        if alt.len() > 1 {
+            let end = std::cmp::min(*start as usize + alt.len(), ref_seq.len());
+            let refr = ref_seq[*start as usize..end].to_vec();
+            let mut base = Base::new(tid as u32, *start, sample_count, refr);
+            base.variant = Variant::MNV(alt.clone());
+            base.depth[sample_idx] = shared_reads.len() as i32;
+            base.filters[sample_idx].insert(Filter::PASS);
+
+            // The MNV quality is bounded by its weakest constituent SNV
+            let variant_con = variant_map.entry(tid).or_insert(HashMap::new());
+            let mut qual = std::f32::MAX;
+            for (offset, alt_base) in alt.iter().enumerate() {
+                match variant_con.get(&(*start + offset as i64)) {
+                    Some(position_variants) => {
+                        match position_variants.get(&Variant::SNV(*alt_base)) {
+                            Some(snv) => qual = qual.min(snv.quals[sample_idx]),
+                            None => {},
+                        }
+                    },
+                    None => {},
+                }
+            }
+            base.quals[sample_idx] = qual;
+
+            let variant_pos = variant_con.entry(*start).or_insert(HashMap::new());
+            variant_pos.entry(base.variant.to_owned()).or_insert(base);
+        }
+        idx = next;
+    }
+}
+
+/// Convert a PHRED scaled base quality into an error probability
+fn phred_to_error(qual: u8) -> f64 {
+    10_f64.powf(-(qual as f64) / 10.)
+}
+
+/// Phred scaled likelihood ratio of a site containing the alternate allele at its observed
+/// frequency versus every alternate observation being a sequencing error
+fn variant_quality(alt_errors: &Vec<f64>, ref_errors: &Vec<f64>) -> f32 {
+    let total = (alt_errors.len() + ref_errors.len()) as f64;
+    if total == 0. {
+        return 0.
+    }
+    let freq = alt_errors.len() as f64 / total;
+
+    let mut ln_null = 0.;
+    let mut ln_alt = 0.;
+    for error in alt_errors.iter() {
+        ln_null += (error / 3.).ln();
+        ln_alt += (freq * (1. - error) + (1. - freq) * error / 3.).ln();
+    }
+    for error in ref_errors.iter() {
+        ln_null += (1. - error).ln();
+        ln_alt += ((1. - freq) * (1. - error) + freq * error / 3.).ln();
+    }
+
+    let qual = 10. / 10_f64.ln() * (ln_alt - ln_null);
+    if qual > 0. {
+        qual as f32
+    } else {
+        0.
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use estimation::bams::process_bam::process_bam;
+    use estimation::variant_matrix::*;
+    use estimation::codon_structs::CodonTable;
+    use coverm::bam_generator::*;
+    use coverm::mosdepth_genome_coverage_estimators::CoverageEstimator;
+    use coverm::genome_parsing::read_genome_fasta_files;
+    use coverm::FlagFilter;
+
+    #[test]
+    fn test_phred_to_error() {
+        assert_eq!(format!("{:.3}", phred_to_error(10)), "0.100");
+        assert_eq!(format!("{:.3}", phred_to_error(20)), "0.010");
+    }
+
+    #[test]
+    fn test_variant_quality() {
+        // No alternate observations means no evidence for a variant
+        assert_eq!(variant_quality(&vec![], &vec![0.001; 10]), 0.);
+
+        // Many high quality alternate observations give a confident call
+        let strong = variant_quality(&vec![0.001; 10], &vec![0.001; 10]);
+        let weak = variant_quality(&vec![0.001; 2], &vec![0.001; 18]);
+        assert!(strong > weak);
+        assert!(weak > 0.);
+    }
+
+    #[test]
+    fn test_call_variants_from_bam() {
+        let params = PileupCallerParameters {
+            min_variant_depth: 5,
+            base_quality_threshold: 13,
+            mapq_threshold: 0,
+            min_allele_fraction: 0.05,
+        };
+        let variant_map = call_variants_from_bam("tests/data/indels.bam", "tests/data/single_genome.fna",
+                                                 1, 0, 10., 1, &params);
+        let contig_variants = &variant_map[&0];
+
+        // Nine of the eleven reads over position 504 delete the two bases after it
+        let deletion = &contig_variants[&504][&Variant::Deletion(2)];
+        assert_eq!(deletion.depth[0], 9);
+        assert!(contig_variants[&504].contains_key(&Variant::None));
+
+        let insertion = &contig_variants[&519][&Variant::Insertion(b"ACCA".to_vec())];
+        assert_eq!(insertion.depth[0], 7);
+
+        // Allele depths are left for process_bam to count, as for freebayes calls
+        for position_variants in contig_variants.values() {
+            for (variant, base) in position_variants.iter() {
+                if *variant != Variant::None {
+                    assert!(base.depth[0] >= 5);
+                    assert_eq!(base.truedepth[0], 0);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_native_allele_depths() {
+        let params = PileupCallerParameters {
+            min_variant_depth: 5,
+            base_quality_threshold: 13,
+            mapq_threshold: 0,
+            min_allele_fraction: 0.05,
+        };
+        let bam_path = "tests/data/indels.bam";
+        let reference = "tests/data/single_genome.fna";
+        let variant_map = call_variants_from_bam(bam_path, reference, 1, 0, 10., 1, &params);
+        let header = bam::Reader::from_path(bam_path).unwrap().header().clone();
+
+        // Add the native calls as pileup_variants does, then count their reads with process_bam
+        let bam_generator = generate_named_bam_readers_from_bam_files(vec![bam_path]).remove(0);
+        let stoit_name = bam_generator.name().to_string().replace("/", ".");
+        let mut variant_matrix = VariantMatrix::new_matrix(1);
+        variant_matrix.add_sample(stoit_name, 0, &variant_map, &header);
+        let mut variant_matrix_map = HashMap::new();
+        variant_matrix_map.insert(0, variant_matrix);
+        let mut reference_map = HashMap::new();
+        reference_map.insert(0, reference.to_string());
+        let mut coverage_estimators = vec![
+            CoverageEstimator::new_estimator_length(),
+            CoverageEstimator::new_estimator_mean(0., 0, false),
+            CoverageEstimator::new_estimator_variance(0., 0),
+        ];
+        let flag_filters = FlagFilter {
+            include_improper_pairs: true,
+            include_secondary: false,
+            include_supplementary: false,
+        };
+        let m = clap::App::new("test").get_matches_from(vec!["test"]);
+        process_bam(bam_generator, 1, &mut coverage_estimators, &mut variant_matrix_map,
+                    &mut HashMap::new(), 1, &m, "", 1., &CodonTable::setup(), 5, 0,
+                    0., 1., 0., "summarize", false, true, &flag_filters, 0, "trimmed_mean",
+                    &HashMap::new(), &read_genome_fasta_files(&vec![reference]), &reference_map);
+
+        match &variant_matrix_map[&0] {
+            VariantMatrix::VariantContigMatrix {
+                all_variants,
+                ..
+            } => {
+                // Each allele depth is the number of reads carrying the allele, counted once
+                let contig_variants = &all_variants[&0];
+                assert_eq!(contig_variants[&504][&Variant::Deletion(2)].truedepth[0], 9);
+                assert_eq!(contig_variants[&519][&Variant::Insertion(b"ACCA".to_vec())].truedepth[0], 7);
+                assert_eq!(contig_variants[&492][&Variant::SNV(b'T')].truedepth[0], 12);
+                assert_eq!(contig_variants[&168][&Variant::SNV(b'C')].truedepth[0], 7);
+            }
+        }
+    }
+}
@@ -0,0 +1,506 @@
+use std;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::str;
+use rust_htslib::bam::{self, Read};
+use bio::alphabets::dna;
+
+use model::variants::*;
+use model::read_names::{ReadInterner, ReadSet};
+use utils::*;
+
+/// Inversions and duplications longer than this are more likely to come from misassembly or a
+/// chimeric read than from a real event, so split alignments further apart are not called
+pub const MAX_SV_SIZE: u32 = 100_000;
+
+/// Long read signatures of the same event disagree on its length by the indel error rate of
+/// the reads, so signatures within this ratio of each other's length are clustered together
+pub const SV_SIZE_SIMILARITY: f64 = 0.7;
+
+/// Parameters used by the native long read structural variant detector
+#[derive(Debug, Clone)]
+pub struct SVCallerParameters {
+    /// Minimum length of an insertion, deletion or inversion
+    pub min_sv_size: u32,
+    /// Maximum length of an inversion or duplication, longer events are ignored
+    pub max_sv_size: u32,
+    /// Maximum distance between breakpoints of two signatures in the same cluster
+    pub cluster_distance: i64,
+    /// Minimum ratio of the smaller to the larger length of two clustered signatures
+    pub size_similarity: f64,
+    /// Minimum number of distinct reads supporting a cluster
+    pub min_support: usize,
+    /// Minimum mapping quality for a read to be considered
+    pub mapq_threshold: u8,
+}
+
+impl SVCallerParameters {
+    pub fn generate_from_clap(m: &clap::ArgMatches) -> SVCallerParameters {
+        SVCallerParameters {
+            min_sv_size: m.value_of("min-sv-size").unwrap().parse().unwrap(),
+            max_sv_size: MAX_SV_SIZE,
+            cluster_distance: m.value_of("sv-cluster-distance").unwrap().parse().unwrap(),
+            size_similarity: SV_SIZE_SIMILARITY,
+            min_support: m.value_of("min-sv-support").unwrap().parse().unwrap(),
+            mapq_threshold: m.value_of("mapq-threshold").unwrap().parse().unwrap(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum SignatureType {
+    Insertion,
+    Deletion,
+    Inversion,
+    Duplication,
+}
+
+/// Evidence for a structural variant from a single read
+#[derive(Debug, Clone)]
+struct SVSignature {
+    sv_type: SignatureType,
+    tid: i32,
+    // 0-based anchor position of the event
+    start: i64,
+    // Length of the event on the reference, or of the inserted sequence
+    len: u32,
+    // Inserted sequence for insertions
+    sequence: Vec<u8>,
+    read: Vec<u8>,
+}
+
+/// One aligned segment of a read, in both reference and read coordinates
+#[derive(Debug, Clone, PartialEq)]
+struct AlignedSegment {
+    tid: i32,
+    ref_start: i64,
+    ref_end: i64,
+    // Coordinates in the original read orientation
+    query_start: i64,
+    query_end: i64,
+    reverse: bool,
+}
+
+impl AlignedSegment {
+    fn from_cigar(tid: i32, ref_start: i64, reverse: bool, cigar: &Vec<(char, u32)>) -> AlignedSegment {
+        let mut leading_clip = 0;
+        let mut trailing_clip = 0;
+        let mut ref_len = 0;
+        let mut query_len = 0;
+        for (idx, (op, len)) in cigar.iter().enumerate() {
+            match op {
+                'S' | 'H' => {
+                    if idx == 0 {
+                        leading_clip += *len as i64;
+                    } else {
+                        trailing_clip += *len as i64;
+                    }
+                },
+                'M' | '=' | 'X' => {
+                    ref_len += *len as i64;
+                    query_len += *len as i64;
+                },
+                'I' => query_len += *len as i64,
+                'D' | 'N' => ref_len += *len as i64,
+                _ => {},
+            }
+        }
+        let query_start = if reverse { trailing_clip } else { leading_clip };
+        AlignedSegment {
+            tid,
+            ref_start,
+            ref_end: ref_start + ref_len,
+            query_start,
+            query_end: query_start + query_len,
+            reverse,
+        }
+    }
+}
+
+/// Detect insertions, deletions, inversions and tandem duplications from large CIGAR operations
+/// and split alignments in a long read BAM file. Signatures are clustered across reads and
+/// returned as `Base` records carrying the names of their supporting reads, ready for
+/// `add_sample`.
+#[allow(unused)]
+pub fn call_structural_variants_from_bam(bam_path: &str,
+                                         reference: &str,
+                                         sample_count: usize,
+                                         sample_idx: usize,
+                                         threads: usize,
+                                         params: &SVCallerParameters,
+                                         read_names: &mut ReadInterner)
+    -> HashMap<i32, HashMap<i64, HashMap<Variant, Base>>> {
+
+    let mut bam_reader = bam::Reader::from_path(&Path::new(bam_path))
+        .expect(&format!("Unable to open BAM file {}", bam_path));
+    bam_reader.set_threads(threads).expect("Unable to set threads on BAM reader");
+    let header = bam_reader.header().clone();
+    let target_names = header.target_names();
+
+    let mut signatures: Vec<SVSignature> = Vec::new();
+    let mut record = bam::Record::new();
+    while bam_reader.read(&mut record).expect("Error while reading BAM record") {
+        if record.is_unmapped()
+            || record.is_secondary()
+            || record.is_supplementary()
+            || record.mapq() < params.mapq_threshold {
+            continue
+        }
+        signatures.extend(signatures_from_cigar(&record, params));
+        signatures.extend(signatures_from_split_alignment(&record, &header, params));
+    }
+    debug!("Found {} structural variant signatures in {}", signatures.len(), bam_path);
+
+    let mut reference_reader = match bio::io::fasta::IndexedReader::from_file(&Path::new(reference)) {
+        Ok(reader) => reader,
+        Err(_e) => generate_faidx(reference),
+    };
+
+    let mut variant_map = HashMap::new();
+    let clusters = cluster_signatures(signatures, params);
+    let mut ref_seqs: HashMap<i32, Vec<u8>> = HashMap::new();
+    for cluster in clusters.iter() {
+        let reads = cluster.iter()
+            .map(|signature| signature.read.clone()).collect::<HashSet<Vec<u8>>>();
+        if reads.len() < params.min_support {
+            continue
+        }
+        let tid = cluster[0].tid;
+        let ref_seq = ref_seqs.entry(tid).or_insert_with(|| {
+            let mut ref_seq = Vec::new();
+            reference_reader.fetch_all(str::from_utf8(target_names[tid as usize]).unwrap())
+                .expect("Cannot read sequence from reference");
+            reference_reader.read(&mut ref_seq)
+                .expect("Cannot read sequence from reference");
+            ref_seq
+        });
+
+        let pos = median(cluster.iter().map(|signature| signature.start).collect());
+        let len = median(cluster.iter().map(|signature| signature.len as i64).collect()) as u32;
+        if pos < 0 || pos as usize >= ref_seq.len() {
+            continue
+        }
+
+        let (variant, refr) = match cluster[0].sv_type {
+            SignatureType::Deletion => {
+                let end = std::cmp::min(pos as usize + len as usize + 1, ref_seq.len());
+                (Variant::Deletion(len), ref_seq[pos as usize..end].to_vec())
+            },
+            SignatureType::Insertion => {
+                // Use the inserted sequence closest to the consensus length
+                let sequence = cluster.iter()
+                    .min_by_key(|signature| (signature.len as i64 - len as i64).abs())
+                    .unwrap().sequence.clone();
+                (Variant::Insertion(sequence), vec![ref_seq[pos as usize]])
+            },
+            SignatureType::Inversion => {
+                let end = std::cmp::min(pos as usize + len as usize, ref_seq.len());
+                let refr = ref_seq[pos as usize..end].to_vec();
+                (Variant::Inversion(dna::revcomp(&refr)), refr)
+            },
+            SignatureType::Duplication => {
+                let end = std::cmp::min(pos as usize + len as usize, ref_seq.len());
+                let sv = SV {
+                    sv: SVType::DUP,
+                    len: (end - pos as usize) as u32,
+                    start: pos as u32,
+                    end: end as u32,
+                };
+                (Variant::SV(sv), ref_seq[pos as usize..end].to_vec())
+            },
+        };
+
+        let mut base = Base::new(tid as u32, pos, sample_count, refr.clone());
+        base.variant = variant;
+        base.quals[sample_idx] = reads.len() as f32;
+        base.depth[sample_idx] = reads.len() as i32;
+        base.truedepth[sample_idx] = reads.len() as i32;
+        base.filters[sample_idx].insert(Filter::PASS);
+        base.reads = ReadSet::from_names(sample_idx, reads.iter(), read_names);
+
+        let variant_con = variant_map.entry(tid).or_insert(HashMap::new());
+        let variant_pos = variant_con.entry(pos).or_insert(HashMap::new());
+        variant_pos.entry(Variant::None)
+            .or_insert(Base::new(tid as u32, pos, sample_count, refr));
+        variant_pos.entry(base.variant.to_owned()).or_insert(base);
+    }
+
+    variant_map
+}
+
+/// Insertions and deletions contained within a single alignment
+fn signatures_from_cigar(record: &bam::Record, params: &SVCallerParameters) -> Vec<SVSignature> {
+    let mut signatures = Vec::new();
+    let mut cursor = record.pos() as i64;
+    let mut read_cursor = 0 as usize;
+    let seq = record.seq().as_bytes();
+    for (op, len) in parse_cigar(&record.cigar().to_string()).iter() {
+        match op {
+            'M' | '=' | 'X' => {
+                cursor += *len as i64;
+                read_cursor += *len as usize;
+            },
+            'D' => {
+                if *len >= params.min_sv_size {
+                    signatures.push(SVSignature {
+                        sv_type: SignatureType::Deletion,
+                        tid: record.tid(),
+                        start: cursor - 1,
+                        len: *len,
+                        sequence: Vec::new(),
+                        read: record.qname().to_vec(),
+                    });
+                }
+                cursor += *len as i64;
+            },
+            'N' => cursor += *len as i64,
+            'I' => {
+                if *len >= params.min_sv_size {
+                    let end = std::cmp::min(read_cursor + *len as usize, seq.len());
+                    signatures.push(SVSignature {
+                        sv_type: SignatureType::Insertion,
+                        tid: record.tid(),
+                        start: cursor - 1,
+                        len: *len,
+                        sequence: seq[read_cursor..end].to_vec(),
+                        read: record.qname().to_vec(),
+                    });
+                }
+                read_cursor += *len as usize;
+            },
+            'S' => read_cursor += *len as usize,
+            _ => {},
+        }
+    }
+    signatures
+}
+
+/// Deletions, tandem duplications and inversions spanning the primary alignment and its
+/// supplementary alignments
+fn signatures_from_split_alignment(record: &bam::Record,
+                                   header: &bam::HeaderView,
+                                   params: &SVCallerParameters) -> Vec<SVSignature> {
+    let mut signatures = Vec::new();
+    let supplementary = match record.aux(b"SA") {
+        Some(aux) => parse_sa_tag(aux.string(), header),
+        None => return signatures,
+    };
+    let primary = AlignedSegment::from_cigar(record.tid(), record.pos() as i64, record.is_reverse(),
+                                             &parse_cigar(&record.cigar().to_string()));
+
+    for segment in supplementary.iter() {
+        if segment.tid != primary.tid {
+            continue
+        }
+        // Order the two segments by their position within the read
+        let (first, second) = if primary.query_start <= segment.query_start {
+            (&primary, segment)
+        } else {
+            (segment, &primary)
+        };
+
+        if first.reverse == second.reverse {
+            // Read continues further along the reference than its sequence accounts for
+            let (left, right) = if first.reverse { (second, first) } else { (first, second) };
+            let ref_gap = right.ref_start - left.ref_end;
+            let read_gap = std::cmp::max(second.query_start - first.query_end, 0);
+            if ref_gap - read_gap >= params.min_sv_size as i64 && read_gap < params.min_sv_size as i64 {
+                signatures.push(SVSignature {
+                    sv_type: SignatureType::Deletion,
+                    tid: primary.tid,
+                    start: left.ref_end - 1,
+                    len: (ref_gap - read_gap) as u32,
+                    sequence: Vec::new(),
+                    read: record.qname().to_vec(),
+                });
+            } else if -ref_gap >= params.min_sv_size as i64 && -ref_gap <= params.max_sv_size as i64
+                && read_gap < params.min_sv_size as i64 {
+                // Read returns to reference it has already covered, repeating the overlap
+                signatures.push(SVSignature {
+                    sv_type: SignatureType::Duplication,
+                    tid: primary.tid,
+                    start: right.ref_start,
+                    len: -ref_gap as u32,
+                    sequence: Vec::new(),
+                    read: record.qname().to_vec(),
+                });
+            }
+        } else {
+            // Segments on opposite strands, the supplementary segment is inverted
+            let (start, end) = (segment.ref_start, segment.ref_end);
+            let len = (end - start) as u32;
+            if len >= params.min_sv_size && len <= params.max_sv_size {
+                signatures.push(SVSignature {
+                    sv_type: SignatureType::Inversion,
+                    tid: primary.tid,
+                    start,
+                    len,
+                    sequence: Vec::new(),
+                    read: record.qname().to_vec(),
+                });
+            }
+        }
+    }
+    signatures
+}
+
+/// Greedily cluster signatures of the same type whose positions and sizes are similar
+fn cluster_signatures(mut signatures: Vec<SVSignature>, params: &SVCallerParameters) -> Vec<Vec<SVSignature>> {
+    signatures.sort_by_key(|signature| (signature.tid, signature.sv_type as u8, signature.start));
+
+    let mut clusters: Vec<Vec<SVSignature>> = Vec::new();
+    for signature in signatures.into_iter() {
+        let joined = match clusters.last_mut() {
+            Some(cluster) => {
+                let last = cluster.last().unwrap();
+                let shortest = std::cmp::min(last.len, signature.len) as f64;
+                let longest = std::cmp::max(last.len, signature.len) as f64;
+                if last.tid == signature.tid
+                    && last.sv_type == signature.sv_type
+                    && signature.start - last.start <= params.cluster_distance
+                    && shortest / longest >= params.size_similarity {
+                    cluster.push(signature.clone());
+                    true
+                } else {
+                    false
+                }
+            },
+            None => false,
+        };
+        if !joined {
+            clusters.push(vec![signature]);
+        }
+    }
+    clusters
+}
+
+/// Parse the SA auxiliary tag into the aligned segments it describes
+fn parse_sa_tag(sa: &[u8], header: &bam::HeaderView) -> Vec<AlignedSegment> {
+    str::from_utf8(sa).unwrap_or("").split(';').filter(|entry| !entry.is_empty())
+        .filter_map(|entry| {
+            let fields = entry.split(',').collect::<Vec<&str>>();
+            if fields.len() < 6 {
+                return None
+            }
+            let tid = header.tid(fields[0].as_bytes())? as i32;
+            let pos = fields[1].parse::<i64>().ok()? - 1;
+            let reverse = fields[2] == "-";
+            Some(AlignedSegment::from_cigar(tid, pos, reverse, &parse_cigar(fields[3])))
+        }).collect()
+}
+
+/// Parse a textual CIGAR string into operation and length pairs
+fn parse_cigar(cigar: &str) -> Vec<(char, u32)> {
+    let mut operations = Vec::new();
+    let mut len = 0;
+    for c in cigar.chars() {
+        if c.is_ascii_digit() {
+            len = len * 10 + c.to_digit(10).unwrap();
+        } else {
+            operations.push((c, len));
+            len = 0;
+        }
+    }
+    operations
+}
+
+fn median(mut values: Vec<i64>) -> i64 {
+    values.sort();
+    values[values.len() / 2]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signature(sv_type: SignatureType, start: i64, len: u32, read: &[u8]) -> SVSignature {
+        SVSignature {
+            sv_type,
+            tid: 0,
+            start,
+            len,
+            sequence: Vec::new(),
+            read: read.to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_parse_cigar() {
+        assert_eq!(parse_cigar("10S100M50D20M5I"),
+                   vec![('S', 10), ('M', 100), ('D', 50), ('M', 20), ('I', 5)]);
+    }
+
+    #[test]
+    fn test_segment_coordinates() {
+        let cigar = parse_cigar("10S100M50D20M5S");
+        let forward = AlignedSegment::from_cigar(0, 1000, false, &cigar);
+        assert_eq!(forward.ref_end, 1170);
+        assert_eq!(forward.query_start, 10);
+        assert_eq!(forward.query_end, 130);
+
+        let reverse = AlignedSegment::from_cigar(0, 1000, true, &cigar);
+        assert_eq!(reverse.query_start, 5);
+        assert_eq!(reverse.query_end, 125);
+    }
+
+    #[test]
+    fn test_cluster_signatures() {
+        let params = SVCallerParameters {
+            min_sv_size: 40,
+            max_sv_size: MAX_SV_SIZE,
+            cluster_distance: 500,
+            size_similarity: SV_SIZE_SIMILARITY,
+            min_support: 2,
+            mapq_threshold: 0,
+        };
+        let signatures = vec![
+            signature(SignatureType::Deletion, 1000, 100, b"read1"),
+            signature(SignatureType::Deletion, 1010, 95, b"read2"),
+            signature(SignatureType::Deletion, 1020, 300, b"read3"),
+            signature(SignatureType::Insertion, 1000, 100, b"read4"),
+            signature(SignatureType::Deletion, 5000, 100, b"read5"),
+        ];
+        let clusters = cluster_signatures(signatures, &params);
+        assert_eq!(clusters.len(), 4);
+        assert_eq!(clusters.iter().map(|cluster| cluster.len()).max().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_split_read_structural_variants() {
+        // One read on each strand is split around an inversion of 400-700 and a tandem
+        // duplication of 1200-1500
+        let params = SVCallerParameters {
+            min_sv_size: 40,
+            max_sv_size: MAX_SV_SIZE,
+            cluster_distance: 500,
+            size_similarity: SV_SIZE_SIMILARITY,
+            min_support: 2,
+            mapq_threshold: 0,
+        };
+        let mut read_names = ReadInterner::new();
+        let variant_map = call_structural_variants_from_bam("tests/data/sv_split_reads.bam",
+                                                            "tests/data/sv_reference.fna",
+                                                            1, 0, 1, &params, &mut read_names);
+        let contig_variants = &variant_map[&0];
+        assert_eq!(contig_variants.len(), 2);
+
+        let inversion = contig_variants[&400].values()
+            .find(|base| base.variant != Variant::None).unwrap();
+        assert_eq!(inversion.refr.len(), 300);
+        assert_eq!(inversion.variant, Variant::Inversion(dna::revcomp(&inversion.refr)));
+        assert_eq!(inversion.truedepth[0], 2);
+        let mut names = inversion.reads.names(&read_names);
+        names.sort();
+        assert_eq!(names, vec![b"inversion_forward".to_vec(), b"inversion_reverse".to_vec()]);
+
+        let duplication = contig_variants[&1200].values()
+            .find(|base| base.variant != Variant::None).unwrap();
+        assert_eq!(duplication.refr.len(), 300);
+        assert_eq!(duplication.variant,
+                   Variant::SV(SV { sv: SVType::DUP, len: 300, start: 1200, end: 1500 }));
+        assert_eq!(duplication.truedepth[0], 2);
+        let mut names = duplication.reads.names(&read_names);
+        names.sort();
+        assert_eq!(names, vec![b"duplication_forward".to_vec(), b"duplication_reverse".to_vec()]);
+    }
+}
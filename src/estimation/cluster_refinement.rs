@@ -0,0 +1,327 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use model::variants::*;
+use model::read_names::*;
+use dbscan::fuzzy::{self, Assignment, Category, Cluster};
+use estimation::linkage::scaled_jaccard;
+
+/// Parameters for refining clusters by read linkage after clustering
+#[derive(Debug, Clone)]
+pub struct ClusterRefinementParameters {
+    /// Minimum scaled Jaccard similarity of two clusters' reads for them to be merged
+    pub merge_similarity: f64,
+    /// Minimum number of shared reads needed to link clusters or noise variants
+    pub minimum_reads_in_link: usize,
+}
+
+impl ClusterRefinementParameters {
+    pub fn generate_from_clap(m: &clap::ArgMatches) -> ClusterRefinementParameters {
+        ClusterRefinementParameters {
+            merge_similarity: m.value_of("cluster-merge-similarity").unwrap().parse().unwrap(),
+            minimum_reads_in_link: m.value_of("minimum-reads-in-link").unwrap().parse().unwrap(),
+        }
+    }
+}
+
+/// Refines clusters using the reads shared between variants. Clusters carrying different alleles
+/// at the same site are split, strongly read linked clusters are merged and unclustered
+/// variants join the cluster they share the most reads with
+pub fn refine_clusters(clusters: Vec<Cluster>,
+                       variant_info: &Vec<fuzzy::Var>,
+                       params: &ClusterRefinementParameters) -> Vec<Cluster> {
+    let mut refined = Vec::new();
+    for (cluster_idx, cluster) in clusters.into_iter().enumerate() {
+        let parts = split_conflicting_alleles(cluster, variant_info);
+        if parts.len() > 1 {
+            info!("Split cluster {} into {} clusters with sizes {:?} as it had conflicting alleles",
+                  cluster_idx + 1, parts.len(), parts.iter().map(|part| part.len()).collect::<Vec<usize>>());
+        }
+        refined.extend(parts);
+    }
+
+    merge_read_linked_clusters(&mut refined, variant_info, params);
+    reassign_noise(&mut refined, variant_info, params);
+    refined
+}
+
+/// Keeps the max_clusters largest clusters. The variants of the removed clusters become noise
+/// and, if refinement parameters are given, are reassigned to the remaining clusters by read
+/// linkage
+pub fn constrain_cluster_count(mut clusters: Vec<Cluster>,
+                               max_clusters: usize,
+                               variant_info: &Vec<fuzzy::Var>,
+                               refinement: Option<&ClusterRefinementParameters>) -> Vec<Cluster> {
+    let max_clusters = std::cmp::max(max_clusters, 1);
+    if clusters.len() <= max_clusters {
+        return clusters
+    }
+    info!("Reducing {} clusters to the {} largest to match the estimated strain count",
+          clusters.len(), max_clusters);
+    // Stable sort so that equally sized clusters keep their order
+    clusters.sort_by(|a, b| b.len().cmp(&a.len()));
+    let removed = clusters.split_off(max_clusters);
+    debug!("Removed clusters with sizes {:?}", removed.iter().map(|cluster| cluster.len()).collect::<Vec<usize>>());
+    if let Some(params) = refinement {
+        reassign_noise(&mut clusters, variant_info, params);
+    }
+    clusters
+}
+
+/// The reads supporting any variant in the cluster
+fn cluster_reads(cluster: &Cluster, variant_info: &Vec<fuzzy::Var>) -> ReadSet {
+    let mut reads = ReadSet::new();
+    for assignment in cluster.iter() {
+        reads.extend(&variant_info[assignment.index].reads);
+    }
+    reads
+}
+
+/// The alleles in a cluster at each site
+fn cluster_sites(cluster: &Cluster, variant_info: &Vec<fuzzy::Var>)
+    -> BTreeMap<(i32, i64), HashSet<Variant>> {
+    let mut sites = BTreeMap::new();
+    for assignment in cluster.iter() {
+        let var = &variant_info[assignment.index];
+        sites.entry((var.tid, var.pos)).or_insert(HashSet::new()).insert(var.var.clone());
+    }
+    sites
+}
+
+/// Splits a cluster at the first site where it has more than one allele. The variants of each
+/// allele seed a new cluster and every other variant joins the seed it shares the most reads
+/// with, or the largest seed when it shares none. Each part is split again until no conflicts
+/// remain
+fn split_conflicting_alleles(cluster: Cluster, variant_info: &Vec<fuzzy::Var>) -> Vec<Cluster> {
+    let conflict = cluster_sites(&cluster, variant_info).into_iter()
+        .find(|(_, alleles)| alleles.len() > 1).map(|(site, _)| site);
+    let site = match conflict {
+        Some(site) => site,
+        None => return vec![cluster],
+    };
+
+    let mut alleles: Vec<Variant> = Vec::new();
+    let mut parts: Vec<Cluster> = Vec::new();
+    let mut others = Vec::new();
+    for assignment in cluster.into_iter() {
+        let var = &variant_info[assignment.index];
+        if (var.tid, var.pos) == site {
+            match alleles.iter().position(|allele| allele == &var.var) {
+                Some(idx) => parts[idx].push(assignment),
+                None => {
+                    alleles.push(var.var.clone());
+                    parts.push(vec![assignment]);
+                }
+            }
+        } else {
+            others.push(assignment);
+        }
+    }
+    debug!("Splitting cluster at {:?} between alleles {:?}", site, alleles);
+
+    let part_reads = parts.iter().map(|part| cluster_reads(part, variant_info)).collect::<Vec<ReadSet>>();
+    let mut sizes = parts.iter().map(|part| part.len()).collect::<Vec<usize>>();
+    for assignment in others.into_iter() {
+        let reads = &variant_info[assignment.index].reads;
+        let shared = part_reads.iter().map(|part| reads.intersection_count(part)).collect::<Vec<usize>>();
+        let best_shared = *shared.iter().max().unwrap();
+        let best = if best_shared > 0 {
+            shared.iter().position(|count| *count == best_shared).unwrap()
+        } else {
+            let largest = *sizes.iter().max().unwrap();
+            sizes.iter().position(|size| *size == largest).unwrap()
+        };
+        sizes[best] += 1;
+        parts[best].push(assignment);
+    }
+
+    parts.into_iter().flat_map(|part| split_conflicting_alleles(part, variant_info)).collect()
+}
+
+/// Whether two clusters would have different alleles at any site if they were merged
+fn conflicting(cluster_1: &Cluster, cluster_2: &Cluster, variant_info: &Vec<fuzzy::Var>) -> bool {
+    let sites = cluster_sites(cluster_1, variant_info);
+    cluster_2.iter().any(|assignment| {
+        let var = &variant_info[assignment.index];
+        match sites.get(&(var.tid, var.pos)) {
+            Some(alleles) => !alleles.contains(&var.var),
+            None => false,
+        }
+    })
+}
+
+/// Repeatedly merges the pair of clusters with the most similar read sets until no pair is
+/// similar enough, skipping pairs that would create conflicting alleles
+fn merge_read_linked_clusters(clusters: &mut Vec<Cluster>,
+                              variant_info: &Vec<fuzzy::Var>,
+                              params: &ClusterRefinementParameters) {
+    loop {
+        let reads = clusters.iter().map(|cluster| cluster_reads(cluster, variant_info))
+            .collect::<Vec<ReadSet>>();
+        let mut best: Option<(usize, usize, f64)> = None;
+        for i in 0..clusters.len() {
+            for j in (i + 1)..clusters.len() {
+                if reads[i].intersection_count(&reads[j]) < std::cmp::max(params.minimum_reads_in_link, 1) {
+                    continue
+                }
+                let similarity = scaled_jaccard(&reads[i], &reads[j]);
+                if similarity >= params.merge_similarity
+                    && best.map(|(_, _, best_similarity)| similarity > best_similarity).unwrap_or(true)
+                    && !conflicting(&clusters[i], &clusters[j], variant_info) {
+                    best = Some((i, j, similarity));
+                }
+            }
+        }
+
+        match best {
+            Some((i, j, similarity)) => {
+                info!("Merging clusters {} and {} with sizes {} and {} sharing reads with similarity {:.3}",
+                      i + 1, j + 1, clusters[i].len(), clusters[j].len(), similarity);
+                let merged = clusters.remove(j);
+                let mut positions: HashMap<usize, usize> = clusters[i].iter().enumerate()
+                    .map(|(position, assignment)| (assignment.index, position)).collect();
+                for assignment in merged.into_iter() {
+                    match positions.get(&assignment.index) {
+                        // Keep the stronger membership of variants present in both clusters
+                        Some(position) => {
+                            let existing = &mut clusters[i][*position];
+                            if assignment.label > existing.label {
+                                *existing = assignment;
+                            }
+                        },
+                        None => {
+                            positions.insert(assignment.index, clusters[i].len());
+                            clusters[i].push(assignment);
+                        }
+                    }
+                }
+            },
+            None => break,
+        }
+    }
+}
+
+/// Adds each unclustered variant to the cluster it shares the most reads with as a border point.
+/// Its label is the fraction of its reads shared with the cluster
+fn reassign_noise(clusters: &mut Vec<Cluster>,
+                  variant_info: &Vec<fuzzy::Var>,
+                  params: &ClusterRefinementParameters) {
+    let clustered = clusters.iter()
+        .flat_map(|cluster| cluster.iter().map(|assignment| assignment.index))
+        .collect::<HashSet<usize>>();
+    let reads = clusters.iter().map(|cluster| cluster_reads(cluster, variant_info))
+        .collect::<Vec<ReadSet>>();
+    let sites = clusters.iter().map(|cluster| cluster_sites(cluster, variant_info))
+        .collect::<Vec<BTreeMap<(i32, i64), HashSet<Variant>>>>();
+
+    let mut reassigned = 0;
+    for (index, var) in variant_info.iter().enumerate() {
+        if clustered.contains(&index) || var.reads.len() == 0 {
+            continue
+        }
+        let mut best: Option<(usize, usize)> = None;
+        for (cluster_idx, cluster_reads) in reads.iter().enumerate() {
+            let compatible = match sites[cluster_idx].get(&(var.tid, var.pos)) {
+                Some(alleles) => alleles.contains(&var.var),
+                None => true,
+            };
+            let shared = var.reads.intersection_count(cluster_reads);
+            if compatible && shared >= std::cmp::max(params.minimum_reads_in_link, 1)
+                && best.map(|(_, best_shared)| shared > best_shared).unwrap_or(true) {
+                best = Some((cluster_idx, shared));
+            }
+        }
+        if let Some((cluster_idx, shared)) = best {
+            debug!("Reassigning noise variant {:?} at {}:{} to cluster {} sharing {} reads",
+                   var.var, var.tid, var.pos, cluster_idx + 1, shared);
+            clusters[cluster_idx].push(Assignment {
+                index,
+                label: shared as f64 / var.reads.len() as f64,
+                category: Category::Border,
+            });
+            reassigned += 1;
+        }
+    }
+    if reassigned > 0 {
+        info!("Reassigned {} noise variants to read linked clusters", reassigned);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_var(pos: i64, alt: u8, reads: &[&[u8]], read_names: &mut ReadInterner) -> fuzzy::Var {
+        fuzzy::Var {
+            pos,
+            var: Variant::SNV(alt),
+            deps: vec![10, 10],
+            vars: vec![5, 5],
+            tid: 0,
+            reads: ReadSet::from_names(0, reads.iter(), read_names),
+        }
+    }
+
+    fn core(index: usize) -> Assignment {
+        Assignment { index, label: 1., category: Category::Core }
+    }
+
+    fn indices(cluster: &Cluster) -> Vec<usize> {
+        let mut indices = cluster.iter().map(|assignment| assignment.index).collect::<Vec<usize>>();
+        indices.sort();
+        indices
+    }
+
+    #[test]
+    fn test_refine_clusters() {
+        let mut read_names = ReadInterner::new();
+        let variant_info = vec![
+            // Two alleles at position 10, each linked to a different variant
+            create_var(10, b'A', &[b"refine_1", b"refine_2"], &mut read_names),
+            create_var(10, b'T', &[b"refine_3", b"refine_4"], &mut read_names),
+            create_var(20, b'G', &[b"refine_1", b"refine_2"], &mut read_names),
+            create_var(30, b'C', &[b"refine_3", b"refine_4"], &mut read_names),
+            // A separate cluster sharing all of its reads with variant 3
+            create_var(40, b'G', &[b"refine_3", b"refine_4"], &mut read_names),
+            // Noise linked to variant 2
+            create_var(50, b'C', &[b"refine_1"], &mut read_names),
+        ];
+        let clusters = vec![vec![core(0), core(1), core(2), core(3)], vec![core(4)]];
+        let params = ClusterRefinementParameters {
+            merge_similarity: 0.8,
+            minimum_reads_in_link: 1,
+        };
+
+        let refined = refine_clusters(clusters, &variant_info, &params);
+        let mut refined_indices = refined.iter().map(|cluster| indices(cluster)).collect::<Vec<Vec<usize>>>();
+        refined_indices.sort();
+        assert_eq!(refined_indices, vec![vec![0, 2, 5], vec![1, 3, 4]]);
+
+        let noise = refined.iter().flat_map(|cluster| cluster.iter())
+            .find(|assignment| assignment.index == 5).unwrap();
+        assert_eq!(noise.category, Category::Border);
+        assert_eq!(noise.label, 1.);
+    }
+
+    #[test]
+    fn test_constrain_cluster_count() {
+        let mut read_names = ReadInterner::new();
+        let variant_info = vec![
+            create_var(10, b'A', &[b"constrain_1", b"constrain_2"], &mut read_names),
+            create_var(20, b'G', &[b"constrain_1", b"constrain_2"], &mut read_names),
+            create_var(30, b'C', &[b"constrain_1"], &mut read_names),
+        ];
+        let params = ClusterRefinementParameters {
+            merge_similarity: 0.8,
+            minimum_reads_in_link: 1,
+        };
+        let clusters = vec![vec![core(2)], vec![core(0), core(1)]];
+
+        let constrained = constrain_cluster_count(clusters.clone(), 1, &variant_info, None);
+        assert_eq!(constrained.iter().map(|cluster| indices(cluster)).collect::<Vec<Vec<usize>>>(),
+                   vec![vec![0, 1]]);
+
+        let constrained = constrain_cluster_count(clusters, 1, &variant_info, Some(&params));
+        assert_eq!(constrained.iter().map(|cluster| indices(cluster)).collect::<Vec<Vec<usize>>>(),
+                   vec![vec![0, 1, 2]]);
+    }
+}
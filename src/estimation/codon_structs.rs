@@ -1,411 +1,1209 @@
-use std::collections::{HashMap};
-use itertools::{izip, Itertools};
-use bio::alphabets::dna;
-use bio_types::strand;
-use model::variants::{Variant, Base};
-
-#[allow(dead_code)]
-pub struct GeneInfo {
-    name: String,
-    locations: HashMap<u32, (u64, u64)>,
-    coverages: HashMap<u32, f64>,
-}
-
-pub struct CodonTable {
-    aminos: HashMap<Vec<u8>, char>,
-    starts: HashMap<Vec<u8>, char>,
-    ns_sites: HashMap<Vec<u8>, f64>,
-}
-
-
-pub struct NCBITable {
-    aas: String,
-    starts: String,
-    base1: String,
-    base2: String,
-    base3: String,
-}
-
-
-impl NCBITable {
-    // get translation tables in NCBI format
-    // Kind of lazy storing and then converting every time but would take way too much time
-    // to write out each table into CodonTable format by hand
-    fn get_translation_table(table_id: usize) -> NCBITable {
-        match table_id {
-            1 => {
-                NCBITable {
-                    aas: "FFLLSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG".to_owned(),
-                    starts: "---M------**--*----M---------------M----------------------------".to_owned(),
-                    base1: "TTTTTTTTTTTTTTTTCCCCCCCCCCCCCCCCAAAAAAAAAAAAAAAAGGGGGGGGGGGGGGGG".to_owned(),
-                    base2: "TTTTCCCCAAAAGGGGTTTTCCCCAAAAGGGGTTTTCCCCAAAAGGGGTTTTCCCCAAAAGGGG".to_owned(),
-                    base3: "TCAGTCAGTCAGTCAGTCAGTCAGTCAGTCAGTCAGTCAGTCAGTCAGTCAGTCAGTCAGTCAG".to_owned(),
-                }
-            },
-            11 => {
-                NCBITable {
-                    aas: "FFLLSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG".to_owned(),
-                    starts: "---M------**--*----M------------MMMM---------------M------------".to_owned(),
-                    base1: "TTTTTTTTTTTTTTTTCCCCCCCCCCCCCCCCAAAAAAAAAAAAAAAAGGGGGGGGGGGGGGGG".to_owned(),
-                    base2: "TTTTCCCCAAAAGGGGTTTTCCCCAAAAGGGGTTTTCCCCAAAAGGGGTTTTCCCCAAAAGGGG".to_owned(),
-                    base3: "TCAGTCAGTCAGTCAGTCAGTCAGTCAGTCAGTCAGTCAGTCAGTCAGTCAGTCAGTCAGTCAG".to_owned(),
-                }
-            },
-            _ => {
-                panic!("Translation table {} not yet implemented", table_id);
-            },
-        }
-    }
-}
-
-impl CodonTable {
-    pub fn setup() -> CodonTable {
-        CodonTable {
-            aminos: HashMap::new(),
-            starts: HashMap::new(),
-            ns_sites: HashMap::new(),
-        }
-    }
-}
-
-pub trait Translations {
-    fn get_codon_table(&mut self, table_id: usize);
-    fn find_mutations(&self,
-                      gene: &bio::io::gff::Record,
-                      variants: &HashMap<i64, HashMap<Variant, Base>>,
-                      ref_sequence: &Vec<u8>) -> f64;
-}
-
-impl Translations for CodonTable {
-    #[allow(unused)]
-    fn get_codon_table(&mut self, table_id: usize) {
-        // Convert NCBI format to something more usable
-        let ncbi_format = NCBITable::get_translation_table(table_id);
-        let mut amino_hash = HashMap::new();
-        let mut start_hash = HashMap::new();
-        for (aa, s, b1, b2, b3) in izip!(ncbi_format.aas.as_bytes(), ncbi_format.starts.as_bytes(),
-                                        ncbi_format.base1.as_bytes(), ncbi_format.base2.as_bytes(),
-                                        ncbi_format.base3.as_bytes()) {
-            let codon = vec!(*b1, *b2, *b3);
-            amino_hash.insert(codon.clone(), *aa as char);
-            start_hash.insert(codon, *s as char);
-        }
-        debug!("Amino hash {:?}", amino_hash);
-        self.aminos = amino_hash;
-        self.starts = start_hash;
-
-        let nucleotides: Vec<u8> = vec!(65, 84, 67, 71);
-        debug!("nucleotides: {} ", String::from_utf8_lossy(&nucleotides));
-        for (codon, _aa) in self.aminos.iter(){
-            let mut n = 0.0;
-            for (pos, cod) in codon.iter().enumerate() {
-                for nuc in nucleotides.iter() {
-                    if cod == nuc {
-                        // Ignore this nucleotide
-                        continue
-                    } else {
-                        let mut codon_shift = codon.clone();
-                        // Change one position of the codon
-                        codon_shift[pos] = nuc.clone();
-
-                        if self.aminos[codon] != self.aminos[&codon_shift] {
-                            // This change can cause a non-synonymous mutation
-                            n += 1.0/3.0;
-                        }
-                    }
-                }
-            }
-            self.ns_sites.insert(codon.clone(), n as f64);
-        }
-    }
-
-    #[allow(unused)]
-    fn find_mutations(&self,
-                      gene: &bio::io::gff::Record,
-                      variants: &HashMap<i64, HashMap<Variant, Base>>,
-                      ref_sequence: &Vec<u8>) -> f64 {
-        match gene.strand() {
-            Some(strand) => {
-
-                // bio::gff documentation says start and end positions are 1-based, so we minus 1
-                // Additionally, end position is non-inclusive
-                let start = gene.start().clone() as usize - 1;
-                let end = gene.end().clone() as usize;
-                let frame: usize = match gene.frame().parse() {
-                    Ok(frame_val) => frame_val,
-                    Err(_) => return 0.
-                };
-                let gene_sequence = ref_sequence[start..end].to_vec();
-                debug!("Gene Seq {:?}", String::from_utf8_lossy(&gene_sequence));
-                let codon_sequence = get_codons(&gene_sequence, frame, strand);
-                debug!("Codon Sequence {:?}", codon_sequence);
-
-                // Calculate N and S
-                let mut big_n: f64 = 0.0;
-                let mut big_s: f64 = 0.0;
-                for codon in codon_sequence.iter() {
-                    if String::from_utf8(codon.clone()).expect("Unable to interpret codon")
-                        .contains("N") || codon.len() != 3 {
-                        continue
-                    } else {
-                        let n = self.ns_sites[codon];
-                        big_n += n;
-                        big_s += 3.0 - n;
-                    }
-                }
-
-                debug!("getting ns_sites N {} S {}", big_n, big_s);
-
-                // Create Nd and Sd values
-                let mut big_nd: f64 = 0.0;
-                let mut big_sd: f64 = 0.0;
-
-                // dN/dS calculations when using NGS reads outlined here:
-                // http://bioinformatics.cvr.ac.uk/blog/calculating-dnds-for-ngs-datasets/
-                // Note, we don't normalize for depth here and instead just use Jukes-Cantor model
-                let mut codon: Vec<u8> = vec!();
-                let mut new_codons: Vec<Vec<u8>> = vec!();
-                let mut positionals = 0;
-                let mut total_variants = 0;
-                let dummy = HashMap::new();
-                for (gene_cursor, cursor) in (start..end).into_iter().enumerate() {
-                    let variant_set = match variants.get(&(cursor as i64)) {
-                        Some(map) => map,
-                        None => &dummy,
-                    };
-
-                    let codon_idx = gene_cursor / 3 as usize;
-                    let codon_cursor = gene_cursor % 3;
-                    if String::from_utf8(codon.clone())
-                        .expect("Unable to interpret codon").contains("N") {
-                        continue
-                    }
-
-                    if codon_cursor == 0
-                        || new_codons.len() == 0 {
-                        for new_codon in new_codons.iter_mut() {
-                            if (codon.len() == 3)
-                                && (new_codon.len() == 3)
-                                && (codon != *new_codon) {
-                                // get indices of different locations
-                                let mut pos = 0 as usize;
-                                let mut diffs = vec!();
-                                for (c1, c2) in codon.iter().zip(new_codon.iter()) {
-                                    if c1 != c2 {
-                                        diffs.push(pos);
-                                    }
-                                    pos += 1;
-                                }
-                                total_variants += diffs.len();
-                                // get permuations of positions
-                                let permutations: Vec<Vec<usize>> = diffs.iter().cloned().permutations(diffs.len()).collect();
-
-                                // calculate synonymous and non-synonymous for each permutation
-                                let mut ns = 0;
-                                let mut ss = 0;
-                                debug!("positional difference {:?} permutations {:?}", diffs, permutations.len());
-                                positionals += permutations.len();
-                                for permutation in permutations.iter() {
-                                    let mut shifting = codon.clone();
-                                    let mut old_shift;
-                                    for pos in permutation {
-                                        // Check if one amino acid change causes an syn or non-syn
-                                        old_shift = shifting.clone();
-                                        shifting[*pos] = new_codon[*pos];
-                                        debug!("Old shift {:?}, new {:?}", old_shift, shifting);
-                                        if self.aminos[&old_shift] != self.aminos[&shifting] {
-                                            ns += 1;
-                                        } else {
-                                            ss += 1;
-                                        }
-                                    }
-                                }
-                                let nd = ns as f64 / permutations.len() as f64;
-                                let sd = ss as f64 / permutations.len() as f64;
-                                big_nd += nd;
-                                big_sd += sd;
-                            }
-                        }
-                        // begin working on new codon
-                        debug!("Codon idx {} codonds {} gene length {} new_codons {:?}",
-                               codon_idx, codon_sequence.len(), gene_sequence.len(), new_codons);
-                        if codon_sequence.len() == 268 {
-                            debug!("{:?}", codon_sequence);
-                        }
-                        codon = codon_sequence[codon_idx].clone();
-                        if codon.len() != 3 {
-                            continue
-                        }
-                        new_codons = Vec::new();
-                        new_codons.push(codon.clone());
-                    }
-                    if variant_set.len() > 0 {
-                        debug!("variant map {:?}", variant_set);
-                        let mut variant_count = 0;
-
-                        for (variant, base_info) in variant_set.iter() {
-                            match variant {
-                                Variant::SNV(var) => {
-                                    if variant_count > 0 {
-                                        // Create a copy of codon up to this point
-                                        // Not sure if reusing previous variants is bad, but
-                                        // not doing so can cause randomness to dN/dS values
-
-                                        new_codons.push(codon.clone());
-
-                                        new_codons[variant_count][codon_cursor] = *var;
-
-                                        debug!("multi variant codon {:?}", new_codons);
-                                    } else {
-                                        for var_idx in 0..new_codons.len() {
-                                            new_codons[var_idx][codon_cursor] = *var;
-                                        }
-                                    }
-                                    variant_count += 1;
-                                },
-                                _ => {
-                                    // Frameshift mutations are not included in dN/dS calculations?
-                                    // Seems weird, but all formulas say no
-                                    debug!("Frameshift mutation variant {:?}", variant);
-                                    continue
-                                },
-                            }
-                        }
-                    }
-                }
-
-                debug!("Nd {} N {}, Sd {} S {} total permutations {} variants {}",
-                       big_nd, big_n, big_sd, big_s, positionals, total_variants);
-                let mut pn = big_nd / big_n;
-                let mut ps = big_sd / big_s;
-                debug!("pn {} ps {}", pn, ps);
-                // Weirdly in the Jukes-Cantor model if pn or ps are 0.75 then the nat log does not resolve
-                // No one talks about this in the literature for some reason
-                if pn == 0.75 {
-                    pn = 0.7499
-                }
-                if ps == 0.75 {
-                    ps = 0.7499
-                }
-                let d_n = -(3.0 / 4.0) * (1.0 - (4.0 * pn) / 3.0).ln();
-                let d_s = -(3.0 / 4.0) * (1.0 - (4.0 * ps) / 3.0).ln();
-                debug!("dN {} dS {}", d_n, d_s);
-                let mut dnds = d_n / d_s;
-
-                // negative dnds values make no sense, but occur nonetheless
-                // Just make them 0.0
-                if dnds < 0.0 {
-                    dnds = 0.0
-                }
-
-                return dnds
-            },
-            _ => return 0.
-        }
-    }
-}
-
-#[allow(unused)]
-pub fn get_codons(sequence: &Vec<u8>, frame: usize, strandedness: strand::Strand) -> Vec<Vec<u8>> {
-
-    let codons = match strandedness{
-        strand::Strand::Forward | strand::Strand::Unknown => {
-            sequence[0+frame..].chunks(3).map(|chunk| chunk.to_vec()).collect::<Vec<Vec<u8>>>()
-        },
-        strand::Strand::Reverse => {
-            let rc = dna::revcomp(sequence);
-            rc[0+frame..].chunks(3).map(|chunk| chunk.to_vec()).collect::<Vec<Vec<u8>>>()
-        }
-    };
-    return codons
-}
-
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::collections::HashSet;
-    use model::variants;
-    use bio::io::{gff};
-
-    fn create_base(ref_sequence: &Vec<u8>, var_char: u8, pos: i64, sample_count: usize) -> Base {
-        Base {
-            tid: 0,
-            pos,
-            refr: ref_sequence[pos as usize..(pos as usize + 1)].to_vec(),
-            variant: Variant::SNV(var_char),
-            filters: vec!(),
-            depth: vec![5; sample_count],
-            truedepth: vec![5; sample_count],
-            totaldepth: vec![5; sample_count],
-            genotypes: HashSet::new(),
-            quals: vec![0.; sample_count],
-            referencedepth: vec![0; sample_count],
-            physicalcov: vec![0; sample_count],
-            baseq: vec![0; sample_count],
-            mapq: vec![0; sample_count],
-            conf: vec![0; sample_count],
-            nucs: HashMap::new(),
-            pernucs: HashMap::new(),
-            ic: vec![0; sample_count],
-            dc: vec![0; sample_count],
-            xc: vec![0; sample_count],
-            ac: vec![0; sample_count],
-            af: vec![0.; sample_count],
-            freq: vec![0.; sample_count],
-            rel_abunds: vec![0.; sample_count],
-            reads: HashSet::new(),
-        }
-    }
-
-    #[test]
-    fn test_floor_division() {
-        assert_eq!(0/3 as usize, 0);
-        assert_eq!(803/3 as usize, 267);
-    }
-
-    #[test]
-    fn test_dnds() {
-        let mut codon_table = CodonTable::setup();
-        codon_table.get_codon_table(11);
-
-        let ref_sequence = "ATGAAACCCGGGTTTTAA".as_bytes().to_vec();
-        let sample_count = 2;
-
-        let mut gene_records
-            = gff::Reader::from_file("tests/data/dnds.gff", gff::GffType::GFF3).expect("Incorrect file path");
-        let mut variant_abundances: HashMap<i64, HashMap<Variant, Base>> = HashMap::new();
-        variant_abundances.insert(13, HashMap::new());
-        variant_abundances.insert(14, HashMap::new());
-        let hash = variant_abundances.entry(7).or_insert(HashMap::new());
-
-        // Create fake variants
-        let var_1 = create_base(&ref_sequence, "G".bytes().nth(0).unwrap(), 7, 2);
-        let var_2 = create_base(&ref_sequence, "C".bytes().nth(0).unwrap(), 11, 2);
-        let var_3 = create_base(&ref_sequence, "A".bytes().nth(0).unwrap(), 13, 2);
-        let var_4 = create_base(&ref_sequence, "C".bytes().nth(0).unwrap(), 14, 2);
-
-
-        hash.insert(var_1.variant.clone(), var_1);
-
-        let hash = variant_abundances.entry(11).or_insert(HashMap::new());
-        hash.insert(var_2.variant.clone(), var_2);
-
-        let hash = variant_abundances.entry(13).or_insert(HashMap::new());
-        hash.insert(var_3.variant.clone(), var_3);
-
-        let hash = variant_abundances.entry(14).or_insert(HashMap::new());
-        hash.insert(var_4.variant.clone(), var_4);
-
-        for gene_record in gene_records.records() {
-            let gene_record = gene_record.unwrap();
-
-            let dnds = codon_table.find_mutations(
-                &gene_record,
-                &variant_abundances,
-                &ref_sequence);
-            assert_eq!(format!("{:.4}", dnds), format!("{}", 0.1247));
-        }
-
-    }
+use std::collections::{HashMap, BTreeMap};
+use std::cmp::Ordering;
+use itertools::{izip, Itertools};
+use bio::alphabets::dna;
+use bio_types::strand;
+use model::variants::{Variant, Base};
+
+#[allow(dead_code)]
+pub struct GeneInfo {
+    name: String,
+    locations: HashMap<u32, (u64, u64)>,
+    coverages: HashMap<u32, f64>,
+}
+
+pub struct CodonTable {
+    aminos: HashMap<Vec<u8>, char>,
+    starts: HashMap<Vec<u8>, char>,
+    ns_sites: HashMap<Vec<u8>, f64>,
+}
+
+
+pub struct NCBITable {
+    aas: String,
+    starts: String,
+    base1: String,
+    base2: String,
+    base3: String,
+}
+
+
+/// NCBI translation table ids, see https://www.ncbi.nlm.nih.gov/Taxonomy/Utils/wprintgc.cgi
+pub const TRANSLATION_TABLES: [usize; 27] = [1, 2, 3, 4, 5, 6, 9, 10, 11, 12, 13, 14, 15, 16, 21, 22, 23, 24, 25, 26, 27, 28, 29, 30, 31, 32, 33];
+
+const NCBI_BASE1: &str = "TTTTTTTTTTTTTTTTCCCCCCCCCCCCCCCCAAAAAAAAAAAAAAAAGGGGGGGGGGGGGGGG";
+const NCBI_BASE2: &str = "TTTTCCCCAAAAGGGGTTTTCCCCAAAAGGGGTTTTCCCCAAAAGGGGTTTTCCCCAAAAGGGG";
+const NCBI_BASE3: &str = "TCAGTCAGTCAGTCAGTCAGTCAGTCAGTCAGTCAGTCAGTCAGTCAGTCAGTCAGTCAGTCAG";
+
+impl NCBITable {
+    // get translation tables in NCBI format
+    // Kind of lazy storing and then converting every time but would take way too much time
+    // to write out each table into CodonTable format by hand
+    // Every table shares the same codon order, so only the amino acids and starts differ.
+    // Stop codons that are only read through in some contexts (tables 27, 28 and 31) are
+    // translated as their amino acid and marked with * in the starts
+    fn get_translation_table(table_id: usize) -> NCBITable {
+        let (aas, starts) = match table_id {
+            // The standard code
+            1 => ("FFLLSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+                   "---M------**--*----M---------------M----------------------------"),
+            // The vertebrate mitochondrial code
+            2 => ("FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNKKSS**VVVVAAAADDEEGGGG",
+                   "----------**--------------------MMMM----------**---M------------"),
+            // The yeast mitochondrial code
+            3 => ("FFLLSSSSYY**CCWWTTTTPPPPHHQQRRRRIIMMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+                   "----------**----------------------MM---------------M------------"),
+            // The mold, protozoan, and coelenterate mitochondrial code and the mycoplasma/spiroplasma code
+            4 => ("FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+                   "--MM------**-------M------------MMMM---------------M------------"),
+            // The invertebrate mitochondrial code
+            5 => ("FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNKKSSSSVVVVAAAADDEEGGGG",
+                   "---M------**--------------------MMMM---------------M------------"),
+            // The ciliate, dasycladacean and hexamita nuclear code
+            6 => ("FFLLSSSSYYQQCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+                   "--------------*--------------------M----------------------------"),
+            // The echinoderm and flatworm mitochondrial code
+            9 => ("FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNNKSSSSVVVVAAAADDEEGGGG",
+                   "----------**-----------------------M---------------M------------"),
+            // The euplotid nuclear code
+            10 => ("FFLLSSSSYY**CCCWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+                    "----------**-----------------------M----------------------------"),
+            // The bacterial, archaeal and plant plastid code
+            11 => ("FFLLSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+                    "---M------**--*----M------------MMMM---------------M------------"),
+            // The alternative yeast nuclear code
+            12 => ("FFLLSSSSYY**CC*WLLLSPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+                    "----------**--*----M---------------M----------------------------"),
+            // The ascidian mitochondrial code
+            13 => ("FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNKKSSGGVVVVAAAADDEEGGGG",
+                    "---M------**----------------------MM---------------M------------"),
+            // The alternative flatworm mitochondrial code
+            14 => ("FFLLSSSSYYY*CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNNKSSSSVVVVAAAADDEEGGGG",
+                    "-----------*-----------------------M----------------------------"),
+            // Blepharisma nuclear code
+            15 => ("FFLLSSSSYY*QCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+                    "----------*---*--------------------M----------------------------"),
+            // Chlorophycean mitochondrial code
+            16 => ("FFLLSSSSYY*LCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+                    "----------*---*--------------------M----------------------------"),
+            // Trematode mitochondrial code
+            21 => ("FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNNKSSSSVVVVAAAADDEEGGGG",
+                    "----------**-----------------------M---------------M------------"),
+            // Scenedesmus obliquus mitochondrial code
+            22 => ("FFLLSS*SYY*LCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+                    "------*---*---*--------------------M----------------------------"),
+            // Thraustochytrium mitochondrial code
+            23 => ("FF*LSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+                    "--*-------**--*-----------------M--M---------------M------------"),
+            // Rhabdopleuridae mitochondrial code
+            24 => ("FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSSKVVVVAAAADDEEGGGG",
+                    "---M------**-------M---------------M---------------M------------"),
+            // Candidate division SR1 and gracilibacteria code
+            25 => ("FFLLSSSSYY**CCGWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+                    "---M------**-----------------------M---------------M------------"),
+            // Pachysolen tannophilus nuclear code
+            26 => ("FFLLSSSSYY**CC*WLLLAPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+                    "----------**--*----M---------------M----------------------------"),
+            // Karyorelict nuclear code
+            27 => ("FFLLSSSSYYQQCCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+                    "--------------*--------------------M----------------------------"),
+            // Condylostoma nuclear code
+            28 => ("FFLLSSSSYYQQCCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+                    "----------**--*--------------------M----------------------------"),
+            // Mesodinium nuclear code
+            29 => ("FFLLSSSSYYYYCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+                    "--------------*--------------------M----------------------------"),
+            // Peritrich nuclear code
+            30 => ("FFLLSSSSYYEECC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+                    "--------------*--------------------M----------------------------"),
+            // Blastocrithidia nuclear code
+            31 => ("FFLLSSSSYYEECCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+                    "----------**-----------------------M----------------------------"),
+            // Balanophoraceae plastid code
+            32 => ("FFLLSSSSYY*WCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+                    "---M------*---*----M------------MMMM---------------M------------"),
+            // Cephalodiscidae mitochondrial UAA-Tyr code
+            33 => ("FFLLSSSSYYY*CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSSKVVVVAAAADDEEGGGG",
+                    "---M-------*-------M---------------M---------------M------------"),
+            _ => {
+                panic!("Translation table {} is not an NCBI translation table. Available tables are {:?}",
+                       table_id, TRANSLATION_TABLES);
+            },
+        };
+        NCBITable {
+            aas: aas.to_owned(),
+            starts: starts.to_owned(),
+            base1: NCBI_BASE1.to_owned(),
+            base2: NCBI_BASE2.to_owned(),
+            base3: NCBI_BASE3.to_owned(),
+        }
+    }
+}
+
+/// Reads a tab separated file of genome names, i.e. reference file stems, and the NCBI
+/// translation table each genome uses. Blank lines and lines starting with # are skipped
+pub fn parse_translation_table_file(file_path: &str) -> HashMap<String, usize> {
+    let contents = std::fs::read_to_string(file_path)
+        .expect(&format!("Unable to read translation table file {}", file_path));
+    let mut genome_tables = HashMap::new();
+    for (line_idx, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue
+        }
+        let fields = line.split('\t').collect::<Vec<&str>>();
+        if fields.len() < 2 {
+            panic!("Line {} of translation table file {} should contain a genome name and a table id",
+                   line_idx + 1, file_path);
+        }
+        let table_id: usize = fields[1].trim().parse()
+            .expect(&format!("Unable to parse translation table id on line {} of {}", line_idx + 1, file_path));
+        if !TRANSLATION_TABLES.contains(&table_id) {
+            panic!("Translation table {} on line {} of {} is not an NCBI translation table. Available tables are {:?}",
+                   table_id, line_idx + 1, file_path, TRANSLATION_TABLES);
+        }
+        genome_tables.insert(fields[0].trim().to_string(), table_id);
+    }
+    genome_tables
+}
+
+impl CodonTable {
+    pub fn setup() -> CodonTable {
+        CodonTable {
+            aminos: HashMap::new(),
+            starts: HashMap::new(),
+            ns_sites: HashMap::new(),
+        }
+    }
+
+    /// Whether the codon is a stop codon in the loaded translation table. Stops that are read
+    /// through in some contexts are only marked in the starts, so both are checked
+    pub fn is_stop(&self, codon: &[u8]) -> bool {
+        self.aminos.get(codon) == Some(&'*') || self.starts.get(codon) == Some(&'*')
+    }
+
+    /// Whether the codon can start translation in the loaded translation table
+    pub fn is_start(&self, codon: &[u8]) -> bool {
+        self.starts.get(codon) == Some(&'M')
+    }
+
+    /// Non-synonymous and synonymous sites of a set of codons
+    fn site_counts(&self, codons: &Vec<Vec<u8>>) -> (f64, f64) {
+        codons.iter().filter(|codon| is_valid_codon(codon)).fold((0., 0.), |(big_n, big_s), codon| {
+            let n = self.ns_sites[codon];
+            (big_n + n, big_s + 3.0 - n)
+        })
+    }
+
+    /// Non-synonymous and synonymous differences between two codons, averaged over every order
+    /// in which the differing positions could have changed
+    fn codon_differences(&self, codon: &Vec<u8>, new_codon: &Vec<u8>) -> (f64, f64) {
+        let diffs = (0..3).filter(|pos| codon[*pos] != new_codon[*pos]).collect::<Vec<usize>>();
+        if diffs.is_empty() {
+            return (0., 0.)
+        }
+        let permutations: Vec<Vec<usize>> = diffs.iter().cloned().permutations(diffs.len()).collect();
+        let mut ns = 0;
+        let mut ss = 0;
+        for permutation in permutations.iter() {
+            let mut shifting = codon.clone();
+            for pos in permutation.iter() {
+                let old_shift = shifting.clone();
+                shifting[*pos] = new_codon[*pos];
+                if self.aminos[&old_shift] != self.aminos[&shifting] {
+                    ns += 1;
+                } else {
+                    ss += 1;
+                }
+            }
+        }
+        (ns as f64 / permutations.len() as f64, ss as f64 / permutations.len() as f64)
+    }
+
+    /// Translates a sequence in frame, with X for codons containing ambiguous bases
+    fn translate(&self, sequence: &[u8]) -> Vec<char> {
+        sequence.chunks(3).filter(|codon| codon.len() == 3)
+            .map(|codon| *self.aminos.get(codon).unwrap_or(&'X')).collect()
+    }
+
+    /// Effect on an overlapping gene of replacing ref_length reference bases from ref_start
+    /// with the alternative bases
+    fn coding_effect(&self,
+                     gene: &bio::io::gff::Record,
+                     gene_codons: &GeneCodons,
+                     ref_start: i64,
+                     ref_length: i64,
+                     alt_bases: &Vec<u8>) -> VariantEffect {
+        let coding_sequence = gene_codons.codons.concat();
+        // Offset of the change along the gene's strand from the first coding base
+        let (cds_start, alt_bases) = if gene_codons.reverse {
+            (gene_codons.end as i64 - ref_start - ref_length - gene_codons.frame as i64,
+             dna::revcomp(&alt_bases.to_ascii_uppercase()))
+        } else {
+            (ref_start - gene_codons.start as i64 - gene_codons.frame as i64,
+             alt_bases.to_ascii_uppercase())
+        };
+        let cds_end = cds_start + ref_length;
+
+        let mut effect = VariantEffect::new(Effect::Synonymous, Some(gene));
+        // Changes reaching past either end of the coding sequence remove its start or stop
+        if cds_start < 0 {
+            effect.effect = Effect::StartLost;
+            return effect
+        } else if cds_end > coding_sequence.len() as i64 {
+            effect.effect = Effect::StopLost;
+            return effect
+        }
+        let (cds_start, cds_end) = (cds_start as usize, cds_end as usize);
+
+        // The codons touched by the change before and after it
+        let first_codon = cds_start / 3;
+        let last_codon = if ref_length > 0 { (cds_end - 1) / 3 } else { first_codon };
+        let region_start = first_codon * 3;
+        let region_end = std::cmp::min((last_codon + 1) * 3, coding_sequence.len());
+        let ref_region = &coding_sequence[region_start..region_end];
+        let alt_region = [&coding_sequence[region_start..cds_start], &alt_bases[..],
+            &coding_sequence[cds_end..region_end]].concat();
+        let ref_aminos = self.translate(ref_region);
+        let alt_aminos = self.translate(&alt_region);
+
+        effect.codon_change = coding_change(cds_start, &coding_sequence[cds_start..cds_end], &alt_bases);
+        effect.cds_position = Some((cds_start + 1, coding_sequence.len()));
+        effect.aa_position = Some((first_codon + 1, coding_sequence.len() / 3));
+
+        let start_lost = first_codon == 0 && coding_sequence.len() >= 3
+            && self.is_start(&coding_sequence[..3])
+            && !(alt_region.len() >= 3 && self.is_start(&alt_region[..3]));
+        effect.effect = if start_lost {
+            Effect::StartLost
+        } else if (alt_region.len() as i64 - ref_region.len() as i64) % 3 != 0 {
+            Effect::Frameshift
+        } else if ref_aminos.contains(&'*') && !alt_aminos.contains(&'*') {
+            Effect::StopLost
+        } else if alt_aminos.contains(&'*') && !ref_aminos.contains(&'*') {
+            Effect::Nonsense
+        } else if alt_region.len() > ref_region.len() {
+            Effect::InframeInsertion
+        } else if alt_region.len() < ref_region.len() {
+            Effect::InframeDeletion
+        } else if ref_aminos == alt_aminos {
+            Effect::Synonymous
+        } else {
+            Effect::Missense
+        };
+        effect.amino_acid_change = match effect.effect {
+            Effect::StartLost => "p.Met1?".to_string(),
+            Effect::Frameshift => match ref_aminos.first() {
+                Some(amino) => format!("p.{}{}fs", three_letter_code(*amino), first_codon + 1),
+                None => String::new(),
+            },
+            _ => protein_change(&ref_aminos, &alt_aminos, first_codon),
+        };
+        effect
+    }
+}
+
+/// Whether a codon is three unambiguous bases
+fn is_valid_codon(codon: &[u8]) -> bool {
+    codon.len() == 3 && codon.iter().all(|base| b"ACGT".contains(base))
+}
+
+/// Jukes-Cantor corrected divergence from a proportion of differing sites
+fn jukes_cantor(p: f64) -> f64 {
+    // The log does not resolve at 0.75 and above, same as in find_mutations
+    -(3.0 / 4.0) * (1.0 - (4.0 * p.min(0.7499)) / 3.0).ln()
+}
+
+/// Codons of a gene read along its strand and where the gene sits on the reference
+struct GeneCodons {
+    codons: Vec<Vec<u8>>,
+    start: usize,
+    end: usize,
+    frame: usize,
+    reverse: bool,
+}
+
+impl GeneCodons {
+    fn new(gene: &bio::io::gff::Record, ref_sequence: &Vec<u8>) -> Option<GeneCodons> {
+        let strand = gene.strand()?;
+        // bio::gff positions are 1-based and the end is inclusive
+        let start = gene.start().clone() as usize - 1;
+        let end = std::cmp::min(gene.end().clone() as usize, ref_sequence.len());
+        let frame: usize = gene.frame().parse().ok()?;
+        if start + frame >= end {
+            return None
+        }
+        let gene_sequence = ref_sequence[start..end].to_ascii_uppercase();
+        Some(GeneCodons {
+            codons: get_codons(&gene_sequence, frame, strand),
+            start,
+            end,
+            frame,
+            reverse: strand == strand::Strand::Reverse,
+        })
+    }
+
+    /// Codon index and position within the codon of a reference position, along with the given
+    /// base as read along the gene's strand
+    fn locate(&self, pos: i64, base: u8) -> Option<(usize, usize, u8)> {
+        if pos < self.start as i64 || pos >= self.end as i64 {
+            return None
+        }
+        let base = base.to_ascii_uppercase();
+        let (cursor, base) = if self.reverse {
+            (self.end - 1 - pos as usize, dna::complement(base))
+        } else {
+            (pos as usize - self.start, base)
+        };
+        if cursor < self.frame {
+            return None
+        }
+        let codon_idx = (cursor - self.frame) / 3;
+        match self.codons.get(codon_idx) {
+            Some(codon) if is_valid_codon(codon) => Some((codon_idx, (cursor - self.frame) % 3, base)),
+            _ => None,
+        }
+    }
+}
+
+/// Variants at most this many bases before the start of a gene, on its strand, are reported as
+/// upstream of it. Bacterial promoters and ribosome binding sites sit well within this distance
+const UPSTREAM_DISTANCE: i64 = 200;
+
+/// The genes of one contig with their codons, built once per contig and sorted by start so that
+/// the genes near a variant are found by binary search rather than by scanning every gene
+pub struct GeneIndex<'a> {
+    genes: Vec<(&'a bio::io::gff::Record, GeneCodons)>,
+    // Length of the longest gene, bounding how far before a variant an overlapping gene starts
+    max_length: i64,
+}
+
+impl<'a> GeneIndex<'a> {
+    pub fn new(genes: &'a Vec<bio::io::gff::Record>, ref_sequence: &Vec<u8>) -> GeneIndex<'a> {
+        let mut genes = genes.iter()
+            .filter_map(|gene| GeneCodons::new(gene, ref_sequence).map(|gene_codons| (gene, gene_codons)))
+            .collect::<Vec<(&bio::io::gff::Record, GeneCodons)>>();
+        genes.sort_by_key(|(_, gene_codons)| (gene_codons.start, gene_codons.end));
+        let max_length = genes.iter()
+            .map(|(_, gene_codons)| (gene_codons.end - gene_codons.start) as i64)
+            .max().unwrap_or(0);
+        GeneIndex {
+            genes,
+            max_length,
+        }
+    }
+
+    /// Genes that may overlap the reference bases from start to end or lie within
+    /// UPSTREAM_DISTANCE of them
+    fn near(&self, start: i64, end: i64) -> &[(&'a bio::io::gff::Record, GeneCodons)] {
+        let first = start - UPSTREAM_DISTANCE - self.max_length;
+        let last = end + UPSTREAM_DISTANCE;
+        let lower = self.genes.binary_search_by(|(_, gene_codons)| {
+            if (gene_codons.start as i64) < first { Ordering::Less } else { Ordering::Greater }
+        }).unwrap_or_else(|idx| idx);
+        let upper = self.genes.binary_search_by(|(_, gene_codons)| {
+            if (gene_codons.start as i64) <= last { Ordering::Less } else { Ordering::Greater }
+        }).unwrap_or_else(|idx| idx);
+        &self.genes[lower..upper]
+    }
+}
+
+/// Functional effect of a variant, reported by its Sequence Ontology term
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Effect {
+    Synonymous,
+    Missense,
+    Nonsense,
+    StopLost,
+    StartLost,
+    Frameshift,
+    InframeInsertion,
+    InframeDeletion,
+    Upstream,
+    Intergenic,
+}
+
+impl Effect {
+    pub fn term(&self) -> &'static str {
+        match self {
+            Effect::Synonymous => "synonymous_variant",
+            Effect::Missense => "missense_variant",
+            Effect::Nonsense => "stop_gained",
+            Effect::StopLost => "stop_lost",
+            Effect::StartLost => "start_lost",
+            Effect::Frameshift => "frameshift_variant",
+            Effect::InframeInsertion => "inframe_insertion",
+            Effect::InframeDeletion => "inframe_deletion",
+            Effect::Upstream => "upstream_gene_variant",
+            Effect::Intergenic => "intergenic_region",
+        }
+    }
+
+    /// Putative impact of the effect, ranked as by snpEff
+    pub fn impact(&self) -> &'static str {
+        match self {
+            Effect::Nonsense | Effect::StopLost | Effect::StartLost | Effect::Frameshift => "HIGH",
+            Effect::Missense | Effect::InframeInsertion | Effect::InframeDeletion => "MODERATE",
+            Effect::Synonymous => "LOW",
+            Effect::Upstream | Effect::Intergenic => "MODIFIER",
+        }
+    }
+}
+
+/// The effect of an allele on a gene, or on no gene when it is intergenic
+#[derive(Debug, Clone, PartialEq)]
+pub struct VariantEffect {
+    pub effect: Effect,
+    pub gene_id: String,
+    pub gene_name: String,
+    /// Change to the coding sequence in HGVS notation, e.g. c.6A>G
+    pub codon_change: String,
+    /// Change to the protein in HGVS notation, e.g. p.Lys2Arg
+    pub amino_acid_change: String,
+    /// First changed base of the coding sequence and the length of the coding sequence
+    pub cds_position: Option<(usize, usize)>,
+    /// First changed codon and the length of the protein
+    pub aa_position: Option<(usize, usize)>,
+    /// Bases between an upstream variant and the start of the gene
+    pub distance: Option<i64>,
+}
+
+impl VariantEffect {
+    fn new(effect: Effect, gene: Option<&bio::io::gff::Record>) -> VariantEffect {
+        // Commas, semicolons, equals signs, pipes and whitespace can't appear in an ANN entry
+        let sanitize = |value: String| value.replace(
+            |c: char| c == ',' || c == ';' || c == '=' || c == '|' || c.is_whitespace(), "_");
+        let (gene_id, gene_name) = match gene {
+            Some(gene) => {
+                let gene_id = gene_identifier(gene);
+                let gene_name = gene.attributes().get("gene").cloned().unwrap_or(gene_id.clone());
+                (sanitize(gene_id), sanitize(gene_name))
+            },
+            None => (String::new(), String::new()),
+        };
+        VariantEffect {
+            effect,
+            gene_id,
+            gene_name,
+            codon_change: String::new(),
+            amino_acid_change: String::new(),
+            cds_position: None,
+            aa_position: None,
+            distance: None,
+        }
+    }
+
+    /// Formats the effect as an entry of a snpEff style ANN INFO field for the given ALT allele
+    pub fn to_ann(&self, allele: &str) -> String {
+        let (feature_type, biotype, rank) = if self.gene_id.is_empty() {
+            ("", "", "")
+        } else {
+            ("transcript", "protein_coding", "1/1")
+        };
+        let format_position = |position: Option<(usize, usize)>| match position {
+            Some((pos, length)) => format!("{}/{}", pos, length),
+            None => String::new(),
+        };
+        format!("{}|{}|{}|{}|{}|{}|{}|{}|{}|{}|{}|{}|{}|{}|{}|",
+                allele, self.effect.term(), self.effect.impact(), self.gene_name, self.gene_id,
+                feature_type, self.gene_id, biotype, rank, self.codon_change, self.amino_acid_change,
+                format_position(self.cds_position), format_position(self.cds_position),
+                format_position(self.aa_position),
+                self.distance.map(|distance| distance.to_string()).unwrap_or(String::new()))
+    }
+}
+
+/// ID attribute of a gene, or its location when it has none
+pub fn gene_identifier(gene: &bio::io::gff::Record) -> String {
+    match gene.attributes().get("ID") {
+        Some(id) => id.to_string(),
+        None => format!("{}:{}-{}", gene.seqname(), gene.start(), gene.end()),
+    }
+}
+
+/// Three letter code of an amino acid, keeping * for stops
+fn three_letter_code(amino: char) -> &'static str {
+    match amino {
+        'A' => "Ala", 'R' => "Arg", 'N' => "Asn", 'D' => "Asp", 'C' => "Cys",
+        'Q' => "Gln", 'E' => "Glu", 'G' => "Gly", 'H' => "His", 'I' => "Ile",
+        'L' => "Leu", 'K' => "Lys", 'M' => "Met", 'F' => "Phe", 'P' => "Pro",
+        'S' => "Ser", 'T' => "Thr", 'W' => "Trp", 'Y' => "Tyr", 'V' => "Val",
+        '*' => "*",
+        _ => "Xaa",
+    }
+}
+
+/// HGVS description of replacing the reference bases of the coding sequence from cds_start
+/// with the alternative bases, both read along the gene's strand
+fn coding_change(cds_start: usize, ref_bases: &[u8], alt_bases: &[u8]) -> String {
+    let alt = String::from_utf8_lossy(alt_bases);
+    let first = cds_start + 1;
+    let last = cds_start + ref_bases.len();
+    if ref_bases.is_empty() {
+        format!("c.{}_{}ins{}", cds_start, first, alt)
+    } else if ref_bases.len() == 1 && alt_bases.len() == 1 {
+        format!("c.{}{}>{}", first, ref_bases[0] as char, alt_bases[0] as char)
+    } else {
+        let range = if last > first { format!("{}_{}", first, last) } else { format!("{}", first) };
+        if alt_bases.is_empty() {
+            format!("c.{}del", range)
+        } else {
+            format!("c.{}delins{}", range, alt)
+        }
+    }
+}
+
+/// HGVS description of the change between the reference and alternative amino acids of the
+/// codons from first_codon, trimmed to the residues that differ
+fn protein_change(ref_aminos: &Vec<char>, alt_aminos: &Vec<char>, first_codon: usize) -> String {
+    if ref_aminos.is_empty() {
+        return String::new()
+    }
+    // Keep at least one reference residue so that every change has a position
+    let prefix = ref_aminos.iter().zip(alt_aminos.iter())
+        .take_while(|(ref_amino, alt_amino)| ref_amino == alt_amino).count()
+        .min(ref_aminos.len() - 1);
+    let suffix = ref_aminos[prefix..].iter().rev().zip(alt_aminos[prefix..].iter().rev())
+        .take_while(|(ref_amino, alt_amino)| ref_amino == alt_amino).count()
+        .min(ref_aminos.len() - prefix - 1);
+    let changed_ref = &ref_aminos[prefix..ref_aminos.len() - suffix];
+    let changed_alt = &alt_aminos[prefix..alt_aminos.len() - suffix];
+
+    let start = first_codon + prefix + 1;
+    let mut range = format!("{}{}", three_letter_code(changed_ref[0]), start);
+    if changed_ref.len() > 1 {
+        range = format!("{}_{}{}", range, three_letter_code(changed_ref[changed_ref.len() - 1]),
+                        start + changed_ref.len() - 1);
+    }
+    if changed_ref.len() == 1 && changed_alt.len() == 1 {
+        format!("p.{}{}", range, three_letter_code(changed_alt[0]))
+    } else if changed_alt.is_empty() {
+        format!("p.{}del", range)
+    } else {
+        format!("p.{}delins{}", range,
+                changed_alt.iter().map(|amino| three_letter_code(*amino)).collect::<String>())
+    }
+}
+
+pub trait Translations {
+    fn get_codon_table(&mut self, table_id: usize);
+    fn find_mutations(&self,
+                      gene: &bio::io::gff::Record,
+                      variants: &HashMap<i64, HashMap<Variant, Base>>,
+                      ref_sequence: &Vec<u8>) -> f64;
+
+    /// pN/pS of a gene in each sample. Each SNV is classified against the reference codon and
+    /// weighted by its allele frequency in the sample. None where pS is zero
+    fn sample_pnps(&self,
+                   gene: &bio::io::gff::Record,
+                   variants: &HashMap<i64, HashMap<Variant, Base>>,
+                   ref_sequence: &Vec<u8>,
+                   sample_count: usize) -> Vec<Option<f64>>;
+
+    /// Jukes-Cantor corrected dN/dS of a strain's copy of a gene against the reference, given
+    /// the alternative base the strain carries at each position. None where dS is zero
+    fn strain_dnds(&self,
+                   gene: &bio::io::gff::Record,
+                   strain_bases: &BTreeMap<i64, u8>,
+                   ref_sequence: &Vec<u8>) -> Option<f64>;
+
+    /// Functional effects of an alternative allele at a reference position on the genes of its
+    /// contig: its effect on every gene it overlaps and, for genes starting within
+    /// UPSTREAM_DISTANCE after it, that it lies upstream. Alleles near no gene are intergenic
+    fn variant_effects(&self,
+                       genes: &GeneIndex,
+                       pos: i64,
+                       variant: &Variant) -> Vec<VariantEffect>;
+}
+
+impl Translations for CodonTable {
+    #[allow(unused)]
+    fn get_codon_table(&mut self, table_id: usize) {
+        // Convert NCBI format to something more usable
+        let ncbi_format = NCBITable::get_translation_table(table_id);
+        let mut amino_hash = HashMap::new();
+        let mut start_hash = HashMap::new();
+        for (aa, s, b1, b2, b3) in izip!(ncbi_format.aas.as_bytes(), ncbi_format.starts.as_bytes(),
+                                        ncbi_format.base1.as_bytes(), ncbi_format.base2.as_bytes(),
+                                        ncbi_format.base3.as_bytes()) {
+            let codon = vec!(*b1, *b2, *b3);
+            amino_hash.insert(codon.clone(), *aa as char);
+            start_hash.insert(codon, *s as char);
+        }
+        debug!("Amino hash {:?}", amino_hash);
+        self.aminos = amino_hash;
+        self.starts = start_hash;
+
+        let nucleotides: Vec<u8> = vec!(65, 84, 67, 71);
+        debug!("nucleotides: {} ", String::from_utf8_lossy(&nucleotides));
+        for (codon, _aa) in self.aminos.iter(){
+            let mut n = 0.0;
+            for (pos, cod) in codon.iter().enumerate() {
+                for nuc in nucleotides.iter() {
+                    if cod == nuc {
+                        // Ignore this nucleotide
+                        continue
+                    } else {
+                        let mut codon_shift = codon.clone();
+                        // Change one position of the codon
+                        codon_shift[pos] = nuc.clone();
+
+                        if self.aminos[codon] != self.aminos[&codon_shift] {
+                            // This change can cause a non-synonymous mutation
+                            n += 1.0/3.0;
+                        }
+                    }
+                }
+            }
+            self.ns_sites.insert(codon.clone(), n as f64);
+        }
+    }
+
+    #[allow(unused)]
+    fn find_mutations(&self,
+                      gene: &bio::io::gff::Record,
+                      variants: &HashMap<i64, HashMap<Variant, Base>>,
+                      ref_sequence: &Vec<u8>) -> f64 {
+        match gene.strand() {
+            Some(strand) => {
+
+                // bio::gff documentation says start and end positions are 1-based, so we minus 1
+                // Additionally, end position is non-inclusive
+                let start = gene.start().clone() as usize - 1;
+                let end = gene.end().clone() as usize;
+                let frame: usize = match gene.frame().parse() {
+                    Ok(frame_val) => frame_val,
+                    Err(_) => return 0.
+                };
+                let gene_sequence = ref_sequence[start..end].to_vec();
+                debug!("Gene Seq {:?}", String::from_utf8_lossy(&gene_sequence));
+                let codon_sequence = get_codons(&gene_sequence, frame, strand);
+                debug!("Codon Sequence {:?}", codon_sequence);
+
+                // Calculate N and S
+                let mut big_n: f64 = 0.0;
+                let mut big_s: f64 = 0.0;
+                for codon in codon_sequence.iter() {
+                    if String::from_utf8(codon.clone()).expect("Unable to interpret codon")
+                        .contains("N") || codon.len() != 3 {
+                        continue
+                    } else {
+                        let n = self.ns_sites[codon];
+                        big_n += n;
+                        big_s += 3.0 - n;
+                    }
+                }
+
+                debug!("getting ns_sites N {} S {}", big_n, big_s);
+
+                // Create Nd and Sd values
+                let mut big_nd: f64 = 0.0;
+                let mut big_sd: f64 = 0.0;
+
+                // dN/dS calculations when using NGS reads outlined here:
+                // http://bioinformatics.cvr.ac.uk/blog/calculating-dnds-for-ngs-datasets/
+                // Note, we don't normalize for depth here and instead just use Jukes-Cantor model
+                let mut codon: Vec<u8> = vec!();
+                let mut new_codons: Vec<Vec<u8>> = vec!();
+                let mut positionals = 0;
+                let mut total_variants = 0;
+                let dummy = HashMap::new();
+                for (gene_cursor, cursor) in (start..end).into_iter().enumerate() {
+                    let variant_set = match variants.get(&(cursor as i64)) {
+                        Some(map) => map,
+                        None => &dummy,
+                    };
+
+                    let codon_idx = gene_cursor / 3 as usize;
+                    let codon_cursor = gene_cursor % 3;
+                    if String::from_utf8(codon.clone())
+                        .expect("Unable to interpret codon").contains("N") {
+                        continue
+                    }
+
+                    if codon_cursor == 0
+                        || new_codons.len() == 0 {
+                        for new_codon in new_codons.iter_mut() {
+                            if (codon.len() == 3)
+                                && (new_codon.len() == 3)
+                                && (codon != *new_codon) {
+                                // get indices of different locations
+                                let mut pos = 0 as usize;
+                                let mut diffs = vec!();
+                                for (c1, c2) in codon.iter().zip(new_codon.iter()) {
+                                    if c1 != c2 {
+                                        diffs.push(pos);
+                                    }
+                                    pos += 1;
+                                }
+                                total_variants += diffs.len();
+                                // get permuations of positions
+                                let permutations: Vec<Vec<usize>> = diffs.iter().cloned().permutations(diffs.len()).collect();
+
+                                // calculate synonymous and non-synonymous for each permutation
+                                let mut ns = 0;
+                                let mut ss = 0;
+                                debug!("positional difference {:?} permutations {:?}", diffs, permutations.len());
+                                positionals += permutations.len();
+                                for permutation in permutations.iter() {
+                                    let mut shifting = codon.clone();
+                                    let mut old_shift;
+                                    for pos in permutation {
+                                        // Check if one amino acid change causes an syn or non-syn
+                                        old_shift = shifting.clone();
+                                        shifting[*pos] = new_codon[*pos];
+                                        debug!("Old shift {:?}, new {:?}", old_shift, shifting);
+                                        if self.aminos[&old_shift] != self.aminos[&shifting] {
+                                            ns += 1;
+                                        } else {
+                                            ss += 1;
+                                        }
+                                    }
+                                }
+                                let nd = ns as f64 / permutations.len() as f64;
+                                let sd = ss as f64 / permutations.len() as f64;
+                                big_nd += nd;
+                                big_sd += sd;
+                            }
+                        }
+                        // begin working on new codon
+                        debug!("Codon idx {} codonds {} gene length {} new_codons {:?}",
+                               codon_idx, codon_sequence.len(), gene_sequence.len(), new_codons);
+                        if codon_sequence.len() == 268 {
+                            debug!("{:?}", codon_sequence);
+                        }
+                        codon = codon_sequence[codon_idx].clone();
+                        if codon.len() != 3 {
+                            continue
+                        }
+                        new_codons = Vec::new();
+                        new_codons.push(codon.clone());
+                    }
+                    if variant_set.len() > 0 {
+                        debug!("variant map {:?}", variant_set);
+                        let mut variant_count = 0;
+
+                        for (variant, base_info) in variant_set.iter() {
+                            match variant {
+                                Variant::SNV(var) => {
+                                    if variant_count > 0 {
+                                        // Create a copy of codon up to this point
+                                        // Not sure if reusing previous variants is bad, but
+                                        // not doing so can cause randomness to dN/dS values
+
+                                        new_codons.push(codon.clone());
+
+                                        new_codons[variant_count][codon_cursor] = *var;
+
+                                        debug!("multi variant codon {:?}", new_codons);
+                                    } else {
+                                        for var_idx in 0..new_codons.len() {
+                                            new_codons[var_idx][codon_cursor] = *var;
+                                        }
+                                    }
+                                    variant_count += 1;
+                                },
+                                _ => {
+                                    // Frameshift mutations are not included in dN/dS calculations?
+                                    // Seems weird, but all formulas say no
+                                    debug!("Frameshift mutation variant {:?}", variant);
+                                    continue
+                                },
+                            }
+                        }
+                    }
+                }
+
+                debug!("Nd {} N {}, Sd {} S {} total permutations {} variants {}",
+                       big_nd, big_n, big_sd, big_s, positionals, total_variants);
+                let mut pn = big_nd / big_n;
+                let mut ps = big_sd / big_s;
+                debug!("pn {} ps {}", pn, ps);
+                // Weirdly in the Jukes-Cantor model if pn or ps are 0.75 then the nat log does not resolve
+                // No one talks about this in the literature for some reason
+                if pn == 0.75 {
+                    pn = 0.7499
+                }
+                if ps == 0.75 {
+                    ps = 0.7499
+                }
+                let d_n = -(3.0 / 4.0) * (1.0 - (4.0 * pn) / 3.0).ln();
+                let d_s = -(3.0 / 4.0) * (1.0 - (4.0 * ps) / 3.0).ln();
+                debug!("dN {} dS {}", d_n, d_s);
+                let mut dnds = d_n / d_s;
+
+                // negative dnds values make no sense, but occur nonetheless
+                // Just make them 0.0
+                if dnds < 0.0 {
+                    dnds = 0.0
+                }
+
+                return dnds
+            },
+            _ => return 0.
+        }
+    }
+
+    fn sample_pnps(&self,
+                   gene: &bio::io::gff::Record,
+                   variants: &HashMap<i64, HashMap<Variant, Base>>,
+                   ref_sequence: &Vec<u8>,
+                   sample_count: usize) -> Vec<Option<f64>> {
+        let gene_codons = match GeneCodons::new(gene, ref_sequence) {
+            Some(gene_codons) => gene_codons,
+            None => return vec![None; sample_count],
+        };
+        let (big_n, big_s) = self.site_counts(&gene_codons.codons);
+
+        // Allele frequency weighted non-synonymous and synonymous differences of each sample
+        let mut big_nd = vec![0.; sample_count];
+        let mut big_sd = vec![0.; sample_count];
+        for pos in gene_codons.start..gene_codons.end {
+            let variant_set = match variants.get(&(pos as i64)) {
+                Some(map) => map,
+                None => continue,
+            };
+            for (variant, base) in variant_set.iter() {
+                let alt = match variant {
+                    Variant::SNV(alt) => *alt,
+                    _ => continue,
+                };
+                let (codon_idx, codon_cursor, alt) = match gene_codons.locate(pos as i64, alt) {
+                    Some(location) => location,
+                    None => continue,
+                };
+                let codon = &gene_codons.codons[codon_idx];
+                let mut new_codon = codon.clone();
+                new_codon[codon_cursor] = alt;
+                if !is_valid_codon(&new_codon) || new_codon == *codon {
+                    continue
+                }
+                let non_synonymous = self.aminos[codon] != self.aminos[&new_codon];
+                for sample_idx in 0..std::cmp::min(sample_count, base.totaldepth.len()) {
+                    if base.totaldepth[sample_idx] > 0 {
+                        let frequency = (base.truedepth[sample_idx] as f64
+                            / base.totaldepth[sample_idx] as f64).min(1.);
+                        if non_synonymous {
+                            big_nd[sample_idx] += frequency;
+                        } else {
+                            big_sd[sample_idx] += frequency;
+                        }
+                    }
+                }
+            }
+        }
+
+        (0..sample_count).map(|sample_idx| {
+            if big_n > 0. && big_s > 0. && big_sd[sample_idx] > 0. {
+                Some((big_nd[sample_idx] / big_n) / (big_sd[sample_idx] / big_s))
+            } else {
+                None
+            }
+        }).collect()
+    }
+
+    fn strain_dnds(&self,
+                   gene: &bio::io::gff::Record,
+                   strain_bases: &BTreeMap<i64, u8>,
+                   ref_sequence: &Vec<u8>) -> Option<f64> {
+        let gene_codons = GeneCodons::new(gene, ref_sequence)?;
+        let mut strain_codons = gene_codons.codons.clone();
+        for (pos, alt) in strain_bases.range(gene_codons.start as i64..gene_codons.end as i64) {
+            if let Some((codon_idx, codon_cursor, alt)) = gene_codons.locate(*pos, *alt) {
+                strain_codons[codon_idx][codon_cursor] = alt;
+            }
+        }
+
+        let (big_n, big_s) = self.site_counts(&gene_codons.codons);
+        let mut big_nd = 0.;
+        let mut big_sd = 0.;
+        for (codon, strain_codon) in gene_codons.codons.iter().zip(strain_codons.iter()) {
+            if codon != strain_codon && is_valid_codon(codon) && is_valid_codon(strain_codon) {
+                let (nd, sd) = self.codon_differences(codon, strain_codon);
+                big_nd += nd;
+                big_sd += sd;
+            }
+        }
+        if big_n <= 0. || big_s <= 0. {
+            return None
+        }
+        let d_s = jukes_cantor(big_sd / big_s);
+        if d_s > 0. {
+            Some(jukes_cantor(big_nd / big_n) / d_s)
+        } else {
+            None
+        }
+    }
+
+    fn variant_effects(&self,
+                       genes: &GeneIndex,
+                       pos: i64,
+                       variant: &Variant) -> Vec<VariantEffect> {
+        // The reference bases the allele replaces and the bases replacing them. Indels are
+        // anchored on the base before them
+        let (ref_start, ref_length, alt_bases) = match variant {
+            Variant::SNV(alt) => (pos, 1, vec![*alt]),
+            Variant::MNV(alt) => (pos, alt.len() as i64, alt.clone()),
+            Variant::Insertion(alt) => (pos + 1, 0, alt.clone()),
+            Variant::Deletion(length) => (pos + 1, *length as i64, Vec::new()),
+            _ => return Vec::new(),
+        };
+        let ref_end = ref_start + ref_length;
+
+        let mut effects = Vec::new();
+        for (gene, gene_codons) in genes.near(ref_start, ref_end).iter() {
+            let (gene_start, gene_end) = (gene_codons.start as i64, gene_codons.end as i64);
+            let overlaps = if ref_length > 0 {
+                ref_start < gene_end && ref_end > gene_start
+            } else {
+                ref_start > gene_start && ref_start < gene_end
+            };
+            if overlaps {
+                effects.push(self.coding_effect(gene, gene_codons, ref_start, ref_length, &alt_bases));
+            } else {
+                let distance = if gene_codons.reverse { pos - (gene_end - 1) } else { gene_start - pos };
+                if distance > 0 && distance <= UPSTREAM_DISTANCE {
+                    let mut effect = VariantEffect::new(Effect::Upstream, Some(gene));
+                    effect.distance = Some(distance);
+                    effects.push(effect);
+                }
+            }
+        }
+        if effects.is_empty() {
+            effects.push(VariantEffect::new(Effect::Intergenic, None));
+        }
+        effects
+    }
+}
+
+#[allow(unused)]
+pub fn get_codons(sequence: &Vec<u8>, frame: usize, strandedness: strand::Strand) -> Vec<Vec<u8>> {
+
+    let codons = match strandedness{
+        strand::Strand::Forward | strand::Strand::Unknown => {
+            sequence[0+frame..].chunks(3).map(|chunk| chunk.to_vec()).collect::<Vec<Vec<u8>>>()
+        },
+        strand::Strand::Reverse => {
+            let rc = dna::revcomp(sequence);
+            rc[0+frame..].chunks(3).map(|chunk| chunk.to_vec()).collect::<Vec<Vec<u8>>>()
+        }
+    };
+    return codons
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use model::variants;
+    use model::read_names::ReadSet;
+    use bio::io::{gff};
+
+    fn create_base(ref_sequence: &Vec<u8>, var_char: u8, pos: i64, sample_count: usize) -> Base {
+        Base {
+            tid: 0,
+            pos,
+            refr: ref_sequence[pos as usize..(pos as usize + 1)].to_vec(),
+            variant: Variant::SNV(var_char),
+            filters: vec!(),
+            depth: vec![5; sample_count],
+            truedepth: vec![5; sample_count],
+            totaldepth: vec![5; sample_count],
+            genotypes: HashSet::new(),
+            quals: vec![0.; sample_count],
+            referencedepth: vec![0; sample_count],
+            physicalcov: vec![0; sample_count],
+            baseq: vec![0; sample_count],
+            mapq: vec![0; sample_count],
+            conf: vec![0; sample_count],
+            nucs: HashMap::new(),
+            pernucs: HashMap::new(),
+            ic: vec![0; sample_count],
+            dc: vec![0; sample_count],
+            xc: vec![0; sample_count],
+            ac: vec![0; sample_count],
+            af: vec![0.; sample_count],
+            freq: vec![0.; sample_count],
+            rel_abunds: vec![0.; sample_count],
+            reads: ReadSet::new(),
+        }
+    }
+
+    #[test]
+    fn test_translation_tables() {
+        for table_id in TRANSLATION_TABLES.iter() {
+            let ncbi_format = NCBITable::get_translation_table(*table_id);
+            assert_eq!(ncbi_format.aas.len(), 64);
+            assert_eq!(ncbi_format.starts.len(), 64);
+        }
+
+        let mut codon_table = CodonTable::setup();
+        codon_table.get_codon_table(4);
+        assert_eq!(codon_table.aminos[&b"TGA".to_vec()], 'W');
+        assert_eq!(codon_table.starts[&b"TTA".to_vec()], 'M');
+
+        codon_table.get_codon_table(6);
+        assert_eq!(codon_table.aminos[&b"TAA".to_vec()], 'Q');
+        assert_eq!(codon_table.aminos[&b"TGA".to_vec()], '*');
+
+        codon_table.get_codon_table(2);
+        assert_eq!(codon_table.aminos[&b"AGA".to_vec()], '*');
+        assert_eq!(codon_table.aminos[&b"ATA".to_vec()], 'M');
+        assert!(codon_table.is_stop(b"AGA"));
+
+        // Stops that can be read through are only marked in the starts
+        codon_table.get_codon_table(27);
+        assert_eq!(codon_table.aminos[&b"TGA".to_vec()], 'W');
+        assert!(codon_table.is_stop(b"TGA"));
+        assert!(!codon_table.is_stop(b"TAA"));
+        codon_table.get_codon_table(31);
+        assert!(codon_table.is_stop(b"TAA") && codon_table.is_stop(b"TAG"));
+        assert!(!codon_table.is_stop(b"TGA"));
+    }
+
+    #[test]
+    fn test_selection() {
+        let mut codon_table = CodonTable::setup();
+        codon_table.get_codon_table(11);
+        let ref_sequence = "ATGAAACCCGGGTTTTAA".as_bytes().to_vec();
+        let mut gene_records
+            = gff::Reader::from_file("tests/data/dnds.gff", gff::GffType::GFF3).expect("Incorrect file path");
+        let gene = gene_records.records().next().unwrap().unwrap();
+
+        // AAA to AAG is synonymous and CCC to CAC is not
+        let mut synonymous = create_base(&ref_sequence, b'G', 5, 2);
+        synonymous.truedepth = vec![5, 2];
+        synonymous.totaldepth = vec![10, 10];
+        let mut missense = create_base(&ref_sequence, b'A', 7, 2);
+        missense.truedepth = vec![5, 0];
+        missense.totaldepth = vec![10, 10];
+        let mut variants: HashMap<i64, HashMap<Variant, Base>> = HashMap::new();
+        variants.entry(5).or_insert(HashMap::new()).insert(synonymous.variant.clone(), synonymous);
+        variants.entry(7).or_insert(HashMap::new()).insert(missense.variant.clone(), missense);
+
+        let codons = get_codons(&ref_sequence, 0, strand::Strand::Forward);
+        let (big_n, big_s) = codon_table.site_counts(&codons);
+        let pnps = codon_table.sample_pnps(&gene, &variants, &ref_sequence, 2);
+        assert!((pnps[0].unwrap() - (0.5 / big_n) / (0.5 / big_s)).abs() < 1e-10);
+        assert_eq!(pnps[1], Some(0.));
+
+        let mut strain_bases = BTreeMap::new();
+        strain_bases.insert(7, b'A');
+        assert_eq!(codon_table.strain_dnds(&gene, &strain_bases, &ref_sequence), None);
+        strain_bases.insert(5, b'G');
+        let expected = jukes_cantor(1. / big_n) / jukes_cantor(1. / big_s);
+        let dnds = codon_table.strain_dnds(&gene, &strain_bases, &ref_sequence).unwrap();
+        assert!((dnds - expected).abs() < 1e-10);
+    }
+
+    #[test]
+    fn test_variant_effects() {
+        let mut codon_table = CodonTable::setup();
+        codon_table.get_codon_table(11);
+        let ref_sequence = "ATGAAACCCGGGTTTTAA".as_bytes().to_vec();
+        let mut gene_records
+            = gff::Reader::from_file("tests/data/dnds.gff", gff::GffType::GFF3).expect("Incorrect file path");
+        let genes = gene_records.records().map(|record| record.unwrap()).collect::<Vec<gff::Record>>();
+
+        let gene_index = GeneIndex::new(&genes, &ref_sequence);
+        let effect_of = |pos: i64, variant: Variant| {
+            let effects = codon_table.variant_effects(&gene_index, pos, &variant);
+            assert_eq!(effects.len(), 1);
+            effects[0].clone()
+        };
+
+        let synonymous = effect_of(5, Variant::SNV(b'G'));
+        assert_eq!(synonymous.effect, Effect::Synonymous);
+        assert_eq!(synonymous.codon_change, "c.6A>G");
+        assert_eq!(synonymous.amino_acid_change, "p.Lys2Lys");
+        assert_eq!(synonymous.to_ann("G"),
+                   "G|synonymous_variant|LOW|1_1|1_1|transcript|1_1|protein_coding|1/1|c.6A>G|p.Lys2Lys|6/18|6/18|2/6||");
+
+        let missense = effect_of(7, Variant::SNV(b'A'));
+        assert_eq!(missense.effect, Effect::Missense);
+        assert_eq!(missense.amino_acid_change, "p.Pro3His");
+        let nonsense = effect_of(3, Variant::SNV(b'T'));
+        assert_eq!(nonsense.effect, Effect::Nonsense);
+        assert_eq!(nonsense.amino_acid_change, "p.Lys2*");
+        assert_eq!(effect_of(16, Variant::SNV(b'C')).effect, Effect::StopLost);
+        assert_eq!(effect_of(1, Variant::SNV(b'C')).effect, Effect::StartLost);
+
+        let frameshift = effect_of(5, Variant::Deletion(1));
+        assert_eq!(frameshift.effect, Effect::Frameshift);
+        assert_eq!(frameshift.codon_change, "c.7del");
+        assert_eq!(frameshift.amino_acid_change, "p.Pro3fs");
+        let deletion = effect_of(5, Variant::Deletion(3));
+        assert_eq!(deletion.effect, Effect::InframeDeletion);
+        assert_eq!(deletion.codon_change, "c.7_9del");
+        assert_eq!(deletion.amino_acid_change, "p.Pro3del");
+        let insertion = effect_of(5, Variant::Insertion(b"GGG".to_vec()));
+        assert_eq!(insertion.effect, Effect::InframeInsertion);
+        assert_eq!(insertion.codon_change, "c.6_7insGGG");
+
+        // The same gene after 100 bases, followed by 300 more
+        let padded_sequence = [vec![b'A'; 100], ref_sequence.clone(), vec![b'A'; 300]].concat();
+        let mut shifted_gene = genes[0].clone();
+        *shifted_gene.start_mut() = 101;
+        *shifted_gene.end_mut() = 118;
+        let shifted_genes = vec![shifted_gene];
+        let shifted_index = GeneIndex::new(&shifted_genes, &padded_sequence);
+        let upstream = codon_table.variant_effects(&shifted_index, 50, &Variant::SNV(b'C'));
+        assert_eq!(upstream[0].effect, Effect::Upstream);
+        assert_eq!(upstream[0].distance, Some(50));
+        let intergenic = codon_table.variant_effects(&shifted_index, 400, &Variant::SNV(b'C'));
+        assert_eq!(intergenic[0].effect, Effect::Intergenic);
+        assert_eq!(intergenic[0].to_ann("C"), "C|intergenic_region|MODIFIER|||||||||||||");
+    }
+
+    #[test]
+    fn test_floor_division() {
+        assert_eq!(0/3 as usize, 0);
+        assert_eq!(803/3 as usize, 267);
+    }
+
+    #[test]
+    fn test_dnds() {
+        let mut codon_table = CodonTable::setup();
+        codon_table.get_codon_table(11);
+
+        let ref_sequence = "ATGAAACCCGGGTTTTAA".as_bytes().to_vec();
+        let sample_count = 2;
+
+        let mut gene_records
+            = gff::Reader::from_file("tests/data/dnds.gff", gff::GffType::GFF3).expect("Incorrect file path");
+        let mut variant_abundances: HashMap<i64, HashMap<Variant, Base>> = HashMap::new();
+        variant_abundances.insert(13, HashMap::new());
+        variant_abundances.insert(14, HashMap::new());
+        let hash = variant_abundances.entry(7).or_insert(HashMap::new());
+
+        // Create fake variants
+        let var_1 = create_base(&ref_sequence, "G".bytes().nth(0).unwrap(), 7, 2);
+        let var_2 = create_base(&ref_sequence, "C".bytes().nth(0).unwrap(), 11, 2);
+        let var_3 = create_base(&ref_sequence, "A".bytes().nth(0).unwrap(), 13, 2);
+        let var_4 = create_base(&ref_sequence, "C".bytes().nth(0).unwrap(), 14, 2);
+
+
+        hash.insert(var_1.variant.clone(), var_1);
+
+        let hash = variant_abundances.entry(11).or_insert(HashMap::new());
+        hash.insert(var_2.variant.clone(), var_2);
+
+        let hash = variant_abundances.entry(13).or_insert(HashMap::new());
+        hash.insert(var_3.variant.clone(), var_3);
+
+        let hash = variant_abundances.entry(14).or_insert(HashMap::new());
+        hash.insert(var_4.variant.clone(), var_4);
+
+        for gene_record in gene_records.records() {
+            let gene_record = gene_record.unwrap();
+
+            let dnds = codon_table.find_mutations(
+                &gene_record,
+                &variant_abundances,
+                &ref_sequence);
+            assert_eq!(format!("{:.4}", dnds), format!("{}", 0.1247));
+        }
+
+    }
 }
\ No newline at end of file
@@ -1,414 +1,682 @@
-use std;
-use std::collections::{HashMap, HashSet};
-use glob::glob;
-
-use external_command_checker;
-use estimation::variant_matrix::*;
-use estimation::vcfs::process_vcf::*;
-use estimation::bams::process_bam::*;
-use estimation::codon_structs::*;
-use coverm::bam_generator::*;
-use bird_tool_utils::{command};
-use utils::*;
-
-use crate::*;
-use std::str;
-use coverm::mosdepth_genome_coverage_estimators::*;
-use coverm::FlagFilter;
-use bio::io::gff;
-use tempdir::TempDir;
-use std::process::Stdio;
-use std::path::Path;
-use coverm::genomes_and_contigs::GenomesAndContigs;
-
-#[allow(unused)]
-pub fn pileup_variants<R: NamedBamReader,
-    G: NamedBamReaderGenerator<R>,
-    S: NamedBamReader,
-    U: NamedBamReaderGenerator<S>>(
-    m: &clap::ArgMatches,
-    bam_readers: Vec<G>,
-    longreads: Option<Vec<U>>,
-    mode: &str,
-    coverage_estimators: &mut Vec<CoverageEstimator>,
-    flag_filters: FlagFilter,
-    mapq_threshold: u8,
-    min_var_depth: usize,
-    min: f32, max: f32,
-    contig_end_exclusion: u64,
-    output_prefix: &str,
-    n_threads: usize,
-    method: &str,
-    coverage_fold: f32,
-    include_indels: bool,
-    include_soft_clipping: bool,
-    is_long_read: bool,
-    genomes_and_contigs: GenomesAndContigs,
-    tmp_bam_file_cache: Option<TempDir>,
-) {
-
-    let references = parse_references(&m);
-    let references = references.iter().map(|p| &**p).collect::<Vec<&str>>();
-
-    let mut short_sample_count = bam_readers.len();
-    let mut long_sample_count = 0;
-    let mut reference_count = references.len();
-    let mut coverage_estimators = coverage_estimators;
-    let mut ani = 0.;
-
-    // Gff map lists coding regions
-    let mut gff_map = HashMap::new();
-    let mut codon_table = CodonTable::setup();
-
-    let longreads = match longreads {
-        Some(vec) => {
-            long_sample_count += vec.len();
-            vec
-        },
-        None => {
-            vec!()
-        }
-    };
-
-    let mut variant_matrix_map = HashMap::new();
-    // Put reference index in the variant map and initialize matrix
-    let mut reference_map = HashMap::new();
-    for reference in references.iter() {
-        debug!("Genomes {:?} contigs {:?}", &genomes_and_contigs.genomes, &genomes_and_contigs.contig_to_genome);
-        let ref_idx = genomes_and_contigs.genome_index(&Path::new(reference)
-            .file_stem().expect("problem determining file stem").to_str().unwrap().to_string()).unwrap();
-        reference_map.entry(ref_idx).or_insert(reference.to_string());
-        variant_matrix_map.entry(ref_idx).or_insert(
-            VariantMatrix::new_matrix((short_sample_count + long_sample_count) / references.len()));
-    }
-    info!("{} Longread BAM files and {} Shortread BAM files {} Total BAMs over {} genome(s)",
-          longreads.len(),
-          bam_readers.len(), (short_sample_count + long_sample_count), reference_count);
-
-    match mode {
-        "evolve" => {
-
-            codon_table.get_codon_table(11);
-            ani = 0.;
-
-            let mut gff_reader;
-            if m.is_present("gff") || !m.is_present("gff") {
-                external_command_checker::check_for_prokka();
-
-                // create new fifo and give read, write and execute rights to the owner.
-                let gff_dir = TempDir::new("lorikeet-prokka")
-                    .expect("unable to create prokka directory");
-                for reference in references.iter() {
-                    let cmd_string = format!(
-                    "set -e -o pipefail; \
-                     prokka --cpus {} --outdir {} --prefix {} --force {} {}",
-                    // prodigal
-                    n_threads,
-                    gff_dir.path().to_str()
-                        .expect("Failed to convert tempfile path to str"),
-                    Path::new(&reference).file_stem().unwrap().to_str().unwrap(),
-                    m.value_of("prokka-params").unwrap_or(""),
-                    &reference);
-                    info!("Queuing cmd_string: {}", cmd_string);
-                    command::finish_command_safely(
-                        std::process::Command::new("bash")
-                            .arg("-c")
-                            .arg(&cmd_string)
-                            .stdout(Stdio::piped())
-                            .stderr(Stdio::piped())
-                            .spawn()
-                            .expect("Unable to execute bash"), "prokka");
-
-                    // Read in newly created gff
-                    gff_reader = gff::Reader::from_file(format!("{}/{}.gff", gff_dir.path().to_str()
-                        .expect("Failed to convert tempfile path to str"),
-                                                                Path::new(&reference).file_stem().unwrap().to_str().unwrap()),
-                                                        bio::io::gff::GffType::GFF3)
-                        .expect("Failed to read prokka output");
-
-                    // Map to reference id
-                    gff_reader.records().into_iter().for_each(|record| {
-                        match record {
-                            Ok(rec) => {
-                                let gff_ref = gff_map.entry(
-                                    genomes_and_contigs.genome_index(
-                                        &Path::new(reference)
-                                            .file_stem().expect("problem determining file stem").to_str().unwrap().to_string()).unwrap()).or_insert(HashMap::new());
-                                let contig_genes = gff_ref.entry(rec.seqname().to_owned())
-                                    .or_insert(Vec::new());
-                                contig_genes.push(rec);
-                            },
-                            _ => {},
-                        };
-                    });
-                }
-
-                gff_dir.close().expect("Failed to close temp directory");
-
-            }
-
-        },
-        "genotype" | "summarize" => {
-            if m.is_present("strain-ani") {
-                ani = parse_percentage(m, "strain-ani");
-            }
-        },
-        _ => {
-//            min_cluster_size = m.value_of("min-cluster-size").unwrap().parse().unwrap();
-//            epsilon = m.value_of("epsilon").unwrap().parse().unwrap();
-        }
-    }
-
-
-
-    let mut sample_groups = HashMap::new();
-
-    info!("Running SNP calling on {} shortread samples", bam_readers.len());
-    let mut prev_ref_idx = -1;
-    let mut per_ref_sample_idx = 0;
-    bam_readers.into_iter().enumerate().for_each(|(sample_idx, bam_generator)|{
-        // Get the appropriate sample index based on how many references we are using
-        process_vcf(bam_generator,
-                    n_threads,
-                    &mut prev_ref_idx,
-                    &mut per_ref_sample_idx,
-                    (short_sample_count + long_sample_count) / references.len(),
-                    &mut variant_matrix_map,
-                    false,
-                    m,
-                    &mut sample_groups,
-                    &genomes_and_contigs,
-                    &reference_map,
-                    &(short_sample_count / references.len()))
-    });
-
-    if m.is_present("include-longread-svs") && (m.is_present("longreads") | m.is_present("longread-bam-files")){
-//        long_threads = std::cmp::max(n_threads / longreads.len(), 1);
-        info!("Running structural variant detection...");
-        // Get the appropriate sample index based on how many references we are using by tracking
-        // changes in references
-        let mut prev_ref_idx = -1;
-        let mut per_ref_sample_idx = 0;
-        longreads.into_iter().enumerate().for_each(|(sample_idx, bam_generator)| {
-            process_vcf(bam_generator,
-                        n_threads,
-                        &mut prev_ref_idx,
-                        &mut per_ref_sample_idx,
-                        (short_sample_count + long_sample_count) / references.len(),
-                        &mut variant_matrix_map,
-                        true,
-                        m,
-                        &mut sample_groups,
-                        &genomes_and_contigs,
-                        &reference_map,
-                        &(short_sample_count / references.len()))
-        });
-    } else if m.is_present("longreads") | m.is_present("longread-bam-files") {
-        // We need update the variant matrix anyway
-        let mut prev_ref_idx = -1;
-        let mut per_ref_sample_idx = 0;
-        longreads.into_iter().enumerate().for_each(|(sample_idx, bam_generator)| {
-            let bam_generated = bam_generator.start();
-            let header = bam_generated.header().clone(); // bam header
-            let target_names = header.target_names(); // contig names
-            let mut variant_map = HashMap::new();
-
-            let stoit_name = bam_generated.name().to_string().replace("/", ".");
-            debug!("Stoit_name {:?}", &stoit_name);
-
-            let group = sample_groups.entry("long").or_insert(HashSet::new());
-            group.insert(stoit_name.clone());
-
-            let reference_stem = genomes_and_contigs.genome_of_contig(
-                &str::from_utf8(&target_names[0]).unwrap().to_string()).unwrap();
-            let ref_idx = genomes_and_contigs.genome_index(&reference_stem).unwrap() as i32;
-            if ref_idx == prev_ref_idx {
-                per_ref_sample_idx += 1;
-            } else {
-                prev_ref_idx = ref_idx;
-                per_ref_sample_idx = 0;
-            }
-            let sample_count_l = (short_sample_count + long_sample_count) / references.len();
-
-            let mut variant_matrix = variant_matrix_map.entry(ref_idx as usize)
-                .or_insert(VariantMatrix::new_matrix(sample_count_l));
-            variant_matrix.
-                add_sample(stoit_name,
-                           ((short_sample_count / references.len()) as i32 + per_ref_sample_idx) as usize, &variant_map, &header);
-
-        });
-
-    }
-
-
-    // Annoyingly read in bam file again
-    let mut bam_readers = vec!();
-
-    // This is going to catch cached longread bam files from mapping
-    if m.is_present("bam-files") {
-        let bam_paths = m.values_of("bam-files").unwrap().collect::<Vec<&str>>();
-        bam_readers = generate_named_bam_readers_from_bam_files(bam_paths);
-
-    } else {
-
-        let mut all_bam_paths = vec!();
-
-        for ref_name in genomes_and_contigs.genomes.iter() {
-            let cache = format!("{}/{}*.bam",
-                                match m.is_present("bam-file-cache-directory") {
-                                    false => {
-                                        tmp_bam_file_cache.as_ref().unwrap().path().to_str().unwrap()
-                                    },
-                                    true => {
-                                        m.value_of("bam-file-cache-directory").unwrap()
-                                    }
-                                },
-                                ref_name);
-            let bam_paths = glob(&cache).expect("Failed to read cache")
-                .map(|p| p.expect("Failed to read cached bam path")
-                    .to_str().unwrap().to_string()).collect::<Vec<String>>();
-            all_bam_paths.extend(bam_paths);
-        }
-        let all_bam_paths = all_bam_paths.iter().map(|p| p.as_str()).collect::<Vec<&str>>();
-        let bam_cnts = all_bam_paths.len();
-        bam_readers = generate_named_bam_readers_from_bam_files(all_bam_paths);
-    }
-
-    // Process Short Read BAMs
-    if bam_readers.len() > 0 {
-        info!("Performing guided variant calling...");
-        bam_readers.into_iter().enumerate().for_each(|(sample_idx, bam_generator)| {
-
-            // Get the appropriate sample index based on how many references we are using
-            process_bam(bam_generator,
-                        (short_sample_count + long_sample_count) / references.len(),
-                        &mut coverage_estimators,
-                        &mut variant_matrix_map,
-                        &mut gff_map,
-                        n_threads,
-                        m,
-                        output_prefix,
-                        coverage_fold,
-                        &codon_table,
-                        min_var_depth,
-                        contig_end_exclusion,
-                        min, max, ani,
-                        mode,
-                        include_soft_clipping,
-                        include_indels,
-                        &flag_filters,
-                        mapq_threshold, method, &sample_groups, &genomes_and_contigs, &reference_map)
-        });
-    }
-
-    // Process Long Read BAMs if they are present
-    if m.is_present("longread-bam-files") {
-
-        let longreads_path = m.values_of("longread-bam-files").unwrap().collect::<Vec<&str>>();
-        let longreads = generate_named_bam_readers_from_bam_files(longreads_path);
-        longreads.into_iter().enumerate().for_each(|(sample_idx, bam_generator)| {
-
-            process_bam(bam_generator,
-                        (short_sample_count + long_sample_count) / references.len(),
-                        &mut coverage_estimators,
-                        &mut variant_matrix_map,
-                        &mut gff_map,
-                        n_threads,
-                        m,
-                        output_prefix,
-                        coverage_fold,
-                        &codon_table,
-                        min_var_depth,
-                        contig_end_exclusion,
-                        min, max, ani,
-                        mode,
-                        include_soft_clipping,
-                        include_indels,
-                        &flag_filters,
-                        mapq_threshold, method, &sample_groups, &genomes_and_contigs, &reference_map)
-        });
-    }
-
-    variant_matrix_map.iter_mut().for_each(|(ref_idx, variant_matrix)|{
-        let per_ref_output_pre = format!("{}/{}", &output_prefix, &genomes_and_contigs.genomes[*ref_idx]);
-
-        if mode == "genotype" {
-            variant_matrix.generate_distances(n_threads, &per_ref_output_pre);
-            let e_min: f64 = m.value_of("e-min").unwrap().parse().unwrap();
-            let e_max: f64 = m.value_of("e-max").unwrap().parse().unwrap();
-            let pts_min: f64 = m.value_of("pts-min").unwrap().parse().unwrap();
-            let pts_max: f64 = m.value_of("pts-max").unwrap().parse().unwrap();
-            let phi: f64 = m.value_of("phi").unwrap().parse().unwrap();
-            let anchor_size: usize = m.value_of("minimum-seed-size").unwrap().parse().unwrap();
-            let anchor_similarity: f64 = m.value_of("maximum-seed-similarity").unwrap().parse().unwrap();
-            let minimum_reads_in_link: usize = m.value_of("minimum-reads-in-link").unwrap().parse().unwrap();
-            let reference_path = reference_map.get(&ref_idx).expect("Unable to retrieve reference path");
-            let mut reference = match bio::io::fasta::IndexedReader::from_file(&Path::new(&reference_path)) {
-                Ok(reader) => reader,
-                Err(_e) => generate_faidx(&reference_path),
-            };
-
-
-            variant_matrix.run_fuzzy_scan(e_min, e_max, pts_min, pts_max, phi,
-                                          anchor_size, anchor_similarity, minimum_reads_in_link);
-            variant_matrix.generate_genotypes(&per_ref_output_pre, &mut reference);
-            variant_matrix.write_vcf(&per_ref_output_pre);
-            if m.is_present("plot") {
-                let window_size = m.value_of("window-size").unwrap().parse().unwrap();
-                variant_matrix.print_variant_stats(&per_ref_output_pre, window_size);
-            }
-        } else if mode == "summarize" {
-            let window_size = m.value_of("window-size").unwrap().parse().unwrap();
-            variant_matrix.write_vcf(&per_ref_output_pre);
-            variant_matrix.print_variant_stats(&per_ref_output_pre, window_size);
-        } else if mode == "evolve" {
-            let reference_path = reference_map.get(&ref_idx).expect("Unable to retrieve reference path");
-            let mut reference = match bio::io::fasta::IndexedReader::from_file(&Path::new(&reference_path)) {
-                Ok(reader) => reader,
-                Err(_e) => generate_faidx(&reference_path),
-            };
-            let mut gff_ref = gff_map.get_mut(ref_idx).expect(&format!("No GFF records for reference {:?}", reference_path));
-            variant_matrix.calc_gene_mutation(&mut gff_ref, &mut reference, &codon_table,
-                                              Path::new(&reference_path).file_stem().unwrap().to_str().unwrap(), &per_ref_output_pre)
-        }
-    });
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::str;
-    use std::fs::File;
-    use coverm::mapping_parameters::*;
-    use coverm::shard_bam_reader::*;
-    use coverm::genome_exclusion::*;
-    use coverm::bam_generator::*;
-//
-//    fn test_with_stream<R: NamedBamReader + Send,
-//        G: NamedBamReaderGenerator<R> + Send>(
-//        expected: &str,
-//        bam_readers: Vec<G>,
-//        mut reference: bio::io::fasta::IndexedReader<File>,
-//        proper_pairs_only: bool,
-//        n_threads: usize,
-//        coverage_fold: f32,
-//        min_var_depth: usize,
-//        min: f32,
-//        max: f32,
-//        mode: &str,
-//        include_indels: bool,
-//        include_soft_clipping: bool) {
-////        let mut stream = Cursor::new(Vec::new());
-//        {
-//            reads_mapped_vec = variant_variants(
-//                bam_readers,
-//                &mut coverage_taker,
-//                coverage_estimators,
-//                print_zero_coverage_contigs,
-//                flag_filters,
-//                false,
-//            );
-//        }
-////        assert_eq!(expected, str::from_utf8(stream.get_ref()).unwrap());
-//    }
+use std;
+use std::collections::{HashMap, HashSet};
+use glob::glob;
+
+use external_command_checker;
+use estimation::variant_matrix::*;
+use estimation::vcfs::process_vcf::*;
+use estimation::bams::process_bam::*;
+use estimation::codon_structs::*;
+use estimation::annotations::*;
+use estimation::gene_calling::*;
+use estimation::read_assignment::*;
+use estimation::cluster_refinement::*;
+use estimation::phasing::*;
+use dbscan::fuzzy;
+use coverm::bam_generator::*;
+use bird_tool_utils::{command};
+use utils::*;
+
+use crate::*;
+use std::str;
+use coverm::mosdepth_genome_coverage_estimators::*;
+use coverm::FlagFilter;
+use bio::io::gff;
+use tempdir::TempDir;
+use std::process::Stdio;
+use std::path::Path;
+use coverm::genomes_and_contigs::GenomesAndContigs;
+
+#[allow(unused)]
+pub fn pileup_variants<R: NamedBamReader,
+    G: NamedBamReaderGenerator<R>,
+    S: NamedBamReader,
+    U: NamedBamReaderGenerator<S>>(
+    m: &clap::ArgMatches,
+    bam_readers: Vec<G>,
+    longreads: Option<Vec<U>>,
+    mode: &str,
+    coverage_estimators: &mut Vec<CoverageEstimator>,
+    flag_filters: FlagFilter,
+    mapq_threshold: u8,
+    min_var_depth: usize,
+    min: f32, max: f32,
+    contig_end_exclusion: u64,
+    output_prefix: &str,
+    n_threads: usize,
+    method: &str,
+    coverage_fold: f32,
+    include_indels: bool,
+    include_soft_clipping: bool,
+    is_long_read: bool,
+    genomes_and_contigs: GenomesAndContigs,
+    tmp_bam_file_cache: Option<TempDir>,
+) {
+
+    let references = parse_references(&m);
+    let references = references.iter().map(|p| &**p).collect::<Vec<&str>>();
+
+    let mut short_sample_count = bam_readers.len();
+    let mut long_sample_count = 0;
+    let mut reference_count = references.len();
+    let mut coverage_estimators = coverage_estimators;
+    let mut ani = 0.;
+
+    // Gff map lists coding regions
+    let mut gff_map = HashMap::new();
+    let mut codon_table = CodonTable::setup();
+    // Genomes using a different translation table than --translation-table
+    let mut genome_codon_tables = HashMap::new();
+
+    let longreads = match longreads {
+        Some(vec) => {
+            long_sample_count += vec.len();
+            vec
+        },
+        None => {
+            vec!()
+        }
+    };
+
+    let mut variant_matrix_map = HashMap::new();
+    // Put reference index in the variant map and initialize matrix
+    let mut reference_map = HashMap::new();
+    for reference in references.iter() {
+        debug!("Genomes {:?} contigs {:?}", &genomes_and_contigs.genomes, &genomes_and_contigs.contig_to_genome);
+        let ref_idx = genomes_and_contigs.genome_index(&Path::new(reference)
+            .file_stem().expect("problem determining file stem").to_str().unwrap().to_string()).unwrap();
+        reference_map.entry(ref_idx).or_insert(reference.to_string());
+        variant_matrix_map.entry(ref_idx).or_insert(
+            VariantMatrix::new_matrix((short_sample_count + long_sample_count) / references.len()));
+    }
+    // Samples from previous runs are loaded from their variant matrix checkpoints and the new
+    // samples are appended after them
+    let mut previous_sample_counts = HashMap::new();
+    if m.is_present("matrix") {
+        for matrix_path in m.values_of("matrix").unwrap() {
+            info!("Loading variant matrix from {}", matrix_path);
+            let mut variant_matrix = VariantMatrix::from_checkpoint(matrix_path);
+            let (ref_idx, previous_count) = match &variant_matrix {
+                VariantMatrix::VariantContigMatrix {
+                    target_names,
+                    sample_names,
+                    ..
+                } => {
+                    let contig_name = target_names.values().next()
+                        .expect(&format!("Variant matrix {} contains no contigs", matrix_path));
+                    let reference_stem = genomes_and_contigs.genome_of_contig(contig_name)
+                        .expect(&format!("Variant matrix {} does not match any provided reference", matrix_path));
+                    (genomes_and_contigs.genome_index(&reference_stem).unwrap(), sample_names.len())
+                }
+            };
+            variant_matrix.extend_samples(
+                previous_count + (short_sample_count + long_sample_count) / references.len());
+            info!("Appending {} new samples to {} previous samples for {}",
+                  (short_sample_count + long_sample_count) / references.len(),
+                  previous_count, &genomes_and_contigs.genomes[ref_idx]);
+            previous_sample_counts.insert(ref_idx, previous_count);
+            variant_matrix_map.insert(ref_idx, variant_matrix);
+        }
+    }
+
+    info!("{} Longread BAM files and {} Shortread BAM files {} Total BAMs over {} genome(s)",
+          longreads.len(),
+          bam_readers.len(), (short_sample_count + long_sample_count), reference_count);
+
+    match mode {
+        "evolve" => {
+            let (default_codon_table, codon_tables, annotations) =
+                annotate_genomes(m, &references, &genomes_and_contigs, n_threads);
+            codon_table = default_codon_table;
+            genome_codon_tables = codon_tables;
+            gff_map = annotations;
+            ani = 0.;
+        },
+        "genotype" | "summarize" => {
+            if m.is_present("strain-ani") {
+                ani = parse_percentage(m, "strain-ani");
+            }
+            if m.is_present("strain-dnds") || m.is_present("variant-effects") {
+                let (default_codon_table, codon_tables, annotations) =
+                    annotate_genomes(m, &references, &genomes_and_contigs, n_threads);
+                codon_table = default_codon_table;
+                genome_codon_tables = codon_tables;
+                gff_map = annotations;
+            }
+        },
+        _ => {
+//            min_cluster_size = m.value_of("min-cluster-size").unwrap().parse().unwrap();
+//            epsilon = m.value_of("epsilon").unwrap().parse().unwrap();
+        }
+    }
+
+
+
+    let mut sample_groups = HashMap::new();
+
+    info!("Running SNP calling on {} shortread samples", bam_readers.len());
+    let mut prev_ref_idx = -1;
+    let mut per_ref_sample_idx = 0;
+    bam_readers.into_iter().enumerate().for_each(|(sample_idx, bam_generator)|{
+        // Get the appropriate sample index based on how many references we are using
+        process_vcf(bam_generator,
+                    n_threads,
+                    &mut prev_ref_idx,
+                    &mut per_ref_sample_idx,
+                    (short_sample_count + long_sample_count) / references.len(),
+                    &mut variant_matrix_map,
+                    false,
+                    m,
+                    &mut sample_groups,
+                    &genomes_and_contigs,
+                    &reference_map,
+                    &(short_sample_count / references.len()),
+                    &previous_sample_counts)
+    });
+
+    if m.is_present("include-longread-svs") && (m.is_present("longreads") | m.is_present("longread-bam-files")){
+//        long_threads = std::cmp::max(n_threads / longreads.len(), 1);
+        info!("Running structural variant detection...");
+        // Get the appropriate sample index based on how many references we are using by tracking
+        // changes in references
+        let mut prev_ref_idx = -1;
+        let mut per_ref_sample_idx = 0;
+        longreads.into_iter().enumerate().for_each(|(sample_idx, bam_generator)| {
+            process_vcf(bam_generator,
+                        n_threads,
+                        &mut prev_ref_idx,
+                        &mut per_ref_sample_idx,
+                        (short_sample_count + long_sample_count) / references.len(),
+                        &mut variant_matrix_map,
+                        true,
+                        m,
+                        &mut sample_groups,
+                        &genomes_and_contigs,
+                        &reference_map,
+                        &(short_sample_count / references.len()),
+                        &previous_sample_counts)
+        });
+    } else if m.is_present("longreads") | m.is_present("longread-bam-files") {
+        // We need update the variant matrix anyway
+        let mut prev_ref_idx = -1;
+        let mut per_ref_sample_idx = 0;
+        longreads.into_iter().enumerate().for_each(|(sample_idx, bam_generator)| {
+            let bam_generated = bam_generator.start();
+            let header = bam_generated.header().clone(); // bam header
+            let target_names = header.target_names(); // contig names
+            let mut variant_map = HashMap::new();
+
+            let stoit_name = bam_generated.name().to_string().replace("/", ".");
+            debug!("Stoit_name {:?}", &stoit_name);
+
+            let group = sample_groups.entry("long").or_insert(HashSet::new());
+            group.insert(stoit_name.clone());
+
+            let reference_stem = genomes_and_contigs.genome_of_contig(
+                &str::from_utf8(&target_names[0]).unwrap().to_string()).unwrap();
+            let ref_idx = genomes_and_contigs.genome_index(&reference_stem).unwrap() as i32;
+            if ref_idx == prev_ref_idx {
+                per_ref_sample_idx += 1;
+            } else {
+                prev_ref_idx = ref_idx;
+                per_ref_sample_idx = 0;
+            }
+            let sample_offset = *previous_sample_counts.get(&(ref_idx as usize)).unwrap_or(&0);
+            let sample_count_l = (short_sample_count + long_sample_count) / references.len() + sample_offset;
+
+            let mut variant_matrix = variant_matrix_map.entry(ref_idx as usize)
+                .or_insert(VariantMatrix::new_matrix(sample_count_l));
+            variant_matrix.
+                add_sample(stoit_name,
+                           sample_offset + ((short_sample_count / references.len()) as i32 + per_ref_sample_idx) as usize, &variant_map, &header);
+
+        });
+
+    }
+
+
+    // Annoyingly read in bam file again
+    let mut bam_readers = vec!();
+    // Kept for assigning reads to strains after clustering
+    let mut assignment_bam_paths: Vec<String> = Vec::new();
+    // Kept for phasing variants with long reads
+    let mut longread_bam_paths: Vec<String> = Vec::new();
+
+    // This is going to catch cached longread bam files from mapping
+    if m.is_present("bam-files") {
+        let bam_paths = m.values_of("bam-files").unwrap().collect::<Vec<&str>>();
+        assignment_bam_paths.extend(bam_paths.iter().map(|p| p.to_string()));
+        bam_readers = generate_named_bam_readers_from_bam_files(bam_paths);
+
+    } else {
+
+        let mut all_bam_paths = vec!();
+
+        for ref_name in genomes_and_contigs.genomes.iter() {
+            let cache = format!("{}/{}*.bam",
+                                match m.is_present("bam-file-cache-directory") {
+                                    false => {
+                                        tmp_bam_file_cache.as_ref().unwrap().path().to_str().unwrap()
+                                    },
+                                    true => {
+                                        m.value_of("bam-file-cache-directory").unwrap()
+                                    }
+                                },
+                                ref_name);
+            let bam_paths = glob(&cache).expect("Failed to read cache")
+                .map(|p| p.expect("Failed to read cached bam path")
+                    .to_str().unwrap().to_string()).collect::<Vec<String>>();
+            all_bam_paths.extend(bam_paths);
+        }
+        assignment_bam_paths.extend(all_bam_paths.iter().cloned());
+        let all_bam_paths = all_bam_paths.iter().map(|p| p.as_str()).collect::<Vec<&str>>();
+        let bam_cnts = all_bam_paths.len();
+        bam_readers = generate_named_bam_readers_from_bam_files(all_bam_paths);
+    }
+
+    // Process Short Read BAMs
+    if bam_readers.len() > 0 {
+        info!("Performing guided variant calling...");
+        bam_readers.into_iter().enumerate().for_each(|(sample_idx, bam_generator)| {
+
+            // Get the appropriate sample index based on how many references we are using
+            process_bam(bam_generator,
+                        (short_sample_count + long_sample_count) / references.len(),
+                        &mut coverage_estimators,
+                        &mut variant_matrix_map,
+                        &mut gff_map,
+                        n_threads,
+                        m,
+                        output_prefix,
+                        coverage_fold,
+                        &codon_table,
+                        min_var_depth,
+                        contig_end_exclusion,
+                        min, max, ani,
+                        mode,
+                        include_soft_clipping,
+                        include_indels,
+                        &flag_filters,
+                        mapq_threshold, method, &sample_groups, &genomes_and_contigs, &reference_map)
+        });
+    }
+
+    // Process Long Read BAMs if they are present
+    if m.is_present("longread-bam-files") {
+
+        let longreads_path = m.values_of("longread-bam-files").unwrap().collect::<Vec<&str>>();
+        assignment_bam_paths.extend(longreads_path.iter().map(|p| p.to_string()));
+        longread_bam_paths.extend(longreads_path.iter().map(|p| p.to_string()));
+        let longreads = generate_named_bam_readers_from_bam_files(longreads_path);
+        longreads.into_iter().enumerate().for_each(|(sample_idx, bam_generator)| {
+
+            process_bam(bam_generator,
+                        (short_sample_count + long_sample_count) / references.len(),
+                        &mut coverage_estimators,
+                        &mut variant_matrix_map,
+                        &mut gff_map,
+                        n_threads,
+                        m,
+                        output_prefix,
+                        coverage_fold,
+                        &codon_table,
+                        min_var_depth,
+                        contig_end_exclusion,
+                        min, max, ani,
+                        mode,
+                        include_soft_clipping,
+                        include_indels,
+                        &flag_filters,
+                        mapq_threshold, method, &sample_groups, &genomes_and_contigs, &reference_map)
+        });
+    }
+
+    variant_matrix_map.iter_mut().for_each(|(ref_idx, variant_matrix)|{
+        let per_ref_output_pre = format!("{}/{}", &output_prefix, &genomes_and_contigs.genomes[*ref_idx]);
+
+        let genome_codon_table = genome_codon_tables.get(ref_idx).unwrap_or(&codon_table);
+        // Genes the VCF annotates the effects of variants on
+        let effect_genes = if m.is_present("variant-effects") {
+            match gff_map.get(ref_idx) {
+                Some(gff_ref) => Some((gff_ref, genome_codon_table)),
+                None => {
+                    warn!("No genes found for {}, skipping variant effect annotation",
+                          &genomes_and_contigs.genomes[*ref_idx]);
+                    None
+                },
+            }
+        } else {
+            None
+        };
+
+        if mode == "genotype" {
+            let reference_path = reference_map.get(&ref_idx).expect("Unable to retrieve reference path");
+            if m.is_present("write-checkpoint") {
+                variant_matrix.write_checkpoint(&per_ref_output_pre);
+            }
+            genotype_variants(variant_matrix, m, reference_path, &per_ref_output_pre, n_threads,
+                              &assignment_bam_paths, &longread_bam_paths, effect_genes);
+            if m.is_present("strain-dnds") {
+                let mut reference = match bio::io::fasta::IndexedReader::from_file(&Path::new(&reference_path)) {
+                    Ok(reader) => reader,
+                    Err(_e) => generate_faidx(&reference_path),
+                };
+                match gff_map.get(ref_idx) {
+                    Some(gff_ref) => variant_matrix.calc_selection(gff_ref, &mut reference, genome_codon_table,
+                                                                   &per_ref_output_pre),
+                    None => warn!("No genes found for reference {}, skipping dN/dS", reference_path),
+                }
+            }
+        } else if mode == "summarize" {
+            let window_size = m.value_of("window-size").unwrap().parse().unwrap();
+            match effect_genes {
+                Some((gff_ref, genome_codon_table)) => {
+                    let reference_path = reference_map.get(&ref_idx).expect("Unable to retrieve reference path");
+                    let mut reference = match bio::io::fasta::IndexedReader::from_file(&Path::new(&reference_path)) {
+                        Ok(reader) => reader,
+                        Err(_e) => generate_faidx(&reference_path),
+                    };
+                    variant_matrix.write_vcf(&per_ref_output_pre,
+                                             Some((gff_ref, genome_codon_table, &mut reference)));
+                },
+                None => variant_matrix.write_vcf(&per_ref_output_pre, None),
+            }
+            variant_matrix.print_variant_stats(&per_ref_output_pre, window_size);
+        } else if mode == "evolve" {
+            let reference_path = reference_map.get(&ref_idx).expect("Unable to retrieve reference path");
+            let mut reference = match bio::io::fasta::IndexedReader::from_file(&Path::new(&reference_path)) {
+                Ok(reader) => reader,
+                Err(_e) => generate_faidx(&reference_path),
+            };
+            let mut gff_ref = gff_map.get_mut(ref_idx).expect(&format!("No GFF records for reference {:?}", reference_path));
+            variant_matrix.calc_gene_mutation(&mut gff_ref, &mut reference, genome_codon_table,
+                                              Path::new(&reference_path).file_stem().unwrap().to_str().unwrap(), &per_ref_output_pre);
+            variant_matrix.calc_selection(gff_ref, &mut reference, genome_codon_table, &per_ref_output_pre);
+        }
+    });
+}
+
+/// Loads the translation tables and gene annotations used to measure selection. Genes of
+/// genomes without annotations given by --gff are predicted. Returns the default codon table,
+/// the codon tables of genomes with their own translation table and the genes of each genome
+/// keyed by contig name
+fn annotate_genomes(m: &clap::ArgMatches,
+                    references: &Vec<&str>,
+                    genomes_and_contigs: &GenomesAndContigs,
+                    n_threads: usize)
+    -> (CodonTable, HashMap<usize, CodonTable>, HashMap<usize, HashMap<String, Vec<gff::Record>>>) {
+    let mut codon_table = CodonTable::setup();
+    // Genomes using a different translation table than --translation-table
+    let mut genome_codon_tables = HashMap::new();
+    let mut gff_map = HashMap::new();
+
+    let translation_table: usize = m.value_of("translation-table").unwrap().parse()
+        .expect("Unable to parse --translation-table");
+    codon_table.get_codon_table(translation_table);
+    if m.is_present("translation-table-file") {
+        let genome_tables = parse_translation_table_file(
+            m.value_of("translation-table-file").unwrap());
+        for (genome, table_id) in genome_tables.into_iter() {
+            match genomes_and_contigs.genome_index(&genome) {
+                Some(ref_idx) => {
+                    info!("Using translation table {} for genome {}", table_id, &genome);
+                    let mut genome_codon_table = CodonTable::setup();
+                    genome_codon_table.get_codon_table(table_id);
+                    genome_codon_tables.insert(ref_idx, genome_codon_table);
+                },
+                None => {
+                    warn!("Genome {} in the translation table file does not match any reference, ignoring it",
+                          &genome);
+                },
+            }
+        }
+    }
+
+    let mut gff_reader;
+    if m.is_present("gff") {
+        let annotation_paths = m.values_of("gff").unwrap().collect::<Vec<&str>>();
+        gff_map = read_genome_annotations(&annotation_paths, &genomes_and_contigs);
+    }
+
+    // Genes of genomes without provided annotations are predicted with the native gene
+    // caller or prokka
+    let unannotated_references = references.iter().filter(|reference| {
+        let ref_idx = genomes_and_contigs.genome_index(&Path::new(reference)
+            .file_stem().expect("problem determining file stem").to_str().unwrap().to_string()).unwrap();
+        !gff_map.contains_key(&ref_idx)
+    }).collect::<Vec<&&str>>();
+    let gene_caller = m.value_of("gene-caller").unwrap();
+    if unannotated_references.len() > 0 && m.is_present("gff") {
+        info!("No annotations provided for {} genome(s), predicting their genes with {}",
+              unannotated_references.len(), gene_caller);
+    }
+    if unannotated_references.len() > 0 && gene_caller == "native" {
+        for reference in unannotated_references.iter() {
+            let ref_idx = genomes_and_contigs.genome_index(&Path::new(reference)
+                .file_stem().expect("problem determining file stem").to_str().unwrap().to_string()).unwrap();
+            let genome_codon_table = genome_codon_tables.get(&ref_idx).unwrap_or(&codon_table);
+            let genes = predict_genes(&read_contigs(reference), genome_codon_table);
+            info!("Predicted {} genes in {}", genes.len(), reference);
+
+            let gff_ref = gff_map.entry(ref_idx).or_insert(HashMap::new());
+            for gene in genes.into_iter() {
+                gff_ref.entry(gene.seqname().to_owned()).or_insert(Vec::new()).push(gene);
+            }
+        }
+    } else if unannotated_references.len() > 0 {
+        external_command_checker::check_for_prokka();
+
+        // create new fifo and give read, write and execute rights to the owner.
+        let gff_dir = TempDir::new("lorikeet-prokka")
+            .expect("unable to create prokka directory");
+        for reference in unannotated_references.iter() {
+            let cmd_string = format!(
+            "set -e -o pipefail; \
+             prokka --cpus {} --outdir {} --prefix {} --force {} {}",
+            // prodigal
+            n_threads,
+            gff_dir.path().to_str()
+                .expect("Failed to convert tempfile path to str"),
+            Path::new(&reference).file_stem().unwrap().to_str().unwrap(),
+            m.value_of("prokka-params").unwrap_or(""),
+            &reference);
+            info!("Queuing cmd_string: {}", cmd_string);
+            command::finish_command_safely(
+                std::process::Command::new("bash")
+                    .arg("-c")
+                    .arg(&cmd_string)
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::piped())
+                    .spawn()
+                    .expect("Unable to execute bash"), "prokka");
+
+            // Read in newly created gff
+            gff_reader = gff::Reader::from_file(format!("{}/{}.gff", gff_dir.path().to_str()
+                .expect("Failed to convert tempfile path to str"),
+                                                        Path::new(&reference).file_stem().unwrap().to_str().unwrap()),
+                                                bio::io::gff::GffType::GFF3)
+                .expect("Failed to read prokka output");
+
+            // Map to reference id
+            gff_reader.records().into_iter().for_each(|record| {
+                match record {
+                    Ok(rec) => {
+                        let gff_ref = gff_map.entry(
+                            genomes_and_contigs.genome_index(
+                                &Path::new(reference)
+                                    .file_stem().expect("problem determining file stem").to_str().unwrap().to_string()).unwrap()).or_insert(HashMap::new());
+                        let contig_genes = gff_ref.entry(rec.seqname().to_owned())
+                            .or_insert(Vec::new());
+                        contig_genes.push(rec);
+                    },
+                    _ => {},
+                };
+            });
+        }
+
+        gff_dir.close().expect("Failed to close temp directory");
+
+    }
+
+    (codon_table, genome_codon_tables, gff_map)
+}
+
+/// Reruns clustering and genotyping on a variant matrix checkpoint written by a previous
+/// genotype run, skipping variant calling and read assignment
+pub fn cluster_checkpoint(m: &clap::ArgMatches, output_prefix: &str, n_threads: usize) {
+    let matrix_path = m.value_of("matrix").unwrap();
+    let reference_path = m.value_of("reference").unwrap();
+    let per_ref_output_pre = format!("{}/{}", output_prefix,
+                                     Path::new(reference_path).file_stem().unwrap().to_str().unwrap());
+
+    info!("Loading variant matrix from {}", matrix_path);
+    let mut variant_matrix = VariantMatrix::from_checkpoint(matrix_path);
+    genotype_variants(&mut variant_matrix, m, reference_path, &per_ref_output_pre, n_threads,
+                      &vec!(), &vec!(), None);
+}
+
+/// Clusters the variants in a matrix and writes the resulting genotypes and VCF. If genes and
+/// their codon table are given, the VCF annotates the effect of each variant on them
+fn genotype_variants(variant_matrix: &mut VariantMatrix,
+                     m: &clap::ArgMatches,
+                     reference_path: &str,
+                     per_ref_output_pre: &str,
+                     n_threads: usize,
+                     bam_paths: &Vec<String>,
+                     longread_bam_paths: &Vec<String>,
+                     effect_genes: Option<(&HashMap<String, Vec<gff::Record>>, &CodonTable)>) {
+    variant_matrix.generate_distances(n_threads, per_ref_output_pre);
+    let anchor_size: usize = m.value_of("minimum-seed-size").unwrap().parse().unwrap();
+    let anchor_similarity: f64 = m.value_of("maximum-seed-similarity").unwrap().parse().unwrap();
+    let minimum_reads_in_link: usize = m.value_of("minimum-reads-in-link").unwrap().parse().unwrap();
+    let clustering_method = m.value_of("clustering-method").unwrap();
+    let distance = fuzzy::DistanceMetric::generate_from_clap(m);
+    let (e_min, e_max, pts_min, pts_max, phi) = if m.is_present("auto-parameters") {
+        variant_matrix.tune_fuzzy_parameters(per_ref_output_pre, anchor_size, anchor_similarity,
+                                             minimum_reads_in_link,
+                                             m.value_of("auto-parameters-criterion").unwrap(),
+                                             clustering_method, distance,
+                                             (m.value_of("e-min").unwrap().parse().unwrap(),
+                                              m.value_of("e-max").unwrap().parse().unwrap(),
+                                              m.value_of("pts-min").unwrap().parse().unwrap(),
+                                              m.value_of("pts-max").unwrap().parse().unwrap()))
+    } else {
+        (m.value_of("e-min").unwrap().parse().unwrap(),
+         m.value_of("e-max").unwrap().parse().unwrap(),
+         m.value_of("pts-min").unwrap().parse().unwrap(),
+         m.value_of("pts-max").unwrap().parse().unwrap(),
+         m.value_of("phi").unwrap().parse().unwrap())
+    };
+    let mut reference = match bio::io::fasta::IndexedReader::from_file(&Path::new(reference_path)) {
+        Ok(reader) => reader,
+        Err(_e) => generate_faidx(reference_path),
+    };
+
+    let refinement = if m.is_present("cluster-refinement") {
+        Some(ClusterRefinementParameters::generate_from_clap(m))
+    } else {
+        None
+    };
+
+    let max_strains: usize = m.value_of("max-strains").unwrap().parse().unwrap();
+    let strain_count = variant_matrix.estimate_strain_count(per_ref_output_pre, max_strains);
+    let max_clusters = if m.is_present("constrain-strain-count") {
+        Some(strain_count)
+    } else {
+        None
+    };
+
+    // The exported distances are those fuzzy DBSCAN clusters on, so the graph is built once
+    let graph = if m.is_present("export-matrices") {
+        Some(variant_matrix.write_feature_matrices(per_ref_output_pre, distance, e_max))
+    } else {
+        None
+    };
+    let strain_inference = m.value_of("strain-inference").unwrap();
+    if m.is_present("phase-long-reads") || strain_inference == "phasing" {
+        if longread_bam_paths.len() > 0 {
+            let params = PhasingParameters::generate_from_clap(m);
+            variant_matrix.phase_long_reads(per_ref_output_pre, longread_bam_paths, n_threads, &params);
+        } else {
+            warn!("Phasing requires long read BAM files given by --longread-bam-files, skipping phasing");
+        }
+    }
+    match strain_inference {
+        "phasing" => {
+            variant_matrix.genotype_from_phasing();
+        },
+        "deconvolution" => {
+            let strains = match m.value_of("deconvolution-strains") {
+                Some(strains) => strains.parse().unwrap(),
+                None => strain_count,
+            };
+            let restarts = m.value_of("deconvolution-restarts").unwrap().parse().unwrap();
+            variant_matrix.run_deconvolution(per_ref_output_pre, strains, restarts);
+        },
+        "read-graph" => {
+            variant_matrix.run_read_graph(anchor_size, anchor_similarity, minimum_reads_in_link);
+        },
+        _ => {
+            variant_matrix.run_clustering(e_min, e_max, pts_min, pts_max, phi,
+                                          anchor_size, anchor_similarity, minimum_reads_in_link,
+                                          clustering_method, distance,
+                                          refinement.as_ref(), max_clusters, graph.as_ref());
+        }
+    }
+    variant_matrix.write_cluster_memberships(per_ref_output_pre);
+    variant_matrix.generate_genotypes(per_ref_output_pre, &mut reference,
+                                      m.value_of("mask-mode").unwrap());
+    variant_matrix.calculate_strain_abundances(per_ref_output_pre);
+    if m.is_present("assign-reads") && bam_paths.len() > 0 {
+        let params = ReadAssignmentParameters::generate_from_clap(m);
+        assign_reads_to_strains(variant_matrix, bam_paths, per_ref_output_pre, n_threads, &params);
+    }
+    let effects = match effect_genes {
+        Some((gff_ref, codon_table)) => Some((gff_ref, codon_table, &mut reference)),
+        None => None,
+    };
+    variant_matrix.write_vcf(per_ref_output_pre, effects);
+    if m.is_present("plot") {
+        let window_size = m.value_of("window-size").unwrap().parse().unwrap();
+        variant_matrix.print_variant_stats(per_ref_output_pre, window_size);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str;
+    use std::fs::File;
+    use coverm::mapping_parameters::*;
+    use coverm::shard_bam_reader::*;
+    use coverm::genome_exclusion::*;
+    use coverm::bam_generator::*;
+//
+//    fn test_with_stream<R: NamedBamReader + Send,
+//        G: NamedBamReaderGenerator<R> + Send>(
+//        expected: &str,
+//        bam_readers: Vec<G>,
+//        mut reference: bio::io::fasta::IndexedReader<File>,
+//        proper_pairs_only: bool,
+//        n_threads: usize,
+//        coverage_fold: f32,
+//        min_var_depth: usize,
+//        min: f32,
+//        max: f32,
+//        mode: &str,
+//        include_indels: bool,
+//        include_soft_clipping: bool) {
+////        let mut stream = Cursor::new(Vec::new());
+//        {
+//            reads_mapped_vec = variant_variants(
+//                bam_readers,
+//                &mut coverage_taker,
+//                coverage_estimators,
+//                print_zero_coverage_contigs,
+//                flag_filters,
+//                false,
+//            );
+//        }
+////        assert_eq!(expected, str::from_utf8(stream.get_ref()).unwrap());
+//    }
 }
\ No newline at end of file
@@ -0,0 +1,313 @@
+use rayon::prelude::*;
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use std::f64::NEG_INFINITY;
+
+/// Chance of observing an allele in a strain that lacks it, or missing it in a strain that has
+/// it, so that sequencing errors do not make a haplotype impossible
+const HAPLOTYPE_EPSILON: f64 = 1e-3;
+
+/// Strain haplotypes and proportions that explain the allele counts of every sample
+#[derive(Debug, Clone)]
+pub struct Deconvolution {
+    /// Posterior probability that each strain carries each allele, indexed by allele then strain
+    pub haplotypes: Vec<Vec<f64>>,
+    /// Proportion of each strain in each sample, indexed by strain then sample
+    pub proportions: Vec<Vec<f64>>,
+    /// Binomial log-likelihood up to the binomial coefficients
+    pub log_likelihood: f64,
+    /// Bayesian information criterion of the fit
+    pub bic: f64,
+}
+
+/// Largest number of strains deconvolution accepts. Every combination of strains carrying the
+/// alleles of a site is considered, which grows exponentially with the strain count
+pub const MAX_DECONVOLUTION_STRAINS: usize = 10;
+
+/// Sites with more allele combinations than this are fitted one strain at a time instead of by
+/// trying every combination
+const MAX_SITE_CONFIGURATIONS: usize = 1 << MAX_DECONVOLUTION_STRAINS;
+
+/// Factorises allele counts into binary strain haplotypes and per-sample strain proportions
+/// in the style of StrainFinder and DESMAN. Each read covering an allele comes from strain k
+/// with the sample's proportion of k and carries the allele if strain k does, up to a small
+/// error. Every strain carries at most one of the alleles of a site. The most likely alleles of
+/// every site and the proportions are updated in turn from several random starts and the most
+/// likely fit is kept.
+///
+/// variant_counts and total_counts hold the reads supporting each allele and the reads covering
+/// its site, indexed by allele then sample. sites groups the indices of alleles at the same
+/// position
+pub fn deconvolve(variant_counts: &Vec<Vec<f64>>, total_counts: &Vec<Vec<f64>>,
+                  sites: &Vec<Vec<usize>>, strains: usize, restarts: usize, seed: u64) -> Deconvolution {
+    let strains = std::cmp::min(std::cmp::max(strains, 1), MAX_DECONVOLUTION_STRAINS);
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut best: Option<Deconvolution> = None;
+    for restart in 0..std::cmp::max(restarts, 1) {
+        let fit = fit_haplotypes(variant_counts, total_counts, sites, strains, &mut rng);
+        debug!("Deconvolution restart {} with {} strains has log-likelihood {}",
+               restart, strains, fit.log_likelihood);
+        if best.as_ref().map(|best| fit.log_likelihood > best.log_likelihood).unwrap_or(true) {
+            best = Some(fit);
+        }
+    }
+    best.unwrap()
+}
+
+/// Log-likelihood of an allele's counts in every sample given which strains carry it
+fn allele_log_likelihood(variant_counts: &Vec<f64>, total_counts: &Vec<f64>,
+                         haplotype: &Vec<f64>, proportions: &Vec<Vec<f64>>) -> f64 {
+    let mut log_likelihood = 0.;
+    for s in 0..total_counts.len() {
+        let n = total_counts[s];
+        if n <= 0. {
+            continue
+        }
+        let x = variant_counts[s].min(n);
+        let frequency = (0..haplotype.len()).map(|k| proportions[k][s] * haplotype[k]).sum::<f64>()
+            .max(HAPLOTYPE_EPSILON).min(1. - HAPLOTYPE_EPSILON);
+        log_likelihood += x * frequency.ln() + (n - x) * (1. - frequency).ln();
+    }
+    log_likelihood
+}
+
+/// Whether each strain carries the allele at position allele_idx of its site, given the
+/// allele each strain carries at the site. Strains carrying another allele, or none of the
+/// site's alleles (0), lack it
+fn allele_haplotype(configuration: &Vec<usize>, allele_idx: usize) -> Vec<f64> {
+    configuration.iter().map(|carried| {
+        if *carried == allele_idx + 1 { 1. - HAPLOTYPE_EPSILON } else { HAPLOTYPE_EPSILON }
+    }).collect()
+}
+
+/// Log-likelihood of the counts of every allele of a site given the allele each strain carries
+fn site_log_likelihood(variant_counts: &Vec<Vec<f64>>, total_counts: &Vec<Vec<f64>>,
+                       site: &Vec<usize>, configuration: &Vec<usize>,
+                       proportions: &Vec<Vec<f64>>) -> f64 {
+    site.iter().enumerate().map(|(allele_idx, v)| {
+        allele_log_likelihood(&variant_counts[*v], &total_counts[*v],
+                              &allele_haplotype(configuration, allele_idx), proportions)
+    }).sum()
+}
+
+/// Every assignment of the site's alleles, or none of them, to each strain
+fn site_configurations(alleles: usize, strains: usize) -> Vec<Vec<usize>> {
+    let options = alleles + 1;
+    let count = options.pow(strains as u32);
+    (0..count).map(|mut configuration| {
+        (0..strains).map(|_| {
+            let carried = configuration % options;
+            configuration /= options;
+            carried
+        }).collect::<Vec<usize>>()
+    }).collect()
+}
+
+/// The most likely allele each strain carries at a site and the probability of each strain
+/// carrying each of its alleles, indexed by allele then strain. Small sites try every
+/// configuration, larger ones change one strain at a time starting from the given configuration
+fn fit_site(variant_counts: &Vec<Vec<f64>>, total_counts: &Vec<Vec<f64>>, site: &Vec<usize>,
+            start: &Vec<usize>, proportions: &Vec<Vec<f64>>) -> (Vec<usize>, f64, Vec<Vec<f64>>) {
+    let strains = start.len();
+    let options = site.len() + 1;
+    let exhaustive = (options as f64).powi(strains as i32) <= MAX_SITE_CONFIGURATIONS as f64;
+    let mut posteriors = vec![vec![0.; strains]; site.len()];
+    if exhaustive {
+        let configurations = site_configurations(site.len(), strains);
+        let log_likelihoods = configurations.iter().map(|configuration| {
+            site_log_likelihood(variant_counts, total_counts, site, configuration, proportions)
+        }).collect::<Vec<f64>>();
+        let (best_idx, best) = log_likelihoods.iter().enumerate()
+            .fold((0, NEG_INFINITY), |best, (idx, ll)| if *ll > best.1 { (idx, *ll) } else { best });
+        let weights = log_likelihoods.iter().map(|ll| (ll - best).exp()).collect::<Vec<f64>>();
+        let total = weights.iter().sum::<f64>();
+        for (configuration, weight) in configurations.iter().zip(weights.iter()) {
+            for (k, carried) in configuration.iter().enumerate() {
+                if *carried > 0 {
+                    posteriors[*carried - 1][k] += weight / total;
+                }
+            }
+        }
+        (configurations[best_idx].clone(), best, posteriors)
+    } else {
+        let mut configuration = start.clone();
+        let mut best = site_log_likelihood(variant_counts, total_counts, site, &configuration, proportions);
+        loop {
+            let mut changed = false;
+            for k in 0..strains {
+                for carried in 0..options {
+                    if carried == configuration[k] {
+                        continue
+                    }
+                    let previous = configuration[k];
+                    configuration[k] = carried;
+                    let ll = site_log_likelihood(variant_counts, total_counts, site, &configuration, proportions);
+                    if ll > best {
+                        best = ll;
+                        changed = true;
+                    } else {
+                        configuration[k] = previous;
+                    }
+                }
+            }
+            if !changed {
+                break
+            }
+        }
+        // Each strain's alleles weighted by their likelihood with the other strains fixed
+        for k in 0..strains {
+            let mut alternative = configuration.clone();
+            let log_likelihoods = (0..options).map(|carried| {
+                alternative[k] = carried;
+                site_log_likelihood(variant_counts, total_counts, site, &alternative, proportions)
+            }).collect::<Vec<f64>>();
+            let max = log_likelihoods.iter().cloned().fold(NEG_INFINITY, f64::max);
+            let weights = log_likelihoods.iter().map(|ll| (ll - max).exp()).collect::<Vec<f64>>();
+            let total = weights.iter().sum::<f64>();
+            for allele_idx in 0..site.len() {
+                posteriors[allele_idx][k] = weights[allele_idx + 1] / total;
+            }
+        }
+        (configuration, best, posteriors)
+    }
+}
+
+fn fit_haplotypes(variant_counts: &Vec<Vec<f64>>, total_counts: &Vec<Vec<f64>>,
+                  sites: &Vec<Vec<usize>>, strains: usize, rng: &mut StdRng) -> Deconvolution {
+    let sample_count = total_counts.first().map(|counts| counts.len()).unwrap_or(0);
+
+    let mut proportions = vec![vec![0.; sample_count]; strains];
+    for s in 0..sample_count {
+        let weights = (0..strains).map(|_| rng.gen_range(0.1, 1.)).collect::<Vec<f64>>();
+        let total = weights.iter().sum::<f64>();
+        for k in 0..strains {
+            proportions[k][s] = weights[k] / total;
+        }
+    }
+
+    // The allele of its site each strain carries, 0 for none of them
+    let mut choices = sites.iter().map(|_| vec![0; strains]).collect::<Vec<Vec<usize>>>();
+    let mut log_likelihood = NEG_INFINITY;
+    for _ in 0..1000 {
+        // Choose the most likely allele of each site for every strain
+        let best = sites.par_iter().zip(choices.par_iter()).map(|(site, start)| {
+            let (configuration, log_likelihood, _) = fit_site(variant_counts, total_counts, site,
+                                                              start, &proportions);
+            (configuration, log_likelihood)
+        }).collect::<Vec<(Vec<usize>, f64)>>();
+        let new_log_likelihood = best.iter().map(|(_, log_likelihood)| log_likelihood).sum::<f64>();
+        choices = best.into_iter().map(|(configuration, _)| configuration).collect();
+
+        // Expected reads from each strain in each sample given the haplotypes
+        let mut strain_reads = vec![vec![0.; sample_count]; strains];
+        for (site, configuration) in sites.iter().zip(choices.iter()) {
+            for (allele_idx, v) in site.iter().enumerate() {
+                let haplotype = allele_haplotype(configuration, allele_idx);
+                for s in 0..sample_count {
+                    let n = total_counts[*v][s];
+                    if n <= 0. {
+                        continue
+                    }
+                    let x = variant_counts[*v][s].min(n);
+                    let with_allele = (0..strains).map(|k| proportions[k][s] * haplotype[k]).sum::<f64>();
+                    let without_allele = (0..strains).map(|k| proportions[k][s] * (1. - haplotype[k])).sum::<f64>();
+                    for k in 0..strains {
+                        if with_allele > 0. {
+                            strain_reads[k][s] += x * proportions[k][s] * haplotype[k] / with_allele;
+                        }
+                        if without_allele > 0. {
+                            strain_reads[k][s] += (n - x) * proportions[k][s] * (1. - haplotype[k]) / without_allele;
+                        }
+                    }
+                }
+            }
+        }
+        for s in 0..sample_count {
+            let sample_reads = (0..strains).map(|k| strain_reads[k][s]).sum::<f64>();
+            if sample_reads > 0. {
+                for k in 0..strains {
+                    proportions[k][s] = strain_reads[k][s] / sample_reads;
+                }
+            }
+        }
+
+        let converged = (new_log_likelihood - log_likelihood).abs()
+            < 1e-8 * new_log_likelihood.abs().max(1.);
+        log_likelihood = new_log_likelihood;
+        if converged {
+            break
+        }
+    }
+
+    // Posterior probability that each strain carries each allele, averaging over the allele
+    // combinations of its site weighted by their likelihood
+    let mut haplotypes = vec![vec![0.; strains]; variant_counts.len()];
+    let site_posteriors = sites.par_iter().zip(choices.par_iter()).map(|(site, start)| {
+        fit_site(variant_counts, total_counts, site, start, &proportions).2
+    }).collect::<Vec<Vec<Vec<f64>>>>();
+    for (site, posteriors) in sites.iter().zip(site_posteriors.into_iter()) {
+        for (v, posterior) in site.iter().zip(posteriors.into_iter()) {
+            haplotypes[*v] = posterior;
+        }
+    }
+
+    // Every strain has an allele per site and a proportion per sample less one
+    let parameters = strains * sites.len() + (strains - 1) * sample_count;
+    let observations = total_counts.iter()
+        .map(|counts| counts.iter().filter(|n| **n > 0.).count()).sum::<usize>();
+    Deconvolution {
+        haplotypes,
+        proportions,
+        log_likelihood,
+        bic: -2. * log_likelihood + parameters as f64 * (observations.max(1) as f64).ln(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deconvolve() {
+        // Two strains at 80/20, 50/50 and 10/90 across three samples. The first allele is only
+        // in strain one, the second only in strain two and the third in both
+        let proportions = vec![0.8, 0.5, 0.1];
+        let haplotypes = vec![(1., 0.), (0., 1.), (1., 1.), (1., 0.), (0., 1.)];
+        let total_counts = vec![vec![100.; 3]; haplotypes.len()];
+        let variant_counts = haplotypes.iter().map(|(h1, h2)| {
+            proportions.iter().map(|p| 100. * (h1 * p + h2 * (1. - p))).collect::<Vec<f64>>()
+        }).collect::<Vec<Vec<f64>>>();
+
+        let sites = (0..haplotypes.len()).map(|v| vec![v]).collect::<Vec<Vec<usize>>>();
+        let fit = deconvolve(&variant_counts, &total_counts, &sites, 2, 5, 0);
+        // Strains can come out in either order
+        let first = if fit.proportions[0][0] > 0.5 { 0 } else { 1 };
+        for (s, p) in proportions.iter().enumerate() {
+            assert!((fit.proportions[first][s] - p).abs() < 0.02);
+        }
+        for (v, (h1, h2)) in haplotypes.iter().enumerate() {
+            assert!((fit.haplotypes[v][first] - h1).abs() < 0.05);
+            assert!((fit.haplotypes[v][1 - first] - h2).abs() < 0.05);
+        }
+    }
+
+    #[test]
+    fn test_one_allele_per_strain() {
+        // Both alleles of the second site appear in every read of every sample, which
+        // independent alleles would explain by giving both to every strain
+        let proportions = vec![0.8, 0.5, 0.1];
+        let total_counts = vec![vec![100.; 3]; 4];
+        let variant_counts = vec![
+            proportions.iter().map(|p| 100. * p).collect::<Vec<f64>>(),
+            proportions.iter().map(|p| 100. * (1. - p)).collect::<Vec<f64>>(),
+            vec![100.; 3],
+            vec![100.; 3],
+        ];
+        let sites = vec![vec![0], vec![1], vec![2, 3]];
+
+        let fit = deconvolve(&variant_counts, &total_counts, &sites, 2, 5, 0);
+        for k in 0..2 {
+            assert!(fit.haplotypes[2][k] + fit.haplotypes[3][k] <= 1. + 1e-10);
+        }
+        assert_eq!(site_configurations(2, 2).len(), 9);
+    }
+}
@@ -0,0 +1,401 @@
+use std::collections::BTreeMap;
+use std::f64::NEG_INFINITY;
+use bio::alphabets::dna;
+use bio::io::{fasta, gff};
+use estimation::codon_structs::*;
+
+/// Shortest gene reported, in bases including the stop codon
+const MIN_GENE_LENGTH: usize = 90;
+/// Open reading frames at least this long are used to train the first gene model
+const MIN_TRAINING_LENGTH: usize = 300;
+/// Largest overlap allowed between two genes on the same or opposite strands
+const MAX_SAME_STRAND_OVERLAP: usize = 60;
+const MAX_OPPOSITE_STRAND_OVERLAP: usize = 200;
+/// Number of times the model is retrained on its own predictions
+const TRAINING_ROUNDS: usize = 2;
+/// Shine-Dalgarno consensus searched for upstream of start codons, along with the shortest
+/// stretch of it counted as a motif and the range of spacers between motif and start codon
+const SHINE_DALGARNO: &[u8] = b"AGGAGG";
+const MIN_MOTIF_LENGTH: usize = 3;
+const MIN_SPACER: usize = 3;
+const MAX_SPACER: usize = 15;
+/// No motif, or a motif length combined with one of three spacer bins
+const RBS_CLASSES: usize = 1 + (6 - MIN_MOTIF_LENGTH + 1) * 3;
+
+/// An open reading frame on one strand of a contig in that strand's coordinates. Starts are
+/// the first base of every candidate start codon and stop is the first base of the stop codon
+struct Orf {
+    contig: usize,
+    reverse: bool,
+    starts: Vec<usize>,
+    stop: usize,
+}
+
+/// A gene in the coordinates of its strand
+#[derive(Debug, Clone)]
+struct Gene {
+    contig: usize,
+    reverse: bool,
+    start: usize,
+    stop: usize,
+    score: f64,
+}
+
+/// Log-odds scores of a gene against the genome background. Coding scores are per codon,
+/// start scores per start codon and RBS scores per upstream motif class
+struct GeneModel {
+    coding: Vec<f64>,
+    start: Vec<f64>,
+    rbs: Vec<f64>,
+}
+
+/// Index of a codon from 0 to 63, or None if it contains anything other than A, C, G or T
+fn codon_index(codon: &[u8]) -> Option<usize> {
+    codon.iter().try_fold(0, |index, base| {
+        let base = match *base {
+            b'A' => 0,
+            b'C' => 1,
+            b'G' => 2,
+            b'T' => 3,
+            _ => return None,
+        };
+        Some(index * 4 + base)
+    })
+}
+
+fn all_codons() -> Vec<Vec<u8>> {
+    let bases = b"ACGT";
+    (0..64).map(|index| vec![bases[index / 16], bases[(index / 4) % 4], bases[index % 4]]).collect()
+}
+
+/// Class of the strongest Shine-Dalgarno motif upstream of a start codon. Longer stretches of
+/// the consensus win, then the closest spacer
+fn rbs_class(seq: &[u8], start: usize) -> usize {
+    for length in (MIN_MOTIF_LENGTH..SHINE_DALGARNO.len() + 1).rev() {
+        for spacer in MIN_SPACER..MAX_SPACER + 1 {
+            if start < spacer + length {
+                break
+            }
+            let motif_start = start - spacer - length;
+            let upstream = &seq[motif_start..motif_start + length];
+            if SHINE_DALGARNO.windows(length).any(|motif| motif == upstream) {
+                let spacer_bin = if spacer <= 4 { 0 } else if spacer <= 10 { 1 } else { 2 };
+                return 1 + (length - MIN_MOTIF_LENGTH) * 3 + spacer_bin
+            }
+        }
+    }
+    0
+}
+
+fn find_orfs(seq: &[u8], contig: usize, reverse: bool,
+             is_start: &Vec<bool>, is_stop: &Vec<bool>) -> Vec<Orf> {
+    let mut orfs = Vec::new();
+    for frame in 0..3 {
+        let mut starts = Vec::new();
+        let mut position = frame;
+        while position + 3 <= seq.len() {
+            match codon_index(&seq[position..position + 3]) {
+                Some(codon) if is_stop[codon] => {
+                    let kept = starts.drain(..)
+                        .filter(|start| position + 3 - start >= MIN_GENE_LENGTH)
+                        .collect::<Vec<usize>>();
+                    if kept.len() > 0 {
+                        orfs.push(Orf { contig, reverse, starts: kept, stop: position });
+                    }
+                },
+                Some(codon) if is_start[codon] => starts.push(position),
+                _ => {},
+            }
+            position += 3;
+        }
+    }
+    orfs
+}
+
+fn strand_sequence<'a>(sequences: &'a Vec<(Vec<u8>, Vec<u8>)>, contig: usize, reverse: bool) -> &'a Vec<u8> {
+    if reverse { &sequences[contig].1 } else { &sequences[contig].0 }
+}
+
+/// Log ratio of each count's frequency to its background frequency, with a pseudocount of one
+fn log_odds(counts: &Vec<f64>, background: &Vec<f64>) -> Vec<f64> {
+    let total = counts.iter().sum::<f64>();
+    let background_total = background.iter().sum::<f64>();
+    counts.iter().zip(background.iter()).map(|(count, expected)| {
+        if *expected > 0. && total > 0. {
+            ((count / total) / (expected / background_total)).ln()
+        } else {
+            0.
+        }
+    }).collect()
+}
+
+/// Trains codon, start codon and RBS scores on a set of genes. The coding background is the
+/// codon frequencies expected from the genome's GC content, the start and RBS backgrounds are
+/// every candidate start codon of every open reading frame
+fn train_model(sequences: &Vec<(Vec<u8>, Vec<u8>)>, orfs: &Vec<Orf>, genes: &Vec<Gene>, gc: f64,
+               is_start: &Vec<bool>, is_stop: &Vec<bool>) -> GeneModel {
+    let base_frequencies = [(1. - gc) / 2., gc / 2., gc / 2., (1. - gc) / 2.];
+    let codon_background = (0..64).map(|codon| {
+        if is_stop[codon] {
+            0.
+        } else {
+            base_frequencies[codon / 16] * base_frequencies[(codon / 4) % 4] * base_frequencies[codon % 4]
+        }
+    }).collect::<Vec<f64>>();
+    let mut codon_counts = (0..64).map(|codon| if is_stop[codon] { 0. } else { 1. }).collect::<Vec<f64>>();
+    let mut start_counts = (0..64).map(|codon| if is_start[codon] { 1. } else { 0. }).collect::<Vec<f64>>();
+    let mut rbs_counts = vec![1.; RBS_CLASSES];
+    for gene in genes.iter() {
+        let seq = strand_sequence(sequences, gene.contig, gene.reverse);
+        let mut position = gene.start;
+        while position < gene.stop {
+            if let Some(codon) = codon_index(&seq[position..position + 3]) {
+                codon_counts[codon] += 1.;
+            }
+            position += 3;
+        }
+        if let Some(codon) = codon_index(&seq[gene.start..gene.start + 3]) {
+            start_counts[codon] += 1.;
+        }
+        rbs_counts[rbs_class(seq, gene.start)] += 1.;
+    }
+
+    let mut start_background = (0..64).map(|codon| if is_start[codon] { 1. } else { 0. }).collect::<Vec<f64>>();
+    let mut rbs_background = vec![1.; RBS_CLASSES];
+    for orf in orfs.iter() {
+        let seq = strand_sequence(sequences, orf.contig, orf.reverse);
+        for start in orf.starts.iter() {
+            if let Some(codon) = codon_index(&seq[*start..*start + 3]) {
+                start_background[codon] += 1.;
+            }
+            rbs_background[rbs_class(seq, *start)] += 1.;
+        }
+    }
+
+    GeneModel {
+        coding: log_odds(&codon_counts, &codon_background),
+        start: log_odds(&start_counts, &start_background),
+        rbs: log_odds(&rbs_counts, &rbs_background),
+    }
+}
+
+/// Chooses the best start of an open reading frame. Each start scores the coding log-odds of
+/// every codon up to the stop plus its start codon and RBS scores
+fn best_start(model: &GeneModel, seq: &[u8], orf: &Orf) -> (usize, f64) {
+    let mut best = (orf.starts[0], NEG_INFINITY);
+    let mut coding = 0.;
+    let mut remaining = orf.starts.len();
+    let mut position = orf.stop;
+    while remaining > 0 {
+        position -= 3;
+        let codon = codon_index(&seq[position..position + 3]);
+        if let Some(codon) = codon {
+            coding += model.coding[codon];
+        }
+        if position == orf.starts[remaining - 1] {
+            remaining -= 1;
+            let score = coding + codon.map(|codon| model.start[codon]).unwrap_or(0.)
+                + model.rbs[rbs_class(seq, position)];
+            if score > best.1 {
+                best = (position, score);
+            }
+        }
+    }
+    best
+}
+
+/// Zero based, half open coordinates of a gene on the forward strand
+fn forward_bounds(gene: &Gene, contig_length: usize) -> (usize, usize) {
+    if gene.reverse {
+        (contig_length - gene.stop - 3, contig_length - gene.start)
+    } else {
+        (gene.start, gene.stop + 3)
+    }
+}
+
+/// Scores every open reading frame by its best start and keeps the positive scoring genes,
+/// highest scoring first, that do not overlap an already kept gene by too much
+fn predict(model: &GeneModel, sequences: &Vec<(Vec<u8>, Vec<u8>)>, orfs: &Vec<Orf>) -> Vec<Gene> {
+    let mut candidates = orfs.iter().filter_map(|orf| {
+        let seq = strand_sequence(sequences, orf.contig, orf.reverse);
+        let (start, score) = best_start(model, seq, orf);
+        if score > 0. {
+            Some(Gene { contig: orf.contig, reverse: orf.reverse, start, stop: orf.stop, score })
+        } else {
+            None
+        }
+    }).collect::<Vec<Gene>>();
+    candidates.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap());
+
+    // Kept genes of each contig keyed by their start, along with the longest kept gene so
+    // only nearby genes are checked for overlaps
+    let mut kept: Vec<BTreeMap<usize, (usize, bool)>> = vec![BTreeMap::new(); sequences.len()];
+    let mut longest = vec![0; sequences.len()];
+    let mut genes = Vec::new();
+    for gene in candidates.into_iter() {
+        let (start, end) = forward_bounds(&gene, sequences[gene.contig].0.len());
+        let conflict = kept[gene.contig].range(start.saturating_sub(longest[gene.contig])..end)
+            .any(|(other_start, (other_end, other_reverse))| {
+                let overlap = std::cmp::min(end, *other_end).saturating_sub(std::cmp::max(start, *other_start));
+                let max_overlap = if *other_reverse == gene.reverse {
+                    MAX_SAME_STRAND_OVERLAP
+                } else {
+                    MAX_OPPOSITE_STRAND_OVERLAP
+                };
+                overlap > max_overlap || overlap == end - start || overlap == other_end - other_start
+            });
+        if !conflict {
+            kept[gene.contig].insert(start, (end, gene.reverse));
+            longest[gene.contig] = std::cmp::max(longest[gene.contig], end - start);
+            genes.push(gene);
+        }
+    }
+    genes
+}
+
+/// Reads the name and sequence of every contig in a FASTA file
+pub fn read_contigs(reference_path: &str) -> Vec<(String, Vec<u8>)> {
+    let reader = fasta::Reader::from_file(reference_path)
+        .expect(&format!("Unable to read reference {}", reference_path));
+    reader.records().map(|record| {
+        let record = record.expect(&format!("Unable to parse record in reference {}", reference_path));
+        (record.id().to_string(), record.seq().to_vec())
+    }).collect()
+}
+
+/// Predicts the protein coding genes of a prokaryotic genome in the style of prodigal. Open
+/// reading frames of both strands are found with the start and stop codons of the given
+/// translation table. A model of codon usage, start codon usage and Shine-Dalgarno motifs is
+/// trained on the longest open reading frames, used to choose the start of every frame and
+/// the non-overlapping set of genes, then retrained on those genes. Genes running off the end
+/// of a contig are not reported
+pub fn predict_genes(contigs: &Vec<(String, Vec<u8>)>, codon_table: &CodonTable) -> Vec<gff::Record> {
+    let codons = all_codons();
+    let is_start = codons.iter().map(|codon| codon_table.is_start(codon)).collect::<Vec<bool>>();
+    let is_stop = codons.iter().map(|codon| codon_table.is_stop(codon)).collect::<Vec<bool>>();
+
+    let sequences = contigs.iter().map(|(_, seq)| {
+        let forward = seq.to_ascii_uppercase();
+        let reverse = dna::revcomp(&forward);
+        (forward, reverse)
+    }).collect::<Vec<(Vec<u8>, Vec<u8>)>>();
+    let mut orfs = Vec::new();
+    for (contig, (forward, reverse)) in sequences.iter().enumerate() {
+        orfs.extend(find_orfs(forward, contig, false, &is_start, &is_stop));
+        orfs.extend(find_orfs(reverse, contig, true, &is_start, &is_stop));
+    }
+    if orfs.len() == 0 {
+        return Vec::new()
+    }
+
+    let (gc_count, base_count) = sequences.iter()
+        .flat_map(|(forward, _)| forward.iter())
+        .fold((0, 0), |(gc_count, base_count), base| match *base {
+            b'G' | b'C' => (gc_count + 1, base_count + 1),
+            b'A' | b'T' => (gc_count, base_count + 1),
+            _ => (gc_count, base_count),
+        });
+    let gc = if base_count > 0 { gc_count as f64 / base_count as f64 } else { 0.5 };
+
+    let mut genes = orfs.iter()
+        .filter(|orf| orf.stop + 3 - orf.starts[0] >= MIN_TRAINING_LENGTH)
+        .map(|orf| Gene { contig: orf.contig, reverse: orf.reverse, start: orf.starts[0], stop: orf.stop, score: 0. })
+        .collect::<Vec<Gene>>();
+    debug!("Training gene model on {} open reading frames with GC content {}", genes.len(), gc);
+    for _ in 0..TRAINING_ROUNDS + 1 {
+        if genes.len() == 0 {
+            break
+        }
+        let model = train_model(&sequences, &orfs, &genes, gc, &is_start, &is_stop);
+        genes = predict(&model, &sequences, &orfs);
+    }
+
+    genes.sort_by_key(|gene| (gene.contig, forward_bounds(gene, sequences[gene.contig].0.len()).0));
+    let mut gene_numbers = vec![0; contigs.len()];
+    genes.iter().map(|gene| {
+        let seq = strand_sequence(&sequences, gene.contig, gene.reverse);
+        let (start, end) = forward_bounds(gene, seq.len());
+        gene_numbers[gene.contig] += 1;
+
+        let mut record = gff::Record::new();
+        *record.seqname_mut() = contigs[gene.contig].0.clone();
+        *record.source_mut() = "lorikeet".to_string();
+        *record.feature_type_mut() = "CDS".to_string();
+        *record.start_mut() = start as u64 + 1;
+        *record.end_mut() = end as u64;
+        *record.score_mut() = ".".to_string();
+        *record.strand_mut() = if gene.reverse { "-".to_string() } else { "+".to_string() };
+        *record.frame_mut() = "0".to_string();
+        record.attributes_mut().insert(
+            "ID".to_string(), format!("{}_{}", contigs[gene.contig].0, gene_numbers[gene.contig]));
+        record.attributes_mut().insert(
+            "start_type".to_string(), String::from_utf8_lossy(&seq[gene.start..gene.start + 3]).to_string());
+        record.attributes_mut().insert(
+            "rbs_class".to_string(), format!("{}", rbs_class(seq, gene.start)));
+        record.attributes_mut().insert("score".to_string(), format!("{:.2}", gene.score));
+        record
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+    use bio_types::strand::Strand;
+
+    fn random_bases(rng: &mut StdRng, length: usize) -> Vec<u8> {
+        (0..length).map(|_| b"ACGT"[rng.gen_range(0, 4)]).collect()
+    }
+
+    #[test]
+    fn test_predict_genes() {
+        assert_eq!(codon_index(b"AAA"), Some(0));
+        assert_eq!(codon_index(b"TTT"), Some(63));
+        assert_eq!(codon_index(b"ANA"), None);
+        assert_eq!(rbs_class(b"AGGAGGTTTTTTTATG", 13), 1 + 3 * 3 + 1);
+        assert_eq!(rbs_class(b"TTTTTTTTTTTTTATG", 13), 0);
+
+        // Genes built from a small set of codons behind a Shine-Dalgarno motif, on both strands
+        // and separated by random sequence
+        let gene_codons = [b"GCC", b"GAA", b"AAA", b"CTG", b"CGT", b"ATC", b"GAC", b"ACC"];
+        let mut rng = StdRng::seed_from_u64(42);
+        let mut contig = random_bases(&mut rng, 200);
+        let mut expected = Vec::new();
+        for gene_idx in 0..20 {
+            let mut block = b"AGGAGG".to_vec();
+            block.extend(random_bases(&mut rng, 7));
+            block.extend(b"ATG");
+            for _ in 0..198 {
+                block.extend(gene_codons[rng.gen_range(0, gene_codons.len())]);
+            }
+            block.extend(b"TAA");
+            let reverse = gene_idx % 2 == 1;
+            // Start and end of the coding sequence, 1-based and inclusive
+            if reverse {
+                expected.push((contig.len() as u64 + 1, contig.len() as u64 + 600, Strand::Reverse));
+                block = dna::revcomp(&block);
+            } else {
+                expected.push((contig.len() as u64 + 14, contig.len() as u64 + 613, Strand::Forward));
+            }
+            contig.extend(block);
+            contig.extend(random_bases(&mut rng, 200));
+        }
+
+        let mut codon_table = CodonTable::setup();
+        codon_table.get_codon_table(11);
+        let records = predict_genes(&vec![("contig_1".to_string(), contig)], &codon_table);
+        for (start, end, strand) in expected.iter() {
+            assert!(records.iter().any(|record| {
+                record.start() == start && record.end() == end && record.strand() == Some(strand.clone())
+            }), "Gene from {} to {} on strand {:?} not predicted", start, end, strand);
+        }
+
+        // No genes are predicted in the random sequence between the real genes
+        let false_positives = records.iter().filter(|record| {
+            !expected.iter().any(|(start, end, strand)| {
+                record.start() == start && record.end() == end && record.strand() == Some(strand.clone())
+            })
+        }).count();
+        assert_eq!(false_positives, 0);
+    }
+}
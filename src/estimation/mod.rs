@@ -1,8 +1,16 @@
 pub mod alignment_properties;
 pub mod codon_structs;
+pub mod annotations;
+pub mod gene_calling;
 pub mod contig;
 pub mod contig_variants;
 pub mod variant_matrix;
 pub mod linkage;
+pub mod cluster_refinement;
+pub mod strain_count;
+pub mod deconvolution;
+pub mod phasing;
+pub mod read_graph;
+pub mod read_assignment;
 pub mod vcfs;
 pub mod bams;
\ No newline at end of file
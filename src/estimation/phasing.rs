@@ -0,0 +1,346 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+use rust_htslib::bam::{self, Read};
+use rand::{Rng, SeedableRng, rngs::StdRng};
+
+use model::read_names::*;
+use dbscan::fuzzy;
+
+/// Parameters for phasing variants with long reads
+#[derive(Debug, Clone)]
+pub struct PhasingParameters {
+    /// Number of haplotypes solved for in each block
+    pub haplotypes: usize,
+    /// Minimum number of variant sites in a block for it to be reported
+    pub min_block_sites: usize,
+    /// Random starts of the MEC search in each block
+    pub restarts: usize,
+}
+
+impl PhasingParameters {
+    pub fn generate_from_clap(m: &clap::ArgMatches) -> PhasingParameters {
+        PhasingParameters {
+            haplotypes: m.value_of("ploidy").unwrap().parse().unwrap(),
+            min_block_sites: m.value_of("min-phased-block-sites").unwrap().parse().unwrap(),
+            restarts: 10,
+        }
+    }
+}
+
+/// A variant site with more than one allele, holding the variant_info index of each allele
+#[derive(Debug, Clone, PartialEq)]
+pub struct PhasingSite {
+    pub tid: i32,
+    pub pos: i64,
+    pub alleles: Vec<usize>,
+}
+
+/// A set of sites connected by reads along with the haplotypes that best explain those reads
+#[derive(Debug, Clone)]
+pub struct PhasedBlock {
+    pub tid: i32,
+    /// Indices of the block's sites, in position order
+    pub sites: Vec<usize>,
+    /// Allele of each haplotype at each site of the block, most supported haplotype first
+    pub haplotypes: Vec<Vec<usize>>,
+    /// Number of reads assigned to each haplotype
+    pub fragments: Vec<usize>,
+    /// Number of reads assigned to each haplotype that carry its allele at each site
+    pub support: Vec<Vec<usize>>,
+    /// Minimum error correction score, the number of read alleles disagreeing with their haplotype
+    pub mec: usize,
+}
+
+impl PhasedBlock {
+    /// The variant_info indices of the alleles of each haplotype with any supporting reads
+    pub fn haplotype_clusters(&self, sites: &Vec<PhasingSite>) -> Vec<fuzzy::Cluster> {
+        self.haplotypes.iter().zip(self.support.iter())
+            .filter(|(_, support)| support.iter().any(|count| *count > 0))
+            .map(|(haplotype, support)| {
+                let reads = std::cmp::max(*support.iter().max().unwrap(), 1);
+                self.sites.iter().zip(haplotype.iter()).zip(support.iter())
+                    .filter(|(_, count)| **count > 0)
+                    .map(|((site, allele), count)| fuzzy::Assignment {
+                        index: sites[*site].alleles[*allele],
+                        label: *count as f64 / reads as f64,
+                        category: fuzzy::Category::Core,
+                    }).collect::<fuzzy::Cluster>()
+            }).collect()
+    }
+}
+
+/// Collects the ids of reads in the given long read BAM files that map to the given contigs.
+/// Only the regions of those contigs are fetched, building the BAM index if it is missing
+pub fn long_read_ids(bam_paths: &Vec<String>, contig_names: &HashSet<String>,
+                     sample_names: &Vec<String>, read_names: &ReadInterner,
+                     threads: usize) -> HashSet<ReadId> {
+    let mut reads = HashSet::new();
+    for bam_path in bam_paths.iter() {
+        let sample_idx = match bam_sample_index(bam_path, sample_names) {
+            Some(sample_idx) => sample_idx,
+            None => {
+                warn!("Long read BAM file {} is not a sample of this genome, skipping it", bam_path);
+                continue
+            }
+        };
+        // check and build bam index if it doesn't exist
+        if !Path::new(&(bam_path.to_string() + ".bai")).exists() {
+            bam::index::build(bam_path, Some(&(bam_path.to_string() + ".bai")),
+                              bam::index::Type::BAI, threads as u32).expect(
+                &format!("Unable to index bam at {}", &bam_path));
+        }
+        let mut bam_reader = bam::IndexedReader::from_path(&Path::new(bam_path))
+            .expect(&format!("Unable to open BAM file {}", bam_path));
+        bam_reader.set_threads(threads).expect("Unable to set threads on BAM reader");
+        let header = bam_reader.header().clone();
+        let tids = header.target_names().iter().enumerate()
+            .filter(|(_, name)| contig_names.contains(std::str::from_utf8(name).unwrap()))
+            .map(|(tid, _)| tid as u32)
+            .collect::<Vec<u32>>();
+        let mut record = bam::Record::new();
+        for tid in tids {
+            let target_len = header.target_len(tid).expect("Contig missing from BAM header");
+            bam_reader.fetch(tid, 0, target_len).expect("Unable to fetch contig from BAM");
+            while bam_reader.read(&mut record).expect("Error while reading BAM record") {
+                if let Some(id) = read_names.get(sample_idx, record.qname()) {
+                    reads.insert(id);
+                }
+            }
+        }
+    }
+    reads
+}
+
+/// Builds the fragment matrix of the given reads: the sites with more than one allele and, for
+/// each read, the allele it carries at every site it covers. Reads supporting several alleles
+/// at one site are ambiguous there and that site is left out of the read's fragment
+pub fn build_fragments(variant_info: &Vec<fuzzy::Var>, reads: &HashSet<ReadId>)
+    -> (Vec<PhasingSite>, Vec<Vec<(usize, usize)>>) {
+    let mut site_alleles: BTreeMap<(i32, i64), Vec<usize>> = BTreeMap::new();
+    for (index, var) in variant_info.iter().enumerate() {
+        site_alleles.entry((var.tid, var.pos)).or_insert(Vec::new()).push(index);
+    }
+    let sites = site_alleles.into_iter()
+        .filter(|(_, alleles)| alleles.len() > 1)
+        .map(|((tid, pos), mut alleles)| {
+            alleles.sort_by_key(|index| variant_info[*index].var.clone());
+            PhasingSite { tid, pos, alleles }
+        }).collect::<Vec<PhasingSite>>();
+
+    let mut observations: HashMap<ReadId, BTreeMap<usize, Vec<usize>>> = HashMap::new();
+    for (site_idx, site) in sites.iter().enumerate() {
+        for (allele_idx, index) in site.alleles.iter().enumerate() {
+            for read in variant_info[*index].reads.iter() {
+                if reads.contains(read) {
+                    observations.entry(*read).or_insert(BTreeMap::new())
+                        .entry(site_idx).or_insert(Vec::new()).push(allele_idx);
+                }
+            }
+        }
+    }
+    let mut read_ids = observations.keys().cloned().collect::<Vec<ReadId>>();
+    read_ids.sort();
+    let fragments = read_ids.iter().map(|read| {
+        observations[read].iter()
+            .filter(|(_, alleles)| alleles.len() == 1)
+            .map(|(site, alleles)| (*site, alleles[0]))
+            .collect::<Vec<(usize, usize)>>()
+    }).filter(|fragment| fragment.len() > 0).collect();
+    (sites, fragments)
+}
+
+fn find(parents: &mut Vec<usize>, site: usize) -> usize {
+    let mut root = site;
+    while parents[root] != root {
+        root = parents[root];
+    }
+    let mut current = site;
+    while parents[current] != root {
+        let next = parents[current];
+        parents[current] = root;
+        current = next;
+    }
+    root
+}
+
+/// Splits sites into blocks connected by reads spanning more than one site and solves the
+/// minimum error correction problem for each block with the given number of haplotypes
+pub fn phase_blocks(sites: &Vec<PhasingSite>, fragments: &Vec<Vec<(usize, usize)>>,
+                    params: &PhasingParameters) -> Vec<PhasedBlock> {
+    let mut parents = (0..sites.len()).collect::<Vec<usize>>();
+    for fragment in fragments.iter() {
+        for window in fragment.windows(2) {
+            if sites[window[0].0].tid == sites[window[1].0].tid {
+                let (root_1, root_2) = (find(&mut parents, window[0].0), find(&mut parents, window[1].0));
+                if root_1 != root_2 {
+                    parents[root_2] = root_1;
+                }
+            }
+        }
+    }
+    let mut block_sites: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for site in 0..sites.len() {
+        let root = find(&mut parents, site);
+        block_sites.entry(root).or_insert(Vec::new()).push(site);
+    }
+    let mut block_fragments: HashMap<usize, Vec<&Vec<(usize, usize)>>> = HashMap::new();
+    for fragment in fragments.iter().filter(|fragment| fragment.len() > 1) {
+        let root = find(&mut parents, fragment[0].0);
+        block_fragments.entry(root).or_insert(Vec::new()).push(fragment);
+    }
+
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut blocks = block_sites.into_iter()
+        .filter(|(_, block)| block.len() >= std::cmp::max(params.min_block_sites, 2))
+        .filter_map(|(root, block)| {
+            let block_fragments = block_fragments.remove(&root)?;
+            let local = block.iter().enumerate()
+                .map(|(local, site)| (*site, local)).collect::<HashMap<usize, usize>>();
+            let local_fragments = block_fragments.iter().map(|fragment| {
+                // Reads split across contigs only contribute their alleles within this block
+                fragment.iter().filter_map(|(site, allele)| local.get(site).map(|site| (*site, *allele)))
+                    .collect::<Vec<(usize, usize)>>()
+            }).collect::<Vec<Vec<(usize, usize)>>>();
+            let allele_counts = block.iter().map(|site| sites[*site].alleles.len()).collect::<Vec<usize>>();
+
+            let (haplotypes, assignments, mec) = solve_mec(
+                &local_fragments, &allele_counts, std::cmp::max(params.haplotypes, 1),
+                std::cmp::max(params.restarts, 1), &mut rng);
+
+            let mut fragment_counts = vec![0; haplotypes.len()];
+            let mut support = vec![vec![0; block.len()]; haplotypes.len()];
+            for (fragment, haplotype) in local_fragments.iter().zip(assignments.iter()) {
+                fragment_counts[*haplotype] += 1;
+                for (site, allele) in fragment.iter() {
+                    if haplotypes[*haplotype][*site] == *allele {
+                        support[*haplotype][*site] += 1;
+                    }
+                }
+            }
+            let mut order = (0..haplotypes.len()).collect::<Vec<usize>>();
+            order.sort_by(|a, b| fragment_counts[*b].cmp(&fragment_counts[*a]));
+
+            debug!("Phased block of {} sites on contig {} from {} reads with MEC score {}",
+                   block.len(), sites[block[0]].tid, local_fragments.len(), mec);
+            Some(PhasedBlock {
+                tid: sites[block[0]].tid,
+                haplotypes: order.iter().map(|h| haplotypes[*h].clone()).collect(),
+                fragments: order.iter().map(|h| fragment_counts[*h]).collect(),
+                support: order.iter().map(|h| support[*h].clone()).collect(),
+                sites: block,
+                mec,
+            })
+        }).collect::<Vec<PhasedBlock>>();
+    blocks.sort_by_key(|block| (block.tid, sites[block.sites[0]].pos));
+    blocks
+}
+
+/// Number of alleles in the fragment that differ from the haplotype
+fn mismatches(fragment: &Vec<(usize, usize)>, haplotype: &Vec<usize>) -> usize {
+    fragment.iter().filter(|(site, allele)| haplotype[*site] != *allele).count()
+}
+
+/// The most common allele at each site among the given reads. Sites without reads and tied
+/// sites keep the current allele so that the search settles
+fn majority<'a, I: Iterator<Item = &'a Vec<(usize, usize)>>>(assigned: I, allele_counts: &Vec<usize>,
+                                                            current: &Vec<usize>) -> Vec<usize> {
+    let mut counts = allele_counts.iter().map(|count| vec![0; *count]).collect::<Vec<Vec<usize>>>();
+    for fragment in assigned {
+        for (site, allele) in fragment.iter() {
+            counts[*site][*allele] += 1;
+        }
+    }
+    counts.iter().zip(current.iter()).map(|(site_counts, allele)| {
+        let best = *site_counts.iter().max().unwrap();
+        if best == 0 || site_counts[*allele] == best {
+            *allele
+        } else {
+            site_counts.iter().position(|count| *count == best).unwrap()
+        }
+    }).collect()
+}
+
+/// Local search for the haplotypes minimising the MEC score. Each start seeds the haplotypes
+/// with random reads, then alternates between assigning reads to their closest haplotype and
+/// setting each haplotype to the majority allele of its reads. Returns the haplotypes, the
+/// haplotype of each read and the MEC score of the best start
+fn solve_mec(fragments: &Vec<Vec<(usize, usize)>>, allele_counts: &Vec<usize>,
+             haplotype_count: usize, restarts: usize, rng: &mut StdRng)
+    -> (Vec<Vec<usize>>, Vec<usize>, usize) {
+    let consensus = majority(fragments.iter(), allele_counts, &vec![0; allele_counts.len()]);
+
+    let mut best: Option<(Vec<Vec<usize>>, Vec<usize>, usize)> = None;
+    for _ in 0..restarts {
+        let mut haplotypes = (0..haplotype_count).map(|_| {
+            let mut haplotype = consensus.clone();
+            for (site, allele) in fragments[rng.gen_range(0, fragments.len())].iter() {
+                haplotype[*site] = *allele;
+            }
+            haplotype
+        }).collect::<Vec<Vec<usize>>>();
+
+        let mut assignments = vec![usize::max_value(); fragments.len()];
+        for _ in 0..100 {
+            let new_assignments = fragments.iter().map(|fragment| {
+                (0..haplotype_count).min_by_key(|h| mismatches(fragment, &haplotypes[*h])).unwrap()
+            }).collect::<Vec<usize>>();
+            if new_assignments == assignments {
+                break
+            }
+            assignments = new_assignments;
+            haplotypes = (0..haplotype_count).map(|h| {
+                majority(fragments.iter().zip(assignments.iter())
+                             .filter(|(_, assigned)| **assigned == h).map(|(fragment, _)| fragment),
+                         allele_counts, &haplotypes[h])
+            }).collect();
+        }
+
+        let mec = fragments.iter().zip(assignments.iter())
+            .map(|(fragment, h)| mismatches(fragment, &haplotypes[*h])).sum::<usize>();
+        if best.as_ref().map(|best| mec < best.2).unwrap_or(true) {
+            best = Some((haplotypes, assignments, mec));
+        }
+    }
+    best.unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_phase_blocks() {
+        let sites = (0..4).map(|pos| PhasingSite {
+            tid: 0,
+            pos: pos * 100,
+            alleles: vec![pos as usize * 2, pos as usize * 2 + 1],
+        }).collect::<Vec<PhasingSite>>();
+        // Five reads from each of two haplotypes spanning the first three sites, with a single
+        // error, and a read covering only the last site
+        let mut fragments = Vec::new();
+        for _ in 0..5 {
+            fragments.push(vec![(0, 0), (1, 0), (2, 0)]);
+            fragments.push(vec![(0, 1), (1, 1), (2, 1)]);
+        }
+        fragments.push(vec![(0, 1), (1, 0), (2, 1)]);
+        fragments.push(vec![(3, 1)]);
+        let params = PhasingParameters {
+            haplotypes: 2,
+            min_block_sites: 2,
+            restarts: 5,
+        };
+
+        let blocks = phase_blocks(&sites, &fragments, &params);
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].sites, vec![0, 1, 2]);
+        assert_eq!(blocks[0].mec, 1);
+        assert_eq!(blocks[0].haplotypes, vec![vec![1, 1, 1], vec![0, 0, 0]]);
+        assert_eq!(blocks[0].fragments, vec![6, 5]);
+
+        let clusters = blocks[0].haplotype_clusters(&sites);
+        let indices = clusters.iter()
+            .map(|cluster| cluster.iter().map(|assignment| assignment.index).collect::<Vec<usize>>())
+            .collect::<Vec<Vec<usize>>>();
+        assert_eq!(indices, vec![vec![1, 3, 5], vec![0, 2, 4]]);
+    }
+}
@@ -0,0 +1,332 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+use rust_htslib::bam::{self, Read};
+
+use estimation::variant_matrix::*;
+use model::variants::*;
+use model::read_names::*;
+use dbscan::fuzzy;
+
+/// Parameters used when assigning reads to strains
+#[derive(Debug, Clone)]
+pub struct ReadAssignmentParameters {
+    /// Probability that an observed allele is a sequencing or mapping error
+    pub error_rate: f64,
+    /// Minimum posterior probability required to assign a read to a strain
+    pub min_posterior: f64,
+    /// Maximum number of EM iterations per sample
+    pub max_iterations: usize,
+    /// Write a BAM file for each strain in each sample
+    pub write_bams: bool,
+}
+
+impl ReadAssignmentParameters {
+    pub fn generate_from_clap(m: &clap::ArgMatches) -> ReadAssignmentParameters {
+        ReadAssignmentParameters {
+            error_rate: 0.01,
+            min_posterior: m.value_of("min-read-posterior").unwrap().parse().unwrap(),
+            max_iterations: 100,
+            write_bams: m.is_present("strain-bams"),
+        }
+    }
+}
+
+/// The allele a strain carries at a site. Core variants are preferred over border and noise
+/// variants, and sites a strain has no variants at carry the reference allele.
+pub fn strain_allele(categories: &HashMap<fuzzy::Category, HashSet<Variant>>) -> Variant {
+    for category in [fuzzy::Category::Core, fuzzy::Category::Border, fuzzy::Category::Noise].iter() {
+        match categories.get(category) {
+            Some(variants) if variants.len() > 0 => {
+                return variants.iter().min().unwrap().clone()
+            },
+            _ => {},
+        }
+    }
+    Variant::None
+}
+
+/// Calculates the posterior probability of each read originating from each strain using EM
+/// over the alleles each read supports, then writes a read to strain table and optionally
+/// per strain BAM files. Reads supporting no clustered variants are written to every strain BAM
+/// and reads that remain ambiguous are counted and written to an unassigned BAM.
+pub fn assign_reads_to_strains(variant_matrix: &VariantMatrix,
+                               bam_paths: &Vec<String>,
+                               output_prefix: &str,
+                               threads: usize,
+                               params: &ReadAssignmentParameters) {
+    match variant_matrix {
+        VariantMatrix::VariantContigMatrix {
+            all_variants,
+            pred_variants,
+            target_names,
+            sample_names,
+            read_names,
+            ..
+        } => {
+            let mut strains = pred_variants.keys().cloned().collect::<Vec<usize>>();
+            strains.sort();
+            if strains.len() == 0 {
+                warn!("No strains found, skipping read assignment");
+                return
+            }
+
+            // Every site at which any strain has a variant, with the alleles observed there
+            let mut sites: Vec<(i32, i64)> = pred_variants.values()
+                .flat_map(|genotype| {
+                    genotype.iter().flat_map(|(tid, positions)| {
+                        positions.keys().map(move |pos| (*tid, *pos))
+                    })
+                }).collect::<HashSet<(i32, i64)>>().into_iter().collect();
+            sites.sort();
+
+            // Reads supporting each allele at each site. Read ids are interned per sample, so
+            // reads of different samples sharing a name never share observations
+            let mut read_observations: HashMap<ReadId, Vec<(usize, Variant)>> = HashMap::new();
+            let mut site_alleles = Vec::with_capacity(sites.len());
+            // The allele carried by each strain at each site
+            let mut strain_alleles = vec![Vec::with_capacity(sites.len()); strains.len()];
+            for (site_idx, (tid, pos)) in sites.iter().enumerate() {
+                let position_variants = all_variants.get(tid).and_then(|contig| contig.get(pos));
+                let allele_count = match position_variants {
+                    Some(position_variants) => {
+                        for (variant, base) in position_variants.iter() {
+                            for read in base.reads.iter() {
+                                read_observations.entry(*read).or_insert(Vec::new())
+                                    .push((site_idx, variant.clone()));
+                            }
+                        }
+                        position_variants.len()
+                    },
+                    None => 1,
+                };
+                site_alleles.push(allele_count);
+                for (strain_idx, strain) in strains.iter().enumerate() {
+                    let allele = match pred_variants[strain].get(tid).and_then(|contig| contig.get(pos)) {
+                        Some(categories) => strain_allele(categories),
+                        None => Variant::None,
+                    };
+                    strain_alleles[strain_idx].push(allele);
+                }
+            }
+
+            let file_name = format!("{}_read_assignments.tsv", output_prefix);
+            let mut file_open = File::create(&file_name)
+                .expect(&format!("Unable to create file {}", &file_name));
+            write!(file_open, "sample\tread\tstrain").expect("Unable to write to file");
+            for strain in strains.iter() {
+                write!(file_open, "\tstrain_{}", strain).expect("Unable to write to file");
+            }
+            write!(file_open, "\n").expect("Unable to write to file");
+
+            let contig_names = target_names.values().cloned().collect::<HashSet<String>>();
+            for bam_path in bam_paths.iter() {
+                let mut bam_reader = bam::Reader::from_path(&Path::new(bam_path))
+                    .expect(&format!("Unable to open BAM file {}", bam_path));
+                bam_reader.set_threads(threads).expect("Unable to set threads on BAM reader");
+                let header = bam_reader.header().clone();
+                // Only BAMs mapped against this genome are relevant
+                if !header.target_names().iter()
+                    .any(|name| contig_names.contains(std::str::from_utf8(name).unwrap())) {
+                    continue
+                }
+                let sample_idx = match bam_sample_index(bam_path, sample_names) {
+                    Some(sample_idx) => sample_idx,
+                    None => {
+                        warn!("BAM file {} is not a sample of this genome, skipping read assignment", bam_path);
+                        continue
+                    }
+                };
+                let sample_name = &sample_names[sample_idx];
+
+                // Collect the informative reads present in this sample
+                let mut reads = Vec::new();
+                let mut seen = HashSet::new();
+                let mut record = bam::Record::new();
+                while bam_reader.read(&mut record).expect("Error while reading BAM record") {
+                    match read_names.get(sample_idx, record.qname()) {
+                        Some(id) if read_observations.contains_key(&id) => {
+                            if seen.insert(id) {
+                                reads.push(id);
+                            }
+                        },
+                        _ => {},
+                    }
+                }
+
+                let log_likelihoods = reads.iter().map(|read| {
+                    strain_alleles.iter().map(|alleles| {
+                        read_observations[read].iter().map(|(site_idx, variant)| {
+                            if &alleles[*site_idx] == variant {
+                                (1. - params.error_rate).ln()
+                            } else {
+                                (params.error_rate
+                                    / std::cmp::max(site_alleles[*site_idx].saturating_sub(1), 1) as f64).ln()
+                            }
+                        }).sum::<f64>()
+                    }).collect::<Vec<f64>>()
+                }).collect::<Vec<Vec<f64>>>();
+
+                let (weights, posteriors) = expectation_maximization(
+                    &log_likelihoods, params.max_iterations, 1e-6);
+                info!("Strain weights for sample {}: {:?}", sample_name, &weights);
+
+                let mut assignments = HashMap::new();
+                for (read, posterior) in reads.iter().zip(posteriors.iter()) {
+                    let (best_idx, best) = posterior.iter().enumerate()
+                        .fold((0, 0.), |acc, (idx, p)| if *p > acc.1 { (idx, *p) } else { acc });
+                    let assigned = if best >= params.min_posterior {
+                        assignments.insert(*read, best_idx);
+                        format!("{}", strains[best_idx])
+                    } else {
+                        "ambiguous".to_string()
+                    };
+                    write!(file_open, "{}\t{}\t{}", sample_name,
+                           std::str::from_utf8(read_names.name(*read)).unwrap(), assigned)
+                        .expect("Unable to write to file");
+                    for p in posterior.iter() {
+                        write!(file_open, "\t{}", p).expect("Unable to write to file");
+                    }
+                    write!(file_open, "\n").expect("Unable to write to file");
+                }
+
+                if assignments.len() < reads.len() {
+                    info!("{} of {} informative reads in sample {} could not be assigned to a strain \
+                           with posterior {} or more", reads.len() - assignments.len(), reads.len(),
+                          sample_name, params.min_posterior);
+                }
+
+                if params.write_bams {
+                    write_strain_bams(bam_path, sample_idx, sample_name, output_prefix, threads,
+                                      &strains, &assignments, &seen, read_names);
+                }
+            }
+            info!("Read assignments written to {}", &file_name);
+        }
+    }
+}
+
+/// Splits a BAM file into one BAM per strain. Informative reads go to the strain they were
+/// assigned to, informative reads that could not be assigned go to an unassigned BAM and
+/// uninformative reads go to every strain.
+fn write_strain_bams(bam_path: &str,
+                     sample_idx: usize,
+                     sample_name: &str,
+                     output_prefix: &str,
+                     threads: usize,
+                     strains: &Vec<usize>,
+                     assignments: &HashMap<ReadId, usize>,
+                     informative: &HashSet<ReadId>,
+                     read_names: &ReadInterner) {
+    let mut bam_reader = bam::Reader::from_path(&Path::new(bam_path))
+        .expect(&format!("Unable to open BAM file {}", bam_path));
+    let header = bam::Header::from_template(bam_reader.header());
+    let mut writers = strains.iter().map(|strain| {
+        let file_name = format!("{}_{}_strain_{}.bam", output_prefix, sample_name, strain);
+        let mut writer = bam::Writer::from_path(&file_name, &header, bam::Format::BAM)
+            .expect(&format!("Unable to create BAM file {}", &file_name));
+        writer.set_threads(threads).expect("Unable to set threads on BAM writer");
+        writer
+    }).collect::<Vec<bam::Writer>>();
+    let unassigned_name = format!("{}_{}_unassigned.bam", output_prefix, sample_name);
+    let mut unassigned = bam::Writer::from_path(&unassigned_name, &header, bam::Format::BAM)
+        .expect(&format!("Unable to create BAM file {}", &unassigned_name));
+    unassigned.set_threads(threads).expect("Unable to set threads on BAM writer");
+
+    let mut record = bam::Record::new();
+    while bam_reader.read(&mut record).expect("Error while reading BAM record") {
+        let id = read_names.get(sample_idx, record.qname());
+        match id.and_then(|id| assignments.get(&id)) {
+            Some(strain_idx) => {
+                writers[*strain_idx].write(&record).expect("Unable to write BAM record");
+            },
+            None => {
+                if id.map(|id| informative.contains(&id)).unwrap_or(false) {
+                    unassigned.write(&record).expect("Unable to write BAM record");
+                } else {
+                    for writer in writers.iter_mut() {
+                        writer.write(&record).expect("Unable to write BAM record");
+                    }
+                }
+            },
+        }
+    }
+}
+
+/// Estimates strain mixture weights and per read strain posteriors from the log likelihood of
+/// each read under each strain
+pub fn expectation_maximization(log_likelihoods: &Vec<Vec<f64>>,
+                                max_iterations: usize,
+                                tolerance: f64) -> (Vec<f64>, Vec<Vec<f64>>) {
+    let strain_count = match log_likelihoods.first() {
+        Some(likelihoods) => likelihoods.len(),
+        None => return (Vec::new(), Vec::new()),
+    };
+    let mut weights = vec![1. / strain_count as f64; strain_count];
+    let mut posteriors = vec![vec![0.; strain_count]; log_likelihoods.len()];
+
+    for _ in 0..max_iterations {
+        // E step
+        for (read_idx, likelihoods) in log_likelihoods.iter().enumerate() {
+            let joint = likelihoods.iter().zip(weights.iter())
+                .map(|(l, w)| l + w.ln()).collect::<Vec<f64>>();
+            let max = joint.iter().cloned().fold(std::f64::NEG_INFINITY, f64::max);
+            let total = max + joint.iter().map(|j| (j - max).exp()).sum::<f64>().ln();
+            for (strain_idx, j) in joint.iter().enumerate() {
+                posteriors[read_idx][strain_idx] = (j - total).exp();
+            }
+        }
+
+        // M step
+        let new_weights = (0..strain_count).map(|strain_idx| {
+            let weight = posteriors.iter().map(|p| p[strain_idx]).sum::<f64>()
+                / log_likelihoods.len() as f64;
+            // Keep weights away from zero so that strains can be recovered
+            weight.max(1e-10)
+        }).collect::<Vec<f64>>();
+        let change = new_weights.iter().zip(weights.iter())
+            .map(|(a, b)| (a - b).abs()).fold(0., f64::max);
+        weights = new_weights;
+        if change < tolerance {
+            break
+        }
+    }
+
+    (weights, posteriors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expectation_maximization() {
+        let matching = (0.99 as f64).ln();
+        let mismatching = (0.01 as f64).ln();
+        // Three reads from the first strain and one from the second
+        let log_likelihoods = vec![
+            vec![matching * 2., mismatching * 2.],
+            vec![matching * 2., mismatching * 2.],
+            vec![matching, mismatching],
+            vec![mismatching * 2., matching * 2.],
+        ];
+        let (weights, posteriors) = expectation_maximization(&log_likelihoods, 100, 1e-8);
+        assert!((weights[0] - 0.75).abs() < 0.01);
+        assert!((weights[1] - 0.25).abs() < 0.01);
+        assert!(posteriors[0][0] > 0.99);
+        assert!(posteriors[3][1] > 0.99);
+        for posterior in posteriors.iter() {
+            assert!((posterior.iter().sum::<f64>() - 1.).abs() < 1e-10);
+        }
+    }
+
+    #[test]
+    fn test_strain_allele() {
+        let mut categories = HashMap::new();
+        categories.insert(fuzzy::Category::Noise, [Variant::SNV(b'A')].iter().cloned().collect());
+        assert_eq!(strain_allele(&categories), Variant::SNV(b'A'));
+        categories.insert(fuzzy::Category::Core, [Variant::SNV(b'T')].iter().cloned().collect());
+        assert_eq!(strain_allele(&categories), Variant::SNV(b'T'));
+    }
+}
@@ -0,0 +1,230 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use model::variants::*;
+use model::read_names::*;
+use dbscan::fuzzy;
+use estimation::linkage::*;
+
+/// Variants joined into one haplotype path along with the allele it carries at each site
+struct Path {
+    members: Vec<usize>,
+    alleles: HashMap<(i32, i64), Variant>,
+}
+
+/// Grows haplotype paths by joining the paths of linked variants. Paths are only joined when
+/// they agree on the allele at every site they share
+struct PathBuilder<'a> {
+    variant_info: &'a Vec<fuzzy::Var>,
+    parents: Vec<usize>,
+    paths: Vec<Option<Path>>,
+}
+
+impl<'a> PathBuilder<'a> {
+    fn new(variant_info: &'a Vec<fuzzy::Var>) -> PathBuilder<'a> {
+        PathBuilder {
+            variant_info,
+            parents: (0..variant_info.len()).collect(),
+            paths: variant_info.iter().enumerate().map(|(index, var)| {
+                let mut alleles = HashMap::new();
+                alleles.insert((var.tid, var.pos), var.var.clone());
+                Some(Path { members: vec![index], alleles })
+            }).collect(),
+        }
+    }
+
+    fn find(&mut self, index: usize) -> usize {
+        let mut root = index;
+        while self.parents[root] != root {
+            root = self.parents[root];
+        }
+        let mut current = index;
+        while self.parents[current] != root {
+            let next = self.parents[current];
+            self.parents[current] = root;
+            current = next;
+        }
+        root
+    }
+
+    /// Joins the paths of the two variants, returning false if they carry different alleles at
+    /// any site
+    fn join(&mut self, index_1: usize, index_2: usize) -> bool {
+        let (root_1, root_2) = (self.find(index_1), self.find(index_2));
+        if root_1 == root_2 {
+            return true
+        }
+        let (large, small) = if self.paths[root_1].as_ref().unwrap().members.len()
+            >= self.paths[root_2].as_ref().unwrap().members.len() {
+            (root_1, root_2)
+        } else {
+            (root_2, root_1)
+        };
+        let conflict = {
+            let large_alleles = &self.paths[large].as_ref().unwrap().alleles;
+            self.paths[small].as_ref().unwrap().alleles.iter().any(|(site, allele)| {
+                match large_alleles.get(site) {
+                    Some(other) => other != allele,
+                    None => false,
+                }
+            })
+        };
+        if conflict {
+            return false
+        }
+
+        let small_path = self.paths[small].take().unwrap();
+        let large_path = self.paths[large].as_mut().unwrap();
+        large_path.members.extend(small_path.members);
+        large_path.alleles.extend(small_path.alleles);
+        self.parents[small] = large;
+        true
+    }
+
+    /// Paths with at least two variants, largest first, each ordered by position
+    fn paths(self) -> Vec<Vec<usize>> {
+        let variant_info = self.variant_info;
+        let mut paths = self.paths.into_iter()
+            .filter_map(|path| path)
+            .filter(|path| path.members.len() > 1)
+            .map(|path| {
+                let mut members = path.members;
+                members.sort_by_key(|index| (variant_info[*index].tid, variant_info[*index].pos));
+                members
+            }).collect::<Vec<Vec<usize>>>();
+        paths.sort_by(|a, b| b.len().cmp(&a.len())
+            .then((variant_info[a[0]].tid, variant_info[a[0]].pos)
+                .cmp(&(variant_info[b[0]].tid, variant_info[b[0]].pos))));
+        paths
+    }
+}
+
+/// Frequency of a variant pooled over samples
+fn allele_frequency(var: &fuzzy::Var) -> f64 {
+    let depth = var.deps.iter().sum::<i32>();
+    if depth > 0 {
+        var.vars.iter().sum::<i32>() as f64 / depth as f64
+    } else {
+        0.
+    }
+}
+
+/// Resolves strains from read linkage alone, for projects with a single sample where depth
+/// based distances can't separate strains. Variants are nodes of a co-occurrence graph whose
+/// edges are weighted by the reads, including mates as they share a read name, that support
+/// both variants. The link sets of
+/// [linkage_clustering_of_variants](::estimation::linkage::linkage_clustering_of_variants) start
+/// the haplotype paths, then edges are added from the strongest down, joining two paths unless
+/// they carry different alleles at a site. Edges of equal weight are taken in order of how
+/// similar the allele frequencies of their variants are. Returns the paths along with the
+/// indices of the variants that were part of a link set.
+pub fn read_graph_clustering(variant_info: &Vec<fuzzy::Var>,
+                             anchor_size: usize,
+                             anchor_similarity: f64,
+                             minimum_reads_in_link: usize) -> (Vec<fuzzy::Cluster>, HashSet<usize>) {
+    let minimum_reads_in_link = std::cmp::max(minimum_reads_in_link, 1);
+    let mut builder = PathBuilder::new(variant_info);
+
+    let mut seeds = linkage_clustering_of_variants(variant_info, anchor_size, anchor_similarity,
+                                                   minimum_reads_in_link);
+    seeds.sort_by(|a, b| b.len().cmp(&a.len()));
+    let seed_variants = seeds.iter()
+        .flat_map(|seed| seed.iter().map(|assignment| assignment.index))
+        .collect::<HashSet<usize>>();
+    let mut seed_conflicts = 0;
+    for seed in seeds.iter() {
+        for assignment in seed.iter().skip(1) {
+            if !builder.join(seed[0].index, assignment.index) {
+                seed_conflicts += 1;
+            }
+        }
+    }
+
+    let frequencies = variant_info.iter().map(|var| allele_frequency(var)).collect::<Vec<f64>>();
+    let mut edges = shared_read_counts(variant_info, minimum_reads_in_link).into_iter()
+        .filter(|(_, shared)| *shared >= minimum_reads_in_link)
+        .collect::<Vec<((usize, usize), usize)>>();
+    edges.sort_by(|((a_1, a_2), a_shared), ((b_1, b_2), b_shared)| {
+        let a_difference = (frequencies[*a_1] - frequencies[*a_2]).abs();
+        let b_difference = (frequencies[*b_1] - frequencies[*b_2]).abs();
+        b_shared.cmp(a_shared)
+            .then(a_difference.partial_cmp(&b_difference).unwrap())
+            .then((a_1, a_2).cmp(&(b_1, b_2)))
+    });
+    let mut edge_conflicts = 0;
+    for ((index_1, index_2), shared) in edges.iter() {
+        if !builder.join(*index_1, *index_2) {
+            debug!("Not joining variants {} and {} sharing {} reads as their paths conflict",
+                   index_1, index_2, shared);
+            edge_conflicts += 1;
+        }
+    }
+
+    let paths = builder.paths();
+    info!("Read graph of {} variants and {} links gave {} haplotype paths from {} seeds, \
+           rejecting {} seed members and {} links with conflicting alleles",
+          variant_info.len(), edges.len(), paths.len(), seeds.len(), seed_conflicts, edge_conflicts);
+
+    // Each variant's label is the fraction of its reads shared with the rest of its path
+    let clusters = paths.into_iter().map(|path| {
+        let mut read_counts: BTreeMap<ReadId, usize> = BTreeMap::new();
+        for index in path.iter() {
+            for read in variant_info[*index].reads.iter() {
+                *read_counts.entry(*read).or_insert(0) += 1;
+            }
+        }
+        path.iter().map(|index| {
+            let reads = &variant_info[*index].reads;
+            let shared = reads.iter().filter(|read| read_counts[*read] > 1).count();
+            let label = if reads.len() > 0 { shared as f64 / reads.len() as f64 } else { 0. };
+            fuzzy::Assignment {
+                index: *index,
+                label,
+                category: if label >= 0.5 { fuzzy::Category::Core } else { fuzzy::Category::Border },
+            }
+        }).collect::<fuzzy::Cluster>()
+    }).collect();
+    (clusters, seed_variants)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_var(pos: i64, alt: u8, reads: &[&[u8]], read_names: &mut ReadInterner) -> fuzzy::Var {
+        fuzzy::Var {
+            pos,
+            var: Variant::SNV(alt),
+            deps: vec![10],
+            vars: vec![reads.len() as i32],
+            tid: 0,
+            reads: ReadSet::from_names(0, reads.iter(), read_names),
+        }
+    }
+
+    #[test]
+    fn test_read_graph_clustering() {
+        let mut read_names = ReadInterner::new();
+        let variant_info = vec![
+            create_var(10, b'A', &[b"graph_1", b"graph_2", b"graph_3"], &mut read_names),
+            create_var(10, b'T', &[b"graph_4", b"graph_5", b"graph_6"], &mut read_names),
+            create_var(20, b'G', &[b"graph_1", b"graph_2", b"graph_3"], &mut read_names),
+            create_var(20, b'C', &[b"graph_4", b"graph_5", b"graph_6"], &mut read_names),
+            // Mostly linked to the first strain, with one read shared with the second
+            create_var(30, b'A', &[b"graph_1", b"graph_2", b"graph_3", b"graph_4"], &mut read_names),
+        ];
+
+        let (clusters, seed_variants) = read_graph_clustering(&variant_info, 10, 0.97, 1);
+        let indices = clusters.iter()
+            .map(|cluster| cluster.iter().map(|assignment| assignment.index).collect::<Vec<usize>>())
+            .collect::<Vec<Vec<usize>>>();
+        assert_eq!(indices, vec![vec![0, 2, 4], vec![1, 3]]);
+        assert_eq!(clusters[0][2].label, 0.75);
+        assert_eq!(clusters[1][0].category, fuzzy::Category::Core);
+
+        // Only the members of link sets are seeds, not every variant on a path
+        let links = linkage_clustering_of_variants(&variant_info, 10, 0.97, 1);
+        assert_eq!(seed_variants, links.iter()
+            .flat_map(|link| link.iter().map(|assignment| assignment.index))
+            .collect::<HashSet<usize>>());
+    }
+}
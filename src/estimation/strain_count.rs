@@ -0,0 +1,207 @@
+use std::f64::NEG_INFINITY;
+
+/// Lower and upper bound of the per base error rate taken from the depth regression
+const MIN_ERROR_RATE: f64 = 1e-4;
+const MAX_ERROR_RATE: f64 = 0.25;
+/// Error rate used when no regression could be fitted for a sample
+pub const DEFAULT_ERROR_RATE: f64 = 0.01;
+/// Upper limit on the number of frequency components tried, regardless of --max-strains
+const MAX_COMPONENTS: usize = 15;
+
+/// A binomial mixture fitted to the allele frequency spectrum of one sample. The first
+/// component is fixed at the error rate, the others are free allele frequencies
+#[derive(Debug, Clone)]
+pub struct FrequencyModel {
+    /// Number of free frequency components
+    pub components: usize,
+    /// Frequencies of the free components, ascending
+    pub frequencies: Vec<f64>,
+    /// Mixing weights with the error component first
+    pub weights: Vec<f64>,
+    /// Log-likelihood up to the binomial coefficients, which are shared by all models
+    pub log_likelihood: f64,
+    pub bic: f64,
+}
+
+/// The strain count estimate of one sample along with every model that was compared
+#[derive(Debug, Clone)]
+pub struct StrainCountEstimate {
+    pub error_rate: f64,
+    pub sites: usize,
+    pub models: Vec<FrequencyModel>,
+    /// Index of the selected model in models
+    pub selected: usize,
+    pub strains: usize,
+}
+
+/// Clamps a regression slope of variant depth on read depth to a usable error rate
+pub fn error_rate_from_slope(slope: f64) -> f64 {
+    if slope.is_finite() && slope > 0. {
+        slope.max(MIN_ERROR_RATE).min(MAX_ERROR_RATE)
+    } else {
+        DEFAULT_ERROR_RATE
+    }
+}
+
+/// With n strains, a variant is carried by a non-empty subset of them so its frequency can take
+/// up to 2^n - 1 distinct values. The number of strains is the fewest that explain the number of
+/// frequency components
+pub fn strains_for_components(components: usize) -> usize {
+    let mut strains = 1;
+    while (1 << strains) - 1 < components {
+        strains += 1;
+    }
+    strains
+}
+
+/// Log of the binomial probability of x successes in n trials without the binomial coefficient
+fn log_binomial(x: f64, n: f64, p: f64) -> f64 {
+    let p = p.max(1e-9).min(1. - 1e-9);
+    x * p.ln() + (n - x) * (1. - p).ln()
+}
+
+fn log_sum_exp(values: &[f64]) -> f64 {
+    let max = values.iter().cloned().fold(NEG_INFINITY, f64::max);
+    if max == NEG_INFINITY {
+        return max
+    }
+    max + values.iter().map(|value| (value - max).exp()).sum::<f64>().ln()
+}
+
+/// Fits a mixture of the fixed error component and the given number of free binomial components
+/// to (variant depth, total depth) pairs by expectation maximisation
+pub fn fit_frequency_mixture(sites: &[(f64, f64)], components: usize, error_rate: f64) -> FrequencyModel {
+    // Start the free components at evenly spaced quantiles of the observed frequencies that are
+    // not explained by errors alone
+    let mut observed = sites.iter().map(|(x, n)| x / n)
+        .filter(|f| *f > error_rate * 3.).collect::<Vec<f64>>();
+    observed.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let mut frequencies = (0..components).map(|j| {
+        if observed.len() > 0 {
+            observed[((j as f64 + 0.5) / components as f64 * observed.len() as f64) as usize]
+        } else {
+            (j as f64 + 0.5) / components as f64
+        }
+    }).map(|f| f.max(error_rate * 2.).min(1. - 1e-6)).collect::<Vec<f64>>();
+    let mut weights = vec![1. / (components + 1) as f64; components + 1];
+
+    let mut log_likelihood = NEG_INFINITY;
+    for _ in 0..500 {
+        let mut weight_sums = vec![0.; components + 1];
+        let mut var_sums = vec![0.; components];
+        let mut dep_sums = vec![0.; components];
+        let mut new_log_likelihood = 0.;
+        for (x, n) in sites.iter() {
+            let log_probs = (0..components + 1).map(|j| {
+                let p = if j == 0 { error_rate } else { frequencies[j - 1] };
+                weights[j].ln() + log_binomial(*x, *n, p)
+            }).collect::<Vec<f64>>();
+            let total = log_sum_exp(&log_probs);
+            new_log_likelihood += total;
+            for j in 0..components + 1 {
+                let responsibility = (log_probs[j] - total).exp();
+                weight_sums[j] += responsibility;
+                if j > 0 {
+                    var_sums[j - 1] += responsibility * x;
+                    dep_sums[j - 1] += responsibility * n;
+                }
+            }
+        }
+
+        for j in 0..components + 1 {
+            weights[j] = (weight_sums[j] / sites.len() as f64).max(1e-12);
+        }
+        for j in 0..components {
+            if dep_sums[j] > 0. {
+                frequencies[j] = (var_sums[j] / dep_sums[j]).max(1e-6).min(1. - 1e-6);
+            }
+        }
+
+        let converged = (new_log_likelihood - log_likelihood).abs() < 1e-6;
+        log_likelihood = new_log_likelihood;
+        if converged {
+            break
+        }
+    }
+
+    // Order the components by frequency so models are easier to read
+    let mut order = (0..components).collect::<Vec<usize>>();
+    order.sort_by(|a, b| frequencies[*a].partial_cmp(&frequencies[*b]).unwrap());
+    let mut sorted_weights = vec![weights[0]];
+    sorted_weights.extend(order.iter().map(|j| weights[j + 1]));
+
+    // Each free component adds a frequency and a weight
+    let parameters = 2 * components;
+    FrequencyModel {
+        components,
+        frequencies: order.iter().map(|j| frequencies[*j]).collect(),
+        weights: sorted_weights,
+        log_likelihood,
+        bic: -2. * log_likelihood + parameters as f64 * (sites.len().max(1) as f64).ln(),
+    }
+}
+
+/// Compares mixtures with 0 up to 2^max_strains - 1 frequency components by BIC and converts
+/// the best number of components into a strain count
+pub fn estimate_strain_count(sites: &[(f64, f64)], error_rate: f64, max_strains: usize) -> StrainCountEstimate {
+    // More strains than needed for MAX_COMPONENTS can't change the result, and bounding them
+    // keeps the shift below from overflowing
+    let max_strains = max_strains.max(1).min(strains_for_components(MAX_COMPONENTS));
+    let max_components = std::cmp::min(
+        std::cmp::min((1 << max_strains) - 1, MAX_COMPONENTS), sites.len());
+    let models = (0..max_components + 1)
+        .map(|components| fit_frequency_mixture(sites, components, error_rate))
+        .collect::<Vec<FrequencyModel>>();
+    let selected = (0..models.len())
+        .min_by(|a, b| models[*a].bic.partial_cmp(&models[*b].bic).unwrap())
+        .unwrap();
+
+    StrainCountEstimate {
+        error_rate,
+        sites: sites.len(),
+        strains: std::cmp::min(strains_for_components(models[selected].components), max_strains.max(1)),
+        models,
+        selected,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_strain_count() {
+        assert_eq!(strains_for_components(0), 1);
+        assert_eq!(strains_for_components(1), 1);
+        assert_eq!(strains_for_components(3), 2);
+        assert_eq!(strains_for_components(4), 3);
+        assert_eq!(error_rate_from_slope(-0.5), DEFAULT_ERROR_RATE);
+        assert_eq!(error_rate_from_slope(1e-6), MIN_ERROR_RATE);
+
+        // Errors plus fixed differences from the reference look like a single strain
+        let mut sites = Vec::new();
+        for i in 0..50 {
+            sites.push(((i % 2) as f64, 100.));
+            sites.push((99., 100.));
+        }
+        let estimate = estimate_strain_count(&sites, 0.01, 4);
+        assert_eq!(estimate.strains, 1);
+
+        // Two strains at 30% and 70% alongside fixed differences
+        for _ in 0..50 {
+            sites.push((30., 100.));
+            sites.push((70., 100.));
+        }
+        let estimate = estimate_strain_count(&sites, 0.01, 4);
+        assert_eq!(estimate.models[estimate.selected].components, 3);
+        assert_eq!(estimate.strains, 2);
+        let frequencies = &estimate.models[estimate.selected].frequencies;
+        assert!((frequencies[0] - 0.3).abs() < 0.02);
+        assert!((frequencies[1] - 0.7).abs() < 0.02);
+
+        // Strain limits beyond what the component limit allows give the same estimate
+        let unbounded = estimate_strain_count(&sites, 0.01, 100);
+        assert_eq!(unbounded.models.len(), estimate.models.len());
+        assert_eq!(unbounded.strains, 2);
+    }
+}
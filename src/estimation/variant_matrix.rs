@@ -1,1342 +1,2840 @@
-use std::collections::{HashMap, HashSet, BTreeMap};
-use estimation::contig_variants::*;
-use estimation::codon_structs::*;
-use estimation::linkage::*;
-use model::variants::*;
-use std::{str};
-use std::path::Path;
-use std::io::prelude::*;
-use rayon::prelude::*;
-use std::sync::{Arc, Mutex};
-use std::fs::File;
-use dbscan::fuzzy;
-use itertools::{izip};
-use rust_htslib::{bcf::{self}, bam::HeaderView};
-use bio::io::gff;
-use bird_tool_utils::command;
-use std::process::{Stdio};
-use tempfile;
-use external_command_checker;
-use rayon::current_num_threads;
-use estimation::codon_structs::CodonTable;
-
-#[derive(Debug)]
-/// Container for all variants within a genome and associated clusters
-pub enum VariantMatrix {
-    VariantContigMatrix {
-        coverages: HashMap<i32, Vec<f64>>,
-        average_genotypes: HashMap<i32, Vec<f64>>,
-        variances: HashMap<i32, Vec<f64>>,
-        // TID, Position, Base, Var Depth, Total Depth
-        all_variants: HashMap<i32, HashMap<i64, HashMap<Variant, Base>>>,
-        // Placeholder hashmap for the depths of each contig for a sample
-        // Deleted after use
-        depths: HashMap<i32, Vec<i32>>,
-        target_names: BTreeMap<i32, String>,
-        target_lengths: HashMap<i32, f64>,
-        sample_names: Vec<String>,
-        kfrequencies: BTreeMap<Vec<u8>, Vec<usize>>,
-        variant_counts: HashMap<usize, HashMap<i32, usize>>,
-        variant_sums: HashMap<usize, HashMap<i32, Vec<Vec<f64>>>>,
-        variant_info: Vec<fuzzy::Var>,
-        geom_mean_var: Vec<f64>,
-        geom_mean_dep: Vec<f64>,
-        geom_mean_frq: Vec<f64>,
-        pred_variants: HashMap<usize, HashMap<i32, HashMap<i64, HashMap<fuzzy::Category, HashSet<Variant>>>>>,
-//        pred_variants_all: HashMap<usize, HashMap<i32, HashMap<i32, HashSet<String>>>>,
-    }
-}
-
-impl VariantMatrix {
-    pub fn new_matrix(sample_count: usize) -> VariantMatrix {
-        VariantMatrix::VariantContigMatrix {
-            coverages: HashMap::new(),
-            variances: HashMap::new(),
-            average_genotypes: HashMap::new(),
-            all_variants: HashMap::new(),
-            depths: HashMap::new(),
-            target_names: BTreeMap::new(),
-            target_lengths: HashMap::new(),
-            sample_names: vec!["".to_string(); sample_count],
-            kfrequencies: BTreeMap::new(),
-            variant_counts: HashMap::new(),
-            variant_sums: HashMap::new(),
-            variant_info: Vec::new(),
-            geom_mean_var: Vec::new(),
-            geom_mean_dep: Vec::new(),
-            geom_mean_frq: Vec::new(),
-            pred_variants: HashMap::new(),
-        }
-    }
-}
-
-pub trait VariantMatrixFunctions {
-    fn setup(&mut self);
-
-    fn add_sample(&mut self, sample_name: String, sample_idx: usize,
-                  variant_records: &HashMap<i32, HashMap<i64, HashMap<Variant, Base>>>,
-                  header: &HeaderView);
-
-    /// Returns the alleles at the current position
-    /// as a mutable reference
-    fn variants(&mut self, tid: i32, pos: i64) -> Option<&mut HashMap<Variant, Base>>;
-
-    fn variants_of_contig(&mut self, tid: i32) -> Option<&mut HashMap<i64, HashMap<Variant, Base>>>;
-
-    /// Takes [VariantStats](contig_variants/VariantStats) struct for single contig and adds to
-    /// [VariantMatrix](VariantMatrix)
-    fn add_contig(&mut self,
-                  variant_stats: VariantStats,
-                  sample_count: usize,
-                  sample_idx: usize);
-
-    /// Converts all variants into fuzzy::Var format
-    fn generate_distances(&mut self, threads: usize, output_prefix: &str);
-
-    /// Perform fuzzy DBSCAN clustering using proportionality
-    fn run_fuzzy_scan(&mut self, e_min: f64, e_max: f64, pts_min: f64, pts_max: f64, phi: f64,
-                      anchor_size: usize, anchor_similarity: f64, minimum_reads_in_link: usize);
-
-    /// Takes clusters from DBSCAN and linkage method and writes variants to file as genotype
-    fn generate_genotypes(&mut self,
-                          output_prefix: &str,
-                          reference: &mut bio::io::fasta::IndexedReader<File>);
-
-    fn print_variant_stats(&self, output_prefix: &str, window_size: f64);
-
-    fn calc_gene_mutation(&self,
-                          gff_map: &mut HashMap<String, Vec<bio::io::gff::Record>>,
-                          reference: &mut bio::io::fasta::IndexedReader<File>,
-                          codon_table: &CodonTable,
-                          ref_name: &str,
-                          output_prefix: &str);
-
-    fn write_vcf(&self, output_prefix: &str);
-}
-
-#[allow(unused)]
-impl VariantMatrixFunctions for VariantMatrix {
-    fn setup(&mut self) {
-        match self {
-            VariantMatrix::VariantContigMatrix {
-                ref mut coverages,
-                ref mut average_genotypes,
-                ref mut all_variants,
-                ref mut target_names,
-                ref mut target_lengths,
-                ref mut sample_names,
-                ref mut kfrequencies,
-                ..
-            } => {
-                *coverages = HashMap::new();
-                *average_genotypes = HashMap::new();
-                *all_variants = HashMap::new();
-                *target_names = BTreeMap::new();
-                *target_lengths = HashMap::new();
-                *sample_names = vec!();
-                *kfrequencies = BTreeMap::new();
-            }
-        }
-    }
-
-    fn add_sample(&mut self, sample_name: String, sample_idx: usize,
-                  variant_records: &HashMap<i32, HashMap<i64, HashMap<Variant, Base>>>,
-                   header: &HeaderView) {
-        match self {
-            VariantMatrix::VariantContigMatrix {
-                ref mut sample_names,
-                ref mut all_variants,
-                ref mut target_names,
-                ref mut target_lengths,
-                ..
-            } => {
-                info!("adding sample {} at index {}", &sample_name, &sample_idx);
-                sample_names[sample_idx] = sample_name;
-                let target_count = header.target_count();
-                let tid_names = header.target_names();
-
-                for target_name in (tid_names).into_iter() {
-                    let tid = header.tid(target_name).unwrap();
-                    target_names.entry(tid as i32)
-                        .or_insert(str::from_utf8(&target_name).unwrap().to_string());
-                    let target_len = header.target_len(tid).unwrap();
-                    target_lengths.entry(tid as i32).or_insert(target_len as f64);
-                    // Initialize contig id in variant hashmap
-                    let contig_variants = all_variants.entry(tid as i32)
-                        .or_insert(HashMap::new());
-                    let placeholder = HashMap::new();
-                    let mut variants = match variant_records.get(&(tid as i32)) {
-                        Some(map) => map,
-                        _ => &placeholder,
-                    };
-                    let target_len = header.target_len(tid).unwrap();
-                    // Apppend the sample index to each variant abundance
-                    // Initialize the variant position index
-                    // Also turns out to be the total number of variant positions
-                    for (pos, abundance_map) in variants.iter() {
-                        let position_variants = contig_variants.entry(*pos as i64)
-                            .or_insert(HashMap::new());
-                        for (variant, base_info) in abundance_map {
-                            let sample_map = position_variants.entry(variant.clone())
-                                .or_insert(base_info.clone());
-                            sample_map.combine_sample(base_info, sample_idx, 0);
-                        }
-                    }
-                }
-            }
-        }
-    }
-
-    fn variants(&mut self, tid: i32, pos: i64) -> Option<&mut HashMap<Variant, Base>> {
-        match self {
-            VariantMatrix::VariantContigMatrix {
-                ref mut all_variants,
-                ..
-            } => {
-                match all_variants.get_mut(&tid) {
-                    Some(contig_variants) => {
-                        contig_variants.get_mut(&pos.clone())
-                    },
-                    _ => {
-                        None
-                    },
-                }
-            }
-        }
-    }
-
-    fn variants_of_contig(&mut self, tid: i32) -> Option<&mut HashMap<i64, HashMap<Variant, Base>>> {
-        match self {
-            VariantMatrix::VariantContigMatrix {
-                ref mut all_variants,
-                ..
-            } => {
-                all_variants.get_mut(&tid)
-            }
-        }
-    }
-
-    fn add_contig(&mut self,
-                  variant_stats: VariantStats,
-                  sample_count: usize,
-                  sample_idx: usize) {
-        match self {
-            VariantMatrix::VariantContigMatrix {
-                ref mut coverages,
-                ref mut all_variants,
-//                ref mut target_names,
-//                ref mut target_lengths,
-                ref mut variances,
-                ref mut depths,
-                ..
-            } => {
-                match variant_stats {
-                    VariantStats::VariantContigStats {
-                        tid,
-//                        target_name,
-//                        target_len,
-                        coverage,
-                        variance,
-                        depth,
-//                        variations_per_n,
-                        ..
-                    } => {
-                        let var = variances.entry(tid).or_insert(
-                            vec![0.0 as f64; sample_count]
-                        );
-                        var[sample_idx] = variance;
-                        let cov = coverages.entry(tid).or_insert(
-                            vec![0.0 as f64; sample_count]
-                        );
-                        cov[sample_idx] = coverage;
-
-                        // copy across depths
-                        let contig_variants = all_variants.entry(tid)
-                            .or_insert(HashMap::new());
-                        for (pos, d) in depth.iter().enumerate() {
-                            let position_variants = contig_variants.entry(pos as i64)
-                                .or_insert(HashMap::new());
-                            for (_variant, base_info) in position_variants.iter_mut() {
-                                base_info.add_depth(sample_idx, *d);
-                            }
-                        }
-                        depths.entry(tid).or_insert(depth);
-                    }
-                }
-            }
-        }
-    }
-
-    fn generate_distances(&mut self, _threads: usize, output_prefix: &str) {
-        match self {
-            VariantMatrix::VariantContigMatrix {
-                all_variants,
-                sample_names,
-                coverages,
-                ref mut variant_info,
-                ref mut geom_mean_var,
-                ref mut geom_mean_dep,
-                ref mut geom_mean_frq,
-                ..
-            } => {
-
-                let sample_count = sample_names.len();
-
-                let variant_info_all =
-                    Arc::new(
-                        Mutex::new(
-                            Vec::new()));
-
-                // A vector the length of the number of samples
-                // Cumulatively calculates the product of variant depths
-                let geom_mean_v =
-                    Arc::new(
-                        Mutex::new(
-                            vec![1. as f64; sample_count as usize]));
-
-                // product of total depth
-                let geom_mean_d =
-                    Arc::new(
-                        Mutex::new(
-                            vec![1. as f64; sample_count as usize]));
-
-                // product of reference frequency
-                let geom_mean_f =
-                    Arc::new(
-                        Mutex::new(
-                            vec![1. as f64; sample_count as usize]));
-
-                // get basic variant info and store as fuzzy::Var
-                all_variants.par_iter_mut().for_each(|(tid, variant_abundances)| {
-
-                    variant_abundances.par_iter_mut().for_each(
-                        |(position, hash)| {
-                            // loop through each position that has variants ignoring positions that
-                            // only contained the reference in all samples
-                            if hash.keys().len() > 0 {
-
-                                for (variant, base_info) in hash.iter_mut() {
-                                    match variant {
-
-                                        Variant::SNV(_) | Variant::None => {
-                                            let _abundance: f64 = 0.;
-                                            let _mean_var: f64 = 0.;
-                                            let mut rel_abund = vec![0.0; sample_count as usize];
-
-                                            // Get the mean abundance across samples
-                                            for index in (0..sample_count).into_iter() {
-                                                let mut geom_mean_v =
-                                                    geom_mean_v.lock().unwrap();
-                                                let mut geom_mean_d =
-                                                    geom_mean_d.lock().unwrap();
-                                                let mut geom_mean_f =
-                                                    geom_mean_f.lock().unwrap();
-
-                                                let mut var_depth
-                                                    = base_info.truedepth[index] as f64;
-
-                                                let total_depth
-                                                    = base_info.totaldepth[index] as f64;
-//                                                println!("var_depth {} tot {}", var_depth, total_depth);
-//                                                base_info.freq[index] = ;
-                                                if total_depth <= 0. {
-                                                    rel_abund[index] =
-                                                        var_depth / 1.;
-                                                } else {
-                                                    rel_abund[index] =
-                                                        var_depth / total_depth;
-                                                }
-
-                                                geom_mean_v[index] += (var_depth + 1.).ln();
-                                                geom_mean_d[index] += (total_depth + 1.).ln();
-                                                geom_mean_f[index] += ((var_depth + 1.)
-                                                    / (total_depth + 1.)).ln();
-                                            };
-
-
-                                            let mut variant_info_all = variant_info_all
-                                                .lock().unwrap();
-//                                            base_info.rel_abunds = rel_abund;
-                                            let point = fuzzy::Var {
-                                                pos: *position,
-                                                var: variant.clone(),
-                                                deps: base_info.totaldepth.clone(),
-                                                vars: base_info.depth.clone(),
-//                                                rel_abunds: rel_abund,
-                                                tid: *tid,
-                                                reads: base_info.reads.clone()
-                                            };
-                                            variant_info_all.push(point);
-                                        },
-                                        _ => {
-                                            let mut rel_abund = vec![0.0; sample_count as usize];
-
-                                            // Get the mean abundance across samples
-                                            for index in (0..sample_count).into_iter() {
-                                                let mut geom_mean_v =
-                                                    geom_mean_v.lock().unwrap();
-                                                let mut geom_mean_d =
-                                                    geom_mean_d.lock().unwrap();
-                                                let mut geom_mean_f =
-                                                    geom_mean_f.lock().unwrap();
-
-                                                let mut var_depth
-                                                    = base_info.depth[index] as f64;
-                                                if var_depth <= 0. {
-
-                                                    var_depth
-                                                        = base_info.truedepth[index] as f64;
-                                                    base_info.depth[index] = base_info.truedepth[index];
-                                                }
-                                                let total_depth
-                                                    = base_info.totaldepth[index] as f64;
-//                                                base_info.freq[index] = ;
-                                                if total_depth <= 0. {
-                                                    rel_abund[index] =
-                                                        var_depth / (1.);
-                                                } else {
-                                                    rel_abund[index] =
-                                                        var_depth / total_depth;
-                                                }
-
-                                                geom_mean_v[index] += (var_depth + 1.).ln();
-                                                geom_mean_d[index] += (total_depth + 1.).ln();
-                                                geom_mean_f[index] += ((var_depth + 1.)
-                                                    / (total_depth + 1.)).ln();
-                                            };
-
-
-                                            let mut variant_info_all = variant_info_all
-                                                .lock().unwrap();
-//                                            base_info.rel_abunds = rel_abund;
-                                            let point = fuzzy::Var {
-                                                pos: *position,
-                                                var: variant.clone(),
-                                                deps: base_info.totaldepth.clone(),
-                                                vars: base_info.depth.clone(),
-//                                                rel_abunds: rel_abund,
-                                                tid: *tid,
-                                                reads: base_info.reads.clone()
-                                            };
-                                            variant_info_all.push(point);
-                                        },
-                                    }
-                                }
-                            }
-                        });
-                });
-
-                let variant_info_all = variant_info_all.lock().unwrap().clone();
-                let geom_mean = |input: &Vec<f64>| -> Vec<f64> {
-                    let output = input.iter()
-                        .map(|sum| {
-                            (sum / variant_info_all.len() as f64).exp()
-                        }).collect::<Vec<f64>>();
-                    return output
-                };
-
-                debug!("geoms {:?} {:?} {:?}", geom_mean_d, geom_mean_v, geom_mean_f);
-
-                let geom_mean_v = geom_mean_v.lock().unwrap().clone();
-                let geom_mean_v = geom_mean(&geom_mean_v);
-                debug!("Geom Mean Var {:?}", geom_mean_v);
-
-                let geom_mean_d = geom_mean_d.lock().unwrap().clone();
-                let geom_mean_d = geom_mean(&geom_mean_d);
-                debug!("Geom Mean Dep {:?}", geom_mean_d);
-
-                let geom_mean_f = geom_mean_f.lock().unwrap().clone();
-                let geom_mean_f = geom_mean(&geom_mean_f);
-                debug!("Geom Mean Frq {:?}", geom_mean_f);
-                debug!("geoms {:?} {:?} {:?}", geom_mean_d, geom_mean_v, geom_mean_f);
-
-                info!("Beginning analysis of {} with {} variants", &output_prefix, &variant_info_all.len());
-
-                *variant_info = variant_info_all;
-                *geom_mean_var = geom_mean_v;
-                *geom_mean_dep = geom_mean_d;
-                *geom_mean_frq = geom_mean_f;
-
-            }
-        }
-    }
-
-    fn run_fuzzy_scan(&mut self, e_min: f64, e_max: f64, pts_min: f64, pts_max: f64, phi: f64,
-                      anchor_size: usize, anchor_similarity: f64, minimum_reads_in_link: usize) {
-        match self {
-            VariantMatrix::VariantContigMatrix {
-                ref mut variant_info,
-                ref mut geom_mean_var,
-                ref mut geom_mean_dep,
-                ref mut geom_mean_frq,
-                ref mut pred_variants,
-                ref mut all_variants,
-                target_lengths,
-                ..
-            } => {
-
-                if variant_info.len() == 1 {
-                    warn!("Where did the variants go? {:?}", variant_info);
-                }
-                let fuzzy_scanner = fuzzy::FuzzyDBSCAN {
-                    eps_min: e_min,
-                    eps_max: e_max,
-                    pts_min: match pts_min {
-                        _ if pts_min > 1. => pts_min,
-                        _ => pts_min*variant_info.len() as f64,
-                        },
-                    pts_max: match pts_max {
-                        _ if pts_max > 1. => pts_max,
-                        _ => pts_max*variant_info.len() as f64,
-                    },
-                    phi,
-                    geom_var: geom_mean_var.to_owned(),
-                    geom_dep: geom_mean_dep.to_owned(),
-                    geom_frq: geom_mean_frq.to_owned(),
-                };
-
-                // Perform read phasing clustering and return initial clusters
-                let links = linkage_clustering_of_variants(&variant_info,
-                                                           anchor_size, anchor_similarity,
-                                                           minimum_reads_in_link);
-
-                // run fuzzy DBSCAN
-                if links.len() > 0 {
-                    info!("Running Seeded fuzzyDBSCAN with {} initial clusters", links.len());
-                } else {
-                    warn!("No initial clusters formed, running fuzzyDBSCAN with no seeds. Perhaps lower linkage thresholds?")
-                }
-                let clusters = fuzzy_scanner.cluster(
-                    &variant_info[..],
-                    links);
-
-                // Since these are hashmaps, I'm using Arc and Mutex here since not sure how
-                // keep hashmaps updated using channel()
-                let prediction_variants =
-                    Mutex::new(
-                        HashMap::new());
-
-
-                let all_variants =
-                    Mutex::new(
-                        all_variants);
-
-                // Organize clusters into genotypes by recollecting full variant information
-                clusters.par_iter().enumerate().for_each(|(rank, cluster)|{
-                    // Sets for each cluster keeping track of which variant types are present in
-                    // a cluster
-                    let prediction_set = Mutex::new(
-                        HashSet::new());
-                    cluster.par_iter().for_each(|assignment|{
-
-                        let variant: &fuzzy::Var = &variant_info[assignment.index];
-
-                        let mut prediction_set = prediction_set.lock().unwrap();
-                        prediction_set.insert(variant.var.to_owned());
-
-                        let mut prediction_variants = prediction_variants
-                            .lock()
-                            .unwrap();
-
-                        let variant_tid = prediction_variants
-                            .entry(rank + 1)
-                            .or_insert(HashMap::new());
-
-                        let variant_pos = variant_tid
-                            .entry(variant.tid)
-                            .or_insert(HashMap::new());
-
-                        let variant_cat = variant_pos
-                            .entry(variant.pos)
-                            .or_insert(HashMap::new());
-
-                        let variant_set = variant_cat
-                            .entry(assignment.category)
-                            .or_insert(HashSet::new());
-
-                        variant_set.insert(variant.var.to_owned());
-
-                        // Add genotypes to base
-                        let mut all_variants = all_variants.lock().unwrap();
-                        let base_tid = all_variants.entry(variant.tid)
-                            .or_insert(HashMap::new());
-                        let base_pos = base_tid.entry(variant.pos)
-                            .or_insert(HashMap::new());
-                        match base_pos.get_mut(&variant.var) {
-                            Some(base_base) => {
-                                base_base.genotypes.insert(rank as i32 + 1);
-                            },
-                            None => {},
-                        };
-
-                    });
-                    let mut prediction_set = prediction_set.lock().unwrap();
-                    if !(prediction_set.len() == 1 && prediction_set.contains(&Variant::None)) {
-
-                    } else {
-                        let mut prediction_variants = prediction_variants
-                            .lock()
-                            .unwrap();
-
-                        prediction_variants
-                            .remove_entry(&(rank + 1)).expect("Unable to remove cluster");
-                    }
-
-                });
-
-                let prediction_variants = prediction_variants.lock().unwrap();
-                let mut all_variants = all_variants.lock().unwrap();
-//                debug!("Prediction count {:?}", prediction_count);
-//                debug!("Prediction categories {:?}", prediction_features);
-                *pred_variants = prediction_variants.clone();
-                **all_variants = all_variants.clone();
-            }
-        }
-    }
-
-    #[allow(unused)]
-    fn generate_genotypes(&mut self, output_prefix: &str,
-                          reference: &mut bio::io::fasta::IndexedReader<File>) {
-        match self {
-            VariantMatrix::VariantContigMatrix {
-                target_names,
-                ref mut pred_variants,
-                ..
-            } => {
-                let reference = Mutex::new(reference);
-                pred_variants.par_iter().for_each(|(strain_index, genotype)|{
-                    let file_name = format!("{}_strain_{}.fna", output_prefix.to_string(), strain_index);
-
-                    let file_path = Path::new(&file_name);
-                    // Open haplotype file or create one
-                    let mut file_open = File::create(file_path)
-                        .expect("No Read or Write Permission in current directory");
-
-                    let mut original_contig = Vec::new();
-
-                    let mut genotype = genotype.clone();
-
-                    let mut total_variant_alleles = 0;
-                    let mut total_reference_alleles = 0;
-
-                    // Generate the variant genome
-                    for (tid, target_name) in target_names.iter() {
-                        let mut contig = String::new();
-                        original_contig = Vec::new();
-                        {
-                            let mut reference = reference.lock().unwrap();
-                            match reference.fetch_all(
-                                std::str::from_utf8(target_name.as_bytes()).unwrap()) {
-                                Ok(reference) => reference,
-                                Err(e) => {
-                                    warn!("Cannot read sequence from reference {:?}", e);
-                                    std::process::exit(1)
-                                },
-                            };
-                            match reference.read(&mut original_contig) {
-                                Ok(reference) => reference,
-                                Err(e) => {
-                                    warn!("Cannot read sequence from reference {:?}", e);
-                                    std::process::exit(1)
-                                },
-                            };
-                        }
-                        let mut skip_n = 0;
-                        let mut skip_cnt = 0;
-//                            let char_cnt = 0;
-                        let mut variations = 0;
-                        let mut ref_alleles = 0;
-
-                        if genotype.contains_key(&tid) {
-                            for (pos, base) in original_contig.iter().enumerate() {
-                                if skip_cnt < skip_n {
-                                    skip_cnt += 1;
-                                } else {
-                                    skip_n = 0;
-                                    skip_cnt = 0;
-    //                                if genotype.contains_key(&tid) {
-                                        let tid_genotype = genotype.get_mut(&tid).unwrap();
-
-                                        if tid_genotype.contains_key(&(pos as i64)) {
-                                            let categories = &genotype[tid][&(pos as i64)];
-                                            let hash;
-                                            if categories.contains_key(&fuzzy::Category::Core) {
-                                                hash = categories[&fuzzy::Category::Core].clone();
-                                            } else if categories.contains_key(&fuzzy::Category::Border) {
-                                                hash = categories[&fuzzy::Category::Border].clone();
-                                            } else {
-                                                hash = categories[&fuzzy::Category::Noise].clone();
-                                            }
-
-                                            let mut max_var = Variant::None;
-
-                                            for var in hash.iter() {
-                                                // If there are two variants possible for
-                                                // a single site and one is the reference
-                                                // we will choose the reference
-                                                if max_var == Variant::None {
-                                                    max_var = var.clone();
-                                                }
-                                            }
-                                            if hash.len() > 1 {
-    //                                            multivariant_sites += 1;
-                                                debug!("Multi hash {:?} {:?}", hash, max_var)
-                                            }
-                                            match max_var {
-                                                Variant::Deletion(size) => {
-                                                    // Skip the next n bases but rescue the reference prefix
-                                                    skip_n = size;
-                                                    skip_cnt = 0;
-                                                    contig = contig + str::from_utf8(&[*base]).unwrap();
-                                                    // If we had the sequence we would rescue first base like this
-    //                                                let first_byte = max_var.as_bytes()[0];
-    //                                                contig = contig + str::from_utf8(
-    //                                                    &[first_byte]).unwrap();
-                                                    variations += 1;
-                                                },
-                                                Variant::Insertion(alt) => {
-
-                                                    // Remove prefix from variant
-                                                    let removed_first_base = str::from_utf8(
-                                                        &alt[1..]).unwrap();
-                                                    contig = contig + removed_first_base;
-                                                    variations += 1;
-                                                },
-                                                Variant::Inversion(alt) | Variant::MNV(alt) => {
-                                                    // Skip the next n bases
-                                                    skip_n = alt.len() as u32 - 1;
-                                                    skip_cnt = 0;
-                                                    // Inversions and MNVs don't have a first base prefix, so take
-                                                    // wholes tring
-                                                    let inversion = str::from_utf8(
-                                                        &alt).unwrap();
-                                                    contig = contig + inversion;
-                                                    variations += 1;
-                                                },
-                                                Variant::None => {
-                                                    contig = contig + str::from_utf8(&[*base]).unwrap();
-                                                    ref_alleles += 1;
-                                                },
-                                                Variant::SNV(alt) => {
-
-                                                    contig = contig + str::from_utf8(&[alt]).unwrap();
-                                                    variations += 1;
-                                                },
-                                                _ => {
-                                                    contig = contig + str::from_utf8(&[*base]).unwrap();
-                                                    ref_alleles += 1;
-                                                }
-                                            }
-                                        } else {
-                                            contig = contig + str::from_utf8(&[*base]).unwrap();
-                                        }
-    //                                } else {
-    //                                    contig = str::from_utf8(&original_contig)
-    //                                        .expect("Can't convert to str").to_string();
-    //                                }
-                                }
-                            };
-                        } else {
-                            contig = str::from_utf8(&original_contig)
-                                .expect("Can't convert to str").to_string();
-                        }
-
-                        writeln!(file_open, ">{}_strain_{}_alt_alleles_{}_ref_alleles_{}",
-                                 target_names[tid],
-                                 strain_index,
-                                 variations, ref_alleles).expect("Unable to write to file");
-
-                        for line in contig.as_bytes().to_vec()[..].chunks(60).into_iter() {
-                            file_open.write(line).unwrap();
-                            file_open.write(b"\n").unwrap();
-                        };
-                        total_variant_alleles += variations;
-                        total_reference_alleles += ref_alleles;
-                    }
-                    info!("Cluster {} contains {} variant alleles and {} reference alleles",
-                          strain_index, total_variant_alleles, total_reference_alleles);
-                });
-            }
-        }
-    }
-
-    fn print_variant_stats(&self, output_prefix: &str, window_size: f64) {
-        match self {
-            VariantMatrix::VariantContigMatrix {
-                target_names,
-                target_lengths,
-                sample_names,
-                all_variants,
-                variant_sums,
-                variant_info,
-                ..
-            } => {
-
-                let file_name = output_prefix.to_string()
-                    + &".tsv".to_owned();
-                let snp_locs = format!("{}_snp_locations.tsv", output_prefix);
-                let file_path = Path::new(&file_name);
-                let mut file_open = match File::create(file_path) {
-                    Ok(fasta) => fasta,
-                    Err(e) => {
-                        println!("Cannot create file {:?}", e);
-                        std::process::exit(1)
-                    },
-                };
-
-                let snp_loc_path = Path::new(&snp_locs);
-                let mut snp_loc_open = match File::create(snp_loc_path) {
-                    Ok(tsv) => tsv,
-                    Err(e) => {
-                        println!("Cannot create file {:?}", e);
-                        std::process::exit(1)
-                    },
-                };
-
-                // Snp density summary start
-                write!(file_open, "contigName\tcontigLen").unwrap();
-
-                // Snp location start
-                write!(snp_loc_open, "SNP\tchr\tpos").unwrap();
-                debug!("Sample Names {:?}", sample_names);
-                for sample_name in sample_names.iter(){
-                    write!(file_open,
-                           "\t{}.snvsPer{}kb\t{}.svsPer{}kb\t{}.snvCount\t{}.svCount",
-                           &sample_name, &window_size, &sample_name, &window_size,
-                           &sample_name, &sample_name).unwrap();
-                }
-                write!(file_open, "\n").unwrap();
-                write!(snp_loc_open, "\n").unwrap();
-
-                let mut snps = 0;
-
-                for (tid, contig_name) in target_names.iter() {
-                    let contig_len =  target_lengths[tid];
-                    write!(file_open, "{}\t{}", contig_name, contig_len).unwrap();
-                    match all_variants.get(tid) {
-                        Some(variants_in_contig) => {
-                            // Set up channels that receive a vector of values for each sample
-                            let mut snps_cnt_vec = vec![0; sample_names.len()];
-                            let svs_cnt_vec = Mutex::new(vec![0; sample_names.len()]);
-//                            let (snp_freq_s, snp_freq_r) = channel();
-//                            let (sv_freq_s, sv_freq_r) = channel();
-//                            let (snp_std_s, snp_std_r) = channel();
-//                            let (sv_std_s, sv_std_r) = channel();
-                            let window = contig_len / window_size;
-                            variants_in_contig.iter().enumerate()
-                                .for_each(|(index, (position, variants))| {
-                                    // Get how many alleles are present at loci
-                                    let alleles = variants.len();
-                                    for (var, base) in variants {
-                                        match var {
-                                            Variant::SNV(_) => {
-                                                write!(snp_loc_open, "SNP{}\t{}\t{}\n",
-                                                       index, contig_name, position).unwrap();
-                                                base.truedepth
-                                                    .iter()
-                                                    .enumerate()
-                                                    .zip(base.depth.iter().enumerate())
-                                                    .for_each(|((index, count_1), (_index_2, count_2))| {
-                                                        if count_1 > &0 || count_2 > &0 {
-                                                            snps_cnt_vec[index] += 1;
-                                                        }
-                                                    });
-                                                snps += 1;
-
-                                            },
-                                            Variant::None => {
-                                                // If biallelic or multiallelic then
-                                                // I don't think we want the ref depth?
-
-                                            },
-                                            _ => {
-                                                base.truedepth
-                                                    .par_iter()
-                                                    .enumerate()
-                                                    .zip(base.depth.par_iter().enumerate())
-                                                    .for_each(|((index, count_1), (_, count_2))| {
-                                                        if count_1 > &0 || count_2 > &0 {
-                                                            let mut svs_cnt_vec
-                                                                = svs_cnt_vec.lock().unwrap();
-                                                            svs_cnt_vec[index] += 1;
-                                                        }
-                                                    })
-                                            }
-                                        };
-                                    };
-
-                                });
-                            let svs_cnt_vec = svs_cnt_vec.lock().unwrap().clone();
-                            let snps_per_win: Vec<_> = snps_cnt_vec.iter().map(|count| *count as f64 / window).collect();
-                            let svs_per_win: Vec<_> = svs_cnt_vec.iter().map(|count| *count as f64 / window).collect();
-
-                            for (snp_w, svs_w, snp_c, svs_c) in izip!(&snps_per_win, &svs_per_win, &snps_cnt_vec, &svs_cnt_vec) {
-                                write!(file_open, "\t{}\t{}\t{}\t{}", snp_w, svs_w, snp_c, svs_c).unwrap();
-                            }
-                            write!(file_open, "\n").unwrap();
-
-                        },
-                        None => {
-                            // Write out zeros for contigs with no variants
-                            for (sample_idx, _sample_name) in sample_names.iter().enumerate() {
-                                write!(file_open,
-                                         "\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
-                                         0., 0., 0., 0., 0., 0., 0.,).unwrap();
-                            }
-                        }
-                    }
-                };
-                if snps > 0 {
-                    let plot_command = format!("set -eou pipefail; snp_density_plots.R {} {} && \
-                                                    mv SNP-Density*.pdf {}_snp_density_plot.pdf",
-                                               output_prefix, window_size, output_prefix);
-                    command::finish_command_safely(
-                        std::process::Command::new("bash")
-                            .arg("-c")
-                            .arg(&plot_command)
-                            .stderr(Stdio::piped())
-                            .stdout(Stdio::piped())
-                            .spawn()
-                            .expect("Unable to execute Rscript"), "CMplot");
-                } else {
-                    std::fs::remove_file(snp_loc_path).expect("Unable to remove file");
-                }
-            }
-        }
-    }
-
-    fn calc_gene_mutation(&self,
-                          gff_map: &mut HashMap<String, Vec<bio::io::gff::Record>>,
-                          reference: &mut bio::io::fasta::IndexedReader<File>,
-                          codon_table: &CodonTable,
-                          ref_name: &str,
-                          output_prefix: &str) {
-        match self {
-            VariantMatrix::VariantContigMatrix {
-                target_names,
-                all_variants,
-                ..
-            } => {
-                let file_name = format!("{}_{}_dnds_values.gff",
-                                        output_prefix.to_string(), ref_name);
-
-                let file_path = Path::new(&file_name);
-//                let mut file_open = File::create(file_path)
-//                    .expect("Unable to create GFF file");
-                let mut gff_writer = gff::Writer::to_file(file_path,
-                                                          bio::io::gff::GffType::GFF3)
-                                                          .expect("unable to create GFF file");
-                for (tid, contig_name) in target_names.iter() {
-
-
-                    let mut ref_sequence = Vec::new();
-                    match reference.fetch_all(
-                        std::str::from_utf8(contig_name.as_bytes()).unwrap()) {
-                        Ok(reference) => reference,
-                        Err(e) => {
-                            println!("Cannot read sequence from reference {:?}", e);
-                            std::process::exit(1)
-                        },
-                    };
-                    match reference.read(&mut ref_sequence) {
-                        Ok(reference) => reference,
-                        Err(e) => {
-                            println!("Cannot read sequence from reference {:?}", e);
-                            std::process::exit(1)
-                        },
-                    };
-                    let mut placeholder = Vec::new();
-                    let gff_records = match gff_map.get_mut(contig_name){
-                        Some(records) => records,
-                        None => &mut placeholder,
-                    };
-                    debug!("Calculating population dN/dS from reads for {} genes on contig {}",
-                           gff_records.len(), contig_name);
-
-                    let placeholder_map = HashMap::new();
-                    let variants = match all_variants.get(tid) {
-                        Some(map) => map,
-                        None => &placeholder_map
-                    };
-
-                    gff_records.iter_mut().enumerate().for_each(|(_id, gene)| {
-                        let dnds = codon_table.find_mutations(gene, variants, &ref_sequence);
-                        gene.attributes_mut().insert(format!("dNdS"), format!("{}", dnds));
-                        debug!("gene {:?} attributes {:?}", gene.seqname(), gene);
-                        gff_writer.write(gene);
-//                        let attributes = if !gene.attributes().is_empty() {
-//                            gene
-//                                .attributes()
-//                                .iter()
-//                                .map(|(a, b)| format!("{}{}{}", a, "=", b))
-//                                .join(";")
-//                        } else {
-//                            "".to_owned()
-//                        };
-//
-//                        let gff_line = format!(
-//                            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t",
-//                                            gene.seqname(),
-//                                            gene.source(),
-//                                            gene.feature_type(),
-//                                            gene.start(),
-//                                            gene.end(),
-//                                            gene.score().unwrap_or(0),
-//                                            gene.strand().unwrap_or(Strand::Unknown),
-//                                            gene.frame(),
-//                                            attributes);
-//                        println!("{}", gff_line);
-//                        writeln!(file_open, "{}", gff_line);
-                    });
-
-                }
-            }
-        }
-    }
-
-    fn write_vcf(&self, output_prefix: &str) {
-        match self {
-            VariantMatrix::VariantContigMatrix {
-                all_variants,
-                target_names,
-                target_lengths,
-                sample_names,
-                variant_info,
-                ..
-            } => {
-                if variant_info.len() > 0 {
-                    // initiate header
-                    let mut header = bcf::Header::new();
-                    // Add program info
-                    header.push_record(format!("##source=lorikeet-v{}",
-                                               env!("CARGO_PKG_VERSION")).as_bytes());
-
-                    debug!("samples {:?}", &sample_names);
-                    for sample in sample_names.iter() {
-                        header.push_sample(&sample.clone().into_bytes()[..]);
-                    }
-
-                    // Add contig info
-                    for (tid, contig_name) in target_names {
-                        header.push_record(
-                            format!("##contig=<ID={}, length={}>",
-                                    contig_name, target_lengths[tid]).as_bytes()
-                        );
-                    }
-
-                    // Add INFO flags
-                    header.push_record(
-                        format!("##INFO=<ID=TYPE,Number=A,Type=String,\
-                    Description=\"The type of allele, either SNV, MNV, INS, DEL, or INV.\">").as_bytes());
-
-                    header.push_record(
-                        format!("##INFO=<ID=SVLEN,Number=1,Type=Integer,\
-                    Description=\"Length of structural variant\">").as_bytes());
-
-                    header.push_record(
-                        b"##INFO=<ID=TDP,Number=A,Type=Integer,\
-                            Description=\"Total observed sequencing depth\">",
-                    );
-
-                    header.push_record(
-                        b"##INFO=<ID=TAD,Number=A,Type=Integer,\
-                            Description=\"Total observed sequencing depth of alternative allele\">",
-                    );
-
-                    header.push_record(
-                        b"##INFO=<ID=TRD,Number=A,Type=Integer,\
-                            Description=\"Total observed sequencing depth of reference allele\">",
-                    );
-
-                    header.push_record(
-                        b"##INFO=<ID=ST,Number=.,Type=Integer,\
-                            Description=\"The strain IDs assigned to this variant\">",
-                    );
-
-                    // Add FORMAT flags
-                    header.push_record(
-                        format!("##FORMAT=<ID=DP,Number={},Type=Integer,\
-                            Description=\"Observed sequencing depth in each sample\">",
-                                sample_names.len()).as_bytes(),
-                    );
-
-                    header.push_record(
-                        format!("##FORMAT=<ID=AD,Number={},Type=Integer,\
-                            Description=\"Observed sequencing depth of alternative allele in each sample\">",
-                                sample_names.len()).as_bytes(),
-                    );
-
-                    header.push_record(
-                        format!("##FORMAT=<ID=RD,Number={},Type=Integer,\
-                            Description=\"Observed sequencing depth of reference allele in each sample\">",
-                                sample_names.len()).as_bytes(),
-                    );
-
-                    header.push_record(
-                        format!("##FORMAT=<ID=QA,Number={},Type=Float,\
-                            Description=\"Quality scores for allele in each sample\">",
-                                sample_names.len()).as_bytes(),
-                    );
-
-                    let vcf_presort = tempfile::Builder::new()
-                        .prefix("lorikeet-fifo")
-                        .tempfile().expect("Unable to create VCF tempfile");
-
-                    // Initiate writer
-                    let mut bcf_writer = bcf::Writer::from_path(
-                        format!("{}.vcf",
-                                output_prefix).as_str(),
-                        &header,
-                        true,
-                        bcf::Format::VCF).expect(
-                        format!("Unable to create VCF output: {}.vcf",
-                                output_prefix).as_str());
-
-                    bcf_writer.set_threads(current_num_threads()).unwrap();
-
-
-                    for (tid, position_variants) in all_variants.into_iter() {
-                        let contig_name = &target_names[tid];
-                        for (pos, variants) in position_variants.iter() {
-                            for (variant, base) in variants.iter() {
-                                // Create empty record for this variant
-                                let mut record = bcf_writer.empty_record();
-                                record.set_rid(
-                                    Some(bcf_writer.header()
-                                        .name2rid(contig_name.as_bytes()).unwrap()));
-                                record.set_pos(*pos);
-
-                                // Sum the quality scores across samples
-                                let qual_sum = base.quals.iter().sum();
-                                record.set_qual(qual_sum);
-
-                                // Collect strain information
-                                let mut strains = base.genotypes.iter().cloned()
-                                    .collect::<Vec<i32>>();
-                                strains.sort();
-
-                                // Push info tags to record
-                                record.push_info_integer(b"TDP", &[base.totaldepth.iter().sum()]);
-                                record.push_info_integer(b"TAD", &[base.truedepth.iter().sum()]);
-                                record.push_info_integer(b"TRD", &[base.referencedepth.iter().sum()]);
-                                record.push_info_integer(b"ST", &strains[..]);
-
-                                // Push format flags to record
-                                record.push_format_integer(b"DP", &base.totaldepth[..]);
-                                record.push_format_integer(b"AD", &base.truedepth[..]);
-                                record.push_format_integer(b"RD", &base.referencedepth[..]);
-                                record.push_format_float(b"QA", &base.quals[..]);
-
-                                let refr = &base.refr[..];
-
-
-                                match variant {
-                                    Variant::SNV(alt) => {
-
-                                        // Collect and set the alleles to record
-                                        let alt = &[*alt];
-                                        let mut collect_alleles = vec![refr, alt];
-                                        record.set_alleles(&collect_alleles).unwrap();
-
-                                        record.push_info_string(b"TYPE", &[b"SNV"]);
-
-                                        bcf_writer.write(&record).expect("Unable to write record");
-                                    },
-                                    Variant::MNV(alt) => {
-
-                                        // Collect and set the alleles to record
-                                        let alt = [&refr[..], &alt[..]].concat();
-                                        let mut collect_alleles = vec![refr, &alt];
-                                        record.set_alleles(&collect_alleles).unwrap();
-
-                                        record.push_info_string(b"TYPE", &[b"MNV"]);
-                                        record.push_info_integer(b"SVLEN", &[alt.len() as i32]);
-
-                                        bcf_writer.write(&record).expect("Unable to write record");
-                                    },
-                                    Variant::Inversion(alt) => {
-                                        // Collect and set the alleles to record
-                                        let alt = [&refr[..], &alt[..]].concat();
-                                        let mut collect_alleles = vec![refr, &alt];
-                                        record.set_alleles(&collect_alleles).unwrap();
-
-                                        record.push_info_string(b"TYPE", &[b"INV"]);
-                                        record.push_info_integer(b"SVLEN", &[alt.len() as i32]);
-
-                                        bcf_writer.write(&record).expect("Unable to write record");
-                                    },
-                                    Variant::Insertion(alt) => {
-                                        // Collect and set the alleles to record
-                                        let alt = [&refr[..], &alt[..]].concat();
-                                        let mut collect_alleles = vec![refr, &alt];
-                                        record.set_alleles(&collect_alleles).unwrap();
-
-                                        record.push_info_string(b"TYPE", &[b"INS"]);
-                                        record.push_info_integer(b"SVLEN", &[alt.len() as i32]);
-
-                                        bcf_writer.write(&record).expect("Unable to write record");
-                                    },
-                                    Variant::Deletion(alt) => {
-                                        // Collect and set the alleles to record
-                                        // Create deletion variant, attach refr to head of N
-                                        record.set_alleles(&vec![refr, format!(
-                                            "{}N", refr[0] as char)
-                                            .as_bytes()]).unwrap();
-
-                                        record.push_info_string(b"TYPE", &[b"DEL"]);
-                                        record.push_info_integer(b"SVLEN", &[*alt as i32]);
-
-                                        bcf_writer.write(&record).expect("Unable to write record");
-                                    },
-                                    _ => {},
-                                }
-                            }
-                        }
-                    }
-
-                    // Initiate sorting command
-                    // Since variants are in HashMap, they are unsorted.
-                    // Sorting using bcftools is fast so just do it for the user.
-                    external_command_checker::check_for_bcftools();
-
-                    // let command_string = format!(
-                    //     "mv {} {}.vcf",
-                    //     &vcf_presort
-                    //         .path()
-                    //         .to_str()
-                    //         .expect("Failed to convert tempfile to path"),
-                    //     output_prefix
-                    // );
-                    // This fails randomly, not sure what causes it so will keep unsorted for now
-//                    let command_string = format!(
-//                        "bcftools sort {} > {}.vcf",
-//                        &vcf_presort
-//                            .path()
-//                            .to_str()
-//                            .expect("Failed to convert tempfile to path"),
-//                        output_prefix
-//                    );
-//                    if log_enabled!(Level::Debug) {
-//                        vcf_presort.keep().unwrap();
-//                    }
-
-                    // command::finish_command_safely(
-                    //     Command::new("bash")
-                    //         .arg("-c")
-                    //         .arg(&command_string)
-                    //         .stderr(Stdio::piped())
-                    //         .spawn()
-                    //         .expect("Unable to execute bash"),
-                    //     "bcftools"
-                    // );
-                    debug!("Finished writing VCF file for {}", &output_prefix);
-                }
-            }
-        }
-    }
-}
-
-/// Add read count entry to cluster hashmap
-pub fn add_entry(shared_read_counts: &mut HashMap<usize, HashMap<usize, usize>>,
-                 clust1: usize, clust2: usize, count: usize) {
-    let entry = shared_read_counts.entry(clust1).or_insert(HashMap::new());
-    entry.insert(clust2, count);
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use estimation::contig_variants::*;
-    use std::collections::HashSet;
-    use model::variants;
-
-    fn create_base(ref_sequence: &Vec<u8>, var_char: u8, pos: i64, sample_count: usize) -> Base {
-        Base {
-            tid: 0,
-            pos,
-            refr: ref_sequence[pos as usize..(pos as usize + 1)].to_vec(),
-            variant: Variant::SNV(var_char),
-            filters: vec!(),
-            depth: vec![0, 5],
-            truedepth: vec![0, 5],
-            totaldepth: vec![5, 5],
-            genotypes: HashSet::new(),
-            quals: vec![0.; sample_count],
-            referencedepth: vec![5, 0],
-            physicalcov: vec![0; sample_count],
-            baseq: vec![0; sample_count],
-            mapq: vec![0; sample_count],
-            conf: vec![0; sample_count],
-            nucs: HashMap::new(),
-            pernucs: HashMap::new(),
-            ic: vec![0; sample_count],
-            dc: vec![0; sample_count],
-            xc: vec![0; sample_count],
-            ac: vec![0; sample_count],
-            af: vec![0.; sample_count],
-            freq: vec![0.; sample_count],
-            rel_abunds: vec![0.; sample_count],
-            reads: HashSet::new(),
-        }
-    }
-
-    #[test]
-    fn test_stats_and_mat() {
-
-        let ref_sequence = "ATGAAACCCGGGTTTTAA".as_bytes().to_vec();
-        let sample_count = 2;
-
-        let mut variant_abundances: HashMap<i64, HashMap<Variant, Base>> = HashMap::new();
-
-        // Setup variant matrix
-        let mut var_mat = VariantMatrix::new_matrix(2);
-
-        // Create fake variants
-        let var_1 = create_base(&ref_sequence, "G".bytes().nth(0).unwrap(), 7, 2);
-        let var_2 = create_base(&ref_sequence, "C".bytes().nth(0).unwrap(), 11, 2);
-        let var_3 = create_base(&ref_sequence, "A".bytes().nth(0).unwrap(), 13, 2);
-        let var_4 = create_base(&ref_sequence, "C".bytes().nth(0).unwrap(), 14, 2);
-
-        let mut ups_and_downs = vec![0; ref_sequence.len()];
-        ups_and_downs[0] = 5;
-        ups_and_downs[ref_sequence.len() - 1] = -5;
-
-        // Setup sample 1
-        let mut var_stats = VariantStats::new_contig_stats(0., 5., 0);
-        var_stats.add_contig(Some(&mut variant_abundances), 0, 0,
-                             b"test".to_vec(), ref_sequence.len(), 0,
-                             vec![10., 10., 0.], ups_and_downs);
-        var_mat.add_contig(var_stats, 2, 0);
-
-        {
-            // Add variants in
-            let hash = variant_abundances.entry(7).or_insert(HashMap::new());
-            hash.insert(var_1.variant.clone(), var_1);
-
-            let hash = variant_abundances.entry(11).or_insert(HashMap::new());
-            hash.insert(var_2.variant.clone(), var_2);
-
-            let hash = variant_abundances.entry(13).or_insert(HashMap::new());
-            hash.insert(var_3.variant.clone(), var_3);
-
-            let hash = variant_abundances.entry(14).or_insert(HashMap::new());
-            hash.insert(var_4.variant.clone(), var_4);
-        }
-        // Add sample 2
-        let mut ups_and_downs = vec![0; ref_sequence.len()];
-        ups_and_downs[0] = 5;
-        ups_and_downs[ref_sequence.len() - 1] = -5;
-        let mut var_stats = VariantStats::new_contig_stats(0., 5., 0);
-        var_stats.add_contig(Some(&mut variant_abundances), 0, 0,
-                             b"test".to_vec(), ref_sequence.len(), 1,
-                             vec![10., 10., 0.], ups_and_downs);
-
-        var_mat.add_contig(var_stats, 2, 1);
-
-        var_mat.generate_distances(0, "test");
-
-        var_mat.run_fuzzy_scan(0.01, 0.05, 0.01, 0.01,
-                               0., 0, 0., 0)
-
-    }
-}
\ No newline at end of file
+use std::collections::{HashMap, HashSet, BTreeMap, BTreeSet};
+use estimation::contig_variants::*;
+use estimation::codon_structs::*;
+use estimation::linkage::*;
+use estimation::cluster_refinement::*;
+use estimation::strain_count::*;
+use estimation::deconvolution::*;
+use estimation::phasing::*;
+use estimation::read_graph::*;
+use model::variants::*;
+use model::read_names::*;
+use std::{str};
+use std::path::Path;
+use std::io::prelude::*;
+use rayon::prelude::*;
+use std::sync::{Arc, Mutex};
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use dbscan::fuzzy;
+use dbscan::tuning::*;
+use dbscan::clustering::*;
+use itertools::{izip};
+use rust_htslib::{bcf::{self}, bam::HeaderView};
+use bio::io::gff;
+use bird_tool_utils::command;
+use std::process::{Stdio};
+use tempfile;
+use external_command_checker;
+use rayon::current_num_threads;
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use estimation::codon_structs::CodonTable;
+
+#[derive(Debug, Serialize, Deserialize)]
+/// Container for all variants within a genome and associated clusters
+pub enum VariantMatrix {
+    VariantContigMatrix {
+        coverages: HashMap<i32, Vec<f64>>,
+        average_genotypes: HashMap<i32, Vec<f64>>,
+        variances: HashMap<i32, Vec<f64>>,
+        // Slope of the regression of non-reference depth on read depth for each contig and sample
+        error_rates: HashMap<i32, Vec<f64>>,
+        // TID, Position, Base, Var Depth, Total Depth
+        all_variants: HashMap<i32, HashMap<i64, HashMap<Variant, Base>>>,
+        target_names: BTreeMap<i32, String>,
+        target_lengths: HashMap<i32, f64>,
+        sample_names: Vec<String>,
+        kfrequencies: BTreeMap<Vec<u8>, Vec<usize>>,
+        variant_counts: HashMap<usize, HashMap<i32, usize>>,
+        variant_sums: HashMap<usize, HashMap<i32, Vec<Vec<f64>>>>,
+        variant_info: Vec<fuzzy::Var>,
+        geom_mean_var: Vec<f64>,
+        geom_mean_dep: Vec<f64>,
+        geom_mean_frq: Vec<f64>,
+        pred_variants: HashMap<usize, HashMap<i32, HashMap<i64, HashMap<fuzzy::Category, HashSet<Variant>>>>>,
+        // Clusters of variant_info indices from the last clustering, ranked as in pred_variants
+        variant_clusters: Vec<fuzzy::Cluster>,
+        // Indices of variants that were part of a read linkage seed
+        seed_variants: HashSet<usize>,
+        // Haplotypes of each block phased with long reads, most supported haplotype first
+        phased_haplotypes: Vec<Vec<fuzzy::Cluster>>,
+        // Proportions of each strain in each sample fitted by deconvolution, ranked as in
+        // pred_variants. Empty when strains were inferred any other way
+        strain_proportions: Vec<Vec<f64>>,
+        // Names of the reads supporting the variants of this matrix
+        read_names: ReadInterner,
+//        pred_variants_all: HashMap<usize, HashMap<i32, HashMap<i32, HashSet<String>>>>,
+    }
+}
+
+impl VariantMatrix {
+    pub fn new_matrix(sample_count: usize) -> VariantMatrix {
+        VariantMatrix::VariantContigMatrix {
+            coverages: HashMap::new(),
+            variances: HashMap::new(),
+            error_rates: HashMap::new(),
+            average_genotypes: HashMap::new(),
+            all_variants: HashMap::new(),
+            target_names: BTreeMap::new(),
+            target_lengths: HashMap::new(),
+            sample_names: vec!["".to_string(); sample_count],
+            kfrequencies: BTreeMap::new(),
+            variant_counts: HashMap::new(),
+            variant_sums: HashMap::new(),
+            variant_info: Vec::new(),
+            geom_mean_var: Vec::new(),
+            geom_mean_dep: Vec::new(),
+            geom_mean_frq: Vec::new(),
+            pred_variants: HashMap::new(),
+            variant_clusters: Vec::new(),
+            seed_variants: HashSet::new(),
+            phased_haplotypes: Vec::new(),
+            strain_proportions: Vec::new(),
+            read_names: ReadInterner::new(),
+        }
+    }
+
+    /// Reads a matrix previously written by
+    /// [write_checkpoint](VariantMatrixFunctions::write_checkpoint) together with the names
+    /// of the reads its variants refer to
+    pub fn from_checkpoint(path: &str) -> VariantMatrix {
+        let file = File::open(path)
+            .expect(&format!("Unable to open variant matrix checkpoint {}", path));
+        let mut variant_matrix: VariantMatrix = bincode::deserialize_from(BufReader::new(file))
+            .expect(&format!("Unable to read variant matrix checkpoint {}", path));
+        match variant_matrix {
+            VariantMatrix::VariantContigMatrix {
+                ref mut read_names,
+                ..
+            } => {
+                read_names.reindex();
+            }
+        }
+        variant_matrix
+    }
+}
+
+pub trait VariantMatrixFunctions {
+    fn setup(&mut self);
+
+    fn add_sample(&mut self, sample_name: String, sample_idx: usize,
+                  variant_records: &HashMap<i32, HashMap<i64, HashMap<Variant, Base>>>,
+                  header: &HeaderView);
+
+    /// Returns the alleles at the current position
+    /// as a mutable reference, along with the read names the alleles' reads are interned in
+    fn variants(&mut self, tid: i32, pos: i64) -> Option<(&mut HashMap<Variant, Base>, &mut ReadInterner)>;
+
+    /// The read names the reads of this matrix's variants are interned in
+    fn read_names_mut(&mut self) -> &mut ReadInterner;
+
+    /// Sorts and deduplicates the read sets of every variant after reads were collected with
+    /// [assign_read](Base::assign_read)
+    fn sort_read_sets(&mut self);
+
+    fn variants_of_contig(&mut self, tid: i32) -> Option<&mut HashMap<i64, HashMap<Variant, Base>>>;
+
+    /// Takes [VariantStats](contig_variants/VariantStats) struct for single contig and adds to
+    /// [VariantMatrix](VariantMatrix)
+    fn add_contig(&mut self,
+                  variant_stats: VariantStats,
+                  sample_count: usize,
+                  sample_idx: usize);
+
+    /// Grows the matrix to hold sample_count samples so that samples can be appended to a
+    /// matrix loaded from a checkpoint. Previous clustering results are cleared.
+    fn extend_samples(&mut self, sample_count: usize);
+
+    /// Converts all variants into fuzzy::Var format
+    fn generate_distances(&mut self, threads: usize, output_prefix: &str);
+
+    /// Clusters variants by the given distance with the given clustering method, seeded by
+    /// read linkage. One of "fuzzy-dbscan", "hdbscan" or "hierarchical". If refinement
+    /// parameters are given, the clusters are then split, merged and extended by read linkage.
+    /// If max_clusters is given, only that many of the largest clusters are kept. A neighbour
+    /// graph already built by [write_feature_matrices](VariantMatrixFunctions::write_feature_matrices)
+    /// with the same distance and e_max is reused by fuzzy DBSCAN rather than built again
+    fn run_clustering(&mut self, e_min: f64, e_max: f64, pts_min: f64, pts_max: f64, phi: f64,
+                      anchor_size: usize, anchor_similarity: f64, minimum_reads_in_link: usize,
+                      clustering_method: &str, distance: fuzzy::DistanceMetric,
+                      refinement: Option<&ClusterRefinementParameters>,
+                      max_clusters: Option<usize>, graph: Option<&fuzzy::NeighbourGraph>);
+
+    /// Phases variants with the reads of the given long read BAM files by solving the minimum
+    /// error correction problem in each block of read connected sites. The phased blocks are
+    /// written to a TSV file and kept as seeds for clustering
+    fn phase_long_reads(&mut self, output_prefix: &str, bam_paths: &Vec<String>, threads: usize,
+                        params: &PhasingParameters);
+
+    /// Uses the long read haplotypes as strains instead of clustering. Blocks are not connected
+    /// to each other, so every haplotype of every block is reported as its own strain rather
+    /// than joining haplotypes of different blocks that no read links
+    fn genotype_from_phasing(&mut self);
+
+    /// Infers strains by factorising the allele count matrix into strain haplotypes and
+    /// per-sample strain proportions instead of clustering. The alleles each strain most likely
+    /// carries become its genotype and the proportions are written to a TSV file
+    fn run_deconvolution(&mut self, output_prefix: &str, strains: usize, restarts: usize);
+
+    /// Infers strains from a co-occurrence graph of variants linked by reads and mates instead
+    /// of clustering, for single sample projects where depth alone rarely separates strains.
+    /// Each haplotype path through the graph becomes a strain
+    fn run_read_graph(&mut self, anchor_size: usize, anchor_similarity: f64,
+                      minimum_reads_in_link: usize);
+
+    /// Scans a grid of fuzzy DBSCAN parameters scaled around the given e_min, e_max, pts_min and
+    /// pts_max, scoring each clustering by the given criterion, and returns the best e_min,
+    /// e_max, pts_min, pts_max and phi. Large variant sets are scored on a subsample of at most
+    /// MAX_TUNING_VARIANTS variants. Every candidate and its scores are written to a TSV file
+    fn tune_fuzzy_parameters(&self, output_prefix: &str, anchor_size: usize, anchor_similarity: f64,
+                             minimum_reads_in_link: usize, criterion: &str,
+                             clustering_method: &str,
+                             distance: fuzzy::DistanceMetric,
+                             initial: (f64, f64, f64, f64)) -> (f64, f64, f64, f64, f64);
+
+    /// Takes clusters from DBSCAN and linkage method and writes variants to file as genotype.
+    /// Sites with several candidate alleles are resolved by read linkage to the strain's other
+    /// variants, and low confidence sites are masked according to mask_mode
+    fn generate_genotypes(&mut self,
+                          output_prefix: &str,
+                          reference: &mut bio::io::fasta::IndexedReader<File>,
+                          mask_mode: &str);
+
+    /// Estimates the number of strains of the genome from the allele frequency spectrum of each
+    /// sample, using the error regression of each contig as the error rate. Writes the estimate
+    /// of each sample and every compared model, and returns the largest estimate
+    fn estimate_strain_count(&self, output_prefix: &str, max_strains: usize) -> usize;
+
+    /// Estimates the relative abundance of each strain in each sample. When strains were
+    /// deconvolved these are the fitted proportions. Otherwise the mean allele frequency of each
+    /// strain's core variants is bootstrapped and normalised so that the strains of each sample
+    /// sum to one. The mean allele frequency is written alongside either way
+    fn calculate_strain_abundances(&self, output_prefix: &str);
+
+    fn print_variant_stats(&self, output_prefix: &str, window_size: f64);
+
+    fn calc_gene_mutation(&self,
+                          gff_map: &mut HashMap<String, Vec<bio::io::gff::Record>>,
+                          reference: &mut bio::io::fasta::IndexedReader<File>,
+                          codon_table: &CodonTable,
+                          ref_name: &str,
+                          output_prefix: &str);
+
+    /// Writes the pN/pS of each gene in each sample, weighting variants by their allele
+    /// frequencies, and when strains have been genotyped the dN/dS of each strain's copy of each
+    /// gene against the reference. Both are gene by sample or strain tables with NA where the
+    /// ratio is undefined
+    fn calc_selection(&self,
+                      gff_map: &HashMap<String, Vec<bio::io::gff::Record>>,
+                      reference: &mut bio::io::fasta::IndexedReader<File>,
+                      codon_table: &CodonTable,
+                      output_prefix: &str);
+
+    /// Writes every variant to a VCF file. When the genes of each contig are given along with
+    /// their codon table and reference, the functional effect of each allele on them is written
+    /// to an ANN INFO field
+    fn write_vcf(&self, output_prefix: &str,
+                 effects: Option<(&HashMap<String, Vec<bio::io::gff::Record>>, &CodonTable,
+                                  &mut bio::io::fasta::IndexedReader<File>)>);
+
+    /// Serializes the complete matrix so clustering can be rerun without variant calling.
+    /// Read names no longer referenced by any variant are dropped first
+    fn write_checkpoint(&mut self, output_prefix: &str);
+
+    /// Writes every clustered variant with its cluster, fuzzy label and category, one row per
+    /// cluster the variant belongs to. Unclustered variants are written as noise
+    fn write_cluster_memberships(&self, output_prefix: &str);
+
+    /// Writes the variant by sample depth matrix, the CLR transformed matrix, the geometric
+    /// means and the sparse matrix of the distances within e_max that clustering uses. Returns
+    /// the neighbour graph of those distances so that clustering can reuse it
+    fn write_feature_matrices(&self, output_prefix: &str,
+                              distance: fuzzy::DistanceMetric, e_max: f64) -> fuzzy::NeighbourGraph;
+}
+
+#[allow(unused)]
+impl VariantMatrixFunctions for VariantMatrix {
+    fn setup(&mut self) {
+        match self {
+            VariantMatrix::VariantContigMatrix {
+                ref mut coverages,
+                ref mut average_genotypes,
+                ref mut all_variants,
+                ref mut target_names,
+                ref mut target_lengths,
+                ref mut sample_names,
+                ref mut kfrequencies,
+                ..
+            } => {
+                *coverages = HashMap::new();
+                *average_genotypes = HashMap::new();
+                *all_variants = HashMap::new();
+                *target_names = BTreeMap::new();
+                *target_lengths = HashMap::new();
+                *sample_names = vec!();
+                *kfrequencies = BTreeMap::new();
+            }
+        }
+    }
+
+    fn add_sample(&mut self, sample_name: String, sample_idx: usize,
+                  variant_records: &HashMap<i32, HashMap<i64, HashMap<Variant, Base>>>,
+                   header: &HeaderView) {
+        match self {
+            VariantMatrix::VariantContigMatrix {
+                ref mut sample_names,
+                ref mut all_variants,
+                ref mut target_names,
+                ref mut target_lengths,
+                ref coverages,
+                ..
+            } => {
+                info!("adding sample {} at index {}", &sample_name, &sample_idx);
+                sample_names[sample_idx] = sample_name;
+                let target_count = header.target_count();
+                let tid_names = header.target_names();
+
+                for target_name in (tid_names).into_iter() {
+                    let tid = header.tid(target_name).unwrap();
+                    target_names.entry(tid as i32)
+                        .or_insert(str::from_utf8(&target_name).unwrap().to_string());
+                    let target_len = header.target_len(tid).unwrap();
+                    target_lengths.entry(tid as i32).or_insert(target_len as f64);
+                    // Initialize contig id in variant hashmap
+                    let contig_variants = all_variants.entry(tid as i32)
+                        .or_insert(HashMap::new());
+                    let placeholder = HashMap::new();
+                    let mut variants = match variant_records.get(&(tid as i32)) {
+                        Some(map) => map,
+                        _ => &placeholder,
+                    };
+                    let target_len = header.target_len(tid).unwrap();
+                    // Apppend the sample index to each variant abundance
+                    // Initialize the variant position index
+                    // Also turns out to be the total number of variant positions
+                    for (pos, abundance_map) in variants.iter() {
+                        let position_variants = contig_variants.entry(*pos as i64)
+                            .or_insert(HashMap::new());
+                        for (variant, base_info) in abundance_map {
+                            // Samples whose contig depths were already added never saw this
+                            // variant, so give them their depth at this position. Only the
+                            // depths at variant positions are kept, so positions that were not
+                            // variant before fall back to the sample's contig coverage
+                            let fill_depths = if position_variants.contains_key(variant) {
+                                Vec::new()
+                            } else if position_variants.len() > 0 {
+                                let sample_total = position_variants.values()
+                                    .map(|other| other.totaldepth.len()).max().unwrap_or(0);
+                                (0..sample_total).filter(|other_idx| *other_idx != sample_idx)
+                                    .map(|other_idx| {
+                                        (other_idx, position_variants.values()
+                                            .map(|other| other.totaldepth[other_idx])
+                                            .max().unwrap_or(0))
+                                    }).collect::<Vec<(usize, i32)>>()
+                            } else {
+                                match coverages.get(&(tid as i32)) {
+                                    Some(contig_coverages) => {
+                                        contig_coverages.iter().enumerate()
+                                            .filter(|(other_idx, _)| *other_idx != sample_idx)
+                                            .map(|(other_idx, coverage)| (other_idx, coverage.round() as i32))
+                                            .collect::<Vec<(usize, i32)>>()
+                                    },
+                                    None => Vec::new(),
+                                }
+                            };
+                            let sample_map = position_variants.entry(variant.clone())
+                                .or_insert(base_info.clone());
+                            sample_map.combine_sample(base_info, sample_idx, 0);
+                            for (other_idx, depth) in fill_depths {
+                                if depth > 0 && other_idx < sample_map.totaldepth.len() {
+                                    sample_map.add_depth(other_idx, depth);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn variants(&mut self, tid: i32, pos: i64) -> Option<(&mut HashMap<Variant, Base>, &mut ReadInterner)> {
+        match self {
+            VariantMatrix::VariantContigMatrix {
+                ref mut all_variants,
+                ref mut read_names,
+                ..
+            } => {
+                match all_variants.get_mut(&tid) {
+                    Some(contig_variants) => {
+                        contig_variants.get_mut(&pos.clone())
+                            .map(|position_variants| (position_variants, read_names))
+                    },
+                    _ => {
+                        None
+                    },
+                }
+            }
+        }
+    }
+
+    fn read_names_mut(&mut self) -> &mut ReadInterner {
+        match self {
+            VariantMatrix::VariantContigMatrix {
+                ref mut read_names,
+                ..
+            } => {
+                read_names
+            }
+        }
+    }
+
+    fn sort_read_sets(&mut self) {
+        match self {
+            VariantMatrix::VariantContigMatrix {
+                ref mut all_variants,
+                ..
+            } => {
+                for contig_variants in all_variants.values_mut() {
+                    for position_variants in contig_variants.values_mut() {
+                        for base in position_variants.values_mut() {
+                            base.reads.sort_dedup();
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn variants_of_contig(&mut self, tid: i32) -> Option<&mut HashMap<i64, HashMap<Variant, Base>>> {
+        match self {
+            VariantMatrix::VariantContigMatrix {
+                ref mut all_variants,
+                ..
+            } => {
+                all_variants.get_mut(&tid)
+            }
+        }
+    }
+
+    fn add_contig(&mut self,
+                  variant_stats: VariantStats,
+                  sample_count: usize,
+                  sample_idx: usize) {
+        match self {
+            VariantMatrix::VariantContigMatrix {
+                ref mut coverages,
+                ref mut all_variants,
+//                ref mut target_names,
+//                ref mut target_lengths,
+                ref mut variances,
+                ref mut error_rates,
+                ..
+            } => {
+                match variant_stats {
+                    VariantStats::VariantContigStats {
+                        tid,
+//                        target_name,
+//                        target_len,
+                        coverage,
+                        variance,
+                        depth,
+                        regression,
+//                        variations_per_n,
+                        ..
+                    } => {
+                        let var = variances.entry(tid).or_insert(
+                            vec![0.0 as f64; sample_count]
+                        );
+                        var[sample_idx] = variance;
+                        let error_rate = error_rates.entry(tid).or_insert(
+                            vec![0.0 as f64; sample_count]
+                        );
+                        error_rate[sample_idx] = regression.1;
+                        let cov = coverages.entry(tid).or_insert(
+                            vec![0.0 as f64; sample_count]
+                        );
+                        cov[sample_idx] = coverage;
+
+                        // copy across depths
+                        let contig_variants = all_variants.entry(tid)
+                            .or_insert(HashMap::new());
+                        for (pos, d) in depth.iter().enumerate() {
+                            let position_variants = contig_variants.entry(pos as i64)
+                                .or_insert(HashMap::new());
+                            for (_variant, base_info) in position_variants.iter_mut() {
+                                base_info.add_depth(sample_idx, *d);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn extend_samples(&mut self, sample_count: usize) {
+        match self {
+            VariantMatrix::VariantContigMatrix {
+                ref mut coverages,
+                ref mut variances,
+                ref mut error_rates,
+                ref mut all_variants,
+                ref mut sample_names,
+                ref mut variant_info,
+                ref mut pred_variants,
+                ref mut variant_clusters,
+                ref mut seed_variants,
+                ref mut phased_haplotypes,
+                ref mut strain_proportions,
+                ..
+            } => {
+                if sample_count < sample_names.len() {
+                    panic!("Cannot shrink variant matrix from {} to {} samples",
+                           sample_names.len(), sample_count)
+                }
+                sample_names.resize(sample_count, "".to_string());
+                coverages.values_mut().for_each(|cov| cov.resize(sample_count, 0.));
+                variances.values_mut().for_each(|var| var.resize(sample_count, 0.));
+                error_rates.values_mut().for_each(|rate| rate.resize(sample_count, 0.));
+                all_variants.par_iter_mut().for_each(|(_tid, contig_variants)| {
+                    contig_variants.values_mut().for_each(|position_variants| {
+                        position_variants.values_mut().for_each(|base| base.extend_samples(sample_count))
+                    })
+                });
+
+                // Clusters need to be recalculated with the new samples
+                *variant_info = Vec::new();
+                *pred_variants = HashMap::new();
+                *variant_clusters = Vec::new();
+                *seed_variants = HashSet::new();
+                *phased_haplotypes = Vec::new();
+                *strain_proportions = Vec::new();
+            }
+        }
+    }
+
+    fn generate_distances(&mut self, _threads: usize, output_prefix: &str) {
+        match self {
+            VariantMatrix::VariantContigMatrix {
+                all_variants,
+                sample_names,
+                coverages,
+                ref mut variant_info,
+                ref mut geom_mean_var,
+                ref mut geom_mean_dep,
+                ref mut geom_mean_frq,
+                ..
+            } => {
+
+                let sample_count = sample_names.len();
+
+                let variant_info_all =
+                    Arc::new(
+                        Mutex::new(
+                            Vec::new()));
+
+                // A vector the length of the number of samples
+                // Cumulatively calculates the product of variant depths
+                let geom_mean_v =
+                    Arc::new(
+                        Mutex::new(
+                            vec![1. as f64; sample_count as usize]));
+
+                // product of total depth
+                let geom_mean_d =
+                    Arc::new(
+                        Mutex::new(
+                            vec![1. as f64; sample_count as usize]));
+
+                // product of reference frequency
+                let geom_mean_f =
+                    Arc::new(
+                        Mutex::new(
+                            vec![1. as f64; sample_count as usize]));
+
+                // get basic variant info and store as fuzzy::Var
+                all_variants.par_iter_mut().for_each(|(tid, variant_abundances)| {
+
+                    variant_abundances.par_iter_mut().for_each(
+                        |(position, hash)| {
+                            // loop through each position that has variants ignoring positions that
+                            // only contained the reference in all samples
+                            if hash.keys().len() > 0 {
+
+                                for (variant, base_info) in hash.iter_mut() {
+                                    match variant {
+
+                                        Variant::SNV(_) | Variant::None => {
+                                            let _abundance: f64 = 0.;
+                                            let _mean_var: f64 = 0.;
+                                            let mut rel_abund = vec![0.0; sample_count as usize];
+
+                                            // Get the mean abundance across samples
+                                            for index in (0..sample_count).into_iter() {
+                                                let mut geom_mean_v =
+                                                    geom_mean_v.lock().unwrap();
+                                                let mut geom_mean_d =
+                                                    geom_mean_d.lock().unwrap();
+                                                let mut geom_mean_f =
+                                                    geom_mean_f.lock().unwrap();
+
+                                                let mut var_depth
+                                                    = base_info.truedepth[index] as f64;
+
+                                                let total_depth
+                                                    = base_info.totaldepth[index] as f64;
+//                                                println!("var_depth {} tot {}", var_depth, total_depth);
+//                                                base_info.freq[index] = ;
+                                                if total_depth <= 0. {
+                                                    rel_abund[index] =
+                                                        var_depth / 1.;
+                                                } else {
+                                                    rel_abund[index] =
+                                                        var_depth / total_depth;
+                                                }
+
+                                                geom_mean_v[index] += (var_depth + 1.).ln();
+                                                geom_mean_d[index] += (total_depth + 1.).ln();
+                                                geom_mean_f[index] += ((var_depth + 1.)
+                                                    / (total_depth + 1.)).ln();
+                                            };
+
+
+                                            let mut variant_info_all = variant_info_all
+                                                .lock().unwrap();
+//                                            base_info.rel_abunds = rel_abund;
+                                            let point = fuzzy::Var {
+                                                pos: *position,
+                                                var: variant.clone(),
+                                                deps: base_info.totaldepth.clone(),
+                                                vars: base_info.depth.clone(),
+//                                                rel_abunds: rel_abund,
+                                                tid: *tid,
+                                                reads: base_info.reads.clone()
+                                            };
+                                            variant_info_all.push(point);
+                                        },
+                                        _ => {
+                                            let mut rel_abund = vec![0.0; sample_count as usize];
+
+                                            // Get the mean abundance across samples
+                                            for index in (0..sample_count).into_iter() {
+                                                let mut geom_mean_v =
+                                                    geom_mean_v.lock().unwrap();
+                                                let mut geom_mean_d =
+                                                    geom_mean_d.lock().unwrap();
+                                                let mut geom_mean_f =
+                                                    geom_mean_f.lock().unwrap();
+
+                                                let mut var_depth
+                                                    = base_info.depth[index] as f64;
+                                                if var_depth <= 0. {
+
+                                                    var_depth
+                                                        = base_info.truedepth[index] as f64;
+                                                    base_info.depth[index] = base_info.truedepth[index];
+                                                }
+                                                let total_depth
+                                                    = base_info.totaldepth[index] as f64;
+//                                                base_info.freq[index] = ;
+                                                if total_depth <= 0. {
+                                                    rel_abund[index] =
+                                                        var_depth / (1.);
+                                                } else {
+                                                    rel_abund[index] =
+                                                        var_depth / total_depth;
+                                                }
+
+                                                geom_mean_v[index] += (var_depth + 1.).ln();
+                                                geom_mean_d[index] += (total_depth + 1.).ln();
+                                                geom_mean_f[index] += ((var_depth + 1.)
+                                                    / (total_depth + 1.)).ln();
+                                            };
+
+
+                                            let mut variant_info_all = variant_info_all
+                                                .lock().unwrap();
+//                                            base_info.rel_abunds = rel_abund;
+                                            let point = fuzzy::Var {
+                                                pos: *position,
+                                                var: variant.clone(),
+                                                deps: base_info.totaldepth.clone(),
+                                                vars: base_info.depth.clone(),
+//                                                rel_abunds: rel_abund,
+                                                tid: *tid,
+                                                reads: base_info.reads.clone()
+                                            };
+                                            variant_info_all.push(point);
+                                        },
+                                    }
+                                }
+                            }
+                        });
+                });
+
+                let variant_info_all = variant_info_all.lock().unwrap().clone();
+                let geom_mean = |input: &Vec<f64>| -> Vec<f64> {
+                    let output = input.iter()
+                        .map(|sum| {
+                            (sum / variant_info_all.len() as f64).exp()
+                        }).collect::<Vec<f64>>();
+                    return output
+                };
+
+                debug!("geoms {:?} {:?} {:?}", geom_mean_d, geom_mean_v, geom_mean_f);
+
+                let geom_mean_v = geom_mean_v.lock().unwrap().clone();
+                let geom_mean_v = geom_mean(&geom_mean_v);
+                debug!("Geom Mean Var {:?}", geom_mean_v);
+
+                let geom_mean_d = geom_mean_d.lock().unwrap().clone();
+                let geom_mean_d = geom_mean(&geom_mean_d);
+                debug!("Geom Mean Dep {:?}", geom_mean_d);
+
+                let geom_mean_f = geom_mean_f.lock().unwrap().clone();
+                let geom_mean_f = geom_mean(&geom_mean_f);
+                debug!("Geom Mean Frq {:?}", geom_mean_f);
+                debug!("geoms {:?} {:?} {:?}", geom_mean_d, geom_mean_v, geom_mean_f);
+
+                info!("Beginning analysis of {} with {} variants", &output_prefix, &variant_info_all.len());
+
+                *variant_info = variant_info_all;
+                *geom_mean_var = geom_mean_v;
+                *geom_mean_dep = geom_mean_d;
+                *geom_mean_frq = geom_mean_f;
+
+            }
+        }
+    }
+
+    fn run_clustering(&mut self, e_min: f64, e_max: f64, pts_min: f64, pts_max: f64, phi: f64,
+                      anchor_size: usize, anchor_similarity: f64, minimum_reads_in_link: usize,
+                      clustering_method: &str, distance: fuzzy::DistanceMetric,
+                      refinement: Option<&ClusterRefinementParameters>,
+                      max_clusters: Option<usize>, graph: Option<&fuzzy::NeighbourGraph>) {
+        match self {
+            VariantMatrix::VariantContigMatrix {
+                ref mut variant_info,
+                ref mut geom_mean_var,
+                ref mut geom_mean_dep,
+                ref mut geom_mean_frq,
+                ref mut pred_variants,
+                ref mut all_variants,
+                ref mut variant_clusters,
+                ref mut seed_variants,
+                ref mut strain_proportions,
+                ref phased_haplotypes,
+                target_lengths,
+                ..
+            } => {
+
+                if variant_info.len() == 1 {
+                    warn!("Where did the variants go? {:?}", variant_info);
+                }
+                let fuzzy_scanner = fuzzy::FuzzyDBSCAN {
+                    eps_min: e_min,
+                    eps_max: e_max,
+                    pts_min: match pts_min {
+                        _ if pts_min > 1. => pts_min,
+                        _ => pts_min*variant_info.len() as f64,
+                        },
+                    pts_max: match pts_max {
+                        _ if pts_max > 1. => pts_max,
+                        _ => pts_max*variant_info.len() as f64,
+                    },
+                    phi,
+                    geom_var: geom_mean_var.to_owned(),
+                    geom_dep: geom_mean_dep.to_owned(),
+                    geom_frq: geom_mean_frq.to_owned(),
+                };
+
+                // Perform read phasing clustering and return initial clusters
+                let mut links = linkage_clustering_of_variants(&variant_info,
+                                                               anchor_size, anchor_similarity,
+                                                               minimum_reads_in_link);
+                // Haplotypes phased with long reads are used as extra seeds
+                if phased_haplotypes.len() > 0 {
+                    let phased_seeds = phased_haplotypes.iter().flatten().cloned()
+                        .collect::<Vec<fuzzy::Cluster>>();
+                    info!("Adding {} long read haplotypes as initial clusters", phased_seeds.len());
+                    links.extend(phased_seeds);
+                }
+
+                // run the chosen clustering method
+                if links.len() > 0 {
+                    info!("Running seeded {} with {} initial clusters", clustering_method, links.len());
+                } else {
+                    warn!("No initial clusters formed, running {} with no seeds. Perhaps lower linkage thresholds?",
+                          clustering_method)
+                }
+                // Transform each variant once rather than for every distance calculation
+                let features = fuzzy::VarFeatures::from_vars(&variant_info[..],
+                                                             geom_mean_var, geom_mean_dep,
+                                                             distance);
+                *seed_variants = links.iter()
+                    .flat_map(|link| link.iter().map(|assignment| assignment.index))
+                    .collect::<HashSet<usize>>();
+                let clusterer = ClusteringMethod::new(clustering_method, fuzzy_scanner);
+                let clusters = match (&clusterer, graph) {
+                    (ClusteringMethod::FuzzyDBSCAN(scanner), Some(graph)) =>
+                        scanner.cluster_graph(graph, links),
+                    _ => clusterer.cluster(&features[..], links),
+                };
+                let clusters = match refinement {
+                    Some(params) => refine_clusters(clusters, &variant_info, params),
+                    None => clusters,
+                };
+                let clusters = match max_clusters {
+                    Some(max_clusters) => constrain_cluster_count(clusters, max_clusters,
+                                                                  &variant_info, refinement),
+                    None => clusters,
+                };
+
+                *pred_variants = genotypes_from_clusters(&clusters, &variant_info, all_variants);
+                *variant_clusters = clusters;
+                *strain_proportions = Vec::new();
+            }
+        }
+    }
+
+    fn phase_long_reads(&mut self, output_prefix: &str, bam_paths: &Vec<String>, threads: usize,
+                        params: &PhasingParameters) {
+        match self {
+            VariantMatrix::VariantContigMatrix {
+                ref variant_info,
+                ref all_variants,
+                ref target_names,
+                ref sample_names,
+                ref read_names,
+                ref mut phased_haplotypes,
+                ..
+            } => {
+                let contig_names = target_names.values().cloned().collect::<HashSet<String>>();
+                let reads = long_read_ids(bam_paths, &contig_names, sample_names, read_names, threads);
+                let (sites, fragments) = build_fragments(variant_info, &reads);
+                info!("Phasing {} sites with {} long read fragments", sites.len(), fragments.len());
+                let blocks = phase_blocks(&sites, &fragments, params);
+
+                let file_name = format!("{}_phased_blocks.tsv", output_prefix);
+                let mut file_open = File::create(&file_name)
+                    .expect(&format!("Unable to create file {}", &file_name));
+                writeln!(file_open, "block\tcontig\tstart\tend\tposition\tref\ttype\talt\thaplotype\treads\tsupport\tMEC")
+                    .expect("Unable to write to file");
+                for (block_idx, block) in blocks.iter().enumerate() {
+                    let start = sites[block.sites[0]].pos;
+                    let end = sites[*block.sites.last().unwrap()].pos;
+                    for (haplotype_idx, haplotype) in block.haplotypes.iter().enumerate() {
+                        for (local, (site, allele)) in block.sites.iter().zip(haplotype.iter()).enumerate() {
+                            let var = &variant_info[sites[*site].alleles[*allele]];
+                            let refr = all_variants.get(&var.tid)
+                                .and_then(|contig_variants| contig_variants.get(&var.pos))
+                                .and_then(|position_variants| position_variants.get(&var.var))
+                                .map(|base| str::from_utf8(&base.refr).unwrap().to_string())
+                                .unwrap_or(".".to_string());
+                            let (variant_type, alt) = variant_type_and_allele(&var.var);
+                            writeln!(file_open, "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                                     block_idx + 1, target_names[&block.tid], start, end, var.pos,
+                                     refr, variant_type, alt, haplotype_idx + 1,
+                                     block.fragments[haplotype_idx], block.support[haplotype_idx][local],
+                                     block.mec)
+                                .expect("Unable to write to file");
+                        }
+                    }
+                    info!("Phased block {} on {} from {} to {} with {} sites, reads per haplotype {:?} and MEC score {}",
+                          block_idx + 1, target_names[&block.tid], start, end, block.sites.len(),
+                          block.fragments, block.mec);
+                }
+                info!("{} phased blocks written to {}", blocks.len(), &file_name);
+
+                *phased_haplotypes = blocks.iter()
+                    .map(|block| block.haplotype_clusters(&sites)).collect();
+            }
+        }
+    }
+
+    fn genotype_from_phasing(&mut self) {
+        match self {
+            VariantMatrix::VariantContigMatrix {
+                ref variant_info,
+                ref phased_haplotypes,
+                ref mut pred_variants,
+                ref mut all_variants,
+                ref mut variant_clusters,
+                ref mut seed_variants,
+                ref mut strain_proportions,
+                ..
+            } => {
+                let clusters = phased_haplotypes.iter()
+                    .flat_map(|block| block.iter().cloned())
+                    .filter(|haplotype| haplotype.len() > 0)
+                    .collect::<Vec<fuzzy::Cluster>>();
+                if clusters.len() == 0 {
+                    warn!("No phased blocks found, no strains will be genotyped. Were long read BAM files given?");
+                }
+
+                *pred_variants = genotypes_from_clusters(&clusters, variant_info, all_variants);
+                // Phasing runs no read linkage, so no variant was part of a seed
+                *seed_variants = HashSet::new();
+                *variant_clusters = clusters;
+                *strain_proportions = Vec::new();
+            }
+        }
+    }
+
+    fn run_deconvolution(&mut self, output_prefix: &str, strains: usize, restarts: usize) {
+        match self {
+            VariantMatrix::VariantContigMatrix {
+                ref variant_info,
+                ref sample_names,
+                ref mut pred_variants,
+                ref mut all_variants,
+                ref mut variant_clusters,
+                ref mut seed_variants,
+                ref mut strain_proportions,
+                ..
+            } => {
+                info!("Deconvolving {} alleles across {} samples into {} strains",
+                      variant_info.len(), sample_names.len(), strains);
+                let variant_counts = variant_info.iter()
+                    .map(|var| var.vars.iter().map(|count| *count as f64).collect::<Vec<f64>>())
+                    .collect::<Vec<Vec<f64>>>();
+                let total_counts = variant_info.iter()
+                    .map(|var| var.deps.iter().map(|count| *count as f64).collect::<Vec<f64>>())
+                    .collect::<Vec<Vec<f64>>>();
+                // Alleles at the same position are alternatives that a strain carries one of
+                let mut site_alleles: BTreeMap<(i32, i64), Vec<usize>> = BTreeMap::new();
+                for (index, var) in variant_info.iter().enumerate() {
+                    site_alleles.entry((var.tid, var.pos)).or_insert(Vec::new()).push(index);
+                }
+                let sites = site_alleles.into_iter().map(|(_, alleles)| alleles).collect::<Vec<Vec<usize>>>();
+                let fit = deconvolve(&variant_counts, &total_counts, &sites, strains, restarts, 0);
+                info!("Deconvolution log-likelihood {} BIC {}", fit.log_likelihood, fit.bic);
+
+                // Rank strains by their mean proportion so the most abundant strain is first
+                let mean_proportion = |k: usize| -> f64 {
+                    fit.proportions[k].iter().sum::<f64>() / std::cmp::max(sample_names.len(), 1) as f64
+                };
+                let mut order = (0..fit.proportions.len()).collect::<Vec<usize>>();
+                order.sort_by(|a, b| mean_proportion(*b).partial_cmp(&mean_proportion(*a)).unwrap());
+
+                let file_name = format!("{}_strain_proportions.tsv", output_prefix);
+                let mut file_open = File::create(&file_name)
+                    .expect(&format!("Unable to create file {}", &file_name));
+                write!(file_open, "strain").expect("Unable to write to file");
+                for sample_name in sample_names.iter() {
+                    write!(file_open, "\t{}", sample_name).expect("Unable to write to file");
+                }
+                write!(file_open, "\n").expect("Unable to write to file");
+                for (rank, k) in order.iter().enumerate() {
+                    write!(file_open, "{}", rank + 1).expect("Unable to write to file");
+                    for proportion in fit.proportions[*k].iter() {
+                        write!(file_open, "\t{}", proportion).expect("Unable to write to file");
+                    }
+                    write!(file_open, "\n").expect("Unable to write to file");
+                }
+
+                // Each strain's genotype holds the alleles it more likely than not carries
+                let clusters = order.iter().map(|k| {
+                    fit.haplotypes.iter().enumerate()
+                        .filter(|(_, haplotype)| haplotype[*k] >= 0.5)
+                        .map(|(index, haplotype)| fuzzy::Assignment {
+                            index,
+                            label: haplotype[*k],
+                            category: if haplotype[*k] >= 0.95 {
+                                fuzzy::Category::Core
+                            } else {
+                                fuzzy::Category::Border
+                            },
+                        }).collect::<fuzzy::Cluster>()
+                }).collect::<Vec<fuzzy::Cluster>>();
+
+                *pred_variants = genotypes_from_clusters(&clusters, variant_info, all_variants);
+                *variant_clusters = clusters;
+                *seed_variants = HashSet::new();
+                *strain_proportions = order.iter().map(|k| fit.proportions[*k].clone()).collect();
+            }
+        }
+    }
+
+    fn run_read_graph(&mut self, anchor_size: usize, anchor_similarity: f64,
+                      minimum_reads_in_link: usize) {
+        match self {
+            VariantMatrix::VariantContigMatrix {
+                ref variant_info,
+                ref sample_names,
+                ref mut pred_variants,
+                ref mut all_variants,
+                ref mut variant_clusters,
+                ref mut seed_variants,
+                ref mut strain_proportions,
+                ..
+            } => {
+                if sample_names.len() > 1 {
+                    info!("Read graph strain inference ignores the allele depths of {} samples \
+                           beyond breaking ties between links", sample_names.len());
+                }
+                let (clusters, seeds) = read_graph_clustering(variant_info, anchor_size,
+                                                              anchor_similarity,
+                                                              minimum_reads_in_link);
+
+                *pred_variants = genotypes_from_clusters(&clusters, variant_info, all_variants);
+                *seed_variants = seeds;
+                *variant_clusters = clusters;
+                *strain_proportions = Vec::new();
+            }
+        }
+    }
+
+    fn tune_fuzzy_parameters(&self, output_prefix: &str, anchor_size: usize, anchor_similarity: f64,
+                             minimum_reads_in_link: usize, criterion: &str,
+                             clustering_method: &str,
+                             distance: fuzzy::DistanceMetric,
+                             initial: (f64, f64, f64, f64)) -> (f64, f64, f64, f64, f64) {
+        match self {
+            VariantMatrix::VariantContigMatrix {
+                variant_info,
+                geom_mean_var,
+                geom_mean_dep,
+                geom_mean_frq,
+                ..
+            } => {
+                let variant_info = tuning_subsample(variant_info.len(), MAX_TUNING_VARIANTS)
+                    .into_iter().map(|index| variant_info[index].clone())
+                    .collect::<Vec<fuzzy::Var>>();
+                info!("Scanning fuzzyDBSCAN parameters on {} variants", variant_info.len());
+                let features = fuzzy::VarFeatures::from_vars(&variant_info[..],
+                                                             geom_mean_var, geom_mean_dep,
+                                                             distance);
+                let matrix = DistanceMatrix::from_points(&features[..], geom_mean_var,
+                                                         geom_mean_dep, geom_mean_frq);
+                let points = (0..variant_info.len()).map(|index| {
+                    PrecomputedPoint { index, matrix: &matrix }
+                }).collect::<Vec<PrecomputedPoint>>();
+                let links = linkage_clustering_of_variants(&variant_info,
+                                                           anchor_size, anchor_similarity,
+                                                           minimum_reads_in_link);
+
+                let file_name = format!("{}_parameter_search.tsv", output_prefix);
+                let mut file_open = File::create(&file_name)
+                    .expect(&format!("Unable to create file {}", &file_name));
+                writeln!(file_open, "e_min\te_max\tpts_min\tpts_max\tphi\tclusters\tclustered_fraction\tsilhouette\tread_consistency\tscore")
+                    .expect("Unable to write to file");
+
+                let (initial_e_min, initial_e_max, initial_pts_min, initial_pts_max) = initial;
+                let pts_ratio = if initial_pts_min > 0. {
+                    (initial_pts_max / initial_pts_min).max(1.)
+                } else {
+                    2.
+                };
+                let scales = [0.5, 1., 2.];
+                let mut best_score = std::f64::NEG_INFINITY;
+                let mut best = (initial_e_min, initial_e_max, initial_pts_min, initial_pts_max, 0.);
+                for e_min in scales.iter().map(|scale| scale * initial_e_min) {
+                    for e_max in scales.iter().map(|scale| scale * initial_e_max) {
+                        if e_max < e_min {
+                            continue
+                        }
+                        for pts_min in scales.iter().map(|scale| scale * initial_pts_min) {
+                            for phi in [0., 0.5].iter() {
+                                let pts_max = pts_min * pts_ratio;
+                                let fuzzy_scanner = fuzzy::FuzzyDBSCAN {
+                                    eps_min: e_min,
+                                    eps_max: e_max,
+                                    pts_min: pts_min * variant_info.len() as f64,
+                                    pts_max: pts_max * variant_info.len() as f64,
+                                    phi: *phi,
+                                    geom_var: geom_mean_var.to_owned(),
+                                    geom_dep: geom_mean_dep.to_owned(),
+                                    geom_frq: geom_mean_frq.to_owned(),
+                                };
+                                let clusterer = ClusteringMethod::new(clustering_method, fuzzy_scanner);
+                                let clusters = clusterer.cluster(&points[..], links.clone());
+                                let assignments = hard_assignments(&clusters, variant_info.len());
+                                let clustered_fraction = assignments.iter().filter(|a| a.is_some()).count() as f64
+                                    / std::cmp::max(variant_info.len(), 1) as f64;
+                                let silhouette = silhouette_score(&matrix, &assignments);
+                                let read_consistency = read_consistency_score(
+                                    &variant_info[..], &assignments, minimum_reads_in_link);
+                                let score = match criterion {
+                                    "read-consistency" => read_consistency,
+                                    _ => silhouette * clustered_fraction,
+                                };
+                                writeln!(file_open, "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                                         e_min, e_max, pts_min, pts_max, phi, clusters.len(),
+                                         clustered_fraction, silhouette, read_consistency, score)
+                                    .expect("Unable to write to file");
+                                if score > best_score {
+                                    best_score = score;
+                                    best = (e_min, e_max, pts_min, pts_max, *phi);
+                                }
+                            }
+                        }
+                    }
+                }
+                info!("Chose e_min {} e_max {} pts_min {} pts_max {} phi {} with {} score {}",
+                      best.0, best.1, best.2, best.3, best.4, criterion, best_score);
+                best
+            }
+        }
+    }
+
+    #[allow(unused)]
+    fn generate_genotypes(&mut self, output_prefix: &str,
+                          reference: &mut bio::io::fasta::IndexedReader<File>,
+                          mask_mode: &str) {
+        match self {
+            VariantMatrix::VariantContigMatrix {
+                target_names,
+                all_variants,
+                ref mut pred_variants,
+                ..
+            } => {
+                let reference = Mutex::new(reference);
+                let all_variants = &*all_variants;
+                pred_variants.par_iter().for_each(|(strain_index, genotype)|{
+                    let file_name = format!("{}_strain_{}.fna", output_prefix.to_string(), strain_index);
+
+                    let file_path = Path::new(&file_name);
+                    // Open haplotype file or create one
+                    let mut file_open = File::create(file_path)
+                        .expect("No Read or Write Permission in current directory");
+
+                    let mut original_contig = Vec::new();
+
+                    let mut genotype = genotype.clone();
+
+                    let mut total_variant_alleles = 0;
+                    let mut total_reference_alleles = 0;
+                    let mut total_masked_alleles = 0;
+
+                    // Reads supporting the strain's unambiguous core alleles, used to resolve
+                    // sites where the strain has several candidate alleles
+                    let strain_reads = strain_read_set(&genotype, all_variants);
+
+                    // Generate the variant genome
+                    for (tid, target_name) in target_names.iter() {
+                        let mut contig = String::new();
+                        original_contig = Vec::new();
+                        {
+                            let mut reference = reference.lock().unwrap();
+                            match reference.fetch_all(
+                                std::str::from_utf8(target_name.as_bytes()).unwrap()) {
+                                Ok(reference) => reference,
+                                Err(e) => {
+                                    warn!("Cannot read sequence from reference {:?}", e);
+                                    std::process::exit(1)
+                                },
+                            };
+                            match reference.read(&mut original_contig) {
+                                Ok(reference) => reference,
+                                Err(e) => {
+                                    warn!("Cannot read sequence from reference {:?}", e);
+                                    std::process::exit(1)
+                                },
+                            };
+                        }
+                        let mut skip_n = 0;
+                        let mut skip_cnt = 0;
+//                            let char_cnt = 0;
+                        let mut variations = 0;
+                        let mut ref_alleles = 0;
+
+                        if genotype.contains_key(&tid) {
+                            for (pos, base) in original_contig.iter().enumerate() {
+                                if skip_cnt < skip_n {
+                                    skip_cnt += 1;
+                                } else {
+                                    skip_n = 0;
+                                    skip_cnt = 0;
+    //                                if genotype.contains_key(&tid) {
+                                        let tid_genotype = genotype.get_mut(&tid).unwrap();
+
+                                        if tid_genotype.contains_key(&(pos as i64)) {
+                                            let categories = &genotype[tid][&(pos as i64)];
+                                            let hash;
+                                            let mut confident = true;
+                                            if categories.contains_key(&fuzzy::Category::Core) {
+                                                hash = categories[&fuzzy::Category::Core].clone();
+                                            } else if categories.contains_key(&fuzzy::Category::Border) {
+                                                hash = categories[&fuzzy::Category::Border].clone();
+                                            } else {
+                                                hash = categories[&fuzzy::Category::Noise].clone();
+                                                confident = false;
+                                            }
+
+                                            let position_variants = all_variants.get(tid)
+                                                .and_then(|contig_variants| contig_variants.get(&(pos as i64)));
+                                            let (max_var, linked) = resolve_allele(
+                                                &hash, position_variants, &strain_reads);
+                                            confident = confident && linked;
+                                            if hash.len() > 1 {
+                                                debug!("Multi hash {:?} resolved to {:?} confident {}",
+                                                       hash, max_var, confident)
+                                            }
+
+                                            // The bases that could be present at this site
+                                            let candidate_bases = hash.iter().filter_map(|var| {
+                                                match var {
+                                                    Variant::SNV(alt) => Some(*alt),
+                                                    Variant::None => Some(*base),
+                                                    _ => None,
+                                                }
+                                            }).collect::<BTreeSet<u8>>();
+                                            let mask = |sequence: &[u8]| -> String {
+                                                if confident {
+                                                    str::from_utf8(sequence).unwrap().to_string()
+                                                } else {
+                                                    mask_sequence(sequence, &candidate_bases, mask_mode)
+                                                }
+                                            };
+                                            if !confident {
+                                                total_masked_alleles += 1;
+                                            }
+
+                                            match max_var {
+                                                Variant::Deletion(size) => {
+                                                    // Skip the next n bases but rescue the reference prefix
+                                                    skip_n = size;
+                                                    skip_cnt = 0;
+                                                    contig = contig + &mask(&[*base]);
+                                                    // If we had the sequence we would rescue first base like this
+    //                                                let first_byte = max_var.as_bytes()[0];
+    //                                                contig = contig + str::from_utf8(
+    //                                                    &[first_byte]).unwrap();
+                                                    variations += 1;
+                                                },
+                                                Variant::Insertion(alt) => {
+
+                                                    // Remove prefix from variant
+                                                    contig = contig + &mask(&alt[1..]);
+                                                    variations += 1;
+                                                },
+                                                Variant::Inversion(alt) | Variant::MNV(alt) => {
+                                                    // Skip the next n bases
+                                                    skip_n = alt.len() as u32 - 1;
+                                                    skip_cnt = 0;
+                                                    // Inversions and MNVs don't have a first base prefix, so take
+                                                    // wholes tring
+                                                    contig = contig + &mask(&alt);
+                                                    variations += 1;
+                                                },
+                                                Variant::None => {
+                                                    contig = contig + &mask(&[*base]);
+                                                    ref_alleles += 1;
+                                                },
+                                                Variant::SNV(alt) => {
+
+                                                    contig = contig + &mask(&[alt]);
+                                                    variations += 1;
+                                                },
+                                                _ => {
+                                                    contig = contig + str::from_utf8(&[*base]).unwrap();
+                                                    ref_alleles += 1;
+                                                }
+                                            }
+                                        } else {
+                                            contig = contig + str::from_utf8(&[*base]).unwrap();
+                                        }
+    //                                } else {
+    //                                    contig = str::from_utf8(&original_contig)
+    //                                        .expect("Can't convert to str").to_string();
+    //                                }
+                                }
+                            };
+                        } else {
+                            contig = str::from_utf8(&original_contig)
+                                .expect("Can't convert to str").to_string();
+                        }
+
+                        writeln!(file_open, ">{}_strain_{}_alt_alleles_{}_ref_alleles_{}",
+                                 target_names[tid],
+                                 strain_index,
+                                 variations, ref_alleles).expect("Unable to write to file");
+
+                        for line in contig.as_bytes().to_vec()[..].chunks(60).into_iter() {
+                            file_open.write(line).unwrap();
+                            file_open.write(b"\n").unwrap();
+                        };
+                        total_variant_alleles += variations;
+                        total_reference_alleles += ref_alleles;
+                    }
+                    info!("Cluster {} contains {} variant alleles and {} reference alleles, {} of which are low confidence",
+                          strain_index, total_variant_alleles, total_reference_alleles, total_masked_alleles);
+                });
+            }
+        }
+    }
+
+    fn print_variant_stats(&self, output_prefix: &str, window_size: f64) {
+        match self {
+            VariantMatrix::VariantContigMatrix {
+                target_names,
+                target_lengths,
+                sample_names,
+                all_variants,
+                variant_sums,
+                variant_info,
+                ..
+            } => {
+
+                let file_name = output_prefix.to_string()
+                    + &".tsv".to_owned();
+                let snp_locs = format!("{}_snp_locations.tsv", output_prefix);
+                let file_path = Path::new(&file_name);
+                let mut file_open = match File::create(file_path) {
+                    Ok(fasta) => fasta,
+                    Err(e) => {
+                        println!("Cannot create file {:?}", e);
+                        std::process::exit(1)
+                    },
+                };
+
+                let snp_loc_path = Path::new(&snp_locs);
+                let mut snp_loc_open = match File::create(snp_loc_path) {
+                    Ok(tsv) => tsv,
+                    Err(e) => {
+                        println!("Cannot create file {:?}", e);
+                        std::process::exit(1)
+                    },
+                };
+
+                // Snp density summary start
+                write!(file_open, "contigName\tcontigLen").unwrap();
+
+                // Snp location start
+                write!(snp_loc_open, "SNP\tchr\tpos").unwrap();
+                debug!("Sample Names {:?}", sample_names);
+                for sample_name in sample_names.iter(){
+                    write!(file_open,
+                           "\t{}.snvsPer{}kb\t{}.svsPer{}kb\t{}.snvCount\t{}.svCount",
+                           &sample_name, &window_size, &sample_name, &window_size,
+                           &sample_name, &sample_name).unwrap();
+                }
+                write!(file_open, "\n").unwrap();
+                write!(snp_loc_open, "\n").unwrap();
+
+                let mut snps = 0;
+
+                for (tid, contig_name) in target_names.iter() {
+                    let contig_len =  target_lengths[tid];
+                    write!(file_open, "{}\t{}", contig_name, contig_len).unwrap();
+                    match all_variants.get(tid) {
+                        Some(variants_in_contig) => {
+                            // Set up channels that receive a vector of values for each sample
+                            let mut snps_cnt_vec = vec![0; sample_names.len()];
+                            let svs_cnt_vec = Mutex::new(vec![0; sample_names.len()]);
+//                            let (snp_freq_s, snp_freq_r) = channel();
+//                            let (sv_freq_s, sv_freq_r) = channel();
+//                            let (snp_std_s, snp_std_r) = channel();
+//                            let (sv_std_s, sv_std_r) = channel();
+                            let window = contig_len / window_size;
+                            variants_in_contig.iter().enumerate()
+                                .for_each(|(index, (position, variants))| {
+                                    // Get how many alleles are present at loci
+                                    let alleles = variants.len();
+                                    for (var, base) in variants {
+                                        match var {
+                                            Variant::SNV(_) => {
+                                                write!(snp_loc_open, "SNP{}\t{}\t{}\n",
+                                                       index, contig_name, position).unwrap();
+                                                base.truedepth
+                                                    .iter()
+                                                    .enumerate()
+                                                    .zip(base.depth.iter().enumerate())
+                                                    .for_each(|((index, count_1), (_index_2, count_2))| {
+                                                        if count_1 > &0 || count_2 > &0 {
+                                                            snps_cnt_vec[index] += 1;
+                                                        }
+                                                    });
+                                                snps += 1;
+
+                                            },
+                                            Variant::None => {
+                                                // If biallelic or multiallelic then
+                                                // I don't think we want the ref depth?
+
+                                            },
+                                            _ => {
+                                                base.truedepth
+                                                    .par_iter()
+                                                    .enumerate()
+                                                    .zip(base.depth.par_iter().enumerate())
+                                                    .for_each(|((index, count_1), (_, count_2))| {
+                                                        if count_1 > &0 || count_2 > &0 {
+                                                            let mut svs_cnt_vec
+                                                                = svs_cnt_vec.lock().unwrap();
+                                                            svs_cnt_vec[index] += 1;
+                                                        }
+                                                    })
+                                            }
+                                        };
+                                    };
+
+                                });
+                            let svs_cnt_vec = svs_cnt_vec.lock().unwrap().clone();
+                            let snps_per_win: Vec<_> = snps_cnt_vec.iter().map(|count| *count as f64 / window).collect();
+                            let svs_per_win: Vec<_> = svs_cnt_vec.iter().map(|count| *count as f64 / window).collect();
+
+                            for (snp_w, svs_w, snp_c, svs_c) in izip!(&snps_per_win, &svs_per_win, &snps_cnt_vec, &svs_cnt_vec) {
+                                write!(file_open, "\t{}\t{}\t{}\t{}", snp_w, svs_w, snp_c, svs_c).unwrap();
+                            }
+                            write!(file_open, "\n").unwrap();
+
+                        },
+                        None => {
+                            // Write out zeros for contigs with no variants
+                            for (sample_idx, _sample_name) in sample_names.iter().enumerate() {
+                                write!(file_open,
+                                         "\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                                         0., 0., 0., 0., 0., 0., 0.,).unwrap();
+                            }
+                        }
+                    }
+                };
+                if snps > 0 {
+                    let plot_command = format!("set -eou pipefail; snp_density_plots.R {} {} && \
+                                                    mv SNP-Density*.pdf {}_snp_density_plot.pdf",
+                                               output_prefix, window_size, output_prefix);
+                    command::finish_command_safely(
+                        std::process::Command::new("bash")
+                            .arg("-c")
+                            .arg(&plot_command)
+                            .stderr(Stdio::piped())
+                            .stdout(Stdio::piped())
+                            .spawn()
+                            .expect("Unable to execute Rscript"), "CMplot");
+                } else {
+                    std::fs::remove_file(snp_loc_path).expect("Unable to remove file");
+                }
+            }
+        }
+    }
+
+    fn calc_gene_mutation(&self,
+                          gff_map: &mut HashMap<String, Vec<bio::io::gff::Record>>,
+                          reference: &mut bio::io::fasta::IndexedReader<File>,
+                          codon_table: &CodonTable,
+                          ref_name: &str,
+                          output_prefix: &str) {
+        match self {
+            VariantMatrix::VariantContigMatrix {
+                target_names,
+                all_variants,
+                ..
+            } => {
+                let file_name = format!("{}_{}_dnds_values.gff",
+                                        output_prefix.to_string(), ref_name);
+
+                let file_path = Path::new(&file_name);
+//                let mut file_open = File::create(file_path)
+//                    .expect("Unable to create GFF file");
+                let mut gff_writer = gff::Writer::to_file(file_path,
+                                                          bio::io::gff::GffType::GFF3)
+                                                          .expect("unable to create GFF file");
+                for (tid, contig_name) in target_names.iter() {
+
+
+                    let mut ref_sequence = Vec::new();
+                    match reference.fetch_all(
+                        std::str::from_utf8(contig_name.as_bytes()).unwrap()) {
+                        Ok(reference) => reference,
+                        Err(e) => {
+                            println!("Cannot read sequence from reference {:?}", e);
+                            std::process::exit(1)
+                        },
+                    };
+                    match reference.read(&mut ref_sequence) {
+                        Ok(reference) => reference,
+                        Err(e) => {
+                            println!("Cannot read sequence from reference {:?}", e);
+                            std::process::exit(1)
+                        },
+                    };
+                    let mut placeholder = Vec::new();
+                    let gff_records = match gff_map.get_mut(contig_name){
+                        Some(records) => records,
+                        None => &mut placeholder,
+                    };
+                    debug!("Calculating population dN/dS from reads for {} genes on contig {}",
+                           gff_records.len(), contig_name);
+
+                    let placeholder_map = HashMap::new();
+                    let variants = match all_variants.get(tid) {
+                        Some(map) => map,
+                        None => &placeholder_map
+                    };
+
+                    gff_records.iter_mut().enumerate().for_each(|(_id, gene)| {
+                        let dnds = codon_table.find_mutations(gene, variants, &ref_sequence);
+                        gene.attributes_mut().insert(format!("dNdS"), format!("{}", dnds));
+                        debug!("gene {:?} attributes {:?}", gene.seqname(), gene);
+                        gff_writer.write(gene);
+//                        let attributes = if !gene.attributes().is_empty() {
+//                            gene
+//                                .attributes()
+//                                .iter()
+//                                .map(|(a, b)| format!("{}{}{}", a, "=", b))
+//                                .join(";")
+//                        } else {
+//                            "".to_owned()
+//                        };
+//
+//                        let gff_line = format!(
+//                            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t",
+//                                            gene.seqname(),
+//                                            gene.source(),
+//                                            gene.feature_type(),
+//                                            gene.start(),
+//                                            gene.end(),
+//                                            gene.score().unwrap_or(0),
+//                                            gene.strand().unwrap_or(Strand::Unknown),
+//                                            gene.frame(),
+//                                            attributes);
+//                        println!("{}", gff_line);
+//                        writeln!(file_open, "{}", gff_line);
+                    });
+
+                }
+            }
+        }
+    }
+
+    fn calc_selection(&self,
+                      gff_map: &HashMap<String, Vec<bio::io::gff::Record>>,
+                      reference: &mut bio::io::fasta::IndexedReader<File>,
+                      codon_table: &CodonTable,
+                      output_prefix: &str) {
+        match self {
+            VariantMatrix::VariantContigMatrix {
+                target_names,
+                all_variants,
+                sample_names,
+                pred_variants,
+                ..
+            } => {
+                let mut strain_indices = pred_variants.keys().cloned().collect::<Vec<usize>>();
+                strain_indices.sort();
+                let format_ratio = |ratio: Option<f64>| match ratio {
+                    Some(ratio) => format!("{}", ratio),
+                    None => "NA".to_string(),
+                };
+
+                let pnps_name = format!("{}_pnps.tsv", output_prefix);
+                let mut pnps_file = File::create(&pnps_name)
+                    .expect(&format!("Unable to create file {}", &pnps_name));
+                write!(pnps_file, "contig\tgene\tstart\tend\tstrand").expect("Unable to write to file");
+                for sample_name in sample_names.iter() {
+                    write!(pnps_file, "\t{}", sample_name).expect("Unable to write to file");
+                }
+                write!(pnps_file, "\n").expect("Unable to write to file");
+
+                let dnds_name = format!("{}_strain_dnds.tsv", output_prefix);
+                let mut dnds_file = if strain_indices.len() > 0 {
+                    let mut file_open = File::create(&dnds_name)
+                        .expect(&format!("Unable to create file {}", &dnds_name));
+                    write!(file_open, "contig\tgene\tstart\tend\tstrand").expect("Unable to write to file");
+                    for strain_index in strain_indices.iter() {
+                        write!(file_open, "\tstrain_{}", strain_index).expect("Unable to write to file");
+                    }
+                    write!(file_open, "\n").expect("Unable to write to file");
+                    Some(file_open)
+                } else {
+                    None
+                };
+
+                let placeholder_map = HashMap::new();
+                for (tid, contig_name) in target_names.iter() {
+                    let genes = match gff_map.get(contig_name) {
+                        Some(genes) => genes,
+                        None => continue,
+                    };
+                    let mut ref_sequence = Vec::new();
+                    reference.fetch_all(contig_name)
+                        .expect(&format!("Cannot read sequence {} from reference", contig_name));
+                    reference.read(&mut ref_sequence)
+                        .expect(&format!("Cannot read sequence {} from reference", contig_name));
+                    let variants = all_variants.get(tid).unwrap_or(&placeholder_map);
+
+                    // The alternative base each strain carries at each position, preferring
+                    // core variants when a strain has several
+                    let strain_bases = strain_indices.iter().map(|strain_index| {
+                        let mut bases = BTreeMap::new();
+                        if let Some(positions) = pred_variants[strain_index].get(tid) {
+                            for (pos, categories) in positions.iter() {
+                                for category in [fuzzy::Category::Core, fuzzy::Category::Border,
+                                                 fuzzy::Category::Noise].iter() {
+                                    let alt = categories.get(category).and_then(|variants| {
+                                        variants.iter().filter_map(|variant| match variant {
+                                            Variant::SNV(alt) => Some(*alt),
+                                            _ => None,
+                                        }).min()
+                                    });
+                                    if let Some(alt) = alt {
+                                        bases.insert(*pos, alt);
+                                        break
+                                    }
+                                }
+                            }
+                        }
+                        bases
+                    }).collect::<Vec<BTreeMap<i64, u8>>>();
+
+                    debug!("Calculating per sample pN/pS and per strain dN/dS for {} genes on contig {}",
+                           genes.len(), contig_name);
+                    for gene in genes.iter() {
+                        let gene_id = gene_identifier(gene);
+                        let strand = match gene.strand() {
+                            Some(bio_types::strand::Strand::Forward) => "+",
+                            Some(bio_types::strand::Strand::Reverse) => "-",
+                            _ => ".",
+                        };
+                        let gene_info = format!("{}\t{}\t{}\t{}\t{}",
+                                                contig_name, gene_id, gene.start(), gene.end(), strand);
+
+                        write!(pnps_file, "{}", gene_info).expect("Unable to write to file");
+                        for pnps in codon_table.sample_pnps(gene, variants, &ref_sequence, sample_names.len()) {
+                            write!(pnps_file, "\t{}", format_ratio(pnps)).expect("Unable to write to file");
+                        }
+                        write!(pnps_file, "\n").expect("Unable to write to file");
+
+                        if let Some(dnds_file) = dnds_file.as_mut() {
+                            write!(dnds_file, "{}", gene_info).expect("Unable to write to file");
+                            for bases in strain_bases.iter() {
+                                write!(dnds_file, "\t{}", format_ratio(codon_table.strain_dnds(gene, bases, &ref_sequence)))
+                                    .expect("Unable to write to file");
+                            }
+                            write!(dnds_file, "\n").expect("Unable to write to file");
+                        }
+                    }
+                }
+                info!("Per sample pN/pS written to {}", &pnps_name);
+                if dnds_file.is_some() {
+                    info!("Per strain dN/dS written to {}", &dnds_name);
+                }
+            }
+        }
+    }
+
+    fn write_vcf(&self, output_prefix: &str,
+                 mut effects: Option<(&HashMap<String, Vec<bio::io::gff::Record>>, &CodonTable,
+                                      &mut bio::io::fasta::IndexedReader<File>)>) {
+        match self {
+            VariantMatrix::VariantContigMatrix {
+                all_variants,
+                target_names,
+                target_lengths,
+                sample_names,
+                variant_info,
+                ..
+            } => {
+                if variant_info.len() > 0 {
+                    // initiate header
+                    let mut header = bcf::Header::new();
+                    // Add program info
+                    header.push_record(format!("##source=lorikeet-v{}",
+                                               env!("CARGO_PKG_VERSION")).as_bytes());
+
+                    debug!("samples {:?}", &sample_names);
+                    for sample in sample_names.iter() {
+                        header.push_sample(&sample.clone().into_bytes()[..]);
+                    }
+
+                    // Add contig info
+                    for (tid, contig_name) in target_names {
+                        header.push_record(
+                            format!("##contig=<ID={}, length={}>",
+                                    contig_name, target_lengths[tid]).as_bytes()
+                        );
+                    }
+
+                    // Add INFO flags
+                    header.push_record(
+                        format!("##INFO=<ID=TYPE,Number=A,Type=String,\
+                    Description=\"The type of allele, either SNV, MNV, INS, DEL, or INV.\">").as_bytes());
+
+                    header.push_record(
+                        format!("##INFO=<ID=SVLEN,Number=1,Type=Integer,\
+                    Description=\"Length of structural variant\">").as_bytes());
+
+                    header.push_record(
+                        b"##INFO=<ID=TDP,Number=A,Type=Integer,\
+                            Description=\"Total observed sequencing depth\">",
+                    );
+
+                    header.push_record(
+                        b"##INFO=<ID=TAD,Number=A,Type=Integer,\
+                            Description=\"Total observed sequencing depth of alternative allele\">",
+                    );
+
+                    header.push_record(
+                        b"##INFO=<ID=TRD,Number=A,Type=Integer,\
+                            Description=\"Total observed sequencing depth of reference allele\">",
+                    );
+
+                    header.push_record(
+                        b"##INFO=<ID=ST,Number=.,Type=Integer,\
+                            Description=\"The strain IDs assigned to this variant\">",
+                    );
+
+                    if effects.is_some() {
+                        header.push_record(
+                            b"##INFO=<ID=ANN,Number=.,Type=String,\
+                                Description=\"Functional annotations: 'Allele | Annotation | \
+                                Annotation_Impact | Gene_Name | Gene_ID | Feature_Type | Feature_ID | \
+                                Transcript_BioType | Rank | HGVS.c | HGVS.p | cDNA.pos / cDNA.length | \
+                                CDS.pos / CDS.length | AA.pos / AA.length | Distance | \
+                                ERRORS / WARNINGS / INFO'\">",
+                        );
+                    }
+
+                    // Add FORMAT flags
+                    header.push_record(
+                        format!("##FORMAT=<ID=DP,Number={},Type=Integer,\
+                            Description=\"Observed sequencing depth in each sample\">",
+                                sample_names.len()).as_bytes(),
+                    );
+
+                    header.push_record(
+                        format!("##FORMAT=<ID=AD,Number={},Type=Integer,\
+                            Description=\"Observed sequencing depth of alternative allele in each sample\">",
+                                sample_names.len()).as_bytes(),
+                    );
+
+                    header.push_record(
+                        format!("##FORMAT=<ID=RD,Number={},Type=Integer,\
+                            Description=\"Observed sequencing depth of reference allele in each sample\">",
+                                sample_names.len()).as_bytes(),
+                    );
+
+                    header.push_record(
+                        format!("##FORMAT=<ID=QA,Number={},Type=Float,\
+                            Description=\"Quality scores for allele in each sample\">",
+                                sample_names.len()).as_bytes(),
+                    );
+
+                    let vcf_presort = tempfile::Builder::new()
+                        .prefix("lorikeet-fifo")
+                        .tempfile().expect("Unable to create VCF tempfile");
+
+                    // Initiate writer
+                    let mut bcf_writer = bcf::Writer::from_path(
+                        format!("{}.vcf",
+                                output_prefix).as_str(),
+                        &header,
+                        true,
+                        bcf::Format::VCF).expect(
+                        format!("Unable to create VCF output: {}.vcf",
+                                output_prefix).as_str());
+
+                    bcf_writer.set_threads(current_num_threads()).unwrap();
+
+
+                    for (tid, position_variants) in all_variants.into_iter() {
+                        let contig_name = &target_names[tid];
+                        // Genes of the contig, with their codons read from its sequence, to
+                        // annotate effects against
+                        let contig_genes = match effects.as_mut() {
+                            Some((gff_map, codon_table, reference)) => {
+                                match gff_map.get(contig_name) {
+                                    Some(genes) => {
+                                        let mut ref_sequence = Vec::new();
+                                        reference.fetch_all(contig_name)
+                                            .expect(&format!("Cannot read sequence {} from reference", contig_name));
+                                        reference.read(&mut ref_sequence)
+                                            .expect(&format!("Cannot read sequence {} from reference", contig_name));
+                                        Some((GeneIndex::new(genes, &ref_sequence), *codon_table))
+                                    },
+                                    None => None,
+                                }
+                            },
+                            None => None,
+                        };
+                        for (pos, variants) in position_variants.iter() {
+                            for (variant, base) in variants.iter() {
+                                // Create empty record for this variant
+                                let mut record = bcf_writer.empty_record();
+                                record.set_rid(
+                                    Some(bcf_writer.header()
+                                        .name2rid(contig_name.as_bytes()).unwrap()));
+                                record.set_pos(*pos);
+
+                                // Sum the quality scores across samples
+                                let qual_sum = base.quals.iter().sum();
+                                record.set_qual(qual_sum);
+
+                                // Collect strain information
+                                let mut strains = base.genotypes.iter().cloned()
+                                    .collect::<Vec<i32>>();
+                                strains.sort();
+
+                                // Push info tags to record
+                                record.push_info_integer(b"TDP", &[base.totaldepth.iter().sum()]);
+                                record.push_info_integer(b"TAD", &[base.truedepth.iter().sum()]);
+                                record.push_info_integer(b"TRD", &[base.referencedepth.iter().sum()]);
+                                record.push_info_integer(b"ST", &strains[..]);
+
+                                // Push format flags to record
+                                record.push_format_integer(b"DP", &base.totaldepth[..]);
+                                record.push_format_integer(b"AD", &base.truedepth[..]);
+                                record.push_format_integer(b"RD", &base.referencedepth[..]);
+                                record.push_format_float(b"QA", &base.quals[..]);
+
+                                let refr = &base.refr[..];
+
+
+                                match variant {
+                                    Variant::SNV(alt) => {
+
+                                        // Collect and set the alleles to record
+                                        let alt = &[*alt];
+                                        let mut collect_alleles = vec![refr, alt];
+                                        record.set_alleles(&collect_alleles).unwrap();
+
+                                        record.push_info_string(b"TYPE", &[b"SNV"]);
+                                    },
+                                    Variant::MNV(alt) => {
+
+                                        // Collect and set the alleles to record
+                                        let alt = [&refr[..], &alt[..]].concat();
+                                        let mut collect_alleles = vec![refr, &alt];
+                                        record.set_alleles(&collect_alleles).unwrap();
+
+                                        record.push_info_string(b"TYPE", &[b"MNV"]);
+                                        record.push_info_integer(b"SVLEN", &[alt.len() as i32]);
+                                    },
+                                    Variant::Inversion(alt) => {
+                                        // Collect and set the alleles to record
+                                        let alt = [&refr[..], &alt[..]].concat();
+                                        let mut collect_alleles = vec![refr, &alt];
+                                        record.set_alleles(&collect_alleles).unwrap();
+
+                                        record.push_info_string(b"TYPE", &[b"INV"]);
+                                        record.push_info_integer(b"SVLEN", &[alt.len() as i32]);
+                                    },
+                                    Variant::Insertion(alt) => {
+                                        // Collect and set the alleles to record
+                                        let alt = [&refr[..], &alt[..]].concat();
+                                        let mut collect_alleles = vec![refr, &alt];
+                                        record.set_alleles(&collect_alleles).unwrap();
+
+                                        record.push_info_string(b"TYPE", &[b"INS"]);
+                                        record.push_info_integer(b"SVLEN", &[alt.len() as i32]);
+                                    },
+                                    Variant::Deletion(alt) => {
+                                        // Collect and set the alleles to record
+                                        // Create deletion variant, attach refr to head of N
+                                        record.set_alleles(&vec![refr, format!(
+                                            "{}N", refr[0] as char)
+                                            .as_bytes()]).unwrap();
+
+                                        record.push_info_string(b"TYPE", &[b"DEL"]);
+                                        record.push_info_integer(b"SVLEN", &[*alt as i32]);
+                                    },
+                                    _ => continue,
+                                }
+
+                                if let Some((gene_index, codon_table)) = contig_genes.as_ref() {
+                                    let allele = str::from_utf8(record.alleles()[1]).unwrap().to_string();
+                                    let annotations = codon_table.variant_effects(gene_index, *pos, variant)
+                                        .iter().map(|effect| effect.to_ann(&allele)).collect::<Vec<String>>();
+                                    if annotations.len() > 0 {
+                                        record.push_info_string(b"ANN", &annotations.iter()
+                                            .map(|annotation| annotation.as_bytes()).collect::<Vec<&[u8]>>()[..]);
+                                    }
+                                }
+                                bcf_writer.write(&record).expect("Unable to write record");
+                            }
+                        }
+                    }
+
+                    // Initiate sorting command
+                    // Since variants are in HashMap, they are unsorted.
+                    // Sorting using bcftools is fast so just do it for the user.
+                    external_command_checker::check_for_bcftools();
+
+                    // let command_string = format!(
+                    //     "mv {} {}.vcf",
+                    //     &vcf_presort
+                    //         .path()
+                    //         .to_str()
+                    //         .expect("Failed to convert tempfile to path"),
+                    //     output_prefix
+                    // );
+                    // This fails randomly, not sure what causes it so will keep unsorted for now
+//                    let command_string = format!(
+//                        "bcftools sort {} > {}.vcf",
+//                        &vcf_presort
+//                            .path()
+//                            .to_str()
+//                            .expect("Failed to convert tempfile to path"),
+//                        output_prefix
+//                    );
+//                    if log_enabled!(Level::Debug) {
+//                        vcf_presort.keep().unwrap();
+//                    }
+
+                    // command::finish_command_safely(
+                    //     Command::new("bash")
+                    //         .arg("-c")
+                    //         .arg(&command_string)
+                    //         .stderr(Stdio::piped())
+                    //         .spawn()
+                    //         .expect("Unable to execute bash"),
+                    //     "bcftools"
+                    // );
+                    debug!("Finished writing VCF file for {}", &output_prefix);
+                }
+            }
+        }
+    }
+
+    fn calculate_strain_abundances(&self, output_prefix: &str) {
+        match self {
+            VariantMatrix::VariantContigMatrix {
+                all_variants,
+                sample_names,
+                pred_variants,
+                strain_proportions,
+                ..
+            } => {
+                let file_name = format!("{}_strain_abundances.tsv", output_prefix);
+                let mut file_open = File::create(&file_name)
+                    .expect(&format!("Unable to create file {}", &file_name));
+                writeln!(file_open, "strain\tsample\tabundance\tlower_ci\tupper_ci\tallele_frequency\tcore_variants")
+                    .expect("Unable to write to file");
+
+                // Deconvolved strains keep their rank even when they carry no alleles
+                let mut strain_indices = if strain_proportions.len() > 0 {
+                    (1..strain_proportions.len() + 1).collect::<Vec<usize>>()
+                } else {
+                    pred_variants.keys().cloned().collect::<Vec<usize>>()
+                };
+                strain_indices.sort();
+
+                // Mean allele frequency of each strain's core variants within each sample, with
+                // its bootstrapped confidence interval and the number of variants it is taken from
+                let mut inconsistent_depths = 0;
+                let strain_frequencies = strain_indices.iter().map(|strain_index| {
+                    let mut frequencies = vec![Vec::new(); sample_names.len()];
+                    let genotype = match pred_variants.get(strain_index) {
+                        Some(genotype) => genotype,
+                        None => return vec![(0., 0., 0., 0); sample_names.len()],
+                    };
+                    for (tid, positions) in genotype.iter() {
+                        for (pos, categories) in positions.iter() {
+                            let core_variants = match categories.get(&fuzzy::Category::Core) {
+                                Some(core_variants) => core_variants,
+                                None => continue,
+                            };
+                            for variant in core_variants.iter() {
+                                let base = match all_variants.get(tid)
+                                    .and_then(|contig| contig.get(pos))
+                                    .and_then(|position| position.get(variant)) {
+                                    Some(base) => base,
+                                    None => continue,
+                                };
+                                for (sample_idx, sample_frequencies) in frequencies.iter_mut().enumerate() {
+                                    if base.totaldepth[sample_idx] == 0 {
+                                        continue
+                                    }
+                                    if base.truedepth[sample_idx] > base.totaldepth[sample_idx] {
+                                        inconsistent_depths += 1;
+                                        continue
+                                    }
+                                    sample_frequencies.push(base.truedepth[sample_idx] as f64
+                                        / base.totaldepth[sample_idx] as f64);
+                                }
+                            }
+                        }
+                    }
+                    frequencies.iter().map(|sample_frequencies| {
+                        let (mean, lower, upper) = bootstrap_mean(sample_frequencies, 1000);
+                        (mean, lower, upper, sample_frequencies.len())
+                    }).collect::<Vec<(f64, f64, f64, usize)>>()
+                }).collect::<Vec<Vec<(f64, f64, f64, usize)>>>();
+                if inconsistent_depths > 0 {
+                    warn!("Skipped {} core variant depths larger than the total depth of their site",
+                          inconsistent_depths);
+                }
+
+                // The allele frequencies of the strains in each sample scale their abundances
+                let totals = (0..sample_names.len()).map(|sample_idx| {
+                    strain_frequencies.iter().map(|frequencies| frequencies[sample_idx].0).sum::<f64>()
+                }).collect::<Vec<f64>>();
+
+                for (rank, strain_index) in strain_indices.iter().enumerate() {
+                    for (sample_idx, sample_name) in sample_names.iter().enumerate() {
+                        let (frequency, lower, upper, core_variants) = strain_frequencies[rank][sample_idx];
+                        if strain_proportions.len() > 0 {
+                            writeln!(file_open, "{}\t{}\t{}\tNA\tNA\t{}\t{}",
+                                     strain_index, sample_name,
+                                     strain_proportions[rank][sample_idx], frequency, core_variants)
+                                .expect("Unable to write to file");
+                        } else {
+                            let total = if totals[sample_idx] > 0. { totals[sample_idx] } else { 1. };
+                            writeln!(file_open, "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                                     strain_index, sample_name,
+                                     frequency / total, lower / total, upper / total,
+                                     frequency, core_variants)
+                                .expect("Unable to write to file");
+                        }
+                    }
+                }
+                info!("Strain abundances written to {}", &file_name);
+            }
+        }
+    }
+
+    fn write_checkpoint(&mut self, output_prefix: &str) {
+        match self {
+            VariantMatrix::VariantContigMatrix {
+                ref mut all_variants,
+                ref mut variant_info,
+                ref mut read_names,
+                ..
+            } => {
+                let referenced = all_variants.values()
+                    .flat_map(|contig_variants| contig_variants.values())
+                    .flat_map(|position_variants| position_variants.values())
+                    .flat_map(|base| base.reads.iter().cloned())
+                    .chain(variant_info.iter().flat_map(|var| var.reads.iter().cloned()))
+                    .collect::<HashSet<ReadId>>();
+                let lookup = read_names.retain(&referenced);
+                for contig_variants in all_variants.values_mut() {
+                    for position_variants in contig_variants.values_mut() {
+                        for base in position_variants.values_mut() {
+                            base.reads = base.reads.remap(&lookup);
+                        }
+                    }
+                }
+                for var in variant_info.iter_mut() {
+                    var.reads = var.reads.remap(&lookup);
+                }
+            }
+        }
+        let file_name = format!("{}_matrix.bin", output_prefix);
+        let file = File::create(&file_name)
+            .expect(&format!("Unable to create variant matrix checkpoint {}", &file_name));
+        bincode::serialize_into(BufWriter::new(file), &*self)
+            .expect("Unable to write variant matrix checkpoint");
+        info!("Variant matrix checkpoint written to {}", &file_name);
+    }
+
+    fn estimate_strain_count(&self, output_prefix: &str, max_strains: usize) -> usize {
+        match self {
+            VariantMatrix::VariantContigMatrix {
+                variant_info,
+                error_rates,
+                target_lengths,
+                sample_names,
+                ..
+            } => {
+                let summary_name = format!("{}_strain_count.tsv", output_prefix);
+                let mut summary = File::create(&summary_name)
+                    .expect(&format!("Unable to create file {}", &summary_name));
+                writeln!(summary, "sample\terror_rate\tsites\tcomponents\tfrequencies\tstrains")
+                    .expect("Unable to write to file");
+                let models_name = format!("{}_strain_count_models.tsv", output_prefix);
+                let mut models_file = File::create(&models_name)
+                    .expect(&format!("Unable to create file {}", &models_name));
+                writeln!(models_file, "sample\tcomponents\tlog_likelihood\tBIC\tfrequencies\tweights\tselected")
+                    .expect("Unable to write to file");
+
+                let format_values = |values: &Vec<f64>| -> String {
+                    values.iter().map(|value| format!("{:.4}", value)).collect::<Vec<String>>().join(",")
+                };
+
+                let mut strains = 1;
+                for (sample_idx, sample_name) in sample_names.iter().enumerate() {
+                    // Length weighted mean of the error slopes of the contigs that could be fitted
+                    let (mut weighted_slopes, mut total_length) = (0., 0.);
+                    for (tid, rates) in error_rates.iter() {
+                        if rates[sample_idx] > 0. {
+                            let length = target_lengths.get(tid).cloned().unwrap_or(1.);
+                            weighted_slopes += rates[sample_idx] * length;
+                            total_length += length;
+                        }
+                    }
+                    let error_rate = if total_length > 0. {
+                        error_rate_from_slope(weighted_slopes / total_length)
+                    } else {
+                        DEFAULT_ERROR_RATE
+                    };
+
+                    let sites = variant_info.iter()
+                        .filter(|var| var.var != Variant::None && var.deps[sample_idx] > 0)
+                        .map(|var| (var.vars[sample_idx].min(var.deps[sample_idx]) as f64,
+                                    var.deps[sample_idx] as f64))
+                        .collect::<Vec<(f64, f64)>>();
+                    if sites.len() == 0 {
+                        debug!("No covered variant sites in sample {}, skipping strain count", sample_name);
+                        continue
+                    }
+
+                    let estimate = estimate_strain_count(&sites, error_rate, max_strains);
+                    for (model_idx, model) in estimate.models.iter().enumerate() {
+                        writeln!(models_file, "{}\t{}\t{}\t{}\t{}\t{}\t{}", sample_name, model.components,
+                                 model.log_likelihood, model.bic, format_values(&model.frequencies),
+                                 format_values(&model.weights), model_idx == estimate.selected)
+                            .expect("Unable to write to file");
+                    }
+                    let selected = &estimate.models[estimate.selected];
+                    writeln!(summary, "{}\t{}\t{}\t{}\t{}\t{}", sample_name, error_rate, estimate.sites,
+                             selected.components, format_values(&selected.frequencies), estimate.strains)
+                        .expect("Unable to write to file");
+                    info!("Sample {} has {} allele frequency components suggesting {} strains",
+                          sample_name, selected.components, estimate.strains);
+                    strains = std::cmp::max(strains, estimate.strains);
+                }
+                writeln!(summary, "genome\tNA\t{}\tNA\tNA\t{}",
+                         variant_info.iter().filter(|var| var.var != Variant::None).count(), strains)
+                    .expect("Unable to write to file");
+                info!("Estimated {} strains, written to {}", strains, &summary_name);
+                strains
+            }
+        }
+    }
+
+    fn write_cluster_memberships(&self, output_prefix: &str) {
+        match self {
+            VariantMatrix::VariantContigMatrix {
+                variant_info,
+                all_variants,
+                target_names,
+                sample_names,
+                variant_clusters,
+                seed_variants,
+                ..
+            } => {
+                let file_name = format!("{}_cluster_memberships.tsv", output_prefix);
+                let mut file_open = File::create(&file_name)
+                    .expect(&format!("Unable to create file {}", &file_name));
+                write!(file_open, "contig\tposition\tref\ttype\talt").expect("Unable to write to file");
+                for sample_name in sample_names.iter() {
+                    write!(file_open, "\t{}.variantDepth\t{}.totalDepth", sample_name, sample_name)
+                        .expect("Unable to write to file");
+                }
+                write!(file_open, "\tcluster\tlabel\tcategory\tseeded\n").expect("Unable to write to file");
+
+                // Memberships of each variant, with clusters numbered as strains are
+                let mut memberships: Vec<Vec<(usize, &fuzzy::Assignment)>> = vec![Vec::new(); variant_info.len()];
+                for (rank, cluster) in variant_clusters.iter().enumerate() {
+                    for assignment in cluster.iter() {
+                        memberships[assignment.index].push((rank + 1, assignment));
+                    }
+                }
+
+                let mut order = (0..variant_info.len()).collect::<Vec<usize>>();
+                order.sort_by_key(|index| (variant_info[*index].tid, variant_info[*index].pos,
+                                           variant_info[*index].var.clone()));
+                for index in order {
+                    let var = &variant_info[index];
+                    let refr = all_variants.get(&var.tid)
+                        .and_then(|contig_variants| contig_variants.get(&var.pos))
+                        .and_then(|position_variants| position_variants.get(&var.var))
+                        .map(|base| str::from_utf8(&base.refr).unwrap().to_string())
+                        .unwrap_or(".".to_string());
+                    let (variant_type, allele) = variant_type_and_allele(&var.var);
+                    let mut row = format!("{}\t{}\t{}\t{}\t{}", target_names[&var.tid], var.pos,
+                                          refr, variant_type, allele);
+                    for (variant_depth, total_depth) in var.vars.iter().zip(var.deps.iter()) {
+                        row.push_str(&format!("\t{}\t{}", variant_depth, total_depth));
+                    }
+                    let seeded = seed_variants.contains(&index);
+
+                    if memberships[index].len() == 0 {
+                        writeln!(file_open, "{}\tNA\tNA\t{:?}\t{}", row, fuzzy::Category::Noise, seeded)
+                            .expect("Unable to write to file");
+                    }
+                    for (cluster, assignment) in memberships[index].iter() {
+                        writeln!(file_open, "{}\t{}\t{}\t{:?}\t{}", row, cluster, assignment.label,
+                                 assignment.category, seeded)
+                            .expect("Unable to write to file");
+                    }
+                }
+                info!("Cluster memberships written to {}", &file_name);
+            }
+        }
+    }
+
+    fn write_feature_matrices(&self, output_prefix: &str,
+                              distance: fuzzy::DistanceMetric, e_max: f64) -> fuzzy::NeighbourGraph {
+        match self {
+            VariantMatrix::VariantContigMatrix {
+                variant_info,
+                target_names,
+                sample_names,
+                geom_mean_var,
+                geom_mean_dep,
+                geom_mean_frq,
+                ..
+            } => {
+                // Rows of every file follow the order of variant_info, numbered from 1 as in
+                // the Matrix Market file
+                let file_name = format!("{}_variant_depths.tsv", output_prefix);
+                let mut depth_file = File::create(&file_name)
+                    .expect(&format!("Unable to create file {}", &file_name));
+                write!(depth_file, "index\tcontig\tposition\ttype\tallele")
+                    .expect("Unable to write to file");
+                for sample_name in sample_names.iter() {
+                    write!(depth_file, "\t{}.variantDepth\t{}.totalDepth", sample_name, sample_name)
+                        .expect("Unable to write to file");
+                }
+                write!(depth_file, "\n").expect("Unable to write to file");
+                for (index, var) in variant_info.iter().enumerate() {
+                    let (variant_type, allele) = variant_type_and_allele(&var.var);
+                    write!(depth_file, "{}\t{}\t{}\t{}\t{}", index + 1, target_names[&var.tid],
+                           var.pos, variant_type, allele).expect("Unable to write to file");
+                    for (variant_depth, total_depth) in var.vars.iter().zip(var.deps.iter()) {
+                        write!(depth_file, "\t{}\t{}", variant_depth, total_depth)
+                            .expect("Unable to write to file");
+                    }
+                    write!(depth_file, "\n").expect("Unable to write to file");
+                }
+
+                let file_name = format!("{}_geometric_means.tsv", output_prefix);
+                let mut geom_file = File::create(&file_name)
+                    .expect(&format!("Unable to create file {}", &file_name));
+                writeln!(geom_file, "sample\tgeomMeanVariantDepth\tgeomMeanTotalDepth\tgeomMeanFrequency")
+                    .expect("Unable to write to file");
+                for (sample_idx, sample_name) in sample_names.iter().enumerate() {
+                    writeln!(geom_file, "{}\t{}\t{}\t{}", sample_name,
+                             geom_mean_var.get(sample_idx).unwrap_or(&0.),
+                             geom_mean_dep.get(sample_idx).unwrap_or(&0.),
+                             geom_mean_frq.get(sample_idx).unwrap_or(&0.))
+                        .expect("Unable to write to file");
+                }
+
+                let features = fuzzy::VarFeatures::from_vars(&variant_info[..],
+                                                             geom_mean_var, geom_mean_dep,
+                                                             distance);
+                let file_name = format!("{}_variant_clr.tsv", output_prefix);
+                let mut clr_file = File::create(&file_name)
+                    .expect(&format!("Unable to create file {}", &file_name));
+                write!(clr_file, "index").expect("Unable to write to file");
+                if sample_names.len() > 1 {
+                    for sample_name in sample_names.iter() {
+                        write!(clr_file, "\t{}", sample_name).expect("Unable to write to file");
+                    }
+                } else {
+                    // A single sample is described by the log ratios of both depths instead
+                    write!(clr_file, "\tvariantDepthLogRatio\ttotalDepthLogRatio")
+                        .expect("Unable to write to file");
+                }
+                write!(clr_file, "\n").expect("Unable to write to file");
+                for (index, feature) in features.iter().enumerate() {
+                    write!(clr_file, "{}", index + 1).expect("Unable to write to file");
+                    for value in feature.clr.iter() {
+                        write!(clr_file, "\t{}", value).expect("Unable to write to file");
+                    }
+                    write!(clr_file, "\n").expect("Unable to write to file");
+                }
+
+                // Only the lower triangle is stored, as the matrix is symmetric
+                let graph = fuzzy::NeighbourGraph::new(&features[..], e_max, geom_mean_var,
+                                                       geom_mean_dep, geom_mean_frq);
+                let entries = (0..features.len()).map(|i| {
+                    graph.neighbours(i).iter().filter(|(j, _)| *j < i).count()
+                }).sum::<usize>();
+                let file_name = format!("{}_variant_distances.mtx", output_prefix);
+                let mut distance_file = File::create(&file_name)
+                    .expect(&format!("Unable to create file {}", &file_name));
+                writeln!(distance_file, "%%MatrixMarket matrix coordinate real symmetric")
+                    .expect("Unable to write to file");
+                writeln!(distance_file, "% {:?} distances between variants no further apart than {}",
+                         distance, e_max).expect("Unable to write to file");
+                writeln!(distance_file, "{} {} {}", features.len(), features.len(), entries)
+                    .expect("Unable to write to file");
+                for i in 0..features.len() {
+                    for (j, value) in graph.neighbours(i).iter() {
+                        if *j < i {
+                            writeln!(distance_file, "{} {} {}", i + 1, j + 1, value)
+                                .expect("Unable to write to file");
+                        }
+                    }
+                }
+                info!("Variant feature matrices written with prefix {}", output_prefix);
+                graph
+            }
+        }
+    }
+}
+
+/// The variant type and alternate allele used to describe a variant in tables
+pub fn variant_type_and_allele(variant: &Variant) -> (&'static str, String) {
+    match variant {
+        Variant::SNV(alt) => ("SNV", (*alt as char).to_string()),
+        Variant::MNV(alt) => ("MNV", str::from_utf8(alt).unwrap().to_string()),
+        Variant::Insertion(alt) => ("INS", str::from_utf8(alt).unwrap().to_string()),
+        Variant::Inversion(alt) => ("INV", str::from_utf8(alt).unwrap().to_string()),
+        Variant::Deletion(len) => ("DEL", len.to_string()),
+        Variant::SV(_) => ("SV", ".".to_string()),
+        Variant::None => ("REF", ".".to_string()),
+    }
+}
+
+/// Organizes clusters of variant_info indices into genotypes, numbering strains from 1 by the
+/// rank of their cluster. Each variant's base is tagged with the strains it belongs to and
+/// clusters of only reference alleles are dropped
+fn genotypes_from_clusters(clusters: &Vec<fuzzy::Cluster>,
+                           variant_info: &Vec<fuzzy::Var>,
+                           all_variants: &mut HashMap<i32, HashMap<i64, HashMap<Variant, Base>>>)
+                           -> HashMap<usize, HashMap<i32, HashMap<i64, HashMap<fuzzy::Category, HashSet<Variant>>>>> {
+    // Since these are hashmaps, I'm using Arc and Mutex here since not sure how
+    // keep hashmaps updated using channel()
+    let prediction_variants =
+        Mutex::new(
+            HashMap::new());
+
+
+    let all_variants =
+        Mutex::new(
+            all_variants);
+
+    // Organize clusters into genotypes by recollecting full variant information
+    clusters.par_iter().enumerate().for_each(|(rank, cluster)|{
+        // Sets for each cluster keeping track of which variant types are present in
+        // a cluster
+        let prediction_set = Mutex::new(
+            HashSet::new());
+        cluster.par_iter().for_each(|assignment|{
+
+            let variant: &fuzzy::Var = &variant_info[assignment.index];
+
+            let mut prediction_set = prediction_set.lock().unwrap();
+            prediction_set.insert(variant.var.to_owned());
+
+            let mut prediction_variants = prediction_variants
+                .lock()
+                .unwrap();
+
+            let variant_tid = prediction_variants
+                .entry(rank + 1)
+                .or_insert(HashMap::new());
+
+            let variant_pos = variant_tid
+                .entry(variant.tid)
+                .or_insert(HashMap::new());
+
+            let variant_cat = variant_pos
+                .entry(variant.pos)
+                .or_insert(HashMap::new());
+
+            let variant_set = variant_cat
+                .entry(assignment.category)
+                .or_insert(HashSet::new());
+
+            variant_set.insert(variant.var.to_owned());
+
+            // Add genotypes to base
+            let mut all_variants = all_variants.lock().unwrap();
+            let base_tid = all_variants.entry(variant.tid)
+                .or_insert(HashMap::new());
+            let base_pos = base_tid.entry(variant.pos)
+                .or_insert(HashMap::new());
+            match base_pos.get_mut(&variant.var) {
+                Some(base_base) => {
+                    base_base.genotypes.insert(rank as i32 + 1);
+                },
+                None => {},
+            };
+
+        });
+        let mut prediction_set = prediction_set.lock().unwrap();
+        if !(prediction_set.len() == 1 && prediction_set.contains(&Variant::None)) {
+
+        } else {
+            let mut prediction_variants = prediction_variants
+                .lock()
+                .unwrap();
+
+            prediction_variants
+                .remove_entry(&(rank + 1)).expect("Unable to remove cluster");
+        }
+
+    });
+
+    prediction_variants.into_inner().unwrap()
+}
+
+/// Mean of the values with a 95% percentile bootstrap confidence interval. Returns zeros when
+/// there are no values
+pub fn bootstrap_mean(values: &[f64], replicates: usize) -> (f64, f64, f64) {
+    if values.len() == 0 {
+        return (0., 0., 0.)
+    }
+    let mean = values.iter().sum::<f64>() / values.len() as f64;
+
+    // Fixed seed so that repeated runs report the same intervals
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut means = (0..replicates).map(|_| {
+        (0..values.len()).map(|_| values[rng.gen_range(0, values.len())]).sum::<f64>()
+            / values.len() as f64
+    }).collect::<Vec<f64>>();
+    means.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+    let lower = means[(replicates as f64 * 0.025) as usize];
+    let upper = means[std::cmp::min((replicates as f64 * 0.975) as usize, replicates - 1)];
+    (mean, lower, upper)
+}
+
+/// Collects the reads supporting a strain's core sites that have a single allele
+fn strain_read_set(genotype: &HashMap<i32, HashMap<i64, HashMap<fuzzy::Category, HashSet<Variant>>>>,
+                   all_variants: &HashMap<i32, HashMap<i64, HashMap<Variant, Base>>>) -> ReadSet {
+    let mut strain_reads = ReadSet::new();
+    for (tid, positions) in genotype.iter() {
+        for (pos, categories) in positions.iter() {
+            match categories.get(&fuzzy::Category::Core) {
+                Some(variants) if variants.len() == 1 => {
+                    let variant = variants.iter().next().unwrap();
+                    if let Some(base) = all_variants.get(tid)
+                        .and_then(|contig_variants| contig_variants.get(pos))
+                        .and_then(|position_variants| position_variants.get(variant)) {
+                        strain_reads.extend(&base.reads);
+                    }
+                },
+                _ => {},
+            }
+        }
+    }
+    strain_reads
+}
+
+/// Chooses the allele at a site from the candidates assigned to a strain. With several
+/// candidates, the allele sharing the most reads with the rest of the strain is chosen and ties
+/// go to the reference allele. Returns the allele and whether the choice is supported by read
+/// linkage
+fn resolve_allele(candidates: &HashSet<Variant>,
+                  position_variants: Option<&HashMap<Variant, Base>>,
+                  strain_reads: &ReadSet) -> (Variant, bool) {
+    if candidates.len() == 1 {
+        return (candidates.iter().next().unwrap().clone(), true)
+    }
+
+    // Sort for a deterministic choice when there is no linkage. The reference sorts last in
+    // Variant, so it is moved to the front to win ties
+    let mut candidates = candidates.iter().cloned().collect::<Vec<Variant>>();
+    candidates.sort_by(|a, b| (*a != Variant::None).cmp(&(*b != Variant::None)).then(a.cmp(b)));
+    let mut support = candidates.iter().map(|variant| {
+        let linked_reads = match position_variants.and_then(|variants| variants.get(variant)) {
+            Some(base) => base.reads.intersection_count(strain_reads),
+            None => 0,
+        };
+        (linked_reads, variant.clone())
+    }).collect::<Vec<(usize, Variant)>>();
+    // Most supported first, ties keep their sorted order
+    support.sort_by(|a, b| b.0.cmp(&a.0));
+
+    let (best, best_variant) = support[0].clone();
+    let second = support.get(1).map(|s| s.0).unwrap_or(0);
+    // The choice is only trusted if it clearly outweighs the alternatives
+    (best_variant, best > 0 && best >= 2 * second)
+}
+
+/// Masks a low confidence sequence. "iupac" writes the ambiguity code of the candidate bases for
+/// single base sites, "lowercase" writes the sequence in lower case and "none" leaves it as is
+pub fn mask_sequence(sequence: &[u8], candidate_bases: &BTreeSet<u8>, mask_mode: &str) -> String {
+    match mask_mode {
+        "iupac" if sequence.len() == 1 && candidate_bases.len() > 1 => {
+            (iupac_code(candidate_bases) as char).to_string()
+        },
+        "none" => str::from_utf8(sequence).unwrap().to_string(),
+        _ => str::from_utf8(sequence).unwrap().to_lowercase(),
+    }
+}
+
+/// IUPAC ambiguity code for a set of nucleotides
+pub fn iupac_code(bases: &BTreeSet<u8>) -> u8 {
+    let bases = bases.iter().map(|b| b.to_ascii_uppercase()).collect::<BTreeSet<u8>>();
+    let bases = bases.iter().cloned().collect::<Vec<u8>>();
+    match &bases[..] {
+        [b] => *b,
+        [b'A', b'G'] => b'R',
+        [b'C', b'T'] => b'Y',
+        [b'C', b'G'] => b'S',
+        [b'A', b'T'] => b'W',
+        [b'G', b'T'] => b'K',
+        [b'A', b'C'] => b'M',
+        [b'C', b'G', b'T'] => b'B',
+        [b'A', b'G', b'T'] => b'D',
+        [b'A', b'C', b'T'] => b'H',
+        [b'A', b'C', b'G'] => b'V',
+        _ => b'N',
+    }
+}
+
+/// Add read count entry to cluster hashmap
+pub fn add_entry(shared_read_counts: &mut HashMap<usize, HashMap<usize, usize>>,
+                 clust1: usize, clust2: usize, count: usize) {
+    let entry = shared_read_counts.entry(clust1).or_insert(HashMap::new());
+    entry.insert(clust2, count);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use estimation::contig_variants::*;
+    use std::collections::HashSet;
+    use model::variants;
+    use rust_htslib::bam::{self, Read};
+
+    fn create_base(ref_sequence: &Vec<u8>, var_char: u8, pos: i64, sample_count: usize) -> Base {
+        Base {
+            tid: 0,
+            pos,
+            refr: ref_sequence[pos as usize..(pos as usize + 1)].to_vec(),
+            variant: Variant::SNV(var_char),
+            filters: vec!(),
+            depth: vec![0, 5],
+            truedepth: vec![0, 5],
+            totaldepth: vec![5, 5],
+            genotypes: HashSet::new(),
+            quals: vec![0.; sample_count],
+            referencedepth: vec![5, 0],
+            physicalcov: vec![0; sample_count],
+            baseq: vec![0; sample_count],
+            mapq: vec![0; sample_count],
+            conf: vec![0; sample_count],
+            nucs: HashMap::new(),
+            pernucs: HashMap::new(),
+            ic: vec![0; sample_count],
+            dc: vec![0; sample_count],
+            xc: vec![0; sample_count],
+            ac: vec![0; sample_count],
+            af: vec![0.; sample_count],
+            freq: vec![0.; sample_count],
+            rel_abunds: vec![0.; sample_count],
+            reads: ReadSet::new(),
+        }
+    }
+
+    #[test]
+    fn test_stats_and_mat() {
+
+        let ref_sequence = "ATGAAACCCGGGTTTTAA".as_bytes().to_vec();
+        let sample_count = 2;
+
+        let mut variant_abundances: HashMap<i64, HashMap<Variant, Base>> = HashMap::new();
+
+        // Setup variant matrix
+        let mut var_mat = VariantMatrix::new_matrix(2);
+
+        // Create fake variants
+        let var_1 = create_base(&ref_sequence, "G".bytes().nth(0).unwrap(), 7, 2);
+        let var_2 = create_base(&ref_sequence, "C".bytes().nth(0).unwrap(), 11, 2);
+        let var_3 = create_base(&ref_sequence, "A".bytes().nth(0).unwrap(), 13, 2);
+        let var_4 = create_base(&ref_sequence, "C".bytes().nth(0).unwrap(), 14, 2);
+
+        let mut ups_and_downs = vec![0; ref_sequence.len()];
+        ups_and_downs[0] = 5;
+        ups_and_downs[ref_sequence.len() - 1] = -5;
+
+        // Setup sample 1
+        let mut var_stats = VariantStats::new_contig_stats(0., 5., 0);
+        var_stats.add_contig(Some(&mut variant_abundances), 0, 0,
+                             b"test".to_vec(), ref_sequence.len(), 0,
+                             vec![10., 10., 0.], ups_and_downs);
+        var_mat.add_contig(var_stats, 2, 0);
+
+        {
+            // Add variants in
+            let hash = variant_abundances.entry(7).or_insert(HashMap::new());
+            hash.insert(var_1.variant.clone(), var_1);
+
+            let hash = variant_abundances.entry(11).or_insert(HashMap::new());
+            hash.insert(var_2.variant.clone(), var_2);
+
+            let hash = variant_abundances.entry(13).or_insert(HashMap::new());
+            hash.insert(var_3.variant.clone(), var_3);
+
+            let hash = variant_abundances.entry(14).or_insert(HashMap::new());
+            hash.insert(var_4.variant.clone(), var_4);
+        }
+        // Add sample 2
+        let mut ups_and_downs = vec![0; ref_sequence.len()];
+        ups_and_downs[0] = 5;
+        ups_and_downs[ref_sequence.len() - 1] = -5;
+        let mut var_stats = VariantStats::new_contig_stats(0., 5., 0);
+        var_stats.add_contig(Some(&mut variant_abundances), 0, 0,
+                             b"test".to_vec(), ref_sequence.len(), 1,
+                             vec![10., 10., 0.], ups_and_downs);
+
+        var_mat.add_contig(var_stats, 2, 1);
+
+        var_mat.generate_distances(0, "test");
+
+        var_mat.run_clustering(0.01, 0.05, 0.01, 0.01,
+                               0., 0, 0., 0, "fuzzy-dbscan", fuzzy::DistanceMetric::default(),
+                               None, None, None)
+
+    }
+
+    #[test]
+    fn test_iupac_code() {
+        let bases = |b: &[u8]| b.iter().cloned().collect::<BTreeSet<u8>>();
+        assert_eq!(iupac_code(&bases(b"AG")), b'R');
+        assert_eq!(iupac_code(&bases(b"tc")), b'Y');
+        assert_eq!(iupac_code(&bases(b"ACG")), b'V');
+        assert_eq!(iupac_code(&bases(b"ACGT")), b'N');
+        assert_eq!(mask_sequence(b"A", &bases(b"AG"), "iupac"), "R");
+        assert_eq!(mask_sequence(b"ACG", &bases(b"A"), "iupac"), "acg");
+        assert_eq!(mask_sequence(b"A", &bases(b"AG"), "lowercase"), "a");
+        assert_eq!(mask_sequence(b"A", &bases(b"AG"), "none"), "A");
+    }
+
+    #[test]
+    fn test_resolve_allele() {
+        let ref_sequence = "ATGAAACCCGGGTTTTAA".as_bytes().to_vec();
+        let mut read_names = ReadInterner::new();
+        let mut var_1 = create_base(&ref_sequence, b'G', 7, 2);
+        var_1.reads = ReadSet::from_names(0, vec![b"read1".to_vec(), b"read2".to_vec()], &mut read_names);
+        let mut var_2 = create_base(&ref_sequence, b'C', 7, 2);
+        var_2.reads = ReadSet::from_names(0, vec![b"read3".to_vec()], &mut read_names);
+
+        let mut position_variants = HashMap::new();
+        position_variants.insert(var_1.variant.clone(), var_1);
+        position_variants.insert(var_2.variant.clone(), var_2);
+        let candidates = [Variant::SNV(b'G'), Variant::SNV(b'C')].iter().cloned().collect::<HashSet<Variant>>();
+
+        let strain_reads = ReadSet::from_names(0, vec![b"read1".to_vec(), b"read2".to_vec()], &mut read_names);
+        assert_eq!(resolve_allele(&candidates, Some(&position_variants), &strain_reads),
+                   (Variant::SNV(b'G'), true));
+
+        let strain_reads = ReadSet::new();
+        assert_eq!(resolve_allele(&candidates, Some(&position_variants), &strain_reads),
+                   (Variant::SNV(b'C'), false));
+
+        // The reference allele wins ties, with or without linkage
+        let mut reference = create_base(&ref_sequence, b'G', 7, 2);
+        reference.variant = Variant::None;
+        reference.reads = ReadSet::from_names(0, vec![b"read4".to_vec(), b"read5".to_vec()], &mut read_names);
+        position_variants.insert(Variant::None, reference);
+        let candidates = [Variant::SNV(b'G'), Variant::None].iter().cloned().collect::<HashSet<Variant>>();
+        assert_eq!(resolve_allele(&candidates, Some(&position_variants), &ReadSet::new()),
+                   (Variant::None, false));
+        let strain_reads = ReadSet::from_names(0, vec![b"read1".to_vec(), b"read4".to_vec()], &mut read_names);
+        assert_eq!(resolve_allele(&candidates, Some(&position_variants), &strain_reads),
+                   (Variant::None, false));
+        let strain_reads = ReadSet::from_names(0, vec![b"read4".to_vec(), b"read5".to_vec()], &mut read_names);
+        assert_eq!(resolve_allele(&candidates, Some(&position_variants), &strain_reads),
+                   (Variant::None, true));
+    }
+
+    #[test]
+    fn test_bootstrap_mean() {
+        assert_eq!(bootstrap_mean(&[], 100), (0., 0., 0.));
+        assert_eq!(bootstrap_mean(&[0.5, 0.5, 0.5], 100), (0.5, 0.5, 0.5));
+
+        let (mean, lower, upper) = bootstrap_mean(&[0.1, 0.2, 0.3, 0.4, 0.5], 1000);
+        assert!((mean - 0.3).abs() < 1e-10);
+        assert!(lower <= mean && mean <= upper);
+        assert!(lower >= 0.1 && upper <= 0.5);
+    }
+
+    #[test]
+    fn test_extend_samples() {
+        let ref_sequence = "ATGAAACCCGGGTTTTAA".as_bytes().to_vec();
+        let mut var_mat = VariantMatrix::new_matrix(2);
+        let mut variant_abundances: HashMap<i64, HashMap<Variant, Base>> = HashMap::new();
+        let var_1 = create_base(&ref_sequence, "G".bytes().nth(0).unwrap(), 7, 2);
+        variant_abundances.entry(7).or_insert(HashMap::new())
+            .insert(var_1.variant.clone(), var_1);
+
+        let mut ups_and_downs = vec![0; ref_sequence.len()];
+        ups_and_downs[0] = 5;
+        ups_and_downs[ref_sequence.len() - 1] = -5;
+        let mut var_stats = VariantStats::new_contig_stats(0., 5., 0);
+        var_stats.add_contig(Some(&mut variant_abundances), 0, 0,
+                             b"test".to_vec(), ref_sequence.len(), 0,
+                             vec![10., 10., 0.], ups_and_downs);
+        var_mat.add_contig(var_stats, 2, 0);
+
+        var_mat.extend_samples(3);
+        match var_mat {
+            VariantMatrix::VariantContigMatrix { sample_names, coverages, all_variants, .. } => {
+                assert_eq!(sample_names.len(), 3);
+                assert!(coverages.values().all(|cov| cov.len() == 3));
+                for contig_variants in all_variants.values() {
+                    for position_variants in contig_variants.values() {
+                        for base in position_variants.values() {
+                            assert_eq!(base.depth.len(), 3);
+                            assert_eq!(base.totaldepth.len(), 3);
+                            assert_eq!(base.filters.len(), 3);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_add_sample_fills_depths() {
+        let ref_sequence = "ATGAAACCCGGGTTTTAA".as_bytes().to_vec();
+        let header = bam::Reader::from_path("tests/data/indels.bam").unwrap().header().clone();
+        let mut var_mat = VariantMatrix::new_matrix(2);
+        let calls = |sample_idx: usize, alleles: Vec<(i64, u8, i32)>| {
+            let mut variant_map = HashMap::new();
+            let contig_variants = variant_map.entry(0).or_insert(HashMap::new());
+            for (pos, alt, depth) in alleles {
+                let position_variants = contig_variants.entry(pos).or_insert(HashMap::new());
+                position_variants.insert(Variant::None,
+                                         Base::new(0, pos, 2, vec![ref_sequence[pos as usize]]));
+                let mut base = Base::new(0, pos, 2, vec![ref_sequence[pos as usize]]);
+                base.variant = Variant::SNV(alt);
+                base.depth[sample_idx] = depth;
+                base.truedepth[sample_idx] = depth;
+                position_variants.insert(base.variant.clone(), base);
+            }
+            variant_map
+        };
+        let add_depths = |var_mat: &mut VariantMatrix, sample_idx: usize, depth: i32| {
+            let mut ups_and_downs = vec![0; ref_sequence.len()];
+            ups_and_downs[0] = depth;
+            ups_and_downs[ref_sequence.len() - 1] = -depth;
+            let mut var_stats = VariantStats::new_contig_stats(0., 5., 0);
+            var_stats.add_contig(var_mat.variants_of_contig(0), 0, 0,
+                                 b"test".to_vec(), ref_sequence.len(), sample_idx,
+                                 vec![depth as f64, depth as f64, 0.], ups_and_downs);
+            var_mat.add_contig(var_stats, 2, sample_idx);
+        };
+
+        // Sample A has a G at position 7 and a depth of 8
+        var_mat.add_sample("sample_a".to_string(), 0, &calls(0, vec![(7, b'G', 3)]), &header);
+        add_depths(&mut var_mat, 0, 8);
+
+        // Sample B has a new allele at position 7 and a variant at a position A has none at
+        var_mat.add_sample("sample_b".to_string(), 1,
+                           &calls(1, vec![(7, b'T', 4), (11, b'C', 2)]), &header);
+        add_depths(&mut var_mat, 1, 6);
+
+        match var_mat {
+            VariantMatrix::VariantContigMatrix { all_variants, .. } => {
+                let contig_variants = &all_variants[&0];
+                // A's depth at position 7 is given to the allele only B has
+                let new_allele = &contig_variants[&7][&Variant::SNV(b'T')];
+                assert_eq!(new_allele.totaldepth, vec![8, 6]);
+                assert_eq!(new_allele.referencedepth, vec![8, 2]);
+                // B's depths are given to the allele only A has
+                let old_allele = &contig_variants[&7][&Variant::SNV(b'G')];
+                assert_eq!(old_allele.totaldepth, vec![8, 6]);
+                assert_eq!(old_allele.referencedepth, vec![5, 6]);
+                // A's contig coverage stands in where A had no variant
+                let new_position = &contig_variants[&11][&Variant::SNV(b'C')];
+                assert_eq!(new_position.totaldepth, vec![8, 6]);
+                assert_eq!(new_position.referencedepth, vec![8, 4]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_checkpoint_round_trip() {
+        let ref_sequence = "ATGAAACCCGGGTTTTAA".as_bytes().to_vec();
+        let mut var_mat = VariantMatrix::new_matrix(2);
+
+        let mut variant_abundances: HashMap<i64, HashMap<Variant, Base>> = HashMap::new();
+        let var_1 = create_base(&ref_sequence, "G".bytes().nth(0).unwrap(), 7, 2);
+        variant_abundances.entry(7).or_insert(HashMap::new())
+            .insert(var_1.variant.clone(), var_1);
+
+        let mut ups_and_downs = vec![0; ref_sequence.len()];
+        ups_and_downs[0] = 5;
+        ups_and_downs[ref_sequence.len() - 1] = -5;
+        let mut var_stats = VariantStats::new_contig_stats(0., 5., 0);
+        var_stats.add_contig(Some(&mut variant_abundances), 0, 0,
+                             b"test".to_vec(), ref_sequence.len(), 0,
+                             vec![10., 10., 0.], ups_and_downs);
+        var_mat.add_contig(var_stats, 2, 0);
+
+        // Only names referenced by a variant are written to the checkpoint
+        {
+            let (position_variants, read_names) = var_mat.variants(0, 7).unwrap();
+            read_names.intern(0, b"unreferenced_read");
+            let mut var_2 = create_base(&ref_sequence, "C".bytes().nth(0).unwrap(), 7, 2);
+            var_2.reads = ReadSet::from_names(0, vec![b"checkpoint_read".to_vec()], read_names);
+            position_variants.insert(var_2.variant.clone(), var_2);
+        }
+
+        let tmp_dir = tempdir::TempDir::new("lorikeet-checkpoint").unwrap();
+        let output_prefix = format!("{}/test", tmp_dir.path().to_str().unwrap());
+        var_mat.write_checkpoint(&output_prefix);
+        let loaded = VariantMatrix::from_checkpoint(&format!("{}_matrix.bin", output_prefix));
+
+        match (var_mat, loaded) {
+            (VariantMatrix::VariantContigMatrix { all_variants: expected, target_names: expected_names, .. },
+             VariantMatrix::VariantContigMatrix { all_variants, target_names, read_names, .. }) => {
+                assert_eq!(expected, all_variants);
+                assert_eq!(expected_names, target_names);
+                assert_eq!(read_names.len(), 1);
+                assert_eq!(read_names.get(0, b"checkpoint_read"), Some(0));
+                assert_eq!(all_variants[&0][&7][&Variant::SNV(b'C')].reads.names(&read_names),
+                           vec![b"checkpoint_read".to_vec()]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_calculate_strain_abundances() {
+        let ref_sequence = "ATGAAACCCGGGTTTTAA".as_bytes().to_vec();
+        let mut var_mat = VariantMatrix::new_matrix(2);
+        let tmp_dir = tempdir::TempDir::new("lorikeet-abundances").unwrap();
+        let output_prefix = format!("{}/test", tmp_dir.path().to_str().unwrap());
+        let read_abundances = |output_prefix: &str| -> Vec<(String, String, f64, String)> {
+            std::fs::read_to_string(format!("{}_strain_abundances.tsv", output_prefix)).unwrap()
+                .lines().skip(1).map(|line| {
+                    let fields = line.split('\t').collect::<Vec<&str>>();
+                    (fields[0].to_string(), fields[1].to_string(),
+                     fields[2].parse::<f64>().unwrap(), fields[3].to_string())
+                }).collect()
+        };
+
+        match var_mat {
+            VariantMatrix::VariantContigMatrix {
+                ref mut sample_names, ref mut all_variants, ref mut pred_variants, ..
+            } => {
+                *sample_names = vec!["s1".to_string(), "s2".to_string()];
+                // Strain 1 has a variant whose depth is larger than its site's in the first
+                // sample, which is skipped rather than counted as a frequency of one
+                for (strain, pos, var_char, truedepth) in vec![(1, 7, b'G', vec![1, 3]),
+                                                               (2, 11, b'C', vec![3, 1]),
+                                                               (1, 13, b'A', vec![9, 1])] {
+                    let mut base = create_base(&ref_sequence, var_char, pos, 2);
+                    base.truedepth = truedepth;
+                    base.totaldepth = vec![4, 4];
+                    all_variants.entry(0).or_insert(HashMap::new())
+                        .entry(pos).or_insert(HashMap::new())
+                        .insert(base.variant.clone(), base);
+                    pred_variants.entry(strain).or_insert(HashMap::new())
+                        .entry(0).or_insert(HashMap::new())
+                        .entry(pos).or_insert(HashMap::new())
+                        .entry(fuzzy::Category::Core).or_insert(HashSet::new())
+                        .insert(Variant::SNV(var_char));
+                }
+            }
+        }
+
+        // Allele frequencies are 0.25 and 0.75 in the first sample and 0.5 and 0.25 in the
+        // second, normalised to sum to one in each sample
+        var_mat.calculate_strain_abundances(&output_prefix);
+        let abundances = read_abundances(&output_prefix);
+        let expected = vec![("1", "s1", 0.25), ("1", "s2", 2. / 3.),
+                            ("2", "s1", 0.75), ("2", "s2", 1. / 3.)];
+        assert_eq!(abundances.len(), expected.len());
+        for (row, (strain, sample, abundance)) in abundances.iter().zip(expected.iter()) {
+            assert_eq!((row.0.as_str(), row.1.as_str()), (*strain, *sample));
+            assert!((row.2 - abundance).abs() < 1e-10);
+        }
+
+        // Deconvolved proportions are written as they were fitted
+        match var_mat {
+            VariantMatrix::VariantContigMatrix { ref mut strain_proportions, .. } => {
+                *strain_proportions = vec![vec![0.4, 0.9], vec![0.6, 0.1]];
+            }
+        }
+        var_mat.calculate_strain_abundances(&output_prefix);
+        let abundances = read_abundances(&output_prefix);
+        let expected = vec![0.4, 0.9, 0.6, 0.1];
+        for (row, abundance) in abundances.iter().zip(expected.iter()) {
+            assert_eq!(row.2, *abundance);
+            assert_eq!(row.3, "NA");
+        }
+    }
+}
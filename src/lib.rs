@@ -22,6 +22,7 @@ extern crate kodama;
 extern crate statrs;
 
 // Utilities
+extern crate bincode;
 extern crate csv;
 extern crate clap;
 extern crate glob;
@@ -1 +1,2 @@
-pub mod variants;
\ No newline at end of file
+pub mod variants;
+pub mod read_names;
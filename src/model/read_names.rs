@@ -0,0 +1,315 @@
+use std::collections::{HashMap, HashSet};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::iter::FromIterator;
+use std::path::Path;
+
+/// Compact identifier of an interned read name
+pub type ReadId = u32;
+
+/// Maps read names to compact ids and back. Only reads that support a variant are interned,
+/// so memory scales with the number of informative reads rather than with every copy of a name.
+/// Each variant matrix owns its interner, so read ids only depend on the reads of that genome.
+/// Read names are only unique within a sample, so a read is identified by its sample index and
+/// name and the same name in two samples gets two ids
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReadInterner {
+    // Ids of each sample's reads keyed by the hash of their name, so that each name is only
+    // stored once in names. Names sharing a hash are told apart by comparing them there.
+    // Rebuilt from the names after deserializing, see [reindex](ReadInterner::reindex)
+    #[serde(skip)]
+    ids: HashMap<usize, HashMap<u64, Vec<ReadId>>>,
+    names: Vec<Vec<u8>>,
+    // Index of the sample each read was sequenced in
+    samples: Vec<usize>,
+}
+
+impl ReadInterner {
+    pub fn new() -> ReadInterner {
+        ReadInterner::default()
+    }
+
+    /// Returns the id of the read name in the given sample, assigning the next free id if it
+    /// is new
+    pub fn intern(&mut self, sample_idx: usize, name: &[u8]) -> ReadId {
+        if let Some(id) = self.get(sample_idx, name) {
+            return id
+        }
+        let id = self.names.len() as ReadId;
+        self.ids.entry(sample_idx).or_insert(HashMap::new())
+            .entry(name_hash(name)).or_insert(Vec::new()).push(id);
+        self.names.push(name.to_vec());
+        self.samples.push(sample_idx);
+        id
+    }
+
+    /// The id of a read name in the given sample if it has been interned
+    pub fn get(&self, sample_idx: usize, name: &[u8]) -> Option<ReadId> {
+        self.ids.get(&sample_idx).and_then(|ids| ids.get(&name_hash(name)))
+            .and_then(|ids| ids.iter().find(|id| &self.names[**id as usize][..] == name))
+            .cloned()
+    }
+
+    pub fn name(&self, id: ReadId) -> &[u8] {
+        &self.names[id as usize]
+    }
+
+    /// Index of the sample the read was sequenced in
+    pub fn sample(&self, id: ReadId) -> usize {
+        self.samples[id as usize]
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    /// Rebuilds the name to id lookup, which is not serialized
+    pub fn reindex(&mut self) {
+        self.ids = HashMap::new();
+        for (id, (name, sample_idx)) in self.names.iter().zip(self.samples.iter()).enumerate() {
+            self.ids.entry(*sample_idx).or_insert(HashMap::new())
+                .entry(name_hash(name)).or_insert(Vec::new()).push(id as ReadId);
+        }
+    }
+
+    /// Drops every name not in the given set of referenced ids and renumbers the rest in
+    /// order. Returns the lookup from old to new ids, which read sets must be remapped with
+    pub fn retain(&mut self, referenced: &HashSet<ReadId>) -> Vec<ReadId> {
+        let mut lookup = vec![0; self.names.len()];
+        let mut names = Vec::with_capacity(referenced.len());
+        let mut samples = Vec::with_capacity(referenced.len());
+        for (id, (name, sample_idx)) in self.names.drain(..).zip(self.samples.drain(..)).enumerate() {
+            if referenced.contains(&(id as ReadId)) {
+                lookup[id] = names.len() as ReadId;
+                names.push(name);
+                samples.push(sample_idx);
+            }
+        }
+        self.names = names;
+        self.samples = samples;
+        self.reindex();
+        lookup
+    }
+}
+
+fn name_hash(name: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    name.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Index of the sample whose reads are in the given BAM file, whose reads must be interned and
+/// looked up under that index. Samples are named after their BAM files without the extension
+pub fn bam_sample_index(bam_path: &str, sample_names: &Vec<String>) -> Option<usize> {
+    let stem = Path::new(bam_path).file_stem().and_then(|stem| stem.to_str());
+    let stoit_name = bam_path.replace("/", ".");
+    sample_names.iter().position(|name| Some(name.as_str()) == stem || name == &stoit_name)
+}
+
+/// A set of read ids stored as a sorted vector
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ReadSet {
+    ids: Vec<ReadId>,
+}
+
+impl ReadSet {
+    pub fn new() -> ReadSet {
+        ReadSet::default()
+    }
+
+    /// Interns each read name of the given sample and collects the ids
+    pub fn from_names<T: AsRef<[u8]>, I: IntoIterator<Item = T>>(sample_idx: usize, names: I,
+                                                                 interner: &mut ReadInterner) -> ReadSet {
+        names.into_iter().map(|name| interner.intern(sample_idx, name.as_ref())).collect()
+    }
+
+    /// Inserts a read id, returning false if it was already present
+    pub fn insert(&mut self, id: ReadId) -> bool {
+        match self.ids.binary_search(&id) {
+            Ok(_) => false,
+            Err(idx) => {
+                self.ids.insert(idx, id);
+                true
+            }
+        }
+    }
+
+    /// Appends a read id without keeping the set sorted, for collecting reads in bulk.
+    /// [sort_dedup](ReadSet::sort_dedup) must be called before the set is used
+    pub fn push(&mut self, id: ReadId) {
+        self.ids.push(id);
+    }
+
+    /// Restores the set after reads were added with [push](ReadSet::push)
+    pub fn sort_dedup(&mut self) {
+        self.ids.sort();
+        self.ids.dedup();
+    }
+
+    /// Adds every read in other to this set
+    pub fn extend(&mut self, other: &ReadSet) {
+        if other.ids.len() > 0 {
+            *self = self.union(other);
+        }
+    }
+
+    pub fn contains(&self, id: ReadId) -> bool {
+        self.ids.binary_search(&id).is_ok()
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<ReadId> {
+        self.ids.iter()
+    }
+
+    /// Number of reads present in both sets
+    pub fn intersection_count(&self, other: &ReadSet) -> usize {
+        let (mut i, mut j, mut count) = (0, 0, 0);
+        while i < self.ids.len() && j < other.ids.len() {
+            if self.ids[i] < other.ids[j] {
+                i += 1;
+            } else if self.ids[i] > other.ids[j] {
+                j += 1;
+            } else {
+                count += 1;
+                i += 1;
+                j += 1;
+            }
+        }
+        count
+    }
+
+    pub fn intersection(&self, other: &ReadSet) -> ReadSet {
+        ReadSet {
+            ids: self.ids.iter().cloned().filter(|id| other.contains(*id)).collect(),
+        }
+    }
+
+    pub fn union(&self, other: &ReadSet) -> ReadSet {
+        let mut ids = Vec::with_capacity(self.ids.len() + other.ids.len());
+        let (mut i, mut j) = (0, 0);
+        while i < self.ids.len() && j < other.ids.len() {
+            if self.ids[i] < other.ids[j] {
+                ids.push(self.ids[i]);
+                i += 1;
+            } else if self.ids[i] > other.ids[j] {
+                ids.push(other.ids[j]);
+                j += 1;
+            } else {
+                ids.push(self.ids[i]);
+                i += 1;
+                j += 1;
+            }
+        }
+        ids.extend_from_slice(&self.ids[i..]);
+        ids.extend_from_slice(&other.ids[j..]);
+        ReadSet { ids }
+    }
+
+    /// Translates ids through the given lookup, e.g. when loading ids interned by another run
+    pub fn remap(&self, lookup: &[ReadId]) -> ReadSet {
+        self.ids.iter().map(|id| lookup[*id as usize]).collect()
+    }
+
+    /// The read names of the ids in this set
+    pub fn names(&self, interner: &ReadInterner) -> Vec<Vec<u8>> {
+        self.ids.iter().map(|id| interner.name(*id).to_vec()).collect()
+    }
+}
+
+impl FromIterator<ReadId> for ReadSet {
+    fn from_iter<I: IntoIterator<Item = ReadId>>(iter: I) -> ReadSet {
+        let mut ids = iter.into_iter().collect::<Vec<ReadId>>();
+        ids.sort();
+        ids.dedup();
+        ReadSet { ids }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_set() {
+        let mut interner = ReadInterner::new();
+        let mut set_1 = ReadSet::from_names(0, vec![b"read_b".to_vec(), b"read_a".to_vec()], &mut interner);
+        let set_2 = ReadSet::from_names(0, vec![b"read_b".to_vec(), b"read_c".to_vec()], &mut interner);
+        assert_eq!(interner.get(0, b"read_a"), Some(interner.intern(0, b"read_a")));
+        let read_c = interner.intern(0, b"read_c");
+        assert_eq!(interner.name(read_c), b"read_c");
+
+        assert_eq!(set_1.len(), 2);
+        assert!(!set_1.insert(interner.intern(0, b"read_a")));
+        assert_eq!(set_1.intersection_count(&set_2), 1);
+        assert_eq!(set_1.intersection(&set_2).names(&interner), vec![b"read_b".to_vec()]);
+        assert_eq!(set_1.union(&set_2).len(), 3);
+
+        set_1.extend(&set_2);
+        assert_eq!(set_1.len(), 3);
+        assert!(set_1.contains(interner.intern(0, b"read_c")));
+        assert!(set_1.iter().zip(set_1.iter().skip(1)).all(|(a, b)| a < b));
+
+        let mut pushed = ReadSet::new();
+        for id in vec![2, 0, 2, 1] {
+            pushed.push(id);
+        }
+        pushed.sort_dedup();
+        assert_eq!(pushed, set_1);
+    }
+
+    #[test]
+    fn test_retain_read_names() {
+        let mut interner = ReadInterner::new();
+        let set = ReadSet::from_names(0, vec![b"read_a".to_vec(), b"read_b".to_vec(), b"read_c".to_vec()],
+                                      &mut interner);
+        let referenced = vec![0, 2].into_iter().collect::<HashSet<ReadId>>();
+        let lookup = interner.retain(&referenced);
+        assert_eq!(interner.len(), 2);
+        assert_eq!(interner.get(0, b"read_b"), None);
+        let remapped = set.intersection(&referenced.iter().cloned().collect()).remap(&lookup);
+        assert_eq!(remapped.names(&interner), vec![b"read_a".to_vec(), b"read_c".to_vec()]);
+    }
+
+    #[test]
+    fn test_read_name_hash_collision() {
+        let mut interner = ReadInterner::new();
+        let read_a = interner.intern(0, b"read_a");
+        let read_b = interner.intern(0, b"read_b");
+        // Put read_b in front of read_a as if their names had the same hash
+        interner.ids.get_mut(&0).unwrap().get_mut(&name_hash(b"read_a")).unwrap().insert(0, read_b);
+        assert_eq!(interner.get(0, b"read_a"), Some(read_a));
+        assert_eq!(interner.intern(0, b"read_a"), read_a);
+
+        interner.reindex();
+        assert_eq!(interner.get(0, b"read_b"), Some(read_b));
+        assert_eq!(interner.ids[&0][&name_hash(b"read_a")], vec![read_a]);
+    }
+
+    #[test]
+    fn test_read_names_per_sample() {
+        let mut interner = ReadInterner::new();
+        let first = interner.intern(0, b"read_a");
+        let second = interner.intern(1, b"read_a");
+        assert_ne!(first, second);
+        assert_eq!(interner.sample(second), 1);
+        assert_eq!(interner.name(second), b"read_a");
+        assert_eq!(interner.get(2, b"read_a"), None);
+
+        // Samples survive retaining and reindexing
+        let lookup = interner.retain(&vec![second].into_iter().collect());
+        assert_eq!(interner.get(1, b"read_a"), Some(lookup[second as usize]));
+        assert_eq!(interner.get(0, b"read_a"), None);
+
+        let sample_names = vec!["sample_1".to_string(), "sample_2".to_string()];
+        assert_eq!(bam_sample_index("tests/data/sample_2.bam", &sample_names), Some(1));
+        assert_eq!(bam_sample_index("tests/data/sample_3.bam", &sample_names), None);
+    }
+}